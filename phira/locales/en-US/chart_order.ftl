@@ -4,3 +4,8 @@ name = Name (Asc.)
 rev-name = Name (Desc.)
 rating = Rating (Desc.)
 rev-rating = Rating (Asc.)
+
+query-invalid-level = Invalid level range: { $value }
+query-invalid-format = Unknown chart format: { $value }
+query-invalid-state = Unknown play state: { $value }
+query-invalid-sort = Unknown sort key: { $value }
@@ -28,5 +28,10 @@ warn-deny-tos-policy = You must accept to use Phira's online services.
 
 open-in-web = View in Web
 
+outbox-synced = { $count } pending actions have been sent.
+
+dl-bulk-done = { $done } charts downloaded, { $failed } failed.
+dl-checksum-mismatch = The downloaded chart does not match the one on the server.
+
 main-character-name = Shee
 main-character-intro = To be added.
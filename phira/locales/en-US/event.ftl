@@ -15,3 +15,8 @@ btn-ongoing = Ongoing
 btn-ended = Ended
 
 ldb = Leaderboard
+
+dl-queued = { $count } charts queued for download.
+dl-nothing = All charts are already downloaded.
+dl-failed = Failed to fetch the charts to download.
+dl-progress = Downloading charts { $finished }/{ $total }
@@ -17,3 +17,19 @@ load-record-failed = Failed to load records.
 last-login = Last login: { $time }
 badge-admin = Admin
 badge-sponsor = Sponsor
+
+history = Local History
+records = Online Records
+local-player = Local Player
+load-history-failed = Failed to load play history.
+history-empty = No plays recorded yet.
+history-summary = { $attempts } plays · Last played { $time }
+history-back = Back
+history-attempts = { $attempts } plays from { $first } to { $last }
+history-best = Best: { $score } ({ $accuracy }%)
+history-latest = Latest: { $score } ({ $accuracy }%)
+
+rating = Local Rating
+rating-empty = No local records count toward the rating yet.
+rating-hint = Computed from your best local records: the best 19 charts and the best chart played with 100% accuracy (φ). The right column shows the accuracy needed to raise the rating.
+rating-target = ↑ { $accuracy }%
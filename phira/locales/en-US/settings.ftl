@@ -25,6 +25,22 @@ item-cache-size-loading = Loading…
 item-cache-size = Cache size: { $size }
 item-clear-cache-btn = Clear
 item-cache-cleared = Cache cleared
+item-verify-charts = Check Chart Files
+item-verify-charts-sub = Find damaged chart files and restore them.
+item-verify-charts-btn = Check
+item-verify-charts-running = Checking…
+item-verify-charts-done = Checked { $checked } files, { $repaired } repaired
+item-verify-charts-damaged = { $count } charts are damaged, please download or import them again
+item-verify-charts-failed = Failed to check chart files
+item-verify-charts-modified = Modified Chart Files
+item-verify-charts-modified-content = { $count } chart files were changed outside of Phira. Restore the original files? Cancel keeps the changes.
+item-verify-charts-restored = Restored { $count } files
+item-outbox = Pending Actions
+item-outbox-sub = { $pending } waiting for the network, { $failed } failed
+item-outbox-btn = Retry
+item-outbox-discard = Discard Failed Actions
+item-outbox-discard-sub = Drop actions rejected by the server.
+item-outbox-discard-btn = Discard
 item-insecure = Insecure Connection
 item-insecure-sub = Enable old devices to use online functionality.
 item-enable-anys = Enable Anys
@@ -32,6 +48,21 @@ item-enable-anys-sub = Use an Anys gateway to improve network stability.
 item-anys-gateway = Anys Gateway
 item-anys-gateway-sub = Use a custom Anys gateway address.
 item-anys-gateway-invalid = Invalid gateway address.
+item-server = Server
+item-server-sub = Each server keeps its own login.
+item-server-switched = Switched to { $name }
+item-server-url = Server Address
+item-server-url-sub = Address of the API of the current server.
+item-server-invalid = Invalid server address.
+item-server-builtin = The official server can't be changed.
+item-server-add = Add Server
+item-server-add-sub = Use a private or local server.
+item-server-add-btn = Add
+item-server-added = Server added
+item-server-remove = Remove Server
+item-server-remove-sub = Remove the current server and its login.
+item-server-remove-btn = Remove
+item-server-removed = Server removed
 
 item-adjust = Automatic Time Adjustment
 item-adjust-sub = Adjust the audio and chart offset dynamically.
@@ -39,6 +70,10 @@ item-music = Music Volume
 item-sfx = SFX Volume
 item-bgm = BGM Volume
 item-cali = Adjust Offset
+item-auto-offset = Automatic Offset
+item-auto-offset-sub = Apply offset suggestions from recent plays automatically.
+item-suggest-offset = Suggested Offset
+item-suggest-offset-sub = Based on the timing of your recent plays. Tap to apply.
 item-preferred-sample-rate = Preferred Sample Rate
 
 item-show-acc = Real-Time Accuracy
@@ -55,6 +90,8 @@ item-chart-debug = Show Line ID
 item-chart-debug-sub = Display the IDs and orientation of lines.
 item-touch-debug = Show Touch Points
 item-touch-debug-sub = Display user touch points.
+item-res-pack-watch = Watch Resource Pack
+item-res-pack-watch-sub = Reload the resource pack during play when its files change.
 
 load-cali-failed = Failed to load calibration audio.
 
@@ -9,9 +9,11 @@ dl-cancel = Cancel
 dl-status-fetch = Fetching info
 dl-status-song = Downloading song...
 dl-status-chart = Downloading chart...
+dl-status-queued = Waiting in queue...
 dl-status-extract = Unzipping chart...
 dl-status-illustration = Downloading illustration...
 dl-status-assets = Downloading assets...
+dl-status-verify = Verifying chart...
 dl-status-saving = Saving...
 dl-failed = Download failed...
 dl-success = Downloaded successfully.
@@ -29,6 +31,16 @@ rate = Rate
 exercise = Practice
 offset = Adjust Offset
 unlock = View Unlock Video
+replay = Watch Last Replay
+replay-none = No replay recorded for this chart yet.
+replay-load-failed = Failed to load replay.
+render-video = Export Autoplay Video
+render-replay = Export Last Replay as Video
+chart-offset-apply = Apply Suggested Offset
+chart-offset-applied = Offset for this chart set to { $offset }ms.
+chart-offset-reset = Use Global Offset
+chart-offset-reset-done = This chart now uses the global offset.
+offset-auto-applied = Offset adjusted to { $offset }ms based on recent plays.
 
 edit-cancel = Cancel
 edit-save = Save
@@ -105,11 +117,22 @@ mods-flip-x = Mirror
 mods-flip-x-sub = Mirrors the chart by the X-axis.
 mods-fade-out = Fade-Out
 mods-fade-out-sub = Makes notes fade out when they approach the judgeline.
-# mods-strict-judge = Strict Judge  
-# mods-strict-judge-sub = Uses stricter judgment windows  
+mods-flip-y = Upside Down
+mods-flip-y-sub = Mirrors the chart by the Y-axis.
+mods-fade-in = Fade-In
+mods-fade-in-sub = Makes notes appear only shortly before they reach the judgeline.
+mods-hide-hold-body = Hidden Hold
+mods-hide-hold-body-sub = Hides the bodies of hold notes.
+mods-random-speed = Random Speed
+mods-random-speed-sub = Gives every note a randomly varied speed.
+mods-strict-judge = Strict Judge
+mods-strict-judge-sub = Uses stricter judgment windows.
+mods-sudden-death = Sudden Death
+mods-sudden-death-sub = The play ends on the first Bad or Miss.
 
 rate-failed = Rate failed.
 rate-done = Rated successfully.
+action-queued = No connection, it will be sent later.
 
 need-update = Update Needed
 need-update-info-only-content = The chart's info has been updated.  Would you like to sync the update?
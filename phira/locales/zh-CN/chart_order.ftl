@@ -4,3 +4,8 @@ name = 名字正序
 rev-name = 名字倒序
 rating = 评分顺序
 rev-rating = 评分逆序
+
+query-invalid-level = 无效的难度范围：{ $value }
+query-invalid-format = 未知的谱面格式：{ $value }
+query-invalid-state = 未知的游玩状态：{ $value }
+query-invalid-sort = 未知的排序方式：{ $value }
@@ -28,6 +28,11 @@ warn-deny-tos-policy = 需接受条款才可使用线上服务
 
 open-in-web = 在网站中打开
 
+outbox-synced = 已发送 { $count } 个待发送的操作
+
+dl-bulk-done = 已下载 { $done } 张谱面，{ $failed } 张失败
+dl-checksum-mismatch = 下载的谱面与服务器上的不一致
+
 main-character-name = 夕
 main-character-intro =
   自断壁残垣中传来的歌声，被繁复乐章所萦绕的，韵律的形状。仿佛奇迹本身，无法用一切已知定律刻画的谜之少女。
@@ -15,3 +15,8 @@ btn-ongoing = 进行中
 btn-ended = 已结束
 
 ldb = 排行榜
+
+dl-queued = 已将 { $count } 张谱面加入下载队列
+dl-nothing = 所有谱面均已下载
+dl-failed = 获取待下载谱面失败
+dl-progress = 正在下载谱面 { $finished }/{ $total }
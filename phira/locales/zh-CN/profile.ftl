@@ -17,3 +17,19 @@ load-record-failed = 加载游玩记录失败
 last-login = 最近登录：{ $time }
 badge-admin = 管理员
 badge-sponsor = 赞助者
+
+history = 本地历史
+records = 在线成绩
+local-player = 本地玩家
+load-history-failed = 加载游玩历史失败
+history-empty = 还没有游玩记录
+history-summary = 游玩 { $attempts } 次 · 最近 { $time }
+history-back = 返回
+history-attempts = 共 { $attempts } 次，{ $first } 至 { $last }
+history-best = 最佳：{ $score }（{ $accuracy }%）
+history-latest = 最近：{ $score }（{ $accuracy }%）
+
+rating = 本地 RKS
+rating-empty = 还没有计入 RKS 的本地成绩
+rating-hint = 根据本地最佳成绩计算：最好的 19 张谱面，以及准确率 100% 的最高难度谱面（φ）。右侧为提升 RKS 所需的准确率。
+rating-target = ↑ { $accuracy }%
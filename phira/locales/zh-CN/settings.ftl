@@ -25,6 +25,22 @@ item-cache-size-loading = 加载中…
 item-cache-size = 缓存大小：{ $size }
 item-clear-cache-btn = 清除
 item-cache-cleared = 缓存已清除
+item-verify-charts = 检查谱面文件
+item-verify-charts-sub = 查找并修复损坏的谱面文件
+item-verify-charts-btn = 检查
+item-verify-charts-running = 检查中…
+item-verify-charts-done = 已检查 { $checked } 个文件，修复了 { $repaired } 个
+item-verify-charts-damaged = { $count } 个谱面已损坏，请重新下载或导入
+item-verify-charts-failed = 检查谱面文件失败
+item-verify-charts-modified = 谱面文件已修改
+item-verify-charts-modified-content = 有 { $count } 个谱面文件在 Phira 外被修改，是否恢复原文件？取消则保留修改。
+item-verify-charts-restored = 已恢复 { $count } 个文件
+item-outbox = 待发送的操作
+item-outbox-sub = { $pending } 个等待网络，{ $failed } 个失败
+item-outbox-btn = 重试
+item-outbox-discard = 丢弃失败的操作
+item-outbox-discard-sub = 丢弃被服务器拒绝的操作
+item-outbox-discard-btn = 丢弃
 item-insecure = 不安全模式
 item-insecure-sub = 当无法使用在线功能时可尝试该功能。这会使得你的连接不安全！
 item-enable-anys = 启用 Anys
@@ -32,6 +48,21 @@ item-enable-anys-sub = 使用 Anys 网关以提升连接质量
 item-anys-gateway = Anys 网关
 item-anys-gateway-sub = Anys 网关地址
 item-anys-gateway-invalid = 无效的网关地址
+item-server = 服务器
+item-server-sub = 每个服务器的登录状态相互独立
+item-server-switched = 已切换到 { $name }
+item-server-url = 服务器地址
+item-server-url-sub = 当前服务器的 API 地址
+item-server-invalid = 无效的服务器地址
+item-server-builtin = 无法修改官方服务器
+item-server-add = 添加服务器
+item-server-add-sub = 使用私有或本地服务器
+item-server-add-btn = 添加
+item-server-added = 已添加服务器
+item-server-remove = 删除服务器
+item-server-remove-sub = 删除当前服务器及其登录状态
+item-server-remove-btn = 删除
+item-server-removed = 已删除服务器
 
 item-adjust = 自动对齐时间
 item-adjust-sub = 自动调整延迟以同步音乐和谱面
@@ -39,6 +70,10 @@ item-music = 音乐音量
 item-sfx = 音效音量
 item-bgm = BGM 音量
 item-cali = 调整延迟
+item-auto-offset = 自动调整延迟
+item-auto-offset-sub = 根据最近的游玩自动应用建议延迟
+item-suggest-offset = 建议延迟
+item-suggest-offset-sub = 根据最近游玩的判定偏差计算，点击以应用
 item-preferred-sample-rate = 首选采样率
 
 item-show-acc = 显示实时准度
@@ -55,6 +90,8 @@ item-chart-debug = 谱面调试
 item-chart-debug-sub = 显示判定线编号和朝向
 item-touch-debug = 触摸调试
 item-touch-debug-sub = 游玩过程中显示触摸点
+item-res-pack-watch = 资源包热重载
+item-res-pack-watch-sub = 游玩过程中资源包文件变化时自动重新加载
 
 load-cali-failed = 加载音频失败
 
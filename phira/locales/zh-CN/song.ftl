@@ -9,9 +9,11 @@ dl-cancel = 取消
 dl-status-fetch = 加载信息
 dl-status-song = 下载歌曲
 dl-status-chart = 下载谱面
+dl-status-queued = 排队中
 dl-status-extract = 解压中
 dl-status-illustration = 下载插图
 dl-status-assets = 下载资源
+dl-status-verify = 校验中
 dl-status-saving = 保存中
 dl-failed = 下载失败
 dl-success = 下载完成
@@ -29,6 +31,16 @@ rate = 评分
 exercise = 练习
 offset = 调整延迟
 unlock = 播放解锁动画
+replay = 观看上次回放
+replay-none = 该谱面还没有回放
+replay-load-failed = 加载回放失败
+render-video = 导出自动游玩视频
+render-replay = 将上次回放导出为视频
+chart-offset-apply = 应用建议延迟
+chart-offset-applied = 该谱面延迟已设为 { $offset }ms
+chart-offset-reset = 使用全局延迟
+chart-offset-reset-done = 该谱面已改用全局延迟
+offset-auto-applied = 已根据最近的游玩将延迟调整为 { $offset }ms
 
 edit-cancel = 取消
 edit-save = 保存
@@ -103,11 +115,22 @@ mods-flip-x = X 轴反转
 mods-flip-x-sub = 在 X 轴上反转谱面
 mods-fade-out = 下隐
 mods-fade-out-sub = 音符在靠近判定线时会隐藏
-# mods-strict-judge = 严判模式  
-# mods-strict-judge-sub = 采用更加严格的判定  
+mods-flip-y = Y 轴反转
+mods-flip-y-sub = 在 Y 轴上反转谱面
+mods-fade-in = 上隐
+mods-fade-in-sub = 音符在接近判定线前才会出现
+mods-hide-hold-body = 隐藏长条
+mods-hide-hold-body-sub = 隐藏 Hold 的长条部分
+mods-random-speed = 随机流速
+mods-random-speed-sub = 每个音符的速度随机变化
+mods-strict-judge = 严判模式
+mods-strict-judge-sub = 采用更加严格的判定
+mods-sudden-death = 一击必杀
+mods-sudden-death-sub = 出现第一个 Bad 或 Miss 时结束游玩
 
 rate-failed = 评分失败
 rate-done = 评分成功
+action-queued = 网络不可用，将在稍后发送
 
 need-update = 谱面更新
 need-update-info-only-content = 谱面信息已更新，需要现在同步这些信息吗？
@@ -5,7 +5,7 @@ use crate::{
     page::{ChartItem, ChartType, Fader, Illustration},
     save_data,
     scene::{render_release_to_refresh, SongScene, MP_PANEL},
-    ttl,
+    store, ttl,
 };
 use anyhow::Result;
 use macroquad::prelude::*;
@@ -185,7 +185,8 @@ impl ChartsView {
                             let handled_by_mp = MP_PANEL.with(|it| {
                                 if let Some(panel) = it.borrow_mut().as_mut() {
                                     if panel.in_room() {
-                                        if let Some(id) = chart.info.id {
+                                        let foreign = chart.local_path.as_deref().is_some_and(|it| get_data().is_foreign(it));
+                                        if let Some(id) = chart.info.id.filter(|_| !foreign) {
                                             panel.select_chart(id);
                                             panel.show(rt);
                                         } else {
@@ -200,7 +201,7 @@ impl ChartsView {
                             if handled_by_mp {
                                 continue;
                             }
-                            let download_path = chart.info.id.map(|it| format!("download/{it}"));
+                            let download_path = chart.info.id.map(|it| get_data().download_path(it));
                             let scene = SongScene::new(
                                 chart.clone(),
                                 if let Some(path) = &chart.local_path {
@@ -257,9 +258,10 @@ impl ChartsView {
                         let path = if let Some(path) = &item.chart.as_ref().unwrap().local_path {
                             path.clone()
                         } else {
-                            format!("download/{}", item.chart.as_ref().unwrap().info.id.unwrap())
+                            data.download_path(item.chart.as_ref().unwrap().info.id.unwrap())
                         };
                         std::fs::remove_dir_all(format!("{}/{path}", dir::charts()?))?;
+                        store::remove(&path)?;
 
                         if let Some(chart) = data.find_chart_by_path(path.as_str()) {
                             data.charts.remove(chart);
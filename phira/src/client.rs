@@ -1,8 +1,12 @@
+mod api;
+pub use api::*;
+mod cache;
+pub use cache::*;
 mod model;
 pub use model::*;
 use tracing::debug;
 
-use crate::{anti_addiction_action, get_data, get_data_mut, save_data};
+use crate::{anti_addiction_action, data::DEFAULT_SERVER_URL, get_data, get_data_mut, save_data};
 use anyhow::{anyhow, bail, Context, Result};
 use arc_swap::ArcSwap;
 use once_cell::sync::Lazy;
@@ -17,10 +21,36 @@ pub static CLIENT_TOKEN: Lazy<ArcSwap<Option<String>>> = Lazy::new(|| ArcSwap::f
 
 static CLIENT: Lazy<ArcSwap<reqwest::Client>> = Lazy::new(|| ArcSwap::from_pointee(basic_client_builder().build().unwrap()));
 
+/// Base URL of the active server, published here since requests are made from worker threads while settings edit the profiles
+static API_URL: Lazy<ArcSwap<String>> = Lazy::new(|| ArcSwap::from_pointee(DEFAULT_SERVER_URL.to_owned()));
+
 pub struct Client;
 
-// const API_URL: &str = "http://localhost:2924";
-const API_URL: &str = "https://phira.5wyxi.com";
+/// Base URL of the active server profile, see [crate::data::ServerProfile]
+pub fn api_url() -> String {
+    API_URL.load().as_ref().clone()
+}
+
+/// Publishes the URL of the active profile to [api_url], has to be called on the main thread after it changes
+pub fn sync_api_url() {
+    API_URL.store(get_data().api_url().to_owned().into());
+}
+
+/// Switches to another server profile, dropping everything cached from the previous server
+pub fn switch_server(id: usize) -> Result<()> {
+    get_data_mut().switch_server(id);
+    sync_api_url();
+    sync_cache_state();
+    clear_caches();
+    set_access_token_sync(get_data().tokens.as_ref().map(|it| &*it.0))?;
+    save_data()
+}
+
+/// Removes a server profile, switching to the official server if it's the active one
+pub fn remove_server(id: usize) -> Result<()> {
+    get_data_mut().remove_server(id);
+    switch_server(get_data().server_id)
+}
 
 pub fn basic_client_builder() -> ClientBuilder {
     let policy = reqwest::redirect::Policy::custom(|attempt| {
@@ -74,7 +104,11 @@ async fn set_access_token(access_token: &str) -> Result<()> {
 }
 
 pub async fn recv_raw(request: RequestBuilder) -> Result<Response> {
-    let response = request.send().await?;
+    check_response(request.send().await?).await
+}
+
+/// Turns an unsuccessful response into an error, using the detail given by the server if any
+pub async fn check_response(response: Response) -> Result<Response> {
     if !response.status().is_success() {
         let status = response.status().as_str().to_owned();
         let text = response.text().await.context("failed to receive text")?;
@@ -118,10 +152,11 @@ impl Client {
     }
 
     pub fn request(method: Method, path: impl AsRef<str>) -> RequestBuilder {
-        CLIENT.load().request(method, API_URL.to_string() + path.as_ref())
+        CLIENT.load().request(method, api_url() + path.as_ref())
     }
 
     pub fn clear_cache<T: Object + 'static>(id: i32) -> Result<bool> {
+        API_CACHE.invalidate(&Self::object_request::<T>(id));
         let map = obtain_map_cache::<T>();
         let mut guard = map.lock().unwrap();
         let Some(actual_map) = guard.downcast_mut::<ObjectMap<T>>() else {
@@ -143,7 +178,7 @@ impl Client {
             drop(guard);
             drop(map);
         }
-        Self::fetch(id).await
+        Self::fetch_with(id, false).await?.ok_or_else(|| anyhow!("entry not found"))
     }
 
     pub async fn fetch<T: Object + 'static>(id: i32) -> Result<Arc<T>> {
@@ -151,7 +186,16 @@ impl Client {
     }
 
     pub async fn fetch_opt<T: Object + 'static>(id: i32) -> Result<Option<Arc<T>>> {
-        let value = Client::fetch_inner::<T>(id).await?;
+        Self::fetch_with(id, true).await
+    }
+
+    fn object_request<T: Object>(id: i32) -> RequestBuilder {
+        Self::get(format!("/{}/{id}", T::QUERY_PATH))
+    }
+
+    /// Fetches the object through the disk cache, see [ApiCache::get]
+    async fn fetch_with<T: Object + 'static>(id: i32, revalidate: bool) -> Result<Option<Arc<T>>> {
+        let value = API_CACHE.get_json::<T>(Self::object_request::<T>(id), revalidate).await?;
         let Some(value) = value else { return Ok(None) };
         let value = Arc::new(value);
         let map = obtain_map_cache::<T>();
@@ -163,24 +207,6 @@ impl Client {
         Ok(Some(value))
     }
 
-    async fn fetch_inner<T: Object>(id: i32) -> Result<Option<T>> {
-        let resp = Self::get(format!("/{}/{id}", T::QUERY_PATH)).send().await?;
-        if resp.status() == StatusCode::NOT_FOUND {
-            return Ok(None);
-        }
-        if !resp.status().is_success() {
-            let status = resp.status().as_str().to_owned();
-            let text = resp.text().await.context("failed to receive text")?;
-            if let Ok(what) = serde_json::from_str::<serde_json::Value>(&text) {
-                if let Some(detail) = what["detail"].as_str() {
-                    bail!("request failed ({status}): {detail}");
-                }
-            }
-            bail!("request failed ({status}): {text}");
-        }
-        Ok(Some(resp.json().await?))
-    }
-
     pub fn query<T: Object>() -> QueryBuilder<T> {
         QueryBuilder {
             queries: HashMap::new(),
@@ -243,7 +269,7 @@ impl Client {
 
     /// Returns Some(new_terms, modified) if the terms have been updated.
     pub async fn fetch_terms(modified: Option<&str>) -> Result<Option<(String, String)>> {
-        let mut req = CLIENT.load().get(format!("{API_URL}/terms/{}.txt", client_locale()));
+        let mut req = CLIENT.load().get(format!("{}/terms/{}.txt", api_url(), client_locale()));
         if let Some(modified) = modified {
             req = req.header(header::IF_MODIFIED_SINCE, header::HeaderValue::from_str(modified)?);
         }
@@ -322,10 +348,10 @@ impl<T: Object> QueryBuilder<T> {
             count: u64,
             results: Vec<T>,
         }
-        let res: PagedResult<T> = recv_raw(Client::get(format!("/{}{}", T::QUERY_PATH, self.suffix)).query(&self.queries))
+        let res: PagedResult<T> = API_CACHE
+            .get_json(Client::get(format!("/{}{}", T::QUERY_PATH, self.suffix)).query(&self.queries), true)
             .await?
-            .json()
-            .await?;
+            .ok_or_else(|| anyhow!("entry not found"))?;
         Ok((res.results, res.count))
     }
 }
@@ -0,0 +1,480 @@
+//! Typed REST API
+//!
+//! Scenes go through the [Api] trait instead of building requests themselves, so that the implementation can be
+//! replaced with [set_api]. [HttpApi] talks to the active server by default, and to the mock server in tests.
+
+#[cfg(test)]
+pub(crate) mod mock;
+
+use super::{check_response, ApiCache, Client, Collection, Record, API_CACHE, CLIENT};
+use anyhow::{anyhow, Error, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use prpr::scene::SimpleRecord;
+use reqwest::{Method, RequestBuilder, Response, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+const IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+
+/// Context of errors caused by the network or a temporarily unavailable server, the request may succeed later
+#[derive(Debug)]
+pub struct ServerUnreachable;
+
+impl fmt::Display for ServerUnreachable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("server unreachable")
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChartUploaded {
+    pub id: i32,
+    pub created: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChartUpdated {
+    pub updated: DateTime<Utc>,
+    pub chart_updated: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadRecord {
+    pub chart: i32,
+    /// Signed record produced by the game
+    pub token: String,
+    pub chart_updated: Option<DateTime<Utc>>,
+    pub mods: i32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadResult {
+    pub id: i32,
+    pub exp_delta: f64,
+    pub new_best: bool,
+    pub improvement: u32,
+    pub new_rks: f32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LeaderboardItem {
+    #[serde(flatten)]
+    pub record: Record,
+    pub rank: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Review {
+    pub approve: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReviewResult {
+    /// Whether the chart got enough approvals to pass
+    #[serde(default)]
+    pub passed: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Stabilize {
+    /// 0 to approve, 1 to approve as ranked, -1 to deny
+    pub kind: i8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StabilizeResult {
+    /// Non-zero once the request is done
+    pub status: i8,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[allow(dead_code)]
+pub struct EventStatus {
+    pub joined: bool,
+    pub rank: Option<i32>,
+    pub score: Option<i32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EventLdbItem {
+    pub player: i32,
+    pub rank: i32,
+    pub score: i32,
+}
+
+/// Endpoints of the REST API
+///
+/// Requests that may be queued and retried take an idempotency key, which is the same for every retry.
+#[async_trait]
+pub trait Api: Send + Sync {
+    async fn upload_chart(&self, file: &str) -> Result<ChartUploaded>;
+    async fn update_chart(&self, id: i32, file: &str, created: DateTime<Utc>) -> Result<ChartUpdated>;
+    async fn delete_chart(&self, id: i32) -> Result<()>;
+    async fn edit_tags(&self, id: i32, tags: &[String], key: &str) -> Result<()>;
+    async fn verify_checksum(&self, id: i32, checksum: &str) -> Result<bool>;
+
+    async fn upload_record(&self, record: &UploadRecord, key: &str) -> Result<UploadResult>;
+    async fn best_record(&self, chart: i32) -> Result<SimpleRecord>;
+    async fn leaderboard(&self, chart: i32, std: bool) -> Result<Vec<LeaderboardItem>>;
+
+    async fn my_rating(&self, chart: i32) -> Result<i16>;
+    async fn rate(&self, chart: i32, score: i16, key: &str) -> Result<()>;
+
+    async fn review(&self, chart: i32, review: &Review, key: &str) -> Result<ReviewResult>;
+    async fn request_stabilize(&self, chart: i32) -> Result<()>;
+    async fn stabilize(&self, chart: i32, stabilize: &Stabilize) -> Result<StabilizeResult>;
+    async fn stabilize_comment(&self, chart: i32, comment: &str) -> Result<()>;
+
+    async fn event_uml(&self, id: i32) -> Result<String>;
+    async fn event_status(&self, id: i32) -> Result<EventStatus>;
+    async fn event_leaderboard(&self, id: i32) -> Result<Vec<EventLdbItem>>;
+    async fn join_event(&self, id: i32) -> Result<()>;
+
+    async fn has_new_messages(&self, checked: DateTime<Utc>) -> Result<bool>;
+
+    async fn collection(&self, id: i32) -> Result<Collection>;
+}
+
+static API: Lazy<RwLock<Arc<dyn Api>>> = Lazy::new(|| RwLock::new(Arc::new(HttpApi::default())));
+
+pub fn api() -> Arc<dyn Api> {
+    Arc::clone(&API.read().unwrap())
+}
+
+pub fn set_api(api: Arc<dyn Api>) {
+    *API.write().unwrap() = api;
+}
+
+/// Sends the request, marking errors that may go away by retrying later with [ServerUnreachable]
+pub(crate) async fn send_request(request: RequestBuilder) -> Result<Response> {
+    let response = request.send().await.map_err(|err| Error::new(err).context(ServerUnreachable))?;
+    let status = response.status();
+    if status.is_server_error() || status == StatusCode::REQUEST_TIMEOUT || status == StatusCode::TOO_MANY_REQUESTS {
+        return Err(anyhow!("request failed ({status})").context(ServerUnreachable));
+    }
+    Ok(response)
+}
+
+/// [Api] over HTTP, using the login of the active profile
+pub struct HttpApi {
+    /// Base URL, the active server if `None`
+    url: Option<String>,
+    /// Where responses worth showing offline are kept
+    cache: Option<Arc<ApiCache>>,
+}
+
+impl Default for HttpApi {
+    fn default() -> Self {
+        Self {
+            url: None,
+            cache: Some(Arc::clone(&API_CACHE)),
+        }
+    }
+}
+
+impl HttpApi {
+    /// Talks to the given server, without caching responses
+    pub fn with_url(url: impl Into<String>) -> Self {
+        Self {
+            url: Some(url.into()),
+            cache: None,
+        }
+    }
+
+    pub fn with_cache(mut self, cache: Arc<ApiCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        match &self.url {
+            Some(url) => CLIENT.load().request(method, format!("{url}{path}")),
+            None => Client::request(method, path),
+        }
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        check_response(send_request(request).await?).await
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        Ok(self.send(self.request(Method::GET, path)).await?.json().await?)
+    }
+
+    /// Like [Self::get], falling back to the last response when the server can't be reached
+    async fn get_cached<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let Some(cache) = &self.cache else {
+            return self.get(path).await;
+        };
+        cache
+            .get_json(self.request(Method::GET, path), true)
+            .await?
+            .ok_or_else(|| anyhow!("request failed (404 Not Found)"))
+    }
+
+    async fn post<T: DeserializeOwned>(&self, path: &str, body: &(impl Serialize + Sync), key: Option<&str>) -> Result<T> {
+        let mut request = self.request(Method::POST, path).json(body);
+        if let Some(key) = key {
+            request = request.header(IDEMPOTENCY_KEY, key);
+        }
+        Ok(self.send(request).await?.json().await?)
+    }
+
+    /// Like [Self::post], ignoring the response
+    async fn post_unit(&self, path: &str, body: &(impl Serialize + Sync), key: Option<&str>) -> Result<()> {
+        let mut request = self.request(Method::POST, path).json(body);
+        if let Some(key) = key {
+            request = request.header(IDEMPOTENCY_KEY, key);
+        }
+        self.send(request).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Api for HttpApi {
+    async fn upload_chart(&self, file: &str) -> Result<ChartUploaded> {
+        self.post("/chart/upload", &json!({ "file": file }), None).await
+    }
+
+    async fn update_chart(&self, id: i32, file: &str, created: DateTime<Utc>) -> Result<ChartUpdated> {
+        let request = self.request(Method::PATCH, &format!("/chart/{id}")).json(&json!({
+            "file": file,
+            "created": created,
+        }));
+        Ok(self.send(request).await?.json().await?)
+    }
+
+    async fn delete_chart(&self, id: i32) -> Result<()> {
+        self.send(self.request(Method::DELETE, &format!("/chart/{id}"))).await?;
+        Ok(())
+    }
+
+    async fn edit_tags(&self, id: i32, tags: &[String], key: &str) -> Result<()> {
+        self.post_unit(&format!("/chart/{id}/edit-tags"), &json!({ "tags": tags }), Some(key))
+            .await
+    }
+
+    async fn verify_checksum(&self, id: i32, checksum: &str) -> Result<bool> {
+        #[derive(Deserialize)]
+        struct Resp {
+            ok: bool,
+        }
+        let resp: Resp = self.get(&format!("/chart/{id}/verify-cksum?checksum={checksum}")).await?;
+        Ok(resp.ok)
+    }
+
+    async fn upload_record(&self, record: &UploadRecord, key: &str) -> Result<UploadResult> {
+        self.post("/play/upload", record, Some(key)).await
+    }
+
+    async fn best_record(&self, chart: i32) -> Result<SimpleRecord> {
+        self.get_cached(&format!("/record/best/{chart}")).await
+    }
+
+    async fn leaderboard(&self, chart: i32, std: bool) -> Result<Vec<LeaderboardItem>> {
+        self.get_cached(&format!("/record/list15/{chart}?std={std}")).await
+    }
+
+    async fn my_rating(&self, chart: i32) -> Result<i16> {
+        #[derive(Deserialize)]
+        struct Resp {
+            score: i16,
+        }
+        let resp: Resp = self.get(&format!("/chart/{chart}/rate")).await?;
+        Ok(resp.score)
+    }
+
+    async fn rate(&self, chart: i32, score: i16, key: &str) -> Result<()> {
+        self.post_unit(&format!("/chart/{chart}/rate"), &json!({ "score": score }), Some(key))
+            .await
+    }
+
+    async fn review(&self, chart: i32, review: &Review, key: &str) -> Result<ReviewResult> {
+        self.post(&format!("/chart/{chart}/review"), review, Some(key)).await
+    }
+
+    async fn request_stabilize(&self, chart: i32) -> Result<()> {
+        self.post_unit(&format!("/chart/{chart}/req-stabilize"), &(), None).await
+    }
+
+    async fn stabilize(&self, chart: i32, stabilize: &Stabilize) -> Result<StabilizeResult> {
+        self.post(&format!("/chart/{chart}/stabilize"), stabilize, None).await
+    }
+
+    async fn stabilize_comment(&self, chart: i32, comment: &str) -> Result<()> {
+        self.post_unit(&format!("/chart/{chart}/stabilize-comment"), &json!({ "comment": comment }), None)
+            .await
+    }
+
+    async fn event_uml(&self, id: i32) -> Result<String> {
+        let request = self
+            .request(Method::GET, &format!("/event/{id}/uml"))
+            .query(&[("version", env!("CARGO_PKG_VERSION"))]);
+        Ok(self.send(request).await?.text().await?)
+    }
+
+    async fn event_status(&self, id: i32) -> Result<EventStatus> {
+        self.get(&format!("/event/{id}/status")).await
+    }
+
+    async fn event_leaderboard(&self, id: i32) -> Result<Vec<EventLdbItem>> {
+        self.get_cached(&format!("/event/{id}/list15")).await
+    }
+
+    async fn join_event(&self, id: i32) -> Result<()> {
+        self.post_unit(&format!("/event/{id}/join"), &(), None).await
+    }
+
+    async fn has_new_messages(&self, checked: DateTime<Utc>) -> Result<bool> {
+        #[derive(Deserialize)]
+        struct Resp {
+            has: bool,
+        }
+        let request = self.request(Method::GET, "/message/has_new").query(&[("checked", checked)]);
+        let resp: Resp = self.send(request).await?.json().await?;
+        Ok(resp.has)
+    }
+
+    async fn collection(&self, id: i32) -> Result<Collection> {
+        self.get_cached(&format!("/collection/{id}")).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mock::MockServer, *};
+    use crate::setup_test as setup;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    fn upload(chart: i32, score: i32) -> UploadRecord {
+        let record = SimpleRecord {
+            score,
+            accuracy: score as f32 / 1e6,
+            full_combo: false,
+        };
+        UploadRecord {
+            chart,
+            token: STANDARD.encode(serde_json::to_vec(&record).unwrap()),
+            chart_updated: None,
+            mods: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn upload_and_leaderboard() -> Result<()> {
+        setup();
+        let server = MockServer::start().await?;
+        let api = HttpApi::with_url(server.url());
+
+        let first = api.upload_record(&upload(7, 900000), "a").await?;
+        assert!(first.new_best);
+        assert!(!api.upload_record(&upload(7, 800000), "b").await?.new_best);
+        let best = api.upload_record(&upload(7, 950000), "c").await?;
+        assert!(best.new_best);
+        assert_eq!(best.improvement, 50000);
+        // a retry gets the original response instead of uploading the record again
+        assert_eq!(api.upload_record(&upload(7, 950000), "c").await?.id, best.id);
+        assert_eq!(server.state.lock().unwrap().records.len(), 3);
+
+        assert_eq!(api.best_record(7).await?.score, 950000);
+        let ldb = api.leaderboard(7, false).await?;
+        assert_eq!(ldb.len(), 1);
+        assert_eq!((ldb[0].rank, ldb[0].record.id), (1, best.id));
+        assert!(api.leaderboard(8, false).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn review_and_rate() -> Result<()> {
+        setup();
+        let server = MockServer::start().await?;
+        let api = HttpApi::with_url(server.url());
+
+        let deny = Review {
+            approve: false,
+            reason: Some("offsync".to_owned()),
+        };
+        let approve = Review { approve: true, reason: None };
+        assert!(!api.review(3, &deny, "a").await?.passed);
+        assert!(!api.review(3, &approve, "b").await?.passed);
+        assert!(api.review(3, &approve, "c").await?.passed);
+        let no_reason = Review {
+            approve: false,
+            reason: None,
+        };
+        let err = api.review(3, &no_reason, "d").await.unwrap_err();
+        assert!(!err.is::<ServerUnreachable>());
+        assert_eq!(server.state.lock().unwrap().denials[&3], ["offsync"]);
+
+        assert_eq!(api.stabilize(3, &Stabilize { kind: 1, reason: None }).await?.status, 1);
+
+        api.rate(3, 8, "e").await?;
+        assert_eq!(api.my_rating(3).await?, 8);
+        api.edit_tags(3, &["hard".to_owned()], "f").await?;
+        assert_eq!(server.state.lock().unwrap().tags[&3], ["hard"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unreachable_server() -> Result<()> {
+        setup();
+        let server = MockServer::start().await?;
+        let api = HttpApi::with_url(server.url());
+
+        server.set_unavailable(true);
+        assert!(api.upload_record(&upload(1, 1000), "a").await.unwrap_err().is::<ServerUnreachable>());
+        server.set_unavailable(false);
+        // the key wasn't used up by the failed attempt
+        assert!(api.upload_record(&upload(1, 1000), "a").await?.new_best);
+        // not found is an answer, the server is reachable
+        assert!(!api.best_record(2).await.unwrap_err().is::<ServerUnreachable>());
+
+        let url = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+            format!("http://{}", listener.local_addr()?)
+        };
+        let err = HttpApi::with_url(url).event_status(1).await.unwrap_err();
+        assert!(err.is::<ServerUnreachable>());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cached_leaderboard() -> Result<()> {
+        setup();
+        let server = MockServer::start().await?;
+        let dir = tempfile::tempdir()?;
+        let api = HttpApi::with_url(server.url()).with_cache(Arc::new(ApiCache::new(dir.path())));
+
+        api.upload_record(&upload(5, 990000), "a").await?;
+        assert_eq!(api.leaderboard(5, false).await?.len(), 1);
+        // unchanged, the cached response is revalidated instead of sent again
+        assert_eq!(api.leaderboard(5, false).await?.len(), 1);
+        assert_eq!(server.state.lock().unwrap().not_modified, 1);
+
+        server.set_unavailable(true);
+        let ldb = api.leaderboard(5, false).await?;
+        assert_eq!(ldb[0].record.score, 990000);
+        // never fetched, nothing to fall back to
+        assert!(api.best_record(5).await.unwrap_err().is::<ServerUnreachable>());
+        server.set_unavailable(false);
+
+        api.upload_record(&upload(5, 995000), "b").await?;
+        assert_eq!(api.leaderboard(5, false).await?[0].record.score, 995000);
+        Ok(())
+    }
+}
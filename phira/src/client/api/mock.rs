@@ -0,0 +1,358 @@
+//! In-process mock of the REST server, serving the endpoints of [Api](super::Api) from memory
+//!
+//! The mock has a single player with id [PLAYER]. Since it can't verify signed records, it takes the token of an
+//! uploaded record to be the base64 encoded JSON of a [SimpleRecord].
+
+use super::{EventStatus, LeaderboardItem, Review, ReviewResult, Stabilize, StabilizeResult, UploadRecord, UploadResult};
+use crate::client::{Ptr, Record};
+use anyhow::{anyhow, bail, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use prpr::scene::SimpleRecord;
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+pub const PLAYER: i32 = 1;
+/// Approvals needed for a chart to pass review
+pub const APPROVALS_TO_PASS: usize = 2;
+
+#[derive(Default)]
+pub struct MockState {
+    pub records: Vec<Record>,
+    pub ratings: HashMap<i32, i16>,
+    pub tags: HashMap<i32, Vec<String>>,
+    pub approvals: HashMap<i32, usize>,
+    pub denials: HashMap<i32, Vec<String>>,
+    pub joined_events: HashSet<i32>,
+    /// Responses of requests with an idempotency key, replayed when the same key is seen again
+    responses: HashMap<String, (StatusCode, Value)>,
+    /// Answer every request with 503 Service Unavailable
+    pub unavailable: bool,
+    /// Number of GET requests answered with 304 Not Modified
+    pub not_modified: usize,
+    /// Files served at `/files/<name>`
+    pub files: HashMap<String, Vec<u8>>,
+    /// Answer requests for a range of a file with the whole file, like servers that don't support ranges
+    pub ignore_range: bool,
+    /// Offsets asked for by requests of files, `None` for those without a range
+    pub file_ranges: Vec<Option<u64>>,
+    /// SHA-256 of the chart file of each chart, checked by `verify-cksum`
+    pub checksums: HashMap<i32, String>,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    key: Option<String>,
+    if_none_match: Option<String>,
+    /// Start of `Range: bytes=<start>-`
+    range: Option<u64>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn json<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
+
+pub struct MockServer {
+    url: String,
+    pub state: Arc<Mutex<MockState>>,
+}
+
+impl MockServer {
+    pub async fn start() -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let server_state = Arc::clone(&state);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let state = Arc::clone(&server_state);
+                tokio::spawn(async move {
+                    let _ = serve(stream, state).await;
+                });
+            }
+        });
+        Ok(Self { url, state })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn set_unavailable(&self, unavailable: bool) {
+        self.state.lock().unwrap().unavailable = unavailable;
+    }
+}
+
+async fn serve(stream: TcpStream, state: Arc<Mutex<MockState>>) -> Result<()> {
+    let mut stream = BufReader::new(stream);
+    let mut line = String::new();
+    stream.read_line(&mut line).await?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        bail!("invalid request line");
+    };
+    let method = method.to_owned();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let path = path.to_owned();
+    let query = query
+        .split('&')
+        .filter_map(|it| it.split_once('='))
+        .map(|(k, v)| (k.to_owned(), v.to_owned()))
+        .collect();
+
+    let mut length = 0;
+    let mut key = None;
+    let mut if_none_match = None;
+    let mut range = None;
+    loop {
+        line.clear();
+        stream.read_line(&mut line).await?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else { continue };
+        match name.to_ascii_lowercase().as_str() {
+            "content-length" => length = value.trim().parse()?,
+            "idempotency-key" => key = Some(value.trim().to_owned()),
+            "if-none-match" => if_none_match = Some(value.trim().to_owned()),
+            "range" => {
+                let start = value.trim().strip_prefix("bytes=").and_then(|it| it.strip_suffix('-'));
+                range = Some(start.ok_or_else(|| anyhow!("unsupported range"))?.parse()?);
+            }
+            _ => {}
+        }
+    }
+    let mut body = vec![0; length];
+    stream.read_exact(&mut body).await?;
+
+    let request = Request {
+        method,
+        path,
+        query,
+        key,
+        if_none_match,
+        range,
+        body,
+    };
+    if let Some(name) = request.path.strip_prefix("/files/") {
+        let (status, headers, body) = serve_file(&mut state.lock().unwrap(), &request, name);
+        let head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\n{headers}Connection: close\r\n\r\n",
+            status.as_u16(),
+            status.canonical_reason().unwrap_or_default(),
+            body.len(),
+        );
+        stream.get_mut().write_all(head.as_bytes()).await?;
+        stream.get_mut().write_all(&body).await?;
+        stream.get_mut().shutdown().await?;
+        return Ok(());
+    }
+    let (status, body, etag) = {
+        let mut state = state.lock().unwrap();
+        let (mut status, value) = respond(&mut state, &request);
+        let mut body = value.to_string();
+        let mut etag = String::new();
+        if request.method == "GET" && status == StatusCode::OK {
+            let tag = format!("\"{:x}\"", Sha256::digest(&body));
+            if request.if_none_match.as_ref() == Some(&tag) {
+                state.not_modified += 1;
+                status = StatusCode::NOT_MODIFIED;
+                body.clear();
+            }
+            etag = format!("ETag: {tag}\r\n");
+        }
+        (status, body, etag)
+    };
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{etag}Connection: close\r\n\r\n{body}",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or_default(),
+        body.len(),
+    );
+    stream.get_mut().write_all(response.as_bytes()).await?;
+    stream.get_mut().shutdown().await?;
+    Ok(())
+}
+
+/// Answers with the file, or with the part of it from the start of the range
+fn serve_file(state: &mut MockState, request: &Request, name: &str) -> (StatusCode, String, Vec<u8>) {
+    state.file_ranges.push(request.range);
+    if state.unavailable {
+        return (StatusCode::SERVICE_UNAVAILABLE, String::new(), Vec::new());
+    }
+    let Some(file) = state.files.get(name) else {
+        return (StatusCode::NOT_FOUND, String::new(), Vec::new());
+    };
+    let len = file.len() as u64;
+    match request.range.filter(|_| !state.ignore_range) {
+        Some(start) if start >= len => (StatusCode::RANGE_NOT_SATISFIABLE, format!("Content-Range: bytes */{len}\r\n"), Vec::new()),
+        Some(start) => (StatusCode::PARTIAL_CONTENT, format!("Content-Range: bytes {start}-{}/{len}\r\n", len - 1), file[start as usize..].to_vec()),
+        None => (StatusCode::OK, String::new(), file.clone()),
+    }
+}
+
+fn respond(state: &mut MockState, request: &Request) -> (StatusCode, Value) {
+    if state.unavailable {
+        return (StatusCode::SERVICE_UNAVAILABLE, json!({ "detail": "unavailable" }));
+    }
+    if let Some(response) = request.key.as_ref().and_then(|key| state.responses.get(key)) {
+        return response.clone();
+    }
+    let response = match route(state, request) {
+        Ok(Some(value)) => (StatusCode::OK, value),
+        Ok(None) => (StatusCode::NOT_FOUND, json!({ "detail": "not found" })),
+        Err(err) => (StatusCode::BAD_REQUEST, json!({ "detail": err.to_string() })),
+    };
+    if let Some(key) = &request.key {
+        state.responses.insert(key.clone(), response.clone());
+    }
+    response
+}
+
+fn leaderboard(state: &MockState, chart: i32) -> Vec<LeaderboardItem> {
+    let mut best = state
+        .records
+        .iter()
+        .filter(|it| it.chart.id == chart && it.best)
+        .cloned()
+        .collect::<Vec<_>>();
+    best.sort_by_key(|it| -it.score);
+    best.into_iter()
+        .enumerate()
+        .map(|(index, record)| LeaderboardItem {
+            record,
+            rank: index as u32 + 1,
+        })
+        .collect()
+}
+
+fn route(state: &mut MockState, request: &Request) -> Result<Option<Value>> {
+    let segments: Vec<_> = request.path.trim_start_matches('/').split('/').collect();
+    let id = |index: usize| -> Result<i32> { Ok(segments.get(index).ok_or_else(|| anyhow!("missing id"))?.parse()?) };
+    Ok(Some(match (request.method.as_str(), segments.as_slice()) {
+        ("POST", ["play", "upload"]) => {
+            let upload: UploadRecord = request.json()?;
+            let simple: SimpleRecord = serde_json::from_slice(&STANDARD.decode(&upload.token)?)?;
+            let previous = state
+                .records
+                .iter_mut()
+                .find(|it| it.chart.id == upload.chart && it.player.id == PLAYER && it.best);
+            let previous_score = previous.as_ref().map_or(0, |it| it.score);
+            let new_best = simple.score > previous_score;
+            if new_best {
+                if let Some(previous) = previous {
+                    previous.best = false;
+                }
+            }
+            let id = state.records.len() as i32 + 1;
+            state.records.push(Record {
+                id,
+                player: Ptr::new(PLAYER),
+                chart: Ptr::new(upload.chart),
+                score: simple.score,
+                accuracy: simple.accuracy,
+                perfect: 0,
+                good: 0,
+                bad: 0,
+                miss: 0,
+                speed: 1.,
+                max_combo: 0,
+                full_combo: simple.full_combo,
+                best: new_best,
+                mods: upload.mods,
+                time: Utc::now(),
+                std: None,
+                std_score: None,
+            });
+            json!(UploadResult {
+                id,
+                exp_delta: 0.,
+                new_best,
+                improvement: (simple.score - previous_score).max(0) as u32,
+                new_rks: 0.,
+            })
+        }
+        ("GET", ["record", "best", _]) => {
+            let chart = id(2)?;
+            let Some(best) = state.records.iter().find(|it| it.chart.id == chart && it.player.id == PLAYER && it.best) else {
+                return Ok(None);
+            };
+            json!(SimpleRecord {
+                score: best.score,
+                accuracy: best.accuracy,
+                full_combo: best.full_combo,
+            })
+        }
+        ("GET", ["record", "list15", _]) => {
+            if request.query.get("std").is_some_and(|it| it == "true") {
+                bail!("std leaderboard is not supported");
+            }
+            json!(leaderboard(state, id(2)?))
+        }
+        ("GET", ["chart", _, "verify-cksum"]) => {
+            json!({ "ok": state.checksums.get(&id(1)?) == request.query.get("checksum") })
+        }
+        ("GET", ["chart", _, "rate"]) => json!({ "score": state.ratings.get(&id(1)?).copied().unwrap_or_default() }),
+        ("POST", ["chart", _, "rate"]) => {
+            let score = request.json::<Value>()?["score"].as_i64().ok_or_else(|| anyhow!("missing score"))?;
+            if !(0..=10).contains(&score) {
+                bail!("invalid score");
+            }
+            state.ratings.insert(id(1)?, score as i16);
+            json!({})
+        }
+        ("POST", ["chart", _, "edit-tags"]) => {
+            let mut body: Value = request.json()?;
+            state.tags.insert(id(1)?, serde_json::from_value(body["tags"].take())?);
+            json!({})
+        }
+        ("POST", ["chart", _, "review"]) => {
+            let review: Review = request.json()?;
+            let chart = id(1)?;
+            if review.approve {
+                *state.approvals.entry(chart).or_default() += 1;
+            } else {
+                let reason = review.reason.ok_or_else(|| anyhow!("missing reason"))?;
+                state.denials.entry(chart).or_default().push(reason);
+            }
+            json!(ReviewResult {
+                passed: state.approvals.get(&chart).copied().unwrap_or_default() >= APPROVALS_TO_PASS,
+            })
+        }
+        ("POST", ["chart", _, "stabilize"]) => {
+            let stabilize: Stabilize = request.json()?;
+            json!(StabilizeResult {
+                status: if stabilize.kind < 0 { -1 } else { 1 },
+            })
+        }
+        ("GET", ["event", _, "status"]) => {
+            let id = id(1)?;
+            json!(EventStatus {
+                joined: state.joined_events.contains(&id),
+                rank: None,
+                score: None,
+            })
+        }
+        ("POST", ["event", _, "join"]) => {
+            state.joined_events.insert(id(1)?);
+            json!({})
+        }
+        _ => return Ok(None),
+    }))
+}
@@ -0,0 +1,172 @@
+//! Persistent cache of API responses
+//!
+//! Responses are stored on disk with their `ETag` and an expiry time. Fresh entries are used without asking the server,
+//! stale ones are revalidated with `If-None-Match`, and when the server can't be reached the stale entry is used anyway,
+//! so that pages seen before can still be browsed offline.
+
+use super::{check_response, send_request, ServerUnreachable};
+use crate::{dir, get_data};
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use once_cell::sync::Lazy;
+use reqwest::{header, RequestBuilder, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicI32, Ordering},
+        Arc,
+    },
+};
+use tracing::warn;
+
+/// How long a response stays fresh if the server doesn't say
+const DEFAULT_MAX_AGE: i64 = 5 * 60;
+
+pub static API_CACHE: Lazy<Arc<ApiCache>> =
+    Lazy::new(|| Arc::new(ApiCache::new(format!("{}/api-cache", dir::cache().unwrap_or_else(|_| ".".to_owned())))));
+
+/// [Config::offline_mode](prpr::config::Config::offline_mode) as of the last [sync_cache_state]
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+/// Id of the logged in user as of the last [sync_cache_state], 0 if nobody is
+static USER_ID: AtomicI32 = AtomicI32::new(0);
+
+/// Publishes the offline mode and the logged in user to requests running on other threads, has to be called on the
+/// main thread after either changes
+pub fn sync_cache_state() {
+    let data = get_data();
+    OFFLINE.store(data.config.offline_mode, Ordering::Relaxed);
+    USER_ID.store(data.me.as_ref().map_or(0, |it| it.id), Ordering::Relaxed);
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    etag: Option<String>,
+    expires: DateTime<Utc>,
+    body: String,
+}
+
+pub struct ApiCache {
+    dir: PathBuf,
+}
+
+impl ApiCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    async fn read(&self, key: &str) -> Option<Entry> {
+        let data = cacache::read(&self.dir, key).await.ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    async fn write(&self, key: &str, entry: &Entry) {
+        let res = async { anyhow::Ok(cacache::write(&self.dir, key, serde_json::to_vec(entry)?).await?) }.await;
+        if let Err(err) = res {
+            warn!(?err, %key, "failed to write api cache");
+        }
+    }
+
+    /// Sends a GET request through the cache, returning `None` if the server answers 404
+    ///
+    /// With `revalidate`, the server is asked even if the cached response is still fresh; it's only skipped when it
+    /// can't be reached. Errors caused by the network are marked with [ServerUnreachable] like those of
+    /// [HttpApi](super::HttpApi).
+    pub async fn get(&self, request: RequestBuilder, revalidate: bool) -> Result<Option<String>> {
+        let Some(key) = key(&request) else {
+            return Ok(Some(check_response(send_request(request).await?).await?.text().await?));
+        };
+
+        let cached = self.read(&key).await;
+        let now = Utc::now();
+        if let Some(entry) = &cached {
+            if OFFLINE.load(Ordering::Relaxed) || (!revalidate && entry.expires > now) {
+                return Ok(Some(entry.body.clone()));
+            }
+        }
+
+        let mut request = request;
+        if let Some(etag) = cached.as_ref().and_then(|it| it.etag.as_deref()) {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        let response = match send_request(request).await {
+            Ok(response) => response,
+            Err(err) => {
+                return match cached {
+                    Some(entry) if err.is::<ServerUnreachable>() => {
+                        warn!(?err, %key, "server unreachable, using cached response");
+                        Ok(Some(entry.body))
+                    }
+                    _ => Err(err),
+                };
+            }
+        };
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND {
+            let _ = cacache::remove(&self.dir, &key).await;
+            return Ok(None);
+        }
+        let expires = now + max_age(response.headers());
+        if status == StatusCode::NOT_MODIFIED {
+            if let Some(mut entry) = cached {
+                entry.expires = expires;
+                self.write(&key, &entry).await;
+                return Ok(Some(entry.body));
+            }
+        }
+
+        let response = check_response(response).await?;
+        let etag = response.headers().get(header::ETAG).and_then(|it| it.to_str().ok()).map(str::to_owned);
+        let no_store = cache_control(response.headers()).any(|it| it == "no-store");
+        let entry = Entry {
+            etag,
+            expires,
+            body: response.text().await?,
+        };
+        if !no_store {
+            self.write(&key, &entry).await;
+        }
+        Ok(Some(entry.body))
+    }
+
+    /// Drops the cached response to the request, so that it's fetched again next time
+    pub fn invalidate(&self, request: &RequestBuilder) {
+        if let Some(key) = key(request) {
+            let _ = cacache::remove_sync(&self.dir, key);
+        }
+    }
+
+    pub async fn get_json<T: DeserializeOwned>(&self, request: RequestBuilder, revalidate: bool) -> Result<Option<T>> {
+        Ok(match self.get(request, revalidate).await? {
+            Some(body) => Some(serde_json::from_str(&body)?),
+            None => None,
+        })
+    }
+}
+
+fn key(request: &RequestBuilder) -> Option<String> {
+    let url = request.try_clone()?.build().ok()?.url().to_string();
+    // responses may depend on who's asking
+    Some(format!("{}@{url}", USER_ID.load(Ordering::Relaxed)))
+}
+
+fn cache_control(headers: &header::HeaderMap) -> impl Iterator<Item = &str> {
+    headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|it| it.to_str().ok())
+        .flat_map(|it| it.split(','))
+        .map(str::trim)
+}
+
+fn max_age(headers: &header::HeaderMap) -> Duration {
+    let mut max_age = DEFAULT_MAX_AGE;
+    for directive in cache_control(headers) {
+        if directive == "no-cache" {
+            max_age = 0;
+        } else if let Some(Ok(value)) = directive.strip_prefix("max-age=").map(str::parse) {
+            max_age = value;
+        }
+    }
+    Duration::seconds(max_age)
+}
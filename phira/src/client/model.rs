@@ -16,7 +16,7 @@ pub use record::*;
 mod user;
 pub use user::*;
 
-use super::{basic_client_builder, Client, API_URL, CLIENT_TOKEN};
+use super::{api_url, basic_client_builder, Client, CLIENT_TOKEN};
 use crate::{
     dir, get_data,
     images::{THUMBNAIL_HEIGHT, THUMBNAIL_WIDTH},
@@ -49,6 +49,12 @@ pub(crate) fn obtain_map_cache<T: Object + 'static>() -> Arc<Mutex<Box<dyn Any +
     )
 }
 
+/// Drops every cached object, e.g. after switching to another server
+pub(crate) fn clear_caches() {
+    CACHES.lock().unwrap().clear();
+    UserManager::clear_all();
+}
+
 pub trait Object: Clone + DeserializeOwned + Send + Sync {
     const QUERY_PATH: &'static str;
 
@@ -141,21 +147,9 @@ impl<T: Object + 'static> Ptr<T> {
         Client::fetch_opt(self.id).await
     }
 
+    #[inline]
     pub async fn load(&self) -> Result<Arc<T>> {
-        // sync locks can not be held accross await point
-        {
-            let map = obtain_map_cache::<T>();
-            let mut guard = map.lock().unwrap();
-            let Some(actual_map) = guard.downcast_mut::<ObjectMap<T>>() else {
-                unreachable!()
-            };
-            if let Some(value) = actual_map.get(&self.id) {
-                return Ok(Arc::clone(value));
-            }
-            drop(guard);
-            drop(map);
-        }
-        self.fetch().await
+        Client::load(self.id).await
     }
 }
 impl<T: Object + 'static> Serialize for Ptr<T> {
@@ -184,9 +178,10 @@ impl File {
         let mut req = basic_client_builder().build().unwrap().get(&self.url);
         // TODO: thread safety?
         if get_data().enable_anys {
-            if let Some(path) = self.url.strip_prefix(API_URL) {
+            let api_url = api_url();
+            if let Some(path) = self.url.strip_prefix(&api_url) {
                 if let Some(rest_path) = path.strip_prefix("/files/") {
-                    let url = format!("{API_URL}/anys/{rest_path}");
+                    let url = format!("{api_url}/anys/{rest_path}");
                     req = basic_client_builder().build().unwrap().get(url);
                 }
             }
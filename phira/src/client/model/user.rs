@@ -141,6 +141,11 @@ impl UserManager {
         Ok(())
     }
 
+    pub fn clear_all() {
+        TASKS.blocking_lock().clear();
+        RESULTS.blocking_lock().clear();
+    }
+
     pub fn request(id: i32) {
         let mut tasks = TASKS.blocking_lock();
         if tasks.contains_key(&id) {
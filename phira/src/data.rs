@@ -1,12 +1,16 @@
 use crate::{
     client::{Character, Ptr, User},
     dir,
+    outbox::OutboxEntry,
+    store,
 };
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use prpr::{
+    calibrate::OffsetHistory,
     config::{Config, Mods},
-    info::ChartInfo,
+    info::{ChartFormat, ChartInfo},
+    rating::{ChartRecord, Rating},
     scene::SimpleRecord,
 };
 use serde::{Deserialize, Serialize};
@@ -161,6 +165,11 @@ pub struct BriefChartInfo {
     pub chart_updated: Option<DateTime<Utc>>,
     #[serde(default)]
     pub has_unlock: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Format declared in `info.yml`, `None` if it is detected when loading
+    #[serde(default)]
+    pub format: Option<ChartFormat>,
 }
 
 impl From<ChartInfo> for BriefChartInfo {
@@ -179,6 +188,8 @@ impl From<ChartInfo> for BriefChartInfo {
             updated: info.updated,
             chart_updated: info.chart_updated,
             has_unlock: info.unlock_video.is_some(),
+            tags: info.tags,
+            format: info.format,
         }
     }
 }
@@ -193,6 +204,58 @@ pub struct LocalChart {
     pub mods: Mods,
     #[serde(default)]
     pub played_unlock: bool,
+    /// Overrides [Config::offset] for this chart
+    #[serde(default)]
+    pub offset: Option<f32>,
+    #[serde(default)]
+    pub offset_history: OffsetHistory,
+    /// URL of the server the id of the chart belongs to
+    #[serde(default)]
+    pub server: Option<String>,
+}
+
+/// The official server, always available as the first profile
+pub const DEFAULT_SERVER_URL: &str = "https://phira.5wyxi.com";
+
+/// Directory of a server under `download`, the official server keeps its charts right in `download`
+fn server_key(url: &str) -> Option<String> {
+    (url != DEFAULT_SERVER_URL).then(|| url.split_once("://").map_or(url, |it| it.1).replace(['/', '\\', ':'], "_"))
+}
+
+/// Local path of a chart downloaded from the server at `url`
+pub fn download_path(url: &str, id: i32) -> String {
+    match server_key(url) {
+        Some(key) => format!("download/{key}/{id}"),
+        None => format!("download/{id}"),
+    }
+}
+
+/// A REST server and the login state on it
+///
+/// The state of the active profile lives in [Data::tokens], [Data::me] and [Data::local_records] instead, and is
+/// moved back here when switching to another profile.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ServerProfile {
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub tokens: Option<(String, String)>,
+    #[serde(default)]
+    pub me: Option<User>,
+    #[serde(default)]
+    pub local_records: HashMap<String, Option<SimpleRecord>>,
+}
+
+impl ServerProfile {
+    pub fn new(name: String, url: String) -> Self {
+        Self {
+            name,
+            url,
+            tokens: None,
+            me: None,
+            local_records: HashMap::new(),
+        }
+    }
 }
 
 fn default_anys_gateway() -> String {
@@ -225,6 +288,15 @@ pub struct Data {
 
     #[serde(default)]
     pub favorites: Favorites,
+
+    /// Recent plays on this device, for [Config::offset] suggestions
+    pub offset_history: OffsetHistory,
+
+    pub servers: Vec<ServerProfile>,
+    pub server_id: usize,
+
+    /// Requests waiting for the network, see [crate::outbox]
+    pub outbox: Vec<OutboxEntry>,
 }
 
 impl Data {
@@ -240,45 +312,74 @@ impl Data {
             if occurred.contains(&filename) {
                 continue;
             }
-            let path = entry.path();
-            let Ok(mut fs) = prpr::fs::fs_from_file(&path) else {
-                continue;
-            };
-            let result = prpr::fs::load_info(fs.deref_mut()).await;
-            if let Ok(info) = result {
+            if let Some(info) = Self::load_brief_info(&entry.path(), &filename).await {
                 self.charts.push(LocalChart {
-                    info: BriefChartInfo { id: None, ..info.into() },
+                    info: BriefChartInfo { id: None, ..info },
                     local_path: filename,
                     record: None,
                     mods: Mods::default(),
                     played_unlock: false,
+                    offset: None,
+                    offset_history: OffsetHistory::default(),
+                    server: None,
                 });
             }
         }
-        for entry in std::fs::read_dir(dir::downloaded_charts()?)? {
-            let entry = entry?;
-            let filename = entry.file_name();
-            let filename = filename.to_str().unwrap();
-            let Ok(id): Result<i32, _> = filename.parse() else { continue };
-            let filename = format!("download/{filename}");
-            if occurred.contains(&filename) {
-                continue;
+        let downloaded = dir::downloaded_charts()?;
+        let mut dirs = vec![(downloaded.clone(), "download".to_owned(), None)];
+        for server in &self.servers {
+            if let Some(key) = server_key(&server.url) {
+                dirs.push((format!("{downloaded}/{key}"), format!("download/{key}"), Some(server.url.clone())));
             }
-            let path = entry.path();
-            let Ok(mut fs) = prpr::fs::fs_from_file(&path) else {
+        }
+        for (path, prefix, server) in dirs {
+            if !Path::new(&path).exists() {
                 continue;
-            };
-            let result = prpr::fs::load_info(fs.deref_mut()).await;
-            if let Ok(info) = result {
-                self.charts.push(LocalChart {
-                    info: BriefChartInfo { id: Some(id), ..info.into() },
-                    local_path: filename,
-                    record: None,
-                    mods: Mods::default(),
-                    played_unlock: false,
-                });
+            }
+            for entry in std::fs::read_dir(path)? {
+                let entry = entry?;
+                let filename = entry.file_name();
+                let filename = filename.to_str().unwrap();
+                let Ok(id): Result<i32, _> = filename.parse() else { continue };
+                let filename = format!("{prefix}/{filename}");
+                if occurred.contains(&filename) {
+                    continue;
+                }
+                if let Some(info) = Self::load_brief_info(&entry.path(), &filename).await {
+                    self.charts.push(LocalChart {
+                        info: BriefChartInfo { id: Some(id), ..info },
+                        local_path: filename,
+                        record: None,
+                        mods: Mods::default(),
+                        played_unlock: false,
+                        offset: None,
+                        offset_history: OffsetHistory::default(),
+                        server: server.clone(),
+                    });
+                }
             }
         }
+        for chart in &mut self.charts {
+            if chart.info.id.is_some() && chart.server.is_none() {
+                // got before servers were recorded, when there was only the official one
+                chart.server = Some(DEFAULT_SERVER_URL.to_owned());
+            }
+        }
+        store::forget_missing()?;
+        let unindexed: Vec<_> = self
+            .charts
+            .iter()
+            .filter(|it| !store::is_indexed(&it.local_path))
+            .map(|it| (it.local_path.clone(), it.info.clone()))
+            .collect();
+        if !unindexed.is_empty() {
+            // hashing large libraries takes a while, don't block startup
+            tokio::task::spawn_blocking(move || {
+                for (local_path, info) in unindexed {
+                    store::index(&local_path, info);
+                }
+            });
+        }
         let respacks: HashSet<_> = self.respacks.iter().cloned().collect();
         for entry in std::fs::read_dir(dir::respacks()?)? {
             let entry = entry?;
@@ -302,9 +403,104 @@ impl Data {
         }
         self.config.init();
         self.favorites.ensure_default();
+        self.ensure_servers();
+        self.drop_stranded_requests();
         Ok(())
     }
 
+    /// Metadata of a chart that is not in the list, from the store index if possible
+    async fn load_brief_info(path: &Path, local_path: &str) -> Option<BriefChartInfo> {
+        if let Some(info) = store::cached_info(local_path) {
+            return Some(info);
+        }
+        let mut fs = prpr::fs::fs_from_file(path).ok()?;
+        prpr::fs::load_info(fs.deref_mut()).await.ok().map(Into::into)
+    }
+
+    /// Rating computed from the best records of local charts
+    pub fn local_rating(&self) -> Rating {
+        Rating::compute(self.charts.iter().filter_map(|it| {
+            it.record.as_ref().map(|record| ChartRecord {
+                key: it.local_path.clone(),
+                difficulty: it.info.difficulty,
+                accuracy: record.accuracy,
+            })
+        }))
+    }
+
+    fn ensure_servers(&mut self) {
+        if self.servers.is_empty() {
+            self.servers.push(ServerProfile::new("Phira".to_owned(), DEFAULT_SERVER_URL.to_owned()));
+        }
+        if self.server_id >= self.servers.len() {
+            self.server_id = 0;
+        }
+    }
+
+    /// Base URL of the REST API of the active profile
+    pub fn api_url(&self) -> &str {
+        self.servers.get(self.server_id).map_or(DEFAULT_SERVER_URL, |it| &it.url)
+    }
+
+    /// Makes another profile active, swapping the login state of both profiles
+    pub fn switch_server(&mut self, id: usize) {
+        if id == self.server_id || id >= self.servers.len() {
+            return;
+        }
+        let old = &mut self.servers[self.server_id];
+        old.tokens = self.tokens.take();
+        old.me = self.me.take();
+        old.local_records = std::mem::take(&mut self.local_records);
+        let new = &mut self.servers[id];
+        self.tokens = new.tokens.take();
+        self.me = new.me.take();
+        self.local_records = std::mem::take(&mut new.local_records);
+        self.server_id = id;
+    }
+
+    /// Removes a profile, switching to the official server first if it's the active one. The official server can't be removed.
+    pub fn remove_server(&mut self, id: usize) {
+        if id == 0 || id >= self.servers.len() {
+            return;
+        }
+        if id == self.server_id {
+            self.switch_server(0);
+        }
+        self.servers.remove(id);
+        if self.server_id > id {
+            self.server_id -= 1;
+        }
+        self.drop_stranded_requests();
+    }
+
+    /// Drops queued requests made on a server no profile points to anymore, since they could never be sent. Called
+    /// after a profile is removed.
+    pub fn drop_stranded_requests(&mut self) {
+        let Self { servers, outbox, .. } = self;
+        outbox.retain(|entry| servers.iter().any(|it| it.url == entry.server));
+    }
+
+    /// Local path of a chart downloaded from the active server
+    pub fn download_path(&self, id: i32) -> String {
+        download_path(self.api_url(), id)
+    }
+
+    /// Whether the id of the local chart belongs to another server than the active one, which makes online features unusable
+    pub fn is_foreign(&self, local_path: &str) -> bool {
+        self.charts
+            .iter()
+            .find(|it| it.local_path == local_path)
+            .is_some_and(|chart| chart.info.id.is_some() && chart.server.as_deref().is_some_and(|it| it != self.api_url()))
+    }
+
+    /// Records that the id of the chart belongs to the active server, after it's downloaded from or uploaded to it
+    pub fn set_chart_server(&mut self, local_path: &str) {
+        let url = self.api_url().to_owned();
+        if let Some(chart) = self.charts.iter_mut().find(|it| it.local_path == local_path) {
+            chart.server = Some(url);
+        }
+    }
+
     pub fn find_chart_by_path(&self, local_path: &str) -> Option<usize> {
         self.charts.iter().position(|local| local.local_path == local_path)
     }
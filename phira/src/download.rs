@@ -0,0 +1,670 @@
+//! Download manager for charts
+//!
+//! Charts are queued with [enqueue] and downloaded in the background, [MAX_CONCURRENT] at a time. The archive is first
+//! written to a partial file, so that an interrupted download continues where it stopped with a range request. Once
+//! extracted, the chart file is checked against the server before the chart is installed.
+
+use crate::{
+    charts_view::NEED_UPDATE,
+    client::{api, basic_client_builder, Chart, CLIENT_TOKEN},
+    data::LocalChart,
+    dir, get_data, get_data_mut, save_data,
+    scene::SongScene,
+    store, ttl,
+};
+use anyhow::{anyhow, bail, Result};
+use futures_util::StreamExt;
+use hex::ToHex;
+use prpr::{calibrate::OffsetHistory, config::Mods, ext::unzip_into, info::ChartInfo, scene::show_message, task::Task};
+use reqwest::{header, StatusCode};
+use sha2::{Digest, Sha256};
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Number of charts downloaded at the same time
+pub const MAX_CONCURRENT: usize = 3;
+
+#[derive(Clone)]
+pub enum Stage {
+    Queued,
+    Downloading,
+    Extracting,
+    Verifying,
+    Saving,
+    /// Installed at the local path, with the info read from the archive
+    Done(String, ChartInfo),
+    Failed(String),
+    Cancelled,
+}
+
+impl Stage {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Done(..) | Self::Failed(_) | Self::Cancelled)
+    }
+}
+
+/// State of a download, shared between the manager and whoever shows it
+pub struct DownloadState {
+    stage: Mutex<Stage>,
+    progress: Mutex<Option<f32>>,
+    cancelled: AtomicBool,
+}
+
+impl DownloadState {
+    fn new() -> Self {
+        Self {
+            stage: Mutex::new(Stage::Queued),
+            progress: Mutex::default(),
+            cancelled: AtomicBool::default(),
+        }
+    }
+
+    pub fn stage(&self) -> Stage {
+        self.stage.lock().unwrap().clone()
+    }
+
+    fn set_stage(&self, stage: Stage) {
+        *self.stage.lock().unwrap() = stage;
+        *self.progress.lock().unwrap() = None;
+    }
+
+    /// Progress of the current stage, if known
+    pub fn progress(&self) -> Option<f32> {
+        *self.progress.lock().unwrap()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn check_cancelled(&self) -> Result<()> {
+        if self.is_cancelled() {
+            bail!("download cancelled");
+        }
+        Ok(())
+    }
+}
+
+struct Job {
+    entity: Chart,
+    /// Server the chart is downloaded from, the active one when it was queued
+    server: String,
+    /// Where the chart is installed, see [Data::download_path](crate::data::Data::download_path)
+    local_path: String,
+    /// Part of a bulk download, reported in the summary rather than on its own
+    bulk: bool,
+    state: Arc<DownloadState>,
+    task: Option<Task<Result<ChartInfo>>>,
+}
+
+#[derive(Default)]
+struct Manager {
+    jobs: Vec<Job>,
+    /// Bulk downloads finished since the queue was last empty
+    done: usize,
+    failed: usize,
+}
+
+impl Manager {
+    fn enqueue(&mut self, entity: Chart, bulk: bool) -> Arc<DownloadState> {
+        let data = get_data();
+        let local_path = data.download_path(entity.id);
+        if let Some(job) = self.jobs.iter_mut().find(|it| it.local_path == local_path && !it.state.is_cancelled()) {
+            job.bulk &= bulk;
+            return Arc::clone(&job.state);
+        }
+        let state = Arc::new(DownloadState::new());
+        self.jobs.push(Job {
+            entity,
+            server: data.api_url().to_owned(),
+            local_path,
+            bulk,
+            state: Arc::clone(&state),
+            task: None,
+        });
+        state
+    }
+
+    fn update(&mut self) {
+        let Self { jobs, done, failed } = self;
+        for job in jobs.iter_mut() {
+            let Some(task) = &mut job.task else {
+                if job.state.is_cancelled() {
+                    job.state.set_stage(Stage::Cancelled);
+                }
+                continue;
+            };
+            let Some(res) = task.take() else { continue };
+            job.task = None;
+            let stage = match res.and_then(|info| install(job, info)) {
+                Ok(stage) => {
+                    *done += job.bulk as usize;
+                    stage
+                }
+                Err(_) if job.state.is_cancelled() => Stage::Cancelled,
+                Err(err) => {
+                    warn!(?err, id = job.entity.id, "failed to download chart");
+                    *failed += job.bulk as usize;
+                    Stage::Failed(format!("{err:?}"))
+                }
+            };
+            job.state.set_stage(stage);
+        }
+        jobs.retain(|it| !it.state.stage().is_finished());
+
+        // a cancelled download may still be writing the partial file, its replacement waits for it to stop
+        let running: Vec<_> = jobs.iter().filter(|it| it.task.is_some()).map(|it| it.local_path.clone()).collect();
+        for job in jobs
+            .iter_mut()
+            .filter(|it| it.task.is_none() && !running.contains(&it.local_path))
+            .take(MAX_CONCURRENT.saturating_sub(running.len()))
+        {
+            let entity = job.entity.clone();
+            let local_path = job.local_path.clone();
+            let state = Arc::clone(&job.state);
+            job.task = Some(Task::new(async move { download(entity, local_path, state).await }));
+        }
+
+        if jobs.is_empty() && *done + *failed != 0 {
+            show_message(ttl!("dl-bulk-done", "done" => *done, "failed" => *failed)).ok();
+            *done = 0;
+            *failed = 0;
+        }
+    }
+}
+
+thread_local! {
+    static MANAGER: RefCell<Manager> = RefCell::default();
+}
+
+/// Queues the chart for download, or returns the state of the download already queued
+pub fn enqueue(entity: Chart) -> Arc<DownloadState> {
+    MANAGER.with(|it| it.borrow_mut().enqueue(entity, false))
+}
+
+/// The download of the chart from the active server, if it's queued
+pub fn find(id: i32) -> Option<Arc<DownloadState>> {
+    let local_path = get_data().download_path(id);
+    MANAGER.with(|it| {
+        it.borrow()
+            .jobs
+            .iter()
+            .find(|job| job.local_path == local_path && !job.state.is_cancelled())
+            .map(|job| Arc::clone(&job.state))
+    })
+}
+
+/// Queues every chart that isn't downloaded yet or has been updated since, returning how many were queued
+pub fn enqueue_all(charts: impl IntoIterator<Item = Chart>) -> usize {
+    let data = get_data();
+    MANAGER.with(|it| {
+        let mut manager = it.borrow_mut();
+        let mut count = 0;
+        for entity in charts {
+            let local_path = data.download_path(entity.id);
+            let up_to_date = data
+                .charts
+                .iter()
+                .any(|it| it.local_path == local_path && it.info.chart_updated == Some(entity.chart_updated));
+            if !up_to_date {
+                manager.enqueue(entity, true);
+                count += 1;
+            }
+        }
+        count
+    })
+}
+
+/// Cancels every bulk download
+pub fn cancel_bulk() {
+    MANAGER.with(|it| {
+        for job in it.borrow().jobs.iter().filter(|it| it.bulk) {
+            job.state.cancel();
+        }
+    });
+}
+
+/// Number of bulk downloads finished and in total, if there are any left
+pub fn bulk_progress() -> Option<(usize, usize)> {
+    MANAGER.with(|it| {
+        let manager = it.borrow();
+        let left = manager.jobs.iter().filter(|it| it.bulk).count();
+        let finished = manager.done + manager.failed;
+        (left != 0).then_some((finished, finished + left))
+    })
+}
+
+/// Installs finished downloads and starts queued ones. Called every frame.
+pub fn update() {
+    MANAGER.with(|it| it.borrow_mut().update());
+}
+
+fn install(job: &Job, info: ChartInfo) -> Result<Stage> {
+    let Job { entity, server, local_path, .. } = job;
+    if let Some(index) = get_data().find_chart_by_path(local_path) {
+        get_data_mut().charts[index].server = Some(server.clone());
+        SongScene::global_update_chart_info(local_path, entity.to_info())?;
+    } else {
+        get_data_mut().charts.push(LocalChart {
+            info: entity.to_info(),
+            local_path: local_path.clone(),
+            record: None,
+            mods: Mods::default(),
+            played_unlock: false,
+            offset: None,
+            offset_history: OffsetHistory::default(),
+            server: Some(server.clone()),
+        });
+        NEED_UPDATE.store(true, Ordering::Relaxed);
+        save_data()?;
+    }
+    Ok(Stage::Done(local_path.clone(), info))
+}
+
+async fn download(entity: Chart, local_path: String, state: Arc<DownloadState>) -> Result<ChartInfo> {
+    let dir = format!("{}/downloads", dir::cache()?);
+    tokio::fs::create_dir_all(&dir).await?;
+    // a new version of the chart can't continue from the old one
+    let name = local_path.strip_prefix("download/").unwrap_or(&local_path).replace('/', "_");
+    let part_name = format!("{name}-{}.part", entity.chart_updated.timestamp());
+    if let Err(err) = remove_stale_parts(&dir, &name, &part_name).await {
+        warn!("failed to remove stale partial downloads of {local_path}: {err:?}");
+    }
+    let part = PathBuf::from(format!("{dir}/{part_name}"));
+
+    state.set_stage(Stage::Downloading);
+    let fetched = fetch_resumable(&entity.file.url, &part, &state).await?;
+    let mut res = extract(&entity, &local_path, &part, &state).await;
+    if !fetched && res.is_err() && !state.is_cancelled() {
+        // the server refused to continue and what we have doesn't pass the checksum, start over
+        tokio::fs::remove_file(&part).await?;
+        state.set_stage(Stage::Downloading);
+        fetch_resumable(&entity.file.url, &part, &state).await?;
+        res = extract(&entity, &local_path, &part, &state).await;
+    }
+    // whether it's installed or broken, there's nothing left to resume
+    let _ = tokio::fs::remove_file(&part).await;
+    res
+}
+
+/// Removes partial downloads of other versions of the chart named `name`, which can't be continued
+async fn remove_stale_parts(dir: &str, name: &str, current: &str) -> Result<()> {
+    let prefix = format!("{name}-");
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let file_name = entry.file_name();
+        let Some(file_name) = file_name.to_str() else {
+            continue;
+        };
+        let stale = file_name != current
+            && file_name
+                .strip_prefix(&prefix)
+                .and_then(|it| it.strip_suffix(".part"))
+                .is_some_and(|it| it.parse::<i64>().is_ok());
+        if stale {
+            tokio::fs::remove_file(entry.path()).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Downloads into `path`, continuing from what's already there
+///
+/// Returns `false` without touching the file if the server refuses to continue from its end, which happens when the file
+/// is already complete. It's up to the caller to check it and to start over if it's not.
+async fn fetch_resumable(url: &str, path: &Path, state: &DownloadState) -> Result<bool> {
+    let mut offset = tokio::fs::metadata(path).await.map_or(0, |it| it.len());
+    let mut req = basic_client_builder().build()?.get(url);
+    if let Some(token) = CLIENT_TOKEN.load().as_ref() {
+        req = req.header(header::AUTHORIZATION, format!("Bearer {token}"));
+    }
+    if offset != 0 {
+        req = req.header(header::RANGE, format!("bytes={offset}-"));
+    }
+    let response = req.send().await?;
+    let response = match response.status() {
+        StatusCode::PARTIAL_CONTENT => response,
+        StatusCode::RANGE_NOT_SATISFIABLE if offset != 0 => return Ok(false),
+        _ => {
+            offset = 0;
+            response.error_for_status()?
+        }
+    };
+
+    let total = response.content_length().map(|it| it + offset);
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(offset != 0)
+        .truncate(offset == 0)
+        .open(path)
+        .await?;
+    let res = async {
+        let mut count = offset;
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            state.check_cancelled()?;
+            let chunk = chunk?;
+            file.write_all(&chunk).await?;
+            count += chunk.len() as u64;
+            if let Some(total) = total {
+                *state.progress.lock().unwrap() = Some(count.min(total) as f32 / total as f32);
+            }
+        }
+        Ok(())
+    }
+    .await;
+    // keep what we've got for the next attempt
+    file.flush().await?;
+    res.map(|_| true)
+}
+
+async fn extract(entity: &Chart, local_path: &str, archive: &Path, state: &DownloadState) -> Result<ChartInfo> {
+    state.set_stage(Stage::Extracting);
+    let path = PathBuf::from(format!("{}/{}", dir::downloaded_charts()?, Uuid::new_v4()));
+    tokio::fs::create_dir(&path).await?;
+    let res = async {
+        let dir = prpr::dir::Dir::new(&path)?;
+        unzip_into(std::fs::File::open(archive)?, &dir, false)?;
+        let mut info = ChartInfo::from_yaml(&String::from_utf8_lossy(&dir.read("info.yml")?))?;
+
+        state.set_stage(Stage::Verifying);
+        let cksum: String = Sha256::digest(dir.read(&info.chart)?).encode_hex();
+        if !api().verify_checksum(entity.id, &cksum).await? {
+            return Err(anyhow!(ttl!("dl-checksum-mismatch")));
+        }
+
+        state.set_stage(Stage::Saving);
+        info.id = Some(entity.id);
+        info.created = Some(entity.created);
+        info.updated = Some(entity.updated);
+        info.chart_updated = Some(entity.chart_updated);
+        info.uploader = Some(entity.uploader.id);
+        serde_yaml::to_writer(dir.create("info.yml")?, &info)?;
+        drop(dir);
+        state.check_cancelled()?;
+
+        let to_path = format!("{}/{local_path}", dir::charts()?);
+        let to_path = Path::new(&to_path);
+        if to_path.is_file() {
+            tokio::fs::remove_file(to_path).await?;
+        } else if to_path.exists() {
+            tokio::fs::remove_dir_all(to_path).await?;
+        } else {
+            tokio::fs::create_dir_all(to_path.parent().unwrap()).await?;
+        }
+        tokio::fs::rename(&path, to_path).await?;
+        store::index(local_path, entity.to_info());
+        Ok(info)
+    }
+    .await;
+    if res.is_err() {
+        let _ = tokio::fs::remove_dir_all(&path).await;
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{mock::MockServer, set_api, HttpApi};
+    use chrono::Utc;
+    use once_cell::sync::Lazy;
+    use serde_json::json;
+    use std::{io::Write, time::Duration};
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    /// The API and the charts directory are global
+    static LOCK: Lazy<tokio::sync::Mutex<()>> = Lazy::new(Default::default);
+
+    async fn setup() -> Result<(tokio::sync::MutexGuard<'static, ()>, MockServer)> {
+        crate::setup_test();
+        let guard = LOCK.lock().await;
+        let server = MockServer::start().await?;
+        set_api(Arc::new(HttpApi::with_url(server.url())));
+        Ok((guard, server))
+    }
+
+    fn chart(id: i32, url: String) -> Chart {
+        let now = Utc::now();
+        serde_json::from_value(json!({
+            "id": id,
+            "name": "Test",
+            "level": "IN Lv.1",
+            "difficulty": 1.,
+            "charter": "",
+            "composer": "",
+            "illustrator": "",
+            "description": null,
+            "ranked": false,
+            "reviewed": true,
+            "stable": false,
+            "stableRequest": false,
+            "illustration": "",
+            "preview": "",
+            "file": url,
+            "uploader": 1,
+            "created": now,
+            "updated": now,
+            "chartUpdated": now,
+            "rating": null,
+        }))
+        .unwrap()
+    }
+
+    fn archive(chart: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut zip = ZipWriter::new(std::io::Cursor::new(&mut bytes));
+        let options = SimpleFileOptions::default();
+        zip.start_file("info.yml", options).unwrap();
+        zip.write_all(b"name: Test\nchart: chart.json\nmusic: song.ogg\nillustration: bg.png\n")
+            .unwrap();
+        zip.start_file("chart.json", options).unwrap();
+        zip.write_all(chart).unwrap();
+        zip.finish().unwrap();
+        bytes
+    }
+
+    fn content() -> Vec<u8> {
+        (0..=255).cycle().take(4096).collect()
+    }
+
+    /// Downloads the file of the mock on top of a partial file holding `partial`
+    async fn resume(server: &MockServer, partial: &[u8]) -> Result<Vec<u8>> {
+        server.state.lock().unwrap().files.insert("chart.zip".to_owned(), content());
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("chart.zip.part");
+        std::fs::write(&path, partial)?;
+        let state = DownloadState::new();
+        fetch_resumable(&format!("{}/files/chart.zip", server.url()), &path, &state).await?;
+        assert_eq!(state.progress(), Some(1.));
+        Ok(std::fs::read(path)?)
+    }
+
+    #[tokio::test]
+    async fn resume_partial() -> Result<()> {
+        let (_guard, server) = setup().await?;
+        assert_eq!(resume(&server, &content()[..1000]).await?, content());
+        assert_eq!(server.state.lock().unwrap().file_ranges, [Some(1000)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn range_ignored() -> Result<()> {
+        let (_guard, server) = setup().await?;
+        server.state.lock().unwrap().ignore_range = true;
+        // the whole file comes back, what was there must not be kept
+        assert_eq!(resume(&server, &[0xff; 1000]).await?, content());
+        assert_eq!(server.state.lock().unwrap().file_ranges, [Some(1000)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn range_not_satisfiable() -> Result<()> {
+        let (_guard, server) = setup().await?;
+        server.state.lock().unwrap().files.insert("chart.zip".to_owned(), content());
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("chart.zip.part");
+        let mut partial = content();
+        partial.extend_from_slice(&[0xff; 1000]);
+        std::fs::write(&path, &partial)?;
+        // it's up to the caller to tell whether the file is complete
+        assert!(!fetch_resumable(&format!("{}/files/chart.zip", server.url()), &path, &DownloadState::new()).await?);
+        assert_eq!(std::fs::read(path)?, partial);
+        assert_eq!(server.state.lock().unwrap().file_ranges, [Some(5096)]);
+        Ok(())
+    }
+
+    fn part_path(entity: &Chart) -> PathBuf {
+        PathBuf::from(format!("{}/downloads/{}-{}.part", dir::cache().unwrap(), entity.id, entity.chart_updated.timestamp()))
+    }
+
+    #[tokio::test]
+    async fn complete_part() -> Result<()> {
+        let (_guard, server) = setup().await?;
+        let chart_file = b"{\"formatVersion\":3}";
+        let archive = archive(chart_file);
+        {
+            let mut state = server.state.lock().unwrap();
+            state.files.insert("9300.zip".to_owned(), archive.clone());
+            state.checksums.insert(9300, hex::encode(Sha256::digest(chart_file)));
+            state.checksums.insert(9301, hex::encode(Sha256::digest(chart_file)));
+        }
+
+        // the whole archive is already there, the server refusing to continue must not throw it away
+        let entity = chart(9300, format!("{}/files/9300.zip", server.url()));
+        std::fs::create_dir_all(part_path(&entity).parent().unwrap())?;
+        std::fs::write(part_path(&entity), &archive)?;
+        download(entity.clone(), "download/9300".to_owned(), Arc::new(DownloadState::new())).await?;
+        assert_eq!(server.state.lock().unwrap().file_ranges, [Some(archive.len() as u64)]);
+        assert!(!part_path(&entity).exists());
+
+        // a file as long as the archive that isn't it is downloaded again
+        let entity = chart(9301, format!("{}/files/9300.zip", server.url()));
+        std::fs::write(part_path(&entity), vec![0xff; archive.len()])?;
+        let info = download(entity, "download/9301".to_owned(), Arc::new(DownloadState::new())).await?;
+        assert_eq!(info.id, Some(9301));
+        assert_eq!(server.state.lock().unwrap().file_ranges[1..], [Some(archive.len() as u64), None]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stale_parts_removed() -> Result<()> {
+        let (_guard, server) = setup().await?;
+        let chart_file = b"{\"formatVersion\":3}";
+        {
+            let mut state = server.state.lock().unwrap();
+            state.files.insert("9400.zip".to_owned(), archive(chart_file));
+            state.checksums.insert(9400, hex::encode(Sha256::digest(chart_file)));
+        }
+        let entity = chart(9400, format!("{}/files/9400.zip", server.url()));
+        let dir = format!("{}/downloads", dir::cache()?);
+        std::fs::create_dir_all(&dir)?;
+        let stale = format!("{dir}/9400-{}.part", entity.chart_updated.timestamp() - 1);
+        // another chart whose name starts the same way
+        let other = format!("{dir}/94001-{}.part", entity.chart_updated.timestamp() - 1);
+        std::fs::write(&stale, [0xff; 100])?;
+        std::fs::write(&other, [0xff; 100])?;
+
+        download(entity, "download/9400".to_owned(), Arc::new(DownloadState::new())).await?;
+        assert!(!Path::new(&stale).exists());
+        assert!(Path::new(&other).exists());
+        std::fs::remove_file(other)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn checksum_mismatch() -> Result<()> {
+        let (_guard, server) = setup().await?;
+        let chart_file = b"{\"formatVersion\":3}";
+        {
+            let mut state = server.state.lock().unwrap();
+            state.files.insert("9001.zip".to_owned(), archive(chart_file));
+            state.checksums.insert(9001, "0".repeat(64));
+            state.checksums.insert(9002, hex::encode(Sha256::digest(chart_file)));
+        }
+        let installed = |id: i32| Path::new(&dir::charts().unwrap()).join(format!("download/{id}")).exists();
+
+        let url = format!("{}/files/9001.zip", server.url());
+        let state = Arc::new(DownloadState::new());
+        assert!(download(chart(9001, url.clone()), "download/9001".to_owned(), Arc::clone(&state)).await.is_err());
+        assert!(!installed(9001));
+        // nothing is left behind in the download directory either
+        for entry in std::fs::read_dir(dir::downloaded_charts()?)? {
+            assert!(entry?.file_name().to_string_lossy().parse::<i32>().is_ok());
+        }
+
+        let info = download(chart(9002, url), "download/9002".to_owned(), Arc::new(DownloadState::new())).await?;
+        assert_eq!(info.id, Some(9002));
+        assert!(installed(9002));
+        Ok(())
+    }
+
+    /// Calls [Manager::update] until `done` holds
+    async fn update_until(manager: &mut Manager, mut done: impl FnMut(&Manager) -> bool) {
+        for _ in 0..500 {
+            manager.update();
+            assert!(manager.jobs.iter().filter(|it| it.task.is_some()).count() <= MAX_CONCURRENT);
+            if done(manager) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("timed out");
+    }
+
+    #[tokio::test]
+    async fn concurrency_cap() -> Result<()> {
+        let (_guard, server) = setup().await?;
+        let mut manager = Manager::default();
+        let states: Vec<_> = (0..5)
+            .map(|id| manager.enqueue(chart(9100 + id, format!("{}/files/missing", server.url())), false))
+            .collect();
+        manager.update();
+        assert_eq!(manager.jobs.iter().filter(|it| it.task.is_some()).count(), MAX_CONCURRENT);
+
+        update_until(&mut manager, |it| it.jobs.is_empty()).await;
+        assert!(states.iter().all(|it| matches!(it.stage(), Stage::Failed(_))));
+        assert_eq!(server.state.lock().unwrap().file_ranges.len(), 5);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cancelled_job_blocks_replacement() -> Result<()> {
+        let (_guard, server) = setup().await?;
+        server.state.lock().unwrap().files.insert("9200.zip".to_owned(), content());
+        let url = format!("{}/files/9200.zip", server.url());
+        let mut manager = Manager::default();
+        let first = manager.enqueue(chart(9200, url.clone()), false);
+        // the task can't run before this test yields, so it's still running when cancelled
+        manager.update();
+        first.cancel();
+        let second = manager.enqueue(chart(9200, url), false);
+        assert!(!Arc::ptr_eq(&first, &second));
+
+        manager.update();
+        assert_eq!(manager.jobs.iter().filter(|it| it.task.is_some()).count(), 1);
+        assert!(matches!(second.stage(), Stage::Queued));
+
+        update_until(&mut manager, |manager| manager.jobs.iter().all(|it| !Arc::ptr_eq(&it.state, &first))).await;
+        assert!(matches!(first.stage(), Stage::Cancelled));
+        manager.update();
+        assert!(manager.jobs.iter().any(|it| Arc::ptr_eq(&it.state, &second) && it.task.is_some()));
+        second.cancel();
+        update_until(&mut manager, |it| it.jobs.is_empty()).await;
+        Ok(())
+    }
+}
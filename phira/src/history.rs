@@ -0,0 +1,150 @@
+//! Local history of every finished play
+//!
+//! Attempts reported by the game (see [prpr::history]) are appended as JSON lines to one file per chart under
+//! `data/history`, so recording a play never rewrites the ones before it.
+
+use crate::dir;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use prpr::{config::Mods, history::PlayAttempt};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+};
+use tracing::warn;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// See [LocalChart::local_path](crate::data::LocalChart::local_path)
+    pub local_path: String,
+    /// Name of the chart when it was played
+    pub name: String,
+    #[serde(flatten)]
+    pub attempt: PlayAttempt,
+}
+
+fn file_name(local_path: &str) -> String {
+    format!("{}.jsonl", local_path.replace(['/', '\\', ':'], "_"))
+}
+
+pub fn record(entry: &HistoryEntry) -> Result<()> {
+    let path = format!("{}/{}", dir::history()?, file_name(&entry.local_path));
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    Ok(())
+}
+
+fn read(path: &Path) -> Result<Vec<HistoryEntry>> {
+    let mut res = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        // a line cut short by a crash should not take the rest of the history with it
+        match serde_json::from_str(&line) {
+            Ok(entry) => res.push(entry),
+            Err(err) => warn!(?err, ?path, "skipping broken history entry"),
+        }
+    }
+    Ok(res)
+}
+
+/// Filter over the history, see [HistoryQuery::run]
+#[derive(Clone, Default)]
+pub struct HistoryQuery {
+    pub local_path: Option<String>,
+    /// Only attempts on this exact version of the chart
+    pub chart_hash: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Only attempts played with all of these mods
+    pub mods: Mods,
+    /// Only attempts played at normal speed or faster
+    pub full_speed: bool,
+    /// Keep only the latest entries
+    pub limit: Option<usize>,
+}
+
+impl HistoryQuery {
+    pub fn chart(local_path: impl Into<String>) -> Self {
+        Self {
+            local_path: Some(local_path.into()),
+            ..Default::default()
+        }
+    }
+
+    fn matches(&self, entry: &HistoryEntry) -> bool {
+        let attempt = &entry.attempt;
+        self.chart_hash.as_ref().map_or(true, |it| *it == attempt.chart_hash)
+            && self.since.map_or(true, |it| attempt.time >= it)
+            && self.until.map_or(true, |it| attempt.time < it)
+            && attempt.mods.contains(self.mods)
+            && (!self.full_speed || attempt.speed >= 1.0 - 1e-3)
+    }
+
+    /// Returns the matching entries from the oldest to the latest
+    pub fn run(&self) -> Result<Vec<HistoryEntry>> {
+        let dir = dir::history()?;
+        let mut res = Vec::new();
+        if let Some(local_path) = &self.local_path {
+            let path = Path::new(&dir).join(file_name(local_path));
+            if path.exists() {
+                res = read(&path)?;
+            }
+        } else {
+            for entry in std::fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.extension().map_or(false, |it| it == "jsonl") {
+                    res.extend(read(&path)?);
+                }
+            }
+        }
+        res.retain(|it| self.matches(it));
+        res.sort_by_key(|it| it.attempt.time);
+        if let Some(limit) = self.limit {
+            res.drain(..res.len().saturating_sub(limit));
+        }
+        Ok(res)
+    }
+}
+
+/// Attempts on a single chart, summed up
+pub struct ChartHistory {
+    pub local_path: String,
+    /// Name of the latest attempt
+    pub name: String,
+    pub attempts: usize,
+    pub best_score: u32,
+    pub full_combo: bool,
+    pub last_played: DateTime<Utc>,
+}
+
+/// Groups `entries` by chart, the most recently played first
+pub fn summarize(entries: &[HistoryEntry]) -> Vec<ChartHistory> {
+    let mut charts: HashMap<&str, ChartHistory> = HashMap::new();
+    for entry in entries {
+        let result = &entry.attempt.result;
+        let chart = charts.entry(&entry.local_path).or_insert_with(|| ChartHistory {
+            local_path: entry.local_path.clone(),
+            name: entry.name.clone(),
+            attempts: 0,
+            best_score: 0,
+            full_combo: false,
+            last_played: entry.attempt.time,
+        });
+        chart.attempts += 1;
+        chart.best_score = chart.best_score.max(result.score);
+        chart.full_combo |= result.max_combo == result.num_of_notes;
+        if entry.attempt.time >= chart.last_played {
+            chart.last_played = entry.attempt.time;
+            chart.name = entry.name.clone();
+        }
+    }
+    let mut res: Vec<_> = charts.into_values().collect();
+    res.sort_by(|x, y| y.last_played.cmp(&x.last_played));
+    res
+}
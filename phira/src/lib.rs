@@ -10,15 +10,19 @@ mod anim;
 mod charts_view;
 mod client;
 mod data;
+mod download;
+mod history;
 mod icons;
 mod images;
 mod login;
 mod mp;
+mod outbox;
 mod page;
 mod popup;
 mod rate;
 mod resource;
 mod scene;
+mod store;
 mod tabs;
 mod tags;
 mod threed;
@@ -85,6 +89,8 @@ pub fn sync_data() {
     if get_data().language.is_none() {
         get_data_mut().language = Some(LANGS[GLOBAL.order.lock().unwrap()[0]].to_owned());
     }
+    client::sync_api_url();
+    client::sync_cache_state();
     let _ = client::set_access_token_sync(get_data().tokens.as_ref().map(|it| &*it.0));
 }
 
@@ -107,6 +113,17 @@ pub fn save_data() -> Result<()> {
     Ok(())
 }
 
+/// Sets up default data in a temporary data directory, shared by every test
+#[cfg(test)]
+fn setup_test() {
+    static INIT: std::sync::Once = std::sync::Once::new();
+    INIT.call_once(|| {
+        let dir = tempfile::tempdir().unwrap().keep();
+        *DATA_PATH.lock().unwrap() = Some(dir.to_string_lossy().into_owned());
+        set_data(Data::default());
+    });
+}
+
 mod dir {
     use anyhow::Result;
 
@@ -156,6 +173,22 @@ mod dir {
     pub fn respacks() -> Result<String> {
         ensure("data/respack")
     }
+
+    pub fn store() -> Result<String> {
+        ensure("data/store")
+    }
+
+    pub fn replays() -> Result<String> {
+        ensure("data/replays")
+    }
+
+    pub fn history() -> Result<String> {
+        ensure("data/history")
+    }
+
+    pub fn videos() -> Result<String> {
+        ensure("data/videos")
+    }
 }
 
 async fn the_main() -> Result<()> {
@@ -189,6 +222,7 @@ async fn the_main() -> Result<()> {
     }
 
     let dir = dir::root()?;
+    prpr::replay::set_replay_dir(dir::replays()?);
     let mut data: Data = std::fs::read_to_string(format!("{dir}/data.json"))
         .map_err(anyhow::Error::new)
         .and_then(|s| Ok(serde_json::from_str(&s)?))
@@ -235,6 +269,8 @@ async fn the_main() -> Result<()> {
         let frame_start = tm.real_time();
         let res = || -> Result<()> {
             main.update()?;
+            outbox::update();
+            download::update();
             main.render(&mut painter)?;
             if let Ok(paused) = rx.try_recv() {
                 if paused {
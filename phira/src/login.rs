@@ -1,7 +1,7 @@
 prpr_l10n::tl_file!("login");
 
 use crate::{
-    client::{Client, LoginParams, User, UserManager},
+    client::{sync_cache_state, Client, LoginParams, User, UserManager},
     get_data_mut,
     page::Fader,
     save_data,
@@ -254,6 +254,7 @@ impl Login {
                         if let Some(user) = user {
                             UserManager::request(user.id);
                             get_data_mut().me = Some(user);
+                            sync_cache_state();
                             save_data()?;
                         }
                         self.t_pwd.clear();
@@ -3,7 +3,7 @@ use crate::{
     client::{Chart, Ptr, UserManager},
     dir, get_data,
     mp::L10N_LOCAL,
-    scene::{Downloading, SongScene, RECORD_ID},
+    scene::{collect_reported, Downloading, SongScene, RECORD_ID},
 };
 use anyhow::{anyhow, Context, Result};
 use macroquad::prelude::*;
@@ -22,7 +22,6 @@ use prpr::{
 };
 use smallvec::SmallVec;
 use std::{
-    fs::File,
     path::Path,
     sync::{atomic::Ordering, Arc},
 };
@@ -263,6 +262,10 @@ impl MPPanel {
 
     pub fn enter(&mut self) {
         self.entered = true;
+        // plays launched from here never return to a song page
+        if let Err(err) = collect_reported() {
+            show_error(err);
+        }
     }
 
     pub fn touch(&mut self, tm: &mut TimeManager, touch: &Touch) -> bool {
@@ -465,7 +468,7 @@ impl MPPanel {
                     self.entered = false;
                     self.scene_task = SongScene::global_launch(
                         Some(id),
-                        &format!("download/{id}"),
+                        &get_data().download_path(id),
                         Mods::default(),
                         GameMode::NoRetry,
                         self.client.as_ref().map(Arc::clone),
@@ -512,10 +515,10 @@ impl MPPanel {
             if let Some(res) = task.take() {
                 match res {
                     Ok(entity) => {
-                        let path = format!("download/{}", entity.id);
+                        let path = get_data().download_path(entity.id);
                         let info_path = format!("{}/{path}/info.yml", dir::charts()?);
                         let should_download = if Path::new(&info_path).exists() {
-                            let local_info: ChartInfo = serde_yaml::from_reader(File::open(info_path)?)?;
+                            let local_info = ChartInfo::from_yaml(&std::fs::read_to_string(info_path)?)?;
                             local_info
                                 .updated
                                 .map_or(entity.updated != entity.created, |local_updated| local_updated != entity.updated)
@@ -523,14 +526,7 @@ impl MPPanel {
                             true
                         };
                         if should_download {
-                            let info = entity.to_info();
-                            self.downloading = Some(SongScene::global_start_download(info, Chart::clone(&entity), {
-                                if Path::new(&format!("{}/{path}", dir::charts()?)).exists() {
-                                    Some(path)
-                                } else {
-                                    None
-                                }
-                            })?);
+                            self.downloading = Some(SongScene::global_start_download(Chart::clone(&entity)));
                         } else {
                             self.post_download();
                         }
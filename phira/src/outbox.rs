@@ -0,0 +1,199 @@
+//! Persistent queue of requests that must reach the server eventually
+//!
+//! Score uploads and chart actions are sent right away, and only queued when the server can't be reached. Queued
+//! requests are kept in [Data::outbox](crate::data::Data::outbox) and retried with backoff from the main loop. Each request
+//! carries an idempotency key so that the server can tell a retry from a new request.
+
+use crate::{
+    client::{api, api_url, Api, Review, ServerUnreachable, UploadRecord},
+    get_data, get_data_mut, save_data, ttl,
+};
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use prpr::{scene::show_message, task::Task};
+use serde::{Deserialize, Serialize};
+use std::{cell::RefCell, sync::Mutex};
+use tracing::warn;
+
+const MIN_BACKOFF: i64 = 10;
+const MAX_BACKOFF: i64 = 10 * 60;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Action {
+    UploadRecord(UploadRecord),
+    Rate { chart: i32, score: i16 },
+    EditTags { chart: i32, tags: Vec<String> },
+    Review { chart: i32, review: Review },
+}
+
+impl Action {
+    async fn perform(&self, api: &dyn Api, key: &str) -> Result<()> {
+        match self {
+            Self::UploadRecord(record) => api.upload_record(record, key).await.map(drop),
+            Self::Rate { chart, score } => api.rate(*chart, *score, key).await,
+            Self::EditTags { chart, tags } => api.edit_tags(*chart, tags, key).await,
+            Self::Review { chart, review } => api.review(*chart, review, key).await.map(drop),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub key: String,
+    /// API URL of the server the request was made on, it's only sent there
+    pub server: String,
+    pub action: Action,
+    pub created: DateTime<Utc>,
+    pub attempts: u32,
+    pub next_try: DateTime<Utc>,
+    pub last_error: Option<String>,
+    /// Rejected by the server, retrying won't help unless the user asks to
+    pub failed: bool,
+}
+
+impl OutboxEntry {
+    /// Made on the active server. Safe to call from tasks, the URL is read from [api_url].
+    pub fn new(action: Action) -> Self {
+        let now = Utc::now();
+        Self {
+            key: uuid::Uuid::new_v4().to_string(),
+            server: api_url(),
+            action,
+            created: now,
+            attempts: 0,
+            next_try: now,
+            last_error: None,
+            failed: false,
+        }
+    }
+
+    /// Uses a key derived from the content, so that sending the same thing twice is only queued once
+    pub fn with_key(mut self, key: String) -> Self {
+        self.key = key;
+        self
+    }
+
+    fn is_due(&self, now: DateTime<Utc>) -> bool {
+        !self.failed && self.next_try <= now && self.server == api_url()
+    }
+}
+
+/// Requests queued by tasks, moved into [Data](crate::data::Data) by [update] on the main thread
+static QUEUED: Mutex<Vec<OutboxEntry>> = Mutex::new(Vec::new());
+
+/// Queues the entry if `res` failed because the server couldn't be reached, returning `None` in that case
+///
+/// ```ignore
+/// let entry = OutboxEntry::new(Action::Rate { chart, score });
+/// let sent = outbox::or_queue(api().rate(chart, score, &entry.key).await, entry)?.is_some();
+/// ```
+pub fn or_queue<T>(res: Result<T>, entry: OutboxEntry) -> Result<Option<T>> {
+    match res {
+        Ok(value) => Ok(Some(value)),
+        Err(err) if err.is::<ServerUnreachable>() => {
+            warn!(?err, "server unreachable, request queued");
+            QUEUED.lock().unwrap().push(entry);
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+struct Flush {
+    key: String,
+    task: Task<Result<()>>,
+}
+
+thread_local! {
+    static FLUSH: RefCell<Option<Flush>> = RefCell::default();
+    static SENT: RefCell<usize> = RefCell::default();
+}
+
+fn backoff(attempts: u32) -> Duration {
+    Duration::seconds((MIN_BACKOFF << attempts.min(16)).min(MAX_BACKOFF))
+}
+
+/// Stores newly queued requests and sends due ones, one at a time. Called every frame.
+pub fn update() {
+    let data = get_data_mut();
+    let queued = std::mem::take(&mut *QUEUED.lock().unwrap());
+    if !queued.is_empty() {
+        for entry in queued {
+            if !data.outbox.iter().any(|it| it.key == entry.key) {
+                data.outbox.push(entry);
+            }
+        }
+        let _ = save_data();
+    }
+
+    FLUSH.with(|flush| {
+        let mut flush = flush.borrow_mut();
+        if let Some(Flush { key, task }) = flush.as_mut() {
+            let Some(res) = task.take() else { return };
+            let now = Utc::now();
+            if let Some(index) = data.outbox.iter().position(|it| it.key == *key) {
+                match res {
+                    Ok(()) => {
+                        data.outbox.remove(index);
+                        SENT.with(|it| *it.borrow_mut() += 1);
+                        // the network is back, no need to wait for the others
+                        for entry in &mut data.outbox {
+                            entry.next_try = entry.next_try.min(now);
+                        }
+                    }
+                    Err(err) => {
+                        let entry = &mut data.outbox[index];
+                        entry.attempts += 1;
+                        entry.last_error = Some(format!("{err:?}"));
+                        if err.is::<ServerUnreachable>() {
+                            entry.next_try = now + backoff(entry.attempts);
+                        } else {
+                            warn!(?err, "queued request rejected");
+                            entry.failed = true;
+                        }
+                    }
+                }
+                let _ = save_data();
+            }
+            *flush = None;
+        }
+
+        if data.config.offline_mode || data.tokens.is_none() {
+            return;
+        }
+        let now = Utc::now();
+        if let Some(entry) = data.outbox.iter().find(|it| it.is_due(now)).cloned() {
+            *flush = Some(Flush {
+                key: entry.key.clone(),
+                task: Task::new(async move { entry.action.perform(&*api(), &entry.key).await }),
+            });
+        } else {
+            let sent = SENT.with(|it| std::mem::take(&mut *it.borrow_mut()));
+            if sent != 0 {
+                show_message(ttl!("outbox-synced", "count" => sent)).ok();
+            }
+        }
+    });
+}
+
+/// Number of requests waiting to be sent and of requests rejected by the server
+pub fn status() -> (usize, usize) {
+    let outbox = &get_data().outbox;
+    let failed = outbox.iter().filter(|it| it.failed).count();
+    (outbox.len() - failed, failed)
+}
+
+/// Sends every request again as soon as possible, including rejected ones
+pub fn retry_all() {
+    let now = Utc::now();
+    for entry in &mut get_data_mut().outbox {
+        entry.failed = false;
+        entry.next_try = now;
+    }
+}
+
+/// Drops requests rejected by the server
+pub fn discard_failed() {
+    get_data_mut().outbox.retain(|it| !it.failed);
+}
@@ -6,7 +6,7 @@ use super::{
 };
 use crate::{
     anim::Anim,
-    client::{recv_raw, Character, Client, LoginParams, User, UserManager},
+    client::{api, recv_raw, sync_cache_state, Character, Client, LoginParams, User, UserManager},
     dir, get_data, get_data_mut,
     icons::Icons,
     login::Login,
@@ -246,17 +246,7 @@ impl HomePage {
 
     fn fetch_has_new(&mut self) {
         let time = get_data().message_check_time.unwrap_or_default();
-        self.has_new_task = Some(Task::new(async move {
-            #[derive(Deserialize)]
-            struct Resp {
-                has: bool,
-            }
-            let resp: Resp = recv_raw(Client::get("/message/has_new").query(&[("checked", time)]))
-                .await?
-                .json()
-                .await?;
-            Ok(resp.has)
-        }));
+        self.has_new_task = Some(Task::new(async move { api().has_new_messages(time).await }));
     }
 
     fn render_not_char(&mut self, ui: &mut Ui, s: &mut SharedState) {
@@ -419,6 +409,9 @@ impl Page for HomePage {
             if let Some(me) = &get_data().me {
                 self.need_back = true;
                 self.sf.goto(t, ProfileScene::new(me.id, self.icons.user.clone(), s.icons.clone()));
+            } else if get_data().config.offline_mode {
+                self.need_back = true;
+                self.sf.goto(t, ProfileScene::local(self.icons.user.clone(), s.icons.clone()));
             } else {
                 self.login.enter(t);
             }
@@ -471,6 +464,7 @@ impl Page for HomePage {
                     }
                     Ok(val) => {
                         get_data_mut().me = Some(val);
+                        sync_cache_state();
                         save_data()?;
                     }
                 }
@@ -494,7 +488,7 @@ impl Page for HomePage {
                 let dir = prpr::dir::Dir::new(format!("{}/{}", dir::charts()?, path))?;
                 self.board_last = Some(path);
                 self.board_task = Some(Task::new(async move {
-                    let info: ChartInfo = serde_yaml::from_reader(dir.open("info.yml")?)?;
+                    let info = ChartInfo::from_yaml(&String::from_utf8_lossy(&dir.read("info.yml")?))?;
                     let bytes = dir.read(info.illustration)?;
                     Ok(Some(image::load_from_memory(&bytes)?))
                 }));
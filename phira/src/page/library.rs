@@ -4,14 +4,14 @@ use super::{CollectionPage, FavoritesPage, NextPage, Page, SharedState};
 use crate::{
     charts_view::{ChartDisplayItem, ChartsView, NEED_UPDATE},
     client::{Chart, Client},
-    data::DEFAULT_FAVORITES_KEY,
+    data::{LocalChart, DEFAULT_FAVORITES_KEY},
     get_data, get_data_mut,
     icons::Icons,
     popup::Popup,
     rate::RateDialog,
     page::favorites::FAV_PAGE_RESULT,
     save_data,
-    scene::{check_read_tos_and_policy, ChartOrder, JUST_LOADED_TOS, ORDERS},
+    scene::{check_read_tos_and_policy, ChartOrder, ChartQuery, JUST_LOADED_TOS, ORDERS},
     tabs::{Tabs, TitleFn},
     tags::TagsDialog,
     ttl,
@@ -27,6 +27,7 @@ use prpr::{
 use std::{
     any::Any,
     borrow::Cow,
+    collections::HashMap,
     ops::Deref,
     sync::{atomic::Ordering, Arc},
 };
@@ -260,21 +261,23 @@ impl LibraryPage {
             let fav_paths: Option<Vec<String>> = fav_folder.as_ref().map(|folder| {
                 get_data().favorites.get_paths(folder)
             });
-            charts.append(
-                &mut s
-                    .charts_local
-                    .iter()
-                    .filter(|it| {
-                        let name_match = it.info.name.contains(&search);
-                        let fav_match = match &fav_paths {
-                            Some(paths) => it.local_path.as_ref().map_or(false, |p| paths.contains(p)),
-                            None => true,
-                        };
-                        name_match && fav_match
-                    })
-                    .map(|it| ChartDisplayItem::new(Some(it.clone()), None))
-                    .collect::<Vec<ChartDisplayItem>>(),
-            );
+            // invalid queries are reported when entered, fall back to searching the text as is
+            let query = ChartQuery::parse(&search).unwrap_or_else(|_| ChartQuery::text(&search));
+            let locals: HashMap<&str, &LocalChart> = get_data().charts.iter().map(|it| (it.local_path.as_str(), it)).collect();
+            let mut matched: Vec<_> = s
+                .charts_local
+                .iter()
+                .map(|it| (it, it.local_path.as_deref().and_then(|p| locals.get(p).copied())))
+                .filter(|(it, local)| {
+                    let fav_match = match &fav_paths {
+                        Some(paths) => it.local_path.as_ref().map_or(false, |p| paths.contains(p)),
+                        None => true,
+                    };
+                    fav_match && query.matches(it, *local)
+                })
+                .collect();
+            query.sort(&mut matched);
+            charts.extend(matched.into_iter().map(|(it, _)| ChartDisplayItem::new(Some(it.clone()), None)));
             list.view.set(s.t, charts);
         }
     }
@@ -471,6 +474,9 @@ impl Page for LibraryPage {
             if id == "search" {
                 self.search_str = text;
                 if is_local {
+                    if let Err(err) = ChartQuery::parse(&self.search_str) {
+                        show_message(err.to_string()).error();
+                    }
                     self.sync_local(s);
                 } else {
                     self.current_page = 0;
@@ -2,10 +2,13 @@ prpr_l10n::tl_file!("settings");
 
 use super::{NextPage, OffsetPage, Page, SharedState};
 use crate::{
-    dir, get_data, get_data_mut,
+    client,
+    data::ServerProfile,
+    dir, get_data, get_data_mut, outbox,
     popup::ChooseButton,
     save_data,
-    scene::BGM_VOLUME_UPDATED,
+    scene::{confirm_dialog, BGM_VOLUME_UPDATED},
+    store::{self, RepairReport},
     sync_data,
     tabs::{Tabs, TitleFn},
 };
@@ -21,7 +24,16 @@ use prpr::{
 };
 use prpr_l10n::{LanguageIdentifier, LANG_IDENTS, LANG_NAMES};
 use reqwest::Url;
-use std::{borrow::Cow, fs, io, net::ToSocketAddrs, path::PathBuf, sync::atomic::Ordering};
+use std::{
+    borrow::Cow,
+    fs, io,
+    net::ToSocketAddrs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
 const ITEM_HEIGHT: f32 = 0.15;
 const INTERACT_WIDTH: f32 = 0.26;
@@ -275,6 +287,9 @@ struct GeneralList {
     fullscreen_btn: DRectButton,
 
     cache_btn: DRectButton,
+    verify_btn: DRectButton,
+    outbox_btn: DRectButton,
+    outbox_discard_btn: DRectButton,
     offline_btn: DRectButton,
     server_status_btn: DRectButton,
     mp_btn: DRectButton,
@@ -283,9 +298,17 @@ struct GeneralList {
     insecure_btn: DRectButton,
     enable_anys_btn: DRectButton,
     anys_gateway_btn: DRectButton,
+    server_btn: ChooseButton,
+    server_url_btn: DRectButton,
+    server_add_btn: DRectButton,
+    server_remove_btn: DRectButton,
 
     cache_size: Option<u64>,
     cache_task: Option<Task<Result<u64>>>,
+    verify_task: Option<Task<Result<RepairReport>>>,
+    /// Files changed outside of Phira found by the last check, restored once the user confirms
+    modified_files: Vec<(String, String)>,
+    should_restore: Arc<AtomicBool>,
 }
 
 impl GeneralList {
@@ -308,6 +331,9 @@ impl GeneralList {
             fullscreen_btn: DRectButton::new(),
 
             cache_btn: DRectButton::new(),
+            verify_btn: DRectButton::new(),
+            outbox_btn: DRectButton::new(),
+            outbox_discard_btn: DRectButton::new(),
             offline_btn: DRectButton::new(),
             server_status_btn: DRectButton::new(),
             mp_btn: DRectButton::new(),
@@ -316,9 +342,16 @@ impl GeneralList {
             insecure_btn: DRectButton::new(),
             enable_anys_btn: DRectButton::new(),
             anys_gateway_btn: DRectButton::new(),
+            server_btn: ChooseButton::new().with_options(Self::server_names()).with_selected(get_data().server_id),
+            server_url_btn: DRectButton::new(),
+            server_add_btn: DRectButton::new(),
+            server_remove_btn: DRectButton::new(),
 
             cache_size: None,
             cache_task: None,
+            verify_task: None,
+            modified_files: Vec::new(),
+            should_restore: Arc::default(),
         };
         let _ = this.update_cache_size();
         this
@@ -328,9 +361,21 @@ impl GeneralList {
         if self.lang_btn.top_touch(touch, t) {
             return true;
         }
+        if self.server_btn.top_touch(touch, t) {
+            return true;
+        }
         false
     }
 
+    fn server_names() -> Vec<String> {
+        get_data().servers.iter().map(|it| it.name.clone()).collect()
+    }
+
+    fn sync_servers(&mut self) {
+        self.server_btn.set_options(Self::server_names());
+        self.server_btn.set_selected(get_data().server_id);
+    }
+
     fn dir_size(path: impl Into<PathBuf>) -> io::Result<u64> {
         fn inner(mut dir: fs::ReadDir) -> io::Result<u64> {
             dir.try_fold(0, |acc, file| {
@@ -373,8 +418,23 @@ impl GeneralList {
             show_message(tl!("item-cache-cleared")).ok();
             return Ok(Some(false));
         }
+        if self.verify_btn.touch(touch, t) {
+            if self.verify_task.is_none() {
+                self.verify_task = Some(Task::new(async { tokio::task::spawn_blocking(store::check_and_repair).await? }));
+            }
+            return Ok(Some(false));
+        }
+        if self.outbox_btn.touch(touch, t) {
+            outbox::retry_all();
+            return Ok(Some(true));
+        }
+        if self.outbox_discard_btn.touch(touch, t) {
+            outbox::discard_failed();
+            return Ok(Some(true));
+        }
         if self.offline_btn.touch(touch, t) {
             config.offline_mode ^= true;
+            client::sync_cache_state();
             return Ok(Some(true));
         }
         if self.server_status_btn.touch(touch, t) {
@@ -405,17 +465,48 @@ impl GeneralList {
             request_input("anys_gateway", &data.anys_gateway);
             return Ok(Some(true));
         }
+        if self.server_btn.touch(touch, t) {
+            return Ok(Some(false));
+        }
+        if self.server_url_btn.touch(touch, t) {
+            if data.server_id == 0 {
+                show_message(tl!("item-server-builtin")).error();
+            } else {
+                request_input("server_url", data.api_url());
+            }
+            return Ok(Some(false));
+        }
+        if self.server_add_btn.touch(touch, t) {
+            request_input("server_add", "https://");
+            return Ok(Some(false));
+        }
+        if self.server_remove_btn.touch(touch, t) {
+            if data.server_id == 0 {
+                show_message(tl!("item-server-builtin")).error();
+            } else {
+                client::remove_server(data.server_id)?;
+                self.sync_servers();
+                show_message(tl!("item-server-removed")).ok();
+            }
+            return Ok(Some(true));
+        }
         Ok(None)
     }
 
     pub fn update(&mut self, t: f32) -> Result<bool> {
         self.lang_btn.update(t);
+        self.server_btn.update(t);
         let data = get_data_mut();
         if self.lang_btn.changed() {
             data.language = Some(LANG_IDENTS[self.lang_btn.selected()].to_string());
             sync_data();
             return Ok(true);
         }
+        if self.server_btn.changed() {
+            client::switch_server(self.server_btn.selected())?;
+            show_message(tl!("item-server-switched", "name" => data.servers[data.server_id].name.clone())).ok();
+            return Ok(true);
+        }
         if let Some((id, text)) = take_input() {
             if id == "mp_addr" {
                 if let Err(err) = text.to_socket_addrs() {
@@ -433,6 +524,40 @@ impl GeneralList {
                     data.anys_gateway = text.trim_end_matches('/').to_string();
                     return Ok(true);
                 }
+            } else if id == "server_url" || id == "server_add" {
+                let url = match Url::parse(&text) {
+                    Ok(url) if matches!(url.scheme(), "http" | "https") && url.host_str().is_some() => url,
+                    Ok(_) => {
+                        show_message(tl!("item-server-invalid")).error();
+                        return Ok(false);
+                    }
+                    Err(err) => {
+                        show_error(anyhow::Error::new(err).context(tl!("item-server-invalid")));
+                        return Ok(false);
+                    }
+                };
+                let text = text.trim_end_matches('/').to_string();
+                if id == "server_url" {
+                    if data.api_url() != text {
+                        // another URL is another server, the login, records and charts of the old one stay with its profile
+                        let index = match data.servers.iter().position(|it| it.url == text) {
+                            Some(index) => index,
+                            None => {
+                                data.servers.push(ServerProfile::new(url.host_str().unwrap().to_owned(), text));
+                                data.servers.len() - 1
+                            }
+                        };
+                        client::switch_server(index)?;
+                        self.sync_servers();
+                    }
+                } else {
+                    let name = url.host_str().unwrap().to_owned();
+                    data.servers.push(ServerProfile::new(name, text));
+                    client::switch_server(data.servers.len() - 1)?;
+                    self.sync_servers();
+                    show_message(tl!("item-server-added")).ok();
+                }
+                return Ok(true);
             } else {
                 return_input(id, text);
             }
@@ -443,6 +568,35 @@ impl GeneralList {
                 self.cache_task = None;
             }
         }
+        if let Some(task) = &mut self.verify_task {
+            if let Some(res) = task.take() {
+                match res {
+                    Err(err) => show_error(err.context(tl!("item-verify-charts-failed"))),
+                    Ok(report) => {
+                        if !report.damaged.is_empty() {
+                            show_message(tl!("item-verify-charts-damaged", "count" => report.damaged.len())).error();
+                        } else {
+                            show_message(tl!("item-verify-charts-done", "checked" => report.checked, "repaired" => report.repaired)).ok();
+                        }
+                        if !report.modified.is_empty() {
+                            confirm_dialog(
+                                tl!("item-verify-charts-modified"),
+                                tl!("item-verify-charts-modified-content", "count" => report.modified.len()),
+                                Arc::clone(&self.should_restore),
+                            );
+                        }
+                        self.modified_files = report.modified;
+                    }
+                }
+                self.verify_task = None;
+            }
+        }
+        if self.should_restore.fetch_and(false, Ordering::Relaxed) {
+            match store::restore(&std::mem::take(&mut self.modified_files)) {
+                Ok(count) => show_message(tl!("item-verify-charts-restored", "count" => count)).ok(),
+                Err(err) => show_error(err.context(tl!("item-verify-charts-failed"))),
+            }
+        }
         Ok(false)
     }
 
@@ -503,6 +657,20 @@ impl GeneralList {
             render_title(ui, tl!("item-clear-cache"), Some(cache_size));
             self.cache_btn.render_text(ui, rr, t, tl!("item-clear-cache-btn"), 0.5, true);
         }
+        item! {
+            render_title(ui, tl!("item-verify-charts"), Some(tl!("item-verify-charts-sub")));
+            let text = if self.verify_task.is_some() { tl!("item-verify-charts-running") } else { tl!("item-verify-charts-btn") };
+            self.verify_btn.render_text(ui, rr, t, text, 0.5, true);
+        }
+        let (pending, failed) = outbox::status();
+        item! {
+            render_title(ui, tl!("item-outbox"), Some(tl!("item-outbox-sub", "pending" => pending, "failed" => failed).into()));
+            self.outbox_btn.render_text(ui, rr, t, tl!("item-outbox-btn"), 0.5, true);
+        }
+        item! {
+            render_title(ui, tl!("item-outbox-discard"), Some(tl!("item-outbox-discard-sub")));
+            self.outbox_discard_btn.render_text(ui, rr, t, tl!("item-outbox-discard-btn"), 0.5, true);
+        }
         h += 0.2;
         item! {
             render_title(ui, tl!("item-insecure"), Some(tl!("item-insecure-sub")));
@@ -516,7 +684,25 @@ impl GeneralList {
             render_title(ui, tl!("item-anys-gateway"), Some(tl!("item-anys-gateway-sub")));
             self.anys_gateway_btn.render_text(ui, rr, t, &data.anys_gateway, 0.4, false);
         }
+        h += 0.2;
+        item! {
+            render_title(ui, tl!("item-server"), Some(tl!("item-server-sub")));
+            self.server_btn.render(ui, rr, t);
+        }
+        item! {
+            render_title(ui, tl!("item-server-url"), Some(tl!("item-server-url-sub")));
+            self.server_url_btn.render_text(ui, rr, t, data.api_url(), 0.4, false);
+        }
+        item! {
+            render_title(ui, tl!("item-server-add"), Some(tl!("item-server-add-sub")));
+            self.server_add_btn.render_text(ui, rr, t, tl!("item-server-add-btn"), 0.5, true);
+        }
+        item! {
+            render_title(ui, tl!("item-server-remove"), Some(tl!("item-server-remove-sub")));
+            self.server_remove_btn.render_text(ui, rr, t, tl!("item-server-remove-btn"), 0.5, true);
+        }
         self.lang_btn.render_top(ui, t, 1.);
+        self.server_btn.render_top(ui, t, 1.);
         (w, h)
     }
 }
@@ -527,6 +713,8 @@ struct AudioList {
     sfx_slider: Slider,
     bgm_slider: Slider,
     cali_btn: DRectButton,
+    auto_offset_btn: DRectButton,
+    suggest_offset_btn: DRectButton,
     preferred_sample_rate_btn: DRectButton,
     cali_task: LocalTask<Result<OffsetPage>>,
     next_page: Option<NextPage>,
@@ -540,6 +728,8 @@ impl AudioList {
             sfx_slider: Slider::new(0.0..2.0, 0.05),
             bgm_slider: Slider::new(0.0..2.0, 0.05),
             cali_btn: DRectButton::new(),
+            auto_offset_btn: DRectButton::new(),
+            suggest_offset_btn: DRectButton::new(),
             preferred_sample_rate_btn: DRectButton::new(),
 
             cali_task: None,
@@ -575,6 +765,17 @@ impl AudioList {
             self.cali_task = Some(Box::pin(OffsetPage::new()));
             return Ok(Some(false));
         }
+        if self.auto_offset_btn.touch(touch, t) {
+            config.auto_offset ^= true;
+            return Ok(Some(true));
+        }
+        if self.suggest_offset_btn.touch(touch, t) {
+            if let Some(offset) = data.offset_history.suggest(config.offset) {
+                config.offset = offset;
+                return Ok(Some(true));
+            }
+            return Ok(Some(false));
+        }
         if self.preferred_sample_rate_btn.touch(touch, t) {
             let options = [44100, 48000, 88200, 96000, 192000];
             let current = config.preferred_sample_rate;
@@ -634,6 +835,18 @@ impl AudioList {
             render_title(ui, tl!("item-cali"), None);
             self.cali_btn.render_text(ui, rr, t, format!("{:.0}ms", config.offset * 1000.), 0.5, true);
         }
+        item! {
+            render_title(ui, tl!("item-auto-offset"), Some(tl!("item-auto-offset-sub")));
+            render_switch(ui, rr, t, &mut self.auto_offset_btn, config.auto_offset);
+        }
+        item! {
+            render_title(ui, tl!("item-suggest-offset"), Some(tl!("item-suggest-offset-sub")));
+            let text = match data.offset_history.suggest(config.offset) {
+                Some(offset) => format!("{:.0}ms", offset * 1000.),
+                None => "-".to_owned(),
+            };
+            self.suggest_offset_btn.render_text(ui, rr, t, text, 0.5, true);
+        }
         item! {
             render_title(ui, tl!("item-preferred-sample-rate"), None);
             self.preferred_sample_rate_btn.render_text(ui, rr, t, format!("{} Hz", config.preferred_sample_rate), 0.5, false);
@@ -764,6 +977,7 @@ impl ChartList {
 struct DebugList {
     chart_debug_btn: DRectButton,
     touch_debug_btn: DRectButton,
+    res_pack_watch_btn: DRectButton,
 }
 
 impl DebugList {
@@ -771,6 +985,7 @@ impl DebugList {
         Self {
             chart_debug_btn: DRectButton::new(),
             touch_debug_btn: DRectButton::new(),
+            res_pack_watch_btn: DRectButton::new(),
         }
     }
 
@@ -789,6 +1004,10 @@ impl DebugList {
             config.touch_debug ^= true;
             return Ok(Some(true));
         }
+        if self.res_pack_watch_btn.touch(touch, t) {
+            config.res_pack_watch ^= true;
+            return Ok(Some(true));
+        }
         Ok(None)
     }
 
@@ -818,6 +1037,10 @@ impl DebugList {
             render_title(ui, tl!("item-touch-debug"), Some(tl!("item-touch-debug-sub")));
             render_switch(ui, rr, t, &mut self.touch_debug_btn, config.touch_debug);
         }
+        item! {
+            render_title(ui, tl!("item-res-pack-watch"), Some(tl!("item-res-pack-watch-sub")));
+            render_switch(ui, rr, t, &mut self.res_pack_watch_btn, config.res_pack_watch);
+        }
         (w, h)
     }
 }
@@ -211,6 +211,16 @@ impl ChooseButton {
         self.popup.changed()
     }
 
+    #[inline]
+    pub fn set_options(&mut self, options: Vec<String>) {
+        self.popup.set_options(options);
+    }
+
+    #[inline]
+    pub fn set_selected(&mut self, selected: usize) {
+        self.popup.set_selected(selected);
+    }
+
     pub fn render(&mut self, ui: &mut Ui, r: Rect, t: f32) {
         self.btn
             .render_text(ui, r, t, &self.popup.options[self.popup.selected].0, self.popup.size, false);
@@ -3,6 +3,9 @@ prpr_l10n::tl_file!("import" itl);
 mod chart_order;
 pub use chart_order::{ChartOrder, ORDERS};
 
+mod chart_query;
+pub use chart_query::ChartQuery;
+
 mod chapter;
 pub use chapter::ChapterScene;
 
@@ -13,7 +16,7 @@ mod main;
 pub use main::{MainScene, BGM_VOLUME_UPDATED, MP_PANEL};
 
 mod song;
-pub use song::{Downloading, SongScene, RECORD_ID};
+pub use song::{collect_reported, Downloading, SongScene, RECORD_ID};
 #[cfg(feature = "video")]
 mod unlock;
 #[cfg(feature = "video")]
@@ -27,12 +30,13 @@ use crate::{
     data::LocalChart,
     dir, get_data, get_data_mut,
     page::Fader,
-    save_data, ttl,
+    save_data, store, ttl,
 };
 use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
 use once_cell::sync::{Lazy, OnceCell};
 use prpr::{
+    calibrate::OffsetHistory,
     config::Mods,
     core::{BOLD_FONT, PGR_FONT},
     ext::{semi_white, unzip_into, RectExt, SafeTexture},
@@ -295,6 +299,9 @@ pub async fn import_chart_to(dir: &Path, id: Uuid, path: String) -> Result<Local
         record: None,
         mods: Mods::default(),
         played_unlock: false,
+        offset: None,
+        offset_history: OffsetHistory::default(),
+        server: None,
     })
 }
 
@@ -305,7 +312,10 @@ pub async fn import_chart(path: String) -> Result<LocalChart> {
             std::fs::remove_dir_all(dir)?;
             Err(err)
         }
-        Ok(val) => Ok(val),
+        Ok(chart) => {
+            store::index(&chart.local_path, chart.info.clone());
+            Ok(chart)
+        }
     }
 }
 
@@ -15,7 +15,7 @@ use prpr::{
     config::Mods,
     core::BOLD_FONT,
     ext::{semi_black, semi_white, RectExt, SafeTexture},
-    info::ChartInfo,
+    info::{ChartInfo, INFO_VERSION},
     scene::{NextScene, Scene},
     time::TimeManager,
     ui::{button_hit, DRectButton, RectButton, Scroll, Ui},
@@ -200,6 +200,8 @@ impl Scene for ChapterScene {
                         updated: None,
                         chart_updated: None,
                         has_unlock: false,
+                        tags: Vec::new(),
+                        format: None,
                     },
                     illu: Illustration::from_done(chart.illu.clone()),
                     local_path: Some(local_path.clone()),
@@ -213,6 +215,8 @@ impl Scene for ChapterScene {
                 }
                 let dir = prpr::dir::Dir::new(dir)?;
                 *ASSET_CHART_INFO.lock().unwrap() = Some(ChartInfo {
+                    version: INFO_VERSION,
+
                     id: None,
                     uploader: None,
 
@@ -259,6 +263,8 @@ impl Scene for ChapterScene {
                     created: None,
                     updated: None,
                     chart_updated: None,
+
+                    local_path: None,
                 });
                 self.sf
                     .goto(t, SongScene::new(item, Some(local_path), Arc::clone(&self.icons), self.rank_icons.clone(), Mods::empty()));
@@ -0,0 +1,308 @@
+//! Query language for searching local charts
+//!
+//! A query is a list of whitespace separated terms, all of which must match. Values containing spaces can be quoted.
+//!
+//! - `word` matches the name, composer or charter, ignoring case
+//! - `name:`, `composer:`, `charter:` and `illustrator:` match a single field
+//! - `tag:x` matches charts tagged with `x`
+//! - `level:13`, `level:13-15`, `level:13.5-`, `level:>=14` match the difficulty; a bound covers every level starting
+//!   with it, so `level:13-15` includes 15.9
+//! - `format:rpe|pec|pgr|pbc` matches the format declared by the chart, or the one detected when it was added if it
+//!   declares none; other registered formats work as well
+//! - `is:record`, `is:fc`, `is:played` and `is:unplayed` match the play state (`has:` works as well), a chart is played
+//!   once it's in the [history](crate::history)
+//! - `sort:key` orders the results by `name`, `composer`, `charter`, `level`, `score`, `acc` or `updated`; `sort:-key`
+//!   reverses it, and later keys break ties of earlier ones
+//!
+//! Any keyed filter can be negated with a leading `-`, e.g. `-tag:easy`. Terms without a known key, including ones
+//! starting with `-`, are searched as plain words.
+
+prpr_l10n::tl_file!("chart_order");
+
+use crate::{data::LocalChart, history, page::ChartItem, store};
+use anyhow::{bail, Result};
+use prpr::{info::ChartFormat, parse::find_parser};
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Text(String),
+    Name(String),
+    Composer(String),
+    Charter(String),
+    Illustrator(String),
+    Tag(String),
+    /// `min <= difficulty < max`
+    Level(f64, f64),
+    Format(ChartFormat),
+    Record,
+    FullCombo,
+    Played,
+}
+
+impl Filter {
+    fn matches(&self, item: &ChartItem, local: Option<&LocalChart>) -> bool {
+        let info = &item.info;
+        let contains = |field: &str, s: &str| field.to_lowercase().contains(s);
+        match self {
+            Self::Text(s) => contains(&info.name, s) || contains(&info.composer, s) || contains(&info.charter, s),
+            Self::Name(s) => contains(&info.name, s),
+            Self::Composer(s) => contains(&info.composer, s),
+            Self::Charter(s) => contains(&info.charter, s),
+            Self::Illustrator(s) => contains(&info.illustrator, s),
+            Self::Tag(s) => info.tags.iter().any(|it| it.to_lowercase() == *s),
+            Self::Level(min, max) => {
+                // difficulties are stored as f32, compare them at the precision they are written in
+                let difficulty = (info.difficulty as f64 * 1000.).round() / 1000.;
+                (*min..*max).contains(&difficulty)
+            }
+            Self::Format(format) => match &info.format {
+                Some(it) => it == format,
+                None => item.local_path.as_deref().and_then(store::format).as_ref() == Some(format),
+            },
+            Self::Record => local.map_or(false, |it| it.record.is_some()),
+            Self::FullCombo => local.and_then(|it| it.record.as_ref()).map_or(false, |it| it.full_combo),
+            // records count too, they may be older than the history
+            Self::Played => local.map_or(false, |it| it.record.is_some() || history::has_played(&it.local_path)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SortKey {
+    Name,
+    Composer,
+    Charter,
+    Level,
+    Score,
+    Accuracy,
+    Updated,
+}
+
+impl SortKey {
+    fn compare(&self, x: (&ChartItem, Option<&LocalChart>), y: (&ChartItem, Option<&LocalChart>)) -> Ordering {
+        let (a, b) = (&x.0.info, &y.0.info);
+        let record = |it: Option<&LocalChart>| it.and_then(|it| it.record.as_ref());
+        match self {
+            Self::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            Self::Composer => a.composer.to_lowercase().cmp(&b.composer.to_lowercase()),
+            Self::Charter => a.charter.to_lowercase().cmp(&b.charter.to_lowercase()),
+            Self::Level => a.difficulty.total_cmp(&b.difficulty),
+            Self::Score => record(x.1).map(|it| it.score).cmp(&record(y.1).map(|it| it.score)),
+            Self::Accuracy => record(x.1)
+                .map(|it| it.accuracy)
+                .partial_cmp(&record(y.1).map(|it| it.accuracy))
+                .unwrap_or(Ordering::Equal),
+            Self::Updated => a.updated.or(a.created).cmp(&b.updated.or(b.created)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChartQuery {
+    /// Filters paired with whether they are negated
+    filters: Vec<(Filter, bool)>,
+    /// Sort keys paired with whether they are reversed
+    sorts: Vec<(SortKey, bool)>,
+}
+
+impl ChartQuery {
+    /// A query matching `text` as a plain word, used when `text` fails to parse
+    pub fn text(text: &str) -> Self {
+        let text = text.trim().to_lowercase();
+        let mut res = Self::default();
+        if !text.is_empty() {
+            res.filters.push((Filter::Text(text), false));
+        }
+        res
+    }
+
+    pub fn parse(query: &str) -> Result<Self> {
+        let mut res = Self::default();
+        for term in split_terms(query) {
+            let (negated, body) = match term.strip_prefix('-') {
+                Some(body) if !body.is_empty() => (true, body),
+                _ => (false, term.as_str()),
+            };
+            let Some((key, value)) = body.split_once(':') else {
+                res.filters.push((Filter::Text(term.to_lowercase()), false));
+                continue;
+            };
+            let key = key.to_lowercase();
+            let lower = value.to_lowercase();
+            let filter = match key.as_str() {
+                "name" => Filter::Name(lower),
+                "composer" => Filter::Composer(lower),
+                "charter" => Filter::Charter(lower),
+                "illustrator" => Filter::Illustrator(lower),
+                "tag" => Filter::Tag(lower),
+                "level" | "lv" => {
+                    let Some((min, max)) = parse_level(value) else {
+                        bail!(tl!("query-invalid-level", "value" => value));
+                    };
+                    Filter::Level(min, max)
+                }
+                "format" => {
+                    let format = ChartFormat::from_name(value);
+                    if find_parser(&format).is_none() {
+                        bail!(tl!("query-invalid-format", "value" => value));
+                    }
+                    Filter::Format(format)
+                }
+                "is" | "has" => {
+                    let (filter, inverted) = match lower.as_str() {
+                        "record" => (Filter::Record, false),
+                        "fc" => (Filter::FullCombo, false),
+                        "played" => (Filter::Played, false),
+                        "unplayed" => (Filter::Played, true),
+                        _ => bail!(tl!("query-invalid-state", "value" => value)),
+                    };
+                    res.filters.push((filter, negated != inverted));
+                    continue;
+                }
+                "sort" => {
+                    if negated {
+                        bail!(tl!("query-invalid-sort", "value" => term.as_str()));
+                    }
+                    let (reversed, name) = match lower.strip_prefix('-') {
+                        Some(name) => (true, name),
+                        None => (false, lower.as_str()),
+                    };
+                    let key = match name {
+                        "name" => SortKey::Name,
+                        "composer" => SortKey::Composer,
+                        "charter" => SortKey::Charter,
+                        "level" | "lv" => SortKey::Level,
+                        "score" => SortKey::Score,
+                        "acc" | "accuracy" => SortKey::Accuracy,
+                        "updated" | "time" => SortKey::Updated,
+                        _ => bail!(tl!("query-invalid-sort", "value" => value)),
+                    };
+                    res.sorts.push((key, reversed));
+                    continue;
+                }
+                _ => {
+                    // not a known key, e.g. a name like `Re:End`
+                    res.filters.push((Filter::Text(term.to_lowercase()), false));
+                    continue;
+                }
+            };
+            res.filters.push((filter, negated));
+        }
+        Ok(res)
+    }
+
+    pub fn matches(&self, item: &ChartItem, local: Option<&LocalChart>) -> bool {
+        self.filters.iter().all(|(filter, negated)| filter.matches(item, local) != *negated)
+    }
+
+    /// Sorts by the keys of this query. The sort is stable, so charts equal in every key keep the [ChartOrder](super::ChartOrder)
+    /// they came in.
+    pub fn sort(&self, charts: &mut [(&ChartItem, Option<&LocalChart>)]) {
+        if self.sorts.is_empty() {
+            return;
+        }
+        charts.sort_by(|x, y| {
+            self.sorts
+                .iter()
+                .map(|(key, reversed)| {
+                    let ord = key.compare(*x, *y);
+                    if *reversed {
+                        ord.reverse()
+                    } else {
+                        ord
+                    }
+                })
+                .find(|it| it.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+    }
+}
+
+/// Splits on whitespace outside of double quotes, dropping the quotes
+fn split_terms(query: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in query.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    terms.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        terms.push(current);
+    }
+    terms
+}
+
+/// Parses a bound into the range of levels starting with it, e.g. `13` into `13..14` and `13.5` into `13.5..13.6`
+fn parse_bound(s: &str) -> Option<(f64, f64)> {
+    let value: f64 = s.parse().ok()?;
+    let decimals = s.split_once('.').map_or(0, |(_, it)| it.len());
+    Some((value, value + 10f64.powi(-(decimals as i32))))
+}
+
+fn parse_level(s: &str) -> Option<(f64, f64)> {
+    let s = s.trim();
+    if let Some(rest) = s.strip_prefix(">=") {
+        Some((parse_bound(rest)?.0, f64::INFINITY))
+    } else if let Some(rest) = s.strip_prefix("<=") {
+        Some((f64::NEG_INFINITY, parse_bound(rest)?.1))
+    } else if let Some(rest) = s.strip_prefix('>') {
+        Some((parse_bound(rest)?.1, f64::INFINITY))
+    } else if let Some(rest) = s.strip_prefix('<') {
+        Some((f64::NEG_INFINITY, parse_bound(rest)?.0))
+    } else if let Some((min, max)) = s.split_once('-') {
+        let min = if min.is_empty() { f64::NEG_INFINITY } else { parse_bound(min)?.0 };
+        let max = if max.is_empty() { f64::INFINITY } else { parse_bound(max)?.1 };
+        Some((min, max))
+    } else {
+        parse_bound(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_terms() {
+        let query = ChartQuery::parse(r#"Re:End composer:"Some One" -tag:Easy level:13-15 is:unplayed sort:-score sort:name"#).unwrap();
+        assert_eq!(
+            query.filters,
+            vec![
+                (Filter::Text("re:end".to_owned()), false),
+                (Filter::Composer("some one".to_owned()), false),
+                (Filter::Tag("easy".to_owned()), true),
+                (Filter::Level(13., 16.), false),
+                (Filter::Played, true),
+            ]
+        );
+        assert_eq!(query.sorts, vec![(SortKey::Score, true), (SortKey::Name, false)]);
+    }
+
+    #[test]
+    fn parse_levels() {
+        assert_eq!(parse_level("13"), Some((13., 14.)));
+        assert_eq!(parse_level(">=14"), Some((14., f64::INFINITY)));
+        assert_eq!(parse_level("<13"), Some((f64::NEG_INFINITY, 13.)));
+        assert_eq!(parse_level("15-"), Some((15., f64::INFINITY)));
+        let (min, max) = parse_level("13.5").unwrap();
+        assert!((min - 13.5).abs() < 1e-9 && (max - 13.6).abs() < 1e-9);
+        assert_eq!(parse_level("hard"), None);
+    }
+
+    #[test]
+    fn reject_invalid_values() {
+        assert!(ChartQuery::parse("level:abc").is_err());
+        assert!(ChartQuery::parse("format:osu").is_err());
+        assert!(ChartQuery::parse("sort:rating").is_err());
+        assert!(ChartQuery::parse("is:cool").is_err());
+    }
+}
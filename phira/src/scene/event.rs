@@ -2,7 +2,8 @@ prpr_l10n::tl_file!("event");
 
 use super::{render_ldb, LdbDisplayItem, ProfileScene};
 use crate::{
-    client::{recv_raw, Client, Event, UserManager},
+    client::{api, Chart, Event, EventStatus, UserManager},
+    download,
     icons::Icons,
     page::{EventPage, Fader, Illustration, SFader},
     uml::{parse_uml, Uml},
@@ -13,35 +14,24 @@ use macroquad::prelude::*;
 use prpr::{
     core::Tweenable,
     ext::{open_url, semi_black, semi_white, RectExt, SafeTexture, ScaleType},
-    scene::{show_error, NextScene, Scene},
+    scene::{show_error, show_message, NextScene, Scene},
     task::Task,
     time::TimeManager,
     ui::{button_hit, DRectButton, LoadingParams, RectButton, Scroll, Ui},
 };
-use serde::Deserialize;
 use std::{any::Any, sync::Arc, time::SystemTime};
 
 const DEBUG_MODE: bool = cfg!(feature = "event_debug");
 const LDB_WIDTH: f32 = 0.94;
 const TRANSIT_TIME: f32 = 0.4;
 
-#[derive(Deserialize)]
 struct LdbItem {
     player: i32,
     rank: i32,
     score: i32,
-    #[serde(skip, default)]
     btn: RectButton,
 }
 
-#[derive(Deserialize)]
-#[allow(dead_code)]
-struct Status {
-    joined: bool,
-    rank: Option<i32>,
-    score: Option<i32>,
-}
-
 pub struct EventScene {
     event: Event,
     illu: Illustration,
@@ -50,8 +40,8 @@ pub struct EventScene {
 
     btn_back: RectButton,
 
-    status_task: Option<Task<Result<Status>>>,
-    status: Option<Status>,
+    status_task: Option<Task<Result<EventStatus>>>,
+    status: Option<EventStatus>,
 
     uml_task: Option<Task<Result<String>>>,
     uml: Uml,
@@ -62,6 +52,8 @@ pub struct EventScene {
     btn_join: DRectButton,
     join_task: Option<Task<Result<()>>>,
 
+    download_task: Option<Task<Result<Vec<Chart>>>>,
+
     scrolled: bool,
     start_time: f32,
 
@@ -97,12 +89,7 @@ impl EventScene {
             uml_task: if DEBUG_MODE {
                 None
             } else {
-                Some(Task::new(async move {
-                    Ok(recv_raw(Client::get(format!("/event/{id}/uml")).query(&[("version", env!("CARGO_PKG_VERSION"))]))
-                        .await?
-                        .text()
-                        .await?)
-                }))
+                Some(Task::new(async move { api().event_uml(id).await }))
             },
             uml: Uml::default(),
             last_modified: SystemTime::now(),
@@ -112,6 +99,8 @@ impl EventScene {
             btn_join: DRectButton::new(),
             join_task: None,
 
+            download_task: None,
+
             scrolled: false,
             start_time: 0.,
 
@@ -134,13 +123,34 @@ impl EventScene {
     fn load_status(&mut self) {
         self.status = None;
         let id = self.event.id;
-        self.status_task = Some(Task::new(async move { Ok(recv_raw(Client::get(format!("/event/{id}/status"))).await?.json().await?) }));
+        self.status_task = Some(Task::new(async move { api().event_status(id).await }));
     }
 
     fn load_ldb(&mut self) {
         let id = self.event.id;
         self.ldb = None;
-        self.ldb_task = Some(Task::new(async move { Ok(recv_raw(Client::get(format!("/event/{id}/list15"))).await?.json().await?) }));
+        self.ldb_task = Some(Task::new(async move {
+            Ok(api()
+                .event_leaderboard(id)
+                .await?
+                .into_iter()
+                .map(|it| LdbItem {
+                    player: it.player,
+                    rank: it.rank,
+                    score: it.score,
+                    btn: RectButton::new(),
+                })
+                .collect())
+        }));
+    }
+
+    fn download_all(charts: Vec<Chart>) {
+        let count = download::enqueue_all(charts);
+        if count == 0 {
+            show_message(tl!("dl-nothing")).ok();
+        } else {
+            show_message(tl!("dl-queued", "count" => count)).ok();
+        }
     }
 
     fn loading(&self) -> bool {
@@ -158,10 +168,7 @@ impl EventScene {
                 }
             } else {
                 let id = self.event.id;
-                self.join_task = Some(Task::new(async move {
-                    recv_raw(Client::post(format!("/event/{id}/join"), &())).await?;
-                    Ok(())
-                }));
+                self.join_task = Some(Task::new(async move { api().join_event(id).await }));
             }
         }
     }
@@ -237,9 +244,17 @@ impl Scene for EventScene {
                     "join" => {
                         self.join_or(rt);
                     }
+                    "download" => {
+                        Self::download_all(self.uml.charts());
+                    }
+                    "cancel-download" => {
+                        download::cancel_bulk();
+                    }
                     x => {
                         if let Some(url) = x.strip_prefix("open:") {
                             open_url(url)?;
+                        } else if let Some(Ok(id)) = x.strip_prefix("download:").map(str::parse::<i32>) {
+                            self.download_task = Some(Task::new(async move { Ok(api().collection(id).await?.charts) }));
                         }
                     }
                 }
@@ -331,6 +346,20 @@ impl Scene for EventScene {
             }
         }
 
+        if let Some(task) = &mut self.download_task {
+            if let Some(res) = task.take() {
+                match res {
+                    Err(err) => {
+                        show_error(err.context(tl!("dl-failed")));
+                    }
+                    Ok(charts) => {
+                        Self::download_all(charts);
+                    }
+                }
+                self.download_task = None;
+            }
+        }
+
         if let Some(task) = &mut self.ldb_task {
             if let Some(res) = task.take() {
                 match res {
@@ -407,6 +436,15 @@ impl Scene for EventScene {
             });
         });
 
+        if let Some((finished, total)) = download::bulk_progress() {
+            ui.text(tl!("dl-progress", "finished" => finished, "total" => total))
+                .pos(0., -ui.top + 0.04)
+                .anchor(0.5, 0.)
+                .size(0.45)
+                .color(semi_white(0.8))
+                .draw();
+        }
+
         let elapsed = t - self.start_time;
         if !self.scrolled && elapsed > 2. {
             let top = ui.top;
@@ -3,26 +3,32 @@ prpr_l10n::tl_file!("profile");
 use super::{confirm_delete, TEX_BACKGROUND, TEX_ICON_BACK};
 use crate::{
     anti_addiction_action,
-    client::{recv_raw, Client, Record, User, UserManager},
+    client::{recv_raw, Client, Record, User, UserManager, API_CACHE},
     get_data, get_data_mut,
+    history::{summarize, ChartHistory, HistoryEntry, HistoryQuery},
     page::{Fader, Illustration, SFader},
     save_data, sync_data, ttl,
 };
 use anyhow::Result;
-use chrono::Local;
+use chrono::{DateTime, Local, Utc};
+use lyon::{math::point, path::Path};
 use macroquad::prelude::*;
 use prpr::{
     ext::{open_url, semi_black, semi_white, RectExt, SafeTexture, ScaleType, BLACK_TEXTURE},
     judge::icon_index,
+    rating::Rating,
     scene::{request_file, return_file, show_error, show_message, take_file, NextScene, Scene},
     task::Task,
     time::TimeManager,
     ui::{button_hit, rounded_rect_shadow, DRectButton, RectButton, Scroll, ShadowConfig, Ui},
 };
 use serde_json::json;
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
+use std::{
+    borrow::Cow,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 use tokio::sync::Notify;
 
@@ -33,8 +39,45 @@ struct RecordItem {
     illu: Illustration,
 }
 
+struct HistoryItem {
+    chart: ChartHistory,
+    btn: DRectButton,
+}
+
+/// Score over time of a single chart
+struct Progression {
+    name: String,
+    entries: Vec<HistoryEntry>,
+}
+
+/// A chart of the local rating
+struct RatingRow {
+    name: String,
+    /// Accuracy needed to raise the rating, see [Rating::target_accuracy]
+    target: Option<f32>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Panel {
+    Records,
+    History,
+    Rating,
+}
+
+impl Panel {
+    fn label(&self) -> Cow<'static, str> {
+        match self {
+            Self::Records => tl!("records"),
+            Self::History => tl!("history"),
+            Self::Rating => tl!("rating"),
+        }
+    }
+}
+
 pub struct ProfileScene {
     id: i32,
+    /// Profile of an offline player, only showing the local history
+    local: bool,
     user: Option<Arc<User>>,
     user_badges: Vec<String>,
 
@@ -62,6 +105,17 @@ pub struct ProfileScene {
     record_task: Option<Task<Result<Vec<RecordItem>>>>,
     record_items: Option<Vec<RecordItem>>,
 
+    panel: Panel,
+    panel_btns: [DRectButton; 3],
+    history_scroll: Scroll,
+    history_task: Option<Task<Result<Vec<HistoryItem>>>>,
+    history_items: Option<Vec<HistoryItem>>,
+    btn_progression_back: DRectButton,
+    progression_task: Option<Task<Result<Progression>>>,
+    progression: Option<Progression>,
+    rating_scroll: Scroll,
+    rating: Option<(Rating, Vec<RatingRow>)>,
+
     sf: SFader,
     fader: Fader,
 
@@ -70,11 +124,26 @@ pub struct ProfileScene {
 
 impl ProfileScene {
     pub fn new(id: i32, icon_user: SafeTexture, rank_icons: [SafeTexture; 8]) -> Self {
-        let _ = UserManager::clear_cache(id);
-        UserManager::request(id);
-        let load_task = Some(Task::new(Client::load(id)));
+        Self::create(id, false, icon_user, rank_icons)
+    }
+
+    /// Profile for players who are not logged in
+    pub fn local(icon_user: SafeTexture, rank_icons: [SafeTexture; 8]) -> Self {
+        let mut res = Self::create(0, true, icon_user, rank_icons);
+        res.load_rating();
+        res.switch_panel(Panel::History);
+        res
+    }
+
+    fn create(id: i32, local: bool, icon_user: SafeTexture, rank_icons: [SafeTexture; 8]) -> Self {
+        if !local {
+            let _ = UserManager::clear_cache(id);
+            UserManager::request(id);
+        }
+        let load_task = (!local).then(|| Task::new(Client::load(id)));
         Self {
             id,
+            local,
             user: None,
             user_badges: Vec::new(),
 
@@ -99,46 +168,326 @@ impl ProfileScene {
             delete_task: None,
 
             scroll: Scroll::new(),
-            record_task: Some(Task::new(async move {
-                let records: Vec<Record> = recv_raw(Client::get(format!("/record?player={id}"))).await?.json().await?;
-                Ok(records
-                    .into_iter()
-                    .map(|it| {
-                        let illu = {
+            record_task: (!local).then(|| {
+                Task::new(async move {
+                    let records: Vec<Record> = API_CACHE
+                        .get_json(Client::get(format!("/record?player={id}")), true)
+                        .await?
+                        .unwrap_or_default();
+                    Ok(records
+                        .into_iter()
+                        .map(|it| {
+                            let illu = {
+                                let chart = it.chart.clone();
+                                let notify = Arc::new(Notify::new());
+                                Illustration {
+                                    texture: (BLACK_TEXTURE.clone(), BLACK_TEXTURE.clone()),
+                                    notify: Arc::clone(&notify),
+                                    task: Some(Task::new({
+                                        async move {
+                                            notify.notified().await;
+                                            let illu = &chart.fetch().await?.illustration;
+                                            Ok((illu.load_thumbnail().await?, None))
+                                        }
+                                    })),
+                                    loaded: Arc::default(),
+                                    load_time: f32::NAN,
+                                }
+                            };
                             let chart = it.chart.clone();
-                            let notify = Arc::new(Notify::new());
-                            Illustration {
-                                texture: (BLACK_TEXTURE.clone(), BLACK_TEXTURE.clone()),
-                                notify: Arc::clone(&notify),
-                                task: Some(Task::new({
-                                    async move {
-                                        notify.notified().await;
-                                        let illu = &chart.fetch().await?.illustration;
-                                        Ok((illu.load_thumbnail().await?, None))
-                                    }
-                                })),
-                                loaded: Arc::default(),
-                                load_time: f32::NAN,
+                            RecordItem {
+                                record: it,
+                                name: Task::new(async move { Ok(chart.fetch().await?.name.clone()) }),
+                                btn: DRectButton::new(),
+                                illu,
                             }
-                        };
-                        let chart = it.chart.clone();
-                        RecordItem {
-                            record: it,
-                            name: Task::new(async move { Ok(chart.fetch().await?.name.clone()) }),
-                            btn: DRectButton::new(),
-                            illu,
-                        }
-                    })
-                    .collect())
-            })),
+                        })
+                        .collect())
+                })
+            }),
             record_items: None,
 
+            panel: Panel::Records,
+            panel_btns: [DRectButton::new(), DRectButton::new(), DRectButton::new()],
+            history_scroll: Scroll::new(),
+            history_task: None,
+            history_items: None,
+            btn_progression_back: DRectButton::new(),
+            progression_task: None,
+            progression: None,
+            rating_scroll: Scroll::new(),
+            rating: None,
+
             sf: SFader::new(),
             fader: Fader::new().with_distance(0.12),
 
             rank_icons,
         }
     }
+
+    fn panels(&self) -> &'static [Panel] {
+        if self.local {
+            &[Panel::History, Panel::Rating]
+        } else if get_data().me.as_ref().is_some_and(|it| it.id == self.id) {
+            &[Panel::Records, Panel::History, Panel::Rating]
+        } else {
+            &[]
+        }
+    }
+
+    fn switch_panel(&mut self, panel: Panel) {
+        match panel {
+            Panel::Records => {}
+            Panel::History => self.load_history(),
+            Panel::Rating => self.load_rating(),
+        }
+        self.panel = panel;
+    }
+
+    fn load_rating(&mut self) {
+        let data = get_data();
+        let rating = data.local_rating();
+        let rows = rating
+            .charts
+            .iter()
+            .map(|it| RatingRow {
+                name: data
+                    .charts
+                    .iter()
+                    .find(|chart| chart.local_path == it.record.key)
+                    .map_or_else(|| it.record.key.clone(), |chart| chart.info.name.clone()),
+                target: rating.target_accuracy(&it.record.key, it.record.difficulty),
+            })
+            .collect();
+        self.rating = Some((rating, rows));
+    }
+
+    fn load_history(&mut self) {
+        self.progression = None;
+        self.history_items = None;
+        self.history_task = Some(Task::new(async move {
+            Ok(summarize(&HistoryQuery::default().run()?)
+                .into_iter()
+                .map(|chart| HistoryItem {
+                    chart,
+                    btn: DRectButton::new(),
+                })
+                .collect())
+        }));
+    }
+
+    fn render_history(&mut self, ui: &mut Ui, r: Rect, t: f32) {
+        if self.progression_task.is_some() {
+            let ct = r.center();
+            ui.loading(ct.x, ct.y, t, WHITE, ());
+            return;
+        }
+        if self.progression.is_some() {
+            self.render_progression(ui, r, t);
+            return;
+        }
+        let Some(items) = &mut self.history_items else {
+            let ct = r.center();
+            ui.loading(ct.x, ct.y, t, WHITE, ());
+            return;
+        };
+        if items.is_empty() {
+            ui.text(tl!("history-empty"))
+                .pos(r.center().x, r.y + 0.2)
+                .anchor(0.5, 0.)
+                .size(0.6)
+                .color(semi_white(0.6))
+                .draw();
+            return;
+        }
+        ui.scope(|ui| {
+            ui.dx(r.x);
+            ui.dy(r.y);
+            self.history_scroll.size((r.w, ui.top - r.y));
+            self.history_scroll.render(ui, |ui| {
+                let h = 0.15;
+                let pad = 0.01;
+                for (i, item) in items.iter_mut().enumerate() {
+                    let r = Rect::new(0., i as f32 * h, r.w, h - pad * 2.);
+                    item.btn.render_shadow(ui, r, t, |ui, path| {
+                        ui.fill_path(&path, semi_black(0.4));
+                    });
+                    let chart = &item.chart;
+                    let s = r.h - pad * 2.;
+                    let ir = Rect::new(r.x + pad, r.y + pad, s, s);
+                    let icon = icon_index(chart.best_score, chart.full_combo);
+                    ui.fill_rect(ir, (*self.rank_icons[icon], ir, ScaleType::Fit));
+                    let lf = ir.right() + 0.02;
+                    ui.text(&chart.name).pos(lf, ir.y).max_width(r.right() - lf - 0.25).size(0.5).draw();
+                    ui.text(tl!("history-summary", "attempts" => chart.attempts, "time" => format_time(&chart.last_played)))
+                        .pos(lf, ir.bottom())
+                        .anchor(0., 1.)
+                        .size(0.36)
+                        .color(semi_white(0.6))
+                        .draw();
+                    ui.text(format!("{:07}", chart.best_score))
+                        .pos(r.right() - 0.02, r.center().y)
+                        .anchor(1., 0.5)
+                        .size(0.6)
+                        .draw();
+                }
+                (r.w, h * items.len() as f32 + 0.04)
+            });
+        });
+    }
+
+    fn render_rating(&mut self, ui: &mut Ui, r: Rect) {
+        let Some((rating, rows)) = &self.rating else { return };
+        if rows.is_empty() {
+            ui.text(tl!("rating-empty"))
+                .pos(r.center().x, r.y + 0.2)
+                .anchor(0.5, 0.)
+                .size(0.6)
+                .color(semi_white(0.6))
+                .draw();
+            return;
+        }
+        ui.scope(|ui| {
+            ui.dx(r.x);
+            ui.dy(r.y);
+            self.rating_scroll.size((r.w, ui.top - r.y));
+            self.rating_scroll.render(ui, |ui| {
+                let h = 0.09;
+                let tr = ui
+                    .text(tl!("rating-hint"))
+                    .pos(0., 0.)
+                    .max_width(r.w)
+                    .multiline()
+                    .size(0.4)
+                    .color(semi_white(0.6))
+                    .draw();
+                let top = tr.bottom() + 0.02;
+                for (i, (chart, row)) in rating.charts.iter().zip(rows).enumerate() {
+                    let r = Rect::new(0., top + i as f32 * h, r.w, h - 0.01);
+                    let counts = rating.counts(i);
+                    ui.fill_rect(r, semi_black(if counts { 0.5 } else { 0.25 }));
+                    let slot = if rating.phi == Some(i) {
+                        "φ".to_owned()
+                    } else if i < rating.best {
+                        format!("#{}", i + 1)
+                    } else {
+                        String::new()
+                    };
+                    let c = semi_white(if counts { 1. } else { 0.6 });
+                    ui.text(slot).pos(r.x + 0.02, r.center().y).anchor(0., 0.5).size(0.4).color(c).draw();
+                    let right = r.right() - 0.02;
+                    let tr = ui
+                        .text(match row.target {
+                            Some(acc) => tl!("rating-target", "accuracy" => format!("{:.2}", acc * 100.)),
+                            None => "-".to_owned(),
+                        })
+                        .pos(right, r.center().y)
+                        .anchor(1., 0.5)
+                        .size(0.36)
+                        .color(semi_white(0.6))
+                        .draw();
+                    let record = &chart.record;
+                    let tr = ui
+                        .text(format!("{:.1}  {:.2}%  {:.2}", record.difficulty, record.accuracy * 100., chart.rating))
+                        .pos(tr.x - 0.03, r.center().y)
+                        .anchor(1., 0.5)
+                        .size(0.4)
+                        .color(c)
+                        .draw();
+                    let lf = r.x + 0.1;
+                    ui.text(&row.name)
+                        .pos(lf, r.center().y)
+                        .anchor(0., 0.5)
+                        .max_width(tr.x - lf - 0.02)
+                        .size(0.44)
+                        .color(c)
+                        .draw();
+                }
+                (r.w, top + h * rows.len() as f32 + 0.04)
+            });
+        });
+    }
+
+    fn render_progression(&mut self, ui: &mut Ui, r: Rect, t: f32) {
+        let Some(progression) = &self.progression else { return };
+        let br = Rect::new(r.x, r.y, 0.16, 0.07);
+        self.btn_progression_back.render_text(ui, br, t, tl!("history-back"), 0.5, true);
+        ui.text(&progression.name)
+            .pos(br.right() + 0.02, br.center().y)
+            .anchor(0., 0.5)
+            .max_width(r.right() - br.right() - 0.02)
+            .size(0.6)
+            .draw();
+
+        let entries = &progression.entries;
+        let (Some(first), Some(last)) = (entries.first(), entries.last()) else {
+            return;
+        };
+        let gr = Rect::new(r.x, br.bottom() + 0.03, r.w, 0.7);
+        ui.fill_rect(gr, semi_black(0.4));
+        // scores below the lowest hundred thousand reached are left out to keep small improvements visible
+        let lowest = entries.iter().map(|it| it.attempt.result.score).min().unwrap_or_default();
+        let lo = (lowest / 100_000).min(9) as f32 * 100_000.;
+        let hi = 1_000_000.;
+        for i in 0..=((hi - lo) / 100_000.) as u32 {
+            let score = lo + i as f32 * 100_000.;
+            let y = gr.bottom() - (score - lo) / (hi - lo) * gr.h;
+            ui.fill_rect(Rect::new(gr.x, y - 0.001, gr.w, 0.002), semi_white(0.15));
+            ui.text(format!("{:.0}K", score / 1000.))
+                .pos(gr.x + 0.01, y - 0.005)
+                .anchor(0., 1.)
+                .size(0.3)
+                .color(semi_white(0.5))
+                .draw();
+        }
+        let span = (last.attempt.time - first.attempt.time).num_seconds() as f32;
+        let points: Vec<_> = entries
+            .iter()
+            .map(|it| {
+                let p = if span > 0. {
+                    (it.attempt.time - first.attempt.time).num_seconds() as f32 / span
+                } else {
+                    0.5
+                };
+                let x = gr.x + 0.03 + p * (gr.w - 0.06);
+                let y = gr.bottom() - (it.attempt.result.score as f32 - lo) / (hi - lo) * gr.h;
+                (x, y)
+            })
+            .collect();
+        if points.len() > 1 {
+            let mut path = Path::builder();
+            path.begin(point(points[0].0, points[0].1));
+            for &(x, y) in &points[1..] {
+                path.line_to(point(x, y));
+            }
+            path.end(false);
+            ui.stroke_path(&path.build(), 0.004, semi_white(0.8));
+        }
+        for (&(x, y), entry) in points.iter().zip(entries) {
+            let result = &entry.attempt.result;
+            let color = if result.max_combo == result.num_of_notes {
+                Color::from_hex(0xffe082)
+            } else {
+                WHITE
+            };
+            ui.fill_circle(x, y, 0.008, color);
+        }
+
+        let best = entries.iter().max_by_key(|it| it.attempt.result.score).unwrap();
+        let mut y = gr.bottom() + 0.02;
+        for text in [
+            tl!("history-attempts", "attempts" => entries.len(), "first" => format_time(&first.attempt.time), "last" => format_time(&last.attempt.time)),
+            tl!("history-best", "score" => format!("{:07}", best.attempt.result.score), "accuracy" => format!("{:.2}", best.attempt.result.accuracy * 100.)),
+            tl!("history-latest", "score" => format!("{:07}", last.attempt.result.score), "accuracy" => format!("{:.2}", last.attempt.result.accuracy * 100.)),
+        ] {
+            let tr = ui.text(text).pos(gr.x, y).size(0.42).color(semi_white(0.8)).draw();
+            y = tr.bottom() + 0.01;
+        }
+    }
+}
+
+fn format_time(time: &DateTime<Utc>) -> String {
+    time.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string()
 }
 
 impl Scene for ProfileScene {
@@ -152,6 +501,8 @@ impl Scene for ProfileScene {
 
         self.pf_scroll.update(t);
         self.scroll.update(t);
+        self.history_scroll.update(t);
+        self.rating_scroll.update(t);
 
         if let Some(task) = &mut self.load_task {
             if let Some(res) = task.take() {
@@ -228,6 +579,25 @@ impl Scene for ProfileScene {
             }
         }
 
+        if let Some(task) = &mut self.history_task {
+            if let Some(res) = task.take() {
+                match res {
+                    Err(err) => show_error(err.context(tl!("load-history-failed"))),
+                    Ok(val) => self.history_items = Some(val),
+                }
+                self.history_task = None;
+            }
+        }
+        if let Some(task) = &mut self.progression_task {
+            if let Some(res) = task.take() {
+                match res {
+                    Err(err) => show_error(err.context(tl!("load-history-failed"))),
+                    Ok(val) => self.progression = Some(val),
+                }
+                self.progression_task = None;
+            }
+        }
+
         if self.should_delete.fetch_and(false, Ordering::Relaxed) {
             self.delete_task = Some(Task::new(async move {
                 Client::post("/delete-account", &()).send().await?.error_for_status()?;
@@ -283,6 +653,44 @@ impl Scene for ProfileScene {
             return Ok(true);
         }
 
+        for (i, panel) in self.panels().iter().enumerate() {
+            if self.panel_btns[i].touch(touch, t) {
+                self.switch_panel(*panel);
+                return Ok(true);
+            }
+        }
+        if self.panel == Panel::Rating {
+            return Ok(self.rating_scroll.touch(touch, t));
+        }
+        if self.panel == Panel::History {
+            if self.progression.is_some() {
+                if self.btn_progression_back.touch(touch, t) {
+                    self.progression = None;
+                    return Ok(true);
+                }
+                return Ok(false);
+            }
+            if self.progression_task.is_some() || self.history_scroll.touch(touch, t) {
+                return Ok(true);
+            }
+            if let Some(items) = &mut self.history_items {
+                for item in items {
+                    if item.btn.touch(touch, t) {
+                        let local_path = item.chart.local_path.clone();
+                        let name = item.chart.name.clone();
+                        self.progression_task = Some(Task::new(async move {
+                            Ok(Progression {
+                                name,
+                                entries: HistoryQuery::chart(local_path).run()?,
+                            })
+                        }));
+                        return Ok(true);
+                    }
+                }
+            }
+            return Ok(false);
+        }
+
         if self.scroll.touch(touch, t) {
             return Ok(true);
         }
@@ -387,12 +795,34 @@ impl Scene for ProfileScene {
                     (ow, r.bottom() - oy + 0.04)
                 });
             });
+        } else if self.local {
+            let cx = r.center().x;
+            let radius = 0.12;
+            let r = ui.avatar(cx, r.y + radius + 0.05, radius, t, Err(self.icon_user.clone()));
+            let r = ui.text(tl!("local-player")).size(0.74).pos(cx, r.bottom() + 0.03).anchor(0.5, 0.).draw();
+            if let Some((rating, _)) = &self.rating {
+                ui.text(format!("RKS {:.2}", rating.rks))
+                    .size(0.5)
+                    .pos(cx, r.bottom() + 0.01)
+                    .anchor(0.5, 0.)
+                    .draw();
+            }
         } else {
             ui.loading(r.center().x, (r.y + r.bottom().min(ui.top)) / 2., t, WHITE, ());
         }
 
         let r = Rect::new(r.right() + 0.05, r.y, 0.9 - r.right(), 1.5);
-        if let Some(items) = &mut self.record_items {
+        let panels = self.panels();
+        for (i, panel) in panels.iter().enumerate() {
+            let w = 0.22;
+            let br = Rect::new(r.right() - (panels.len() - i) as f32 * (w + 0.02), -ui.top + 0.015, w, 0.07);
+            self.panel_btns[i].render_text(ui, br, t, panel.label(), 0.5, *panel == self.panel);
+        }
+        if self.panel == Panel::History {
+            self.render_history(ui, r, t);
+        } else if self.panel == Panel::Rating {
+            self.render_rating(ui, r);
+        } else if let Some(items) = &mut self.record_items {
             self.fader.reset();
             self.fader.for_sub(|f| {
                 ui.scope(|ui| {
@@ -7,46 +7,47 @@ use super::{
 };
 use crate::{
     charts_view::NEED_UPDATE,
-    client::{basic_client_builder, recv_raw, Chart, Client, Permissions, Ptr, Record, UserManager, CLIENT_TOKEN},
-    data::{BriefChartInfo, LocalChart, DEFAULT_FAVORITES_KEY},
-    dir, get_data, get_data_mut,
+    client::{api, sync_cache_state, Chart, Client, Permissions, Ptr, Record, Review, Stabilize, UploadRecord, UserManager},
+    data::{BriefChartInfo, DEFAULT_FAVORITES_KEY},
+    dir,
+    download::{self, DownloadState, Stage},
+    get_data, get_data_mut,
+    history::{self, HistoryEntry},
     icons::Icons,
+    outbox::{self, Action, OutboxEntry},
     page::{local_illustration, thumbnail_path, ChartItem, ChartType, Fader, Illustration, SFader},
     popup::Popup,
     rate::RateDialog,
-    save_data,
+    save_data, store,
     tags::TagsDialog,
     ttl,
 };
 use ::rand::{thread_rng, Rng};
 use anyhow::{anyhow, bail, Context, Result};
 use base64::{engine::general_purpose::STANDARD, Engine};
-use chrono::{DateTime, Utc};
-use futures_util::StreamExt;
+use chrono::Utc;
 use macroquad::prelude::*;
 use phira_mp_common::{ClientCommand, CompactPos, JudgeEvent, TouchFrame};
+#[cfg(feature = "video")]
+use prpr::scene::{EncoderOptions, RenderScene};
 use prpr::{
-    config::Mods,
+    calibrate,
+    config::{Config, Mods},
     core::{Tweenable, BOLD_FONT},
-    ext::{
-        open_url, poll_future, rect_shadow, semi_black, semi_white, unzip_into, JoinToString, LocalTask, RectExt, SafeTexture, ScaleType,
-        BLACK_TEXTURE,
-    },
+    ext::{open_url, poll_future, rect_shadow, semi_black, semi_white, JoinToString, LocalTask, RectExt, SafeTexture, ScaleType, BLACK_TEXTURE},
     fs::{self},
     info::ChartInfo,
     judge::{icon_index, Judge},
+    replay::{chart_hash, Replay},
     scene::{
-        request_file, request_input, return_file, return_input, show_error, show_message, take_file, take_input, BasicPlayer, GameMode, LoadingScene,
-        LocalSceneTask, NextScene, RecordUpdateState, Scene, SimpleRecord, UpdateFn, UploadFn,
+        request_file, request_input, return_file, return_input, show_error, show_message, take_file, take_input, BasicPlayer, GameMode, GameScene,
+        LoadingScene, LocalSceneTask, NextScene, RecordUpdateState, Scene, SimpleRecord, UpdateFn, UploadFn, UploadQueued,
     },
     task::Task,
     time::TimeManager,
     ui::{button_hit, render_chart_info, ChartInfoEdit, DRectButton, Dialog, LoadingParams, RectButton, Scroll, Ui, UI_AUDIO},
 };
-use reqwest::Method;
 use sasa::{AudioClip, Frame, Music, MusicParams};
-use serde::{Deserialize, Serialize};
-use serde_json::json;
 use sha2::{Digest, Sha256};
 use std::{
     any::Any,
@@ -57,13 +58,12 @@ use std::{
     path::Path,
     sync::{
         atomic::{AtomicBool, AtomicI32, Ordering},
-        Arc, Mutex, Weak,
+        Arc, Mutex,
     },
     thread_local,
 };
 use tokio::net::TcpStream;
 use tracing::{error, warn};
-use uuid::Uuid;
 use walkdir::WalkDir;
 use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
 
@@ -130,63 +130,75 @@ async fn load_local_tuple(local_path: &str, def_illu: SafeTexture, info: ChartIn
     Ok((local_path.to_owned(), info, preview, illu))
 }
 
+/// Overlay showing the download of a chart, see [download]
 pub struct Downloading {
-    info: BriefChartInfo,
     local_path: Option<String>,
     loading_last: f32,
     cancel_download_btn: DRectButton,
-    status: Arc<Mutex<Cow<'static, str>>>,
-    prog: Arc<Mutex<Option<f32>>>,
-    task: Task<Result<(LocalChart, LocalTuple)>>,
+    state: Arc<DownloadState>,
+    /// Whether the download was started here, rather than joined while queued by someone else
+    owned: bool,
+    tuple_task: Option<Task<Result<LocalTuple>>>,
+}
+
+impl Drop for Downloading {
+    fn drop(&mut self) {
+        if self.owned {
+            self.state.cancel();
+        }
+    }
 }
 
 impl Downloading {
     pub fn touch(&mut self, touch: &Touch, t: f32) -> bool {
-        self.cancel_download_btn.touch(touch, t)
+        if self.cancel_download_btn.touch(touch, t) {
+            self.state.cancel();
+            return true;
+        }
+        false
     }
 
     pub fn render(&mut self, ui: &mut Ui, t: f32) {
         ui.fill_rect(ui.screen_rect(), semi_black(0.6));
-        ui.loading(0., -0.06, t, WHITE, (*self.prog.lock().unwrap(), &mut self.loading_last));
-        ui.text(self.status.lock().unwrap().clone())
-            .pos(0., 0.02)
-            .anchor(0.5, 0.)
-            .size(0.6)
-            .draw();
+        ui.loading(0., -0.06, t, WHITE, (self.state.progress(), &mut self.loading_last));
+        let status = match self.state.stage() {
+            Stage::Queued => tl!("dl-status-queued"),
+            Stage::Downloading => tl!("dl-status-chart"),
+            Stage::Extracting => tl!("dl-status-extract"),
+            Stage::Verifying => tl!("dl-status-verify"),
+            _ => tl!("dl-status-saving"),
+        };
+        ui.text(status).pos(0., 0.02).anchor(0.5, 0.).size(0.6).draw();
         let size = 0.7;
         let r = ui.text(tl!("dl-cancel")).pos(0., 0.12).anchor(0.5, 0.).size(size).measure().feather(0.02);
         self.cancel_download_btn.render_text(ui, r, t, tl!("dl-cancel"), 0.6, true);
     }
 
     pub fn check(&mut self) -> Result<Option<Option<LocalTuple>>> {
-        if let Some(res) = self.task.take() {
-            match res {
+        if let Some(task) = &mut self.tuple_task {
+            return Ok(task.take().map(|res| match res {
+                Ok(tuple) => {
+                    show_message(tl!("dl-success")).ok();
+                    Some(tuple)
+                }
                 Err(err) => {
-                    let path = format!("{}/{}", dir::downloaded_charts()?, self.info.id.unwrap());
-                    let path = Path::new(&path);
-                    if path.exists() {
-                        std::fs::remove_dir_all(path)?;
-                    }
                     show_error(err.context(tl!("dl-failed")));
-                    Ok(Some(None))
-                }
-                Ok((chart, tuple)) => {
-                    self.info = chart.info.clone();
-                    if let Some(local_path) = &self.local_path {
-                        // update
-                        SongScene::global_update_chart_info(local_path, self.info.clone())?;
-                    } else {
-                        NEED_UPDATE.store(true, Ordering::Relaxed);
-                        self.local_path = Some(chart.local_path.clone());
-                        get_data_mut().charts.push(chart);
-                    }
-                    save_data()?;
-                    show_message(tl!("dl-success")).ok();
-                    Ok(Some(Some(tuple)))
+                    None
                 }
+            }));
+        }
+        match self.state.stage() {
+            Stage::Done(local_path, info) => {
+                self.local_path = Some(local_path.clone());
+                self.tuple_task = Some(Task::new(async move { load_local_tuple(&local_path, BLACK_TEXTURE.clone(), info).await }));
+                Ok(None)
             }
-        } else {
-            Ok(None)
+            Stage::Failed(err) => {
+                show_error(anyhow!(err).context(tl!("dl-failed")));
+                Ok(Some(None))
+            }
+            Stage::Cancelled => Ok(Some(None)),
+            _ => Ok(None),
         }
     }
 }
@@ -209,20 +221,62 @@ impl SideContent {
     }
 }
 
-#[derive(Deserialize)]
-struct StableR {
-    status: i8,
-}
-
-#[derive(Deserialize)]
 struct LdbItem {
-    #[serde(flatten)]
     pub inner: Record,
     pub rank: u32,
-    #[serde(skip, default)]
     pub btn: RectButton,
 }
 
+/// Collect what finished plays reported, crediting each chart with its own plays
+///
+/// Has to be called wherever plays are launched from.
+pub fn collect_reported() -> Result<()> {
+    collect_history();
+    collect_offset_samples()
+}
+
+/// Store offset samples with their chart, possibly applying the new suggestion
+fn collect_offset_samples() -> Result<()> {
+    let samples = calibrate::REPORTED.take();
+    if samples.is_empty() {
+        return Ok(());
+    }
+    let data = get_data_mut();
+    for (local_path, sample) in samples {
+        let chart = data.charts.iter_mut().find(|it| it.local_path == local_path);
+        // plays with an override of the chart say nothing about the global offset
+        if chart.as_ref().map_or(true, |it| it.offset.is_none()) {
+            data.offset_history.push(sample);
+        }
+        if let Some(chart) = chart {
+            chart.offset_history.push(sample);
+        }
+    }
+    if data.config.auto_offset {
+        if let Some(offset) = data.offset_history.suggest(data.config.offset) {
+            data.config.offset = offset;
+            show_message(tl!("offset-auto-applied", "offset" => format!("{:.0}", offset * 1000.))).ok();
+        }
+    }
+    save_data()?;
+    Ok(())
+}
+
+/// Store attempts in the local history of their chart
+fn collect_history() {
+    for (local_path, attempt) in prpr::history::REPORTED.take() {
+        let name = get_data()
+            .charts
+            .iter()
+            .find(|it| it.local_path == local_path)
+            .map_or_else(|| local_path.clone(), |it| it.info.name.clone());
+        let entry = HistoryEntry { local_path, name, attempt };
+        if let Err(err) = history::record(&entry) {
+            warn!(?err, "failed to record play history");
+        }
+    }
+}
+
 pub struct SongScene {
     illu: Illustration,
 
@@ -242,6 +296,8 @@ pub struct SongScene {
     entity: Option<Chart>,
     info: BriefChartInfo,
     local_path: Option<String>,
+    /// Downloaded from another server than the active one, its id means nothing here
+    foreign: bool,
 
     downloading: Option<Downloading>,
     loading_last: f32,
@@ -291,11 +347,13 @@ pub struct SongScene {
     review_task: Option<Task<Result<String>>>,
     chart_should_delete: Arc<AtomicBool>,
 
-    edit_tags_task: Option<Task<Result<()>>>,
+    /// Whether the tags were sent, they are queued otherwise
+    edit_tags_task: Option<Task<Result<bool>>>,
     tags: TagsDialog,
 
     rate_dialog: RateDialog,
-    rate_task: Option<Task<Result<()>>>,
+    /// Whether the rating was sent, it is queued otherwise
+    rate_task: Option<Task<Result<bool>>>,
 
     should_update: Arc<AtomicBool>,
 
@@ -323,16 +381,22 @@ pub struct SongScene {
 
     update_cksum_passed: Option<bool>,
     update_cksum_task: Option<Task<Result<bool>>>,
+    replay_task: Option<Task<Result<Option<Replay>>>>,
+    /// Render the loaded replay into a video instead of watching it
+    replay_render: bool,
     chart_type: ChartType,
 }
 
 impl SongScene {
     pub fn new(mut chart: ChartItem, local_path: Option<String>, icons: Arc<Icons>, rank_icons: [SafeTexture; 8], mods: Mods) -> Self {
         if let Some(path) = &local_path {
-            if let Some(id) = path.strip_prefix("download/") {
-                chart.info.id = Some(id.parse().unwrap());
+            if let Some(path) = path.strip_prefix("download/") {
+                // charts of other servers are in a directory of their own
+                chart.info.id = Some(path.rsplit('/').next().unwrap().parse().unwrap());
             }
         }
+        let foreign = local_path.as_deref().is_some_and(|it| get_data().is_foreign(it));
+        let online_id = chart.info.id.filter(|_| !foreign);
         let illu = if let Some(path) = &chart.local_path {
             let illu = local_illustration(path.clone(), chart.illu.texture.1.clone(), true);
             illu.notify.notify_one();
@@ -361,11 +425,11 @@ impl SongScene {
             .and_then(|it| it.record.clone())
             .or_else(|| local_path.as_ref().and_then(|path| get_data().local_records.get(path).cloned().flatten()));
         let fetch_best_task = if get_data().me.is_some() {
-            chart.info.id.map(|id| Task::new(Client::best_record(id)))
+            online_id.map(|id| Task::new(async move { api().best_record(id).await }))
         } else {
             None
         };
-        let id = chart.info.id;
+        let id = online_id;
         let offline_mode = get_data().config.offline_mode;
         let icon_star = icons.star.clone();
         Self {
@@ -406,6 +470,7 @@ impl SongScene {
             entity: None,
             info: chart.info,
             local_path,
+            foreign,
 
             downloading: None,
             loading_last: 0.,
@@ -466,16 +531,7 @@ impl SongScene {
             my_rating_task: if offline_mode {
                 None
             } else {
-                id.map(|id| {
-                    Task::new(async move {
-                        #[derive(Deserialize)]
-                        struct Resp {
-                            score: i16,
-                        }
-                        let resp: Resp = recv_raw(Client::get(format!("/chart/{id}/rate"))).await?.json().await?;
-                        Ok(resp.score)
-                    })
-                })
+                id.map(|id| Task::new(async move { api().my_rating(id).await }))
             },
             my_rate_score: None,
 
@@ -499,135 +555,52 @@ impl SongScene {
 
             update_cksum_passed: None,
             update_cksum_task: None,
+            replay_task: None,
+            replay_render: false,
             chart_type: chart.chart_type,
         }
     }
 
     fn start_download(&mut self) -> Result<()> {
-        let chart = self.info.clone();
         let Some(entity) = self.entity.clone() else {
             show_error(anyhow!(tl!("no-chart-for-download")));
             return Ok(());
         };
         self.loading_last = 0.;
-        self.downloading = Some(Self::global_start_download(chart, entity, self.local_path.clone())?);
+        self.downloading = Some(Self::global_start_download(entity));
         Ok(())
     }
 
-    pub fn global_start_download(chart: BriefChartInfo, entity: Chart, local_path: Option<String>) -> Result<Downloading> {
-        let progress = Arc::new(Mutex::new(None));
-        let prog_wk = Arc::downgrade(&progress);
-        let status = Arc::new(Mutex::new(tl!("dl-status-fetch")));
-        let status_shared = Arc::clone(&status);
-        Ok(Downloading {
-            info: chart.clone(),
-            local_path,
+    pub fn global_start_download(entity: Chart) -> Downloading {
+        let owned = download::find(entity.id).is_none();
+        Downloading {
+            local_path: None,
             loading_last: 0.,
             cancel_download_btn: DRectButton::new(),
-            prog: progress,
-            status: status_shared,
-            task: Task::new({
-                let path = format!("{}/{}", dir::downloaded_charts()?, Uuid::new_v4());
-                async move {
-                    let path = std::path::Path::new(&path);
-                    tokio::fs::create_dir(path).await?;
-                    let dir = prpr::dir::Dir::new(path)?;
-
-                    let chart = chart;
-                    async fn download(mut file: impl Write, url: &str, prog_wk: &Weak<Mutex<Option<f32>>>) -> Result<()> {
-                        let Some(prog) = prog_wk.upgrade() else { return Ok(()) };
-                        *prog.lock().unwrap() = None;
-                        let req = basic_client_builder().build().unwrap().get(url);
-                        let req = if let Some(token) = CLIENT_TOKEN.load().as_ref() {
-                            req.header("Authorization", format!("Bearer {token}"))
-                        } else {
-                            req
-                        };
-                        let res = req.send().await.with_context(|| tl!("request-failed"))?.error_for_status()?;
-                        let size = res.content_length();
-                        let mut stream = res.bytes_stream();
-                        let mut count = 0;
-                        while let Some(chunk) = stream.next().await {
-                            let chunk = chunk?;
-                            file.write_all(&chunk)?;
-                            count += chunk.len() as u64;
-                            if let Some(size) = size {
-                                *prog.lock().unwrap() = Some(count.min(size) as f32 / size as f32);
-                            }
-                            if prog_wk.strong_count() == 1 {
-                                // cancelled
-                                break;
-                            }
-                        }
-                        Ok(())
-                    }
-
-                    *status.lock().unwrap() = tl!("dl-status-chart");
-                    let mut bytes = Vec::new();
-                    download(Cursor::new(&mut bytes), &entity.file.url, &prog_wk).await?;
-                    *status.lock().unwrap() = tl!("dl-status-extract");
-                    if prog_wk.strong_count() != 0 {
-                        unzip_into(Cursor::new(bytes), &dir, false)?;
-                    }
-                    *status.lock().unwrap() = tl!("dl-status-saving");
-                    if let Some(prog) = prog_wk.upgrade() {
-                        *prog.lock().unwrap() = None;
-                    }
-                    let mut info: ChartInfo = serde_yaml::from_reader(dir.open("info.yml")?)?;
-                    info.id = Some(entity.id);
-                    info.created = Some(entity.created);
-                    info.updated = Some(entity.updated);
-                    info.chart_updated = Some(entity.chart_updated);
-                    info.uploader = Some(entity.uploader.id);
-                    serde_yaml::to_writer(dir.create("info.yml")?, &info)?;
-
-                    if prog_wk.strong_count() == 0 {
-                        // cancelled
-                        drop(dir);
-                        tokio::fs::remove_dir_all(&path).await?;
-                    }
-
-                    let local_path = format!("download/{}", chart.id.unwrap());
-                    let to_path = format!("{}/{local_path}", dir::charts()?);
-                    let to_path = Path::new(&to_path);
-                    if to_path.exists() {
-                        if to_path.is_file() {
-                            tokio::fs::remove_file(to_path).await?;
-                        } else {
-                            tokio::fs::remove_dir_all(to_path).await?;
-                        }
-                    }
-                    tokio::fs::rename(path, to_path).await?;
-
-                    let tuple = load_local_tuple(&local_path, BLACK_TEXTURE.clone(), info).await?;
-
-                    Ok((
-                        LocalChart {
-                            info: entity.to_info(),
-                            local_path,
-                            record: None,
-                            mods: Mods::default(),
-                            played_unlock: false,
-                        },
-                        tuple,
-                    ))
-                }
-            }),
-        })
+            state: download::enqueue(entity),
+            owned,
+            tuple_task: None,
+        }
     }
 
     fn load_ldb(&mut self) {
         if get_data().config.offline_mode {
             return;
         }
-        let Some(id) = self.info.id else { return };
+        let Some(id) = self.online_id() else { return };
         self.ldb = None;
         let std = self.ldb_std;
         self.ldb_task = Some(Task::new(async move {
-            Ok(recv_raw(Client::get(format!("/record/list15/{id}")).query(&[("std", std)]))
+            Ok(api()
+                .leaderboard(id, std)
                 .await?
-                .json()
-                .await?)
+                .into_iter()
+                .map(|it| LdbItem {
+                    inner: it.record,
+                    rank: it.rank,
+                    btn: RectButton::new(),
+                })
+                .collect())
         }));
     }
 
@@ -667,12 +640,26 @@ impl SongScene {
         if self.local_path.as_ref().is_some_and(|it| !it.starts_with(':')) {
             self.menu_options.push("delete");
         }
-        if self.info.id.is_some() {
+        if self.online_id().is_some() {
             self.menu_options.push("rate");
         }
         if let Some(local_path) = &self.local_path {
             self.menu_options.push("exercise");
             self.menu_options.push("offset");
+            if let Some(chart) = get_data().charts.iter().find(|it| it.local_path == *local_path) {
+                if chart.offset_history.suggest(chart.offset.unwrap_or(get_data().config.offset)).is_some() {
+                    self.menu_options.push("chart-offset-apply");
+                }
+                if chart.offset.is_some() {
+                    self.menu_options.push("chart-offset-reset");
+                }
+            }
+            self.menu_options.push("replay");
+            #[cfg(feature = "video")]
+            {
+                self.menu_options.push("render-video");
+                self.menu_options.push("render-replay");
+            }
             if get_data()
                 .charts
                 .iter()
@@ -687,23 +674,23 @@ impl SongScene {
             .me
             .as_ref()
             .is_some_and(|it| Some(it.id) == self.info.uploader.as_ref().map(|it| it.id));
-        if self.info.id.is_some() && (perms.contains(Permissions::REVIEW) || perms.contains(Permissions::REVIEW_PECJAM)) {
+        if self.online_id().is_some() && (perms.contains(Permissions::REVIEW) || perms.contains(Permissions::REVIEW_PECJAM)) {
             if self.entity.as_ref().is_some_and(|it| !it.reviewed && !it.stable_request) {
                 self.menu_options.push("review-approve");
                 self.menu_options.push("review-deny");
             }
             self.menu_options.push("review-edit-tags");
         }
-        if self.info.id.is_some() && is_uploader && self.entity.as_ref().is_some_and(|it| !it.stable && !it.stable_request) {
+        if self.online_id().is_some() && is_uploader && self.entity.as_ref().is_some_and(|it| !it.stable && !it.stable_request) {
             self.menu_options.push("stabilize");
         }
-        if self.info.id.is_some() && self.entity.as_ref().is_some_and(|it| it.stable_request) && perms.contains(Permissions::STABILIZE_CHART) {
+        if self.online_id().is_some() && self.entity.as_ref().is_some_and(|it| it.stable_request) && perms.contains(Permissions::STABILIZE_CHART) {
             self.menu_options.push("stabilize-approve");
             self.menu_options.push("stabilize-approve-ranked");
             self.menu_options.push("stabilize-comment");
             self.menu_options.push("stabilize-deny");
         }
-        if self.info.id.is_some()
+        if self.online_id().is_some()
             && self.entity.as_ref().is_some_and(|it| {
                 if it.stable {
                     perms.contains(Permissions::DELETE_STABLE)
@@ -728,11 +715,50 @@ impl SongScene {
                     .is_some_and(|it| it.info.has_unlock && !it.played_unlock));
 
         self.scene_task =
-            Self::global_launch(self.info.id, local_path, self.mods, mode, None, Some(self.background.clone()), self.record.clone(), is_unlock)?;
+            Self::global_launch(self.online_id(), local_path, self.mods, mode, None, Some(self.background.clone()), self.record.clone(), is_unlock)?;
 
         Ok(())
     }
 
+    fn launch_config(mods: Mods, chart_offset: Option<f32>) -> Result<Config> {
+        let mut config = get_data().config.clone();
+        config.player_name = get_data()
+            .me
+            .as_ref()
+            .map(|it| it.name.clone())
+            .unwrap_or_else(|| tl!("guest").into_owned());
+        config.res_pack_path = {
+            let id = get_data().respack_id;
+            if id == 0 {
+                None
+            } else {
+                Some(format!("{}/{}", dir::respacks()?, get_data().respacks[id - 1]))
+            }
+        };
+        config.mods = mods;
+        if let Some(offset) = chart_offset {
+            config.offset = offset;
+        }
+        Ok(config)
+    }
+
+    /// Renders a play of `mode` into a video file, see [RenderScene]
+    #[cfg(feature = "video")]
+    fn render_video(&mut self, mode: GameMode) -> Result<()> {
+        let local_path = self.local_path.clone().unwrap();
+        let mut fs = fs_from_path(&local_path)?;
+        let chart_offset = get_data().charts.iter().find(|it| it.local_path == local_path).and_then(|it| it.offset);
+        let config = Self::launch_config(self.mods, chart_offset)?;
+        let name = self.info.name.replace(|c: char| !c.is_alphanumeric(), "_");
+        let path = format!("{}/{name}-{}.mp4", dir::videos()?, Utc::now().format("%Y%m%d-%H%M%S"));
+        self.scene_task = Some(Box::pin(async move {
+            let info = fs::load_info(fs.as_mut()).await?;
+            let (illustration, background, _) = LoadingScene::load(fs.as_mut(), &info.illustration).await?;
+            Ok(NextScene::Overlay(Box::new(RenderScene::new(mode, info, config, fs, background, illustration, path, EncoderOptions::default()))))
+        }));
+        Ok(())
+    }
+
     #[must_use = "futures do nothing unless you `.await` or poll them"]
     pub fn global_launch(
         id: Option<i32>,
@@ -749,7 +775,7 @@ impl SongScene {
         #[cfg(feature = "closed")]
         let rated = {
             let config = &get_data().config;
-            !config.offline_mode && can_rated && !mods.contains(Mods::AUTOPLAY) && config.speed >= 1.0 - 1e-3
+            !config.offline_mode && can_rated && !mods.contains(Mods::AUTOPLAY) && !mods.intersects(Mods::UNRANKED) && config.speed >= 1.0 - 1e-3
         };
         #[cfg(not(feature = "closed"))]
         let rated = false;
@@ -856,25 +882,14 @@ impl SongScene {
             update_fn
         });
 
+        let chart_offset = get_data().charts.iter().find(|it| it.local_path == local_path).and_then(|it| it.offset);
+        let local_path = local_path.to_owned();
         Ok(Some(Box::pin(async move {
             let mut info = fs::load_info(fs.as_mut()).await?;
             info.id = id;
-            let mut config = get_data().config.clone();
-            config.player_name = get_data()
-                .me
-                .as_ref()
-                .map(|it| it.name.clone())
-                .unwrap_or_else(|| tl!("guest").into_owned());
-            config.res_pack_path = {
-                let id = get_data().respack_id;
-                if id == 0 {
-                    None
-                } else {
-                    Some(format!("{}/{}", dir::respacks()?, get_data().respacks[id - 1]))
-                }
-            };
+            info.local_path = Some(local_path.clone());
+            let config = Self::launch_config(mods, chart_offset)?;
             let chart_updated = info.chart_updated;
-            config.mods = mods;
             let preload = LoadingScene::load(fs.as_mut(), &info.illustration).await?;
             if let Some(output) = background_output {
                 *output.lock().unwrap() = Some(preload.1.clone());
@@ -887,33 +902,18 @@ impl SongScene {
             });
             let upload_fn: Option<UploadFn> = Some(Arc::new(move |data: Vec<u8>| {
                 Task::new(async move {
-                    #[derive(Serialize)]
-                    #[serde(rename_all = "camelCase")]
-                    struct Req {
-                        chart: i32,
-                        token: String,
-                        chart_updated: Option<DateTime<Utc>>,
-                    }
-                    #[derive(Deserialize)]
-                    #[serde(rename_all = "camelCase")]
-                    struct Resp {
-                        id: i32,
-                        exp_delta: f64,
-                        new_best: bool,
-                        improvement: u32,
-                        new_rks: f32,
-                    }
-                    let resp: Resp = recv_raw(Client::post(
-                        "/play/upload",
-                        &Req {
-                            chart: id.unwrap(),
-                            token: STANDARD.encode(data),
-                            chart_updated,
-                        },
-                    ))
-                    .await?
-                    .json()
-                    .await?;
+                    // the same record is only queued once, however many times the upload is retried
+                    let key = hex::encode(Sha256::digest(&data));
+                    let record = UploadRecord {
+                        chart: id.unwrap(),
+                        token: STANDARD.encode(data),
+                        chart_updated,
+                        mods: mods.bits(),
+                    };
+                    let res = api().upload_record(&record, &key).await;
+                    let Some(resp) = outbox::or_queue(res, OutboxEntry::new(Action::UploadRecord(record)).with_key(key))? else {
+                        return Err(UploadQueued.into());
+                    };
                     RECORD_ID.store(resp.id, Ordering::Relaxed);
                     Ok(RecordUpdateState {
                         best: resp.new_best,
@@ -951,9 +951,16 @@ impl SongScene {
         })))
     }
 
+    /// Id of the chart on the active server
+    fn online_id(&self) -> Option<i32> {
+        self.info.id.filter(|_| !self.foreign)
+    }
+
     fn is_owner(&self) -> bool {
-        self.info.id.is_none()
-            || (self.info.created.is_some() && self.info.uploader.as_ref().map(|it| it.id) == get_data().me.as_ref().map(|it| it.id))
+        // a chart from another server is treated like one downloaded from someone else, so that it can't be uploaded here
+        !self.foreign
+            && (self.info.id.is_none()
+                || (self.info.created.is_some() && self.info.uploader.as_ref().map(|it| it.id) == get_data().me.as_ref().map(|it| it.id)))
     }
 
     fn side_chart_info(&mut self, ui: &mut Ui, rt: f32) -> Result<()> {
@@ -962,7 +969,7 @@ impl SongScene {
         let width = self.side_content.width() - pad;
 
         let is_owner = self.is_owner();
-        let online = self.info.id.is_some();
+        let online = self.online_id().is_some();
         let vpad = 0.02;
         let hpad = 0.01;
         let dx = width / if is_owner { 3. } else { 2. };
@@ -975,7 +982,7 @@ impl SongScene {
             if ui.button(
                 "upload",
                 r,
-                if self.info.id.is_none() {
+                if self.online_id().is_none() {
                     tl!("edit-upload")
                 } else {
                     tl!("edit-update")
@@ -1097,7 +1104,7 @@ impl SongScene {
                 }};
             }
             let mw = width - pad * 3.;
-            if self.info.id.is_some() {
+            if self.online_id().is_some() {
                 let r = Rect::new(0.03, 0., mw, 0.12).nonuniform_feather(-0.03, -0.01);
                 self.open_web_btn.render_text(ui, r, rt, ttl!("open-in-web"), 0.6, true);
                 dy!(r.h + 0.04);
@@ -1145,7 +1152,7 @@ impl SongScene {
                 );
                 item(tl!("info-tags"), entity.tags.iter().map(|it| format!("#{it}")).join(" ").into());
             }
-            if let Some(id) = self.info.id {
+            if let Some(id) = self.online_id() {
                 item("ID".into(), id.to_string().into());
             }
             (width, h)
@@ -1231,7 +1238,12 @@ impl SongScene {
             item(tl!("mods-autoplay"), Some(tl!("mods-autoplay-sub")), Mods::AUTOPLAY);
             item(tl!("mods-flip-x"), Some(tl!("mods-flip-x-sub")), Mods::FLIP_X);
             item(tl!("mods-fade-out"), Some(tl!("mods-fade-out-sub")), Mods::FADE_OUT);
-            // item(tl!("mods-strict-judge"), Some(tl!("mods-strict-judge-sub")), Mods::STRICT_JUDGE);  // 严判模式已注释
+            item(tl!("mods-flip-y"), Some(tl!("mods-flip-y-sub")), Mods::FLIP_Y);
+            item(tl!("mods-fade-in"), Some(tl!("mods-fade-in-sub")), Mods::FADE_IN);
+            item(tl!("mods-hide-hold-body"), Some(tl!("mods-hide-hold-body-sub")), Mods::HIDE_HOLD_BODY);
+            item(tl!("mods-random-speed"), Some(tl!("mods-random-speed-sub")), Mods::RANDOM_SPEED);
+            item(tl!("mods-strict-judge"), Some(tl!("mods-strict-judge-sub")), Mods::STRICT_JUDGE);
+            item(tl!("mods-sudden-death"), Some(tl!("mods-sudden-death-sub")), Mods::SUDDEN_DEATH);
             (width, h)
         });
     }
@@ -1253,6 +1265,7 @@ impl SongScene {
                 dir.create(name)?.write_all(&bytes)?;
             }
             let _ = std::fs::remove_file(thumbnail_path(&path)?);
+            store::index(&path, info.clone().into());
             load_local_tuple(&path, def_illu, info).await
         }));
     }
@@ -1261,7 +1274,7 @@ impl SongScene {
         Self::global_update_chart_info(self.local_path.as_ref().unwrap(), self.info.clone())
     }
 
-    fn global_update_chart_info(local_path: &str, info: BriefChartInfo) -> Result<()> {
+    pub(crate) fn global_update_chart_info(local_path: &str, info: BriefChartInfo) -> Result<()> {
         let _ = std::fs::remove_file(thumbnail_path(local_path)?);
         get_data_mut().charts[get_data().find_chart_by_path(local_path).unwrap()].info = info;
         NEED_UPDATE.store(true, Ordering::Relaxed);
@@ -1321,9 +1334,10 @@ impl Scene for SongScene {
                             }
                         }
                         _ => {
-                            let mut info: ChartInfo = serde_yaml::from_reader(&dir.open("info.yml")?)?;
+                            let mut info = ChartInfo::from_yaml(&String::from_utf8_lossy(&dir.read("info.yml")?))?;
                             info.offset = offset;
                             dir.create("info.yml")?.write_all(serde_yaml::to_string(&info)?.as_bytes())?;
+                            store::index(self.local_path.as_ref().unwrap(), info.into());
                             let path = thumbnail_path(self.local_path.as_ref().unwrap())?;
                             if path.exists() {
                                 std::fs::remove_file(path)?;
@@ -1363,6 +1377,7 @@ impl Scene for SongScene {
             music.seek_to(0.)?;
             music.play()?;
         }
+        collect_reported()?;
         self.update_menu();
         Ok(())
     }
@@ -1377,6 +1392,7 @@ impl Scene for SongScene {
             || self.rate_task.is_some()
             || self.overwrite_task.is_some()
             || self.update_cksum_task.is_some()
+            || self.replay_task.is_some()
         {
             return Ok(true);
         }
@@ -1459,7 +1475,7 @@ impl Scene for SongScene {
                             return Ok(true);
                         }
                         if self.open_web_btn.touch(touch, rt) {
-                            open_url(&format!("https://phira.moe/chart/{}", self.info.id.unwrap()))?;
+                            open_url(&format!("https://phira.moe/chart/{}", self.online_id().unwrap()))?;
                             return Ok(true);
                         }
                     }
@@ -1558,7 +1574,7 @@ impl Scene for SongScene {
         if let Some(path) = &self.local_path {
             if self.edit_btn.touch(touch) {
                 button_hit();
-                let mut info: ChartInfo = serde_yaml::from_str(&std::fs::read_to_string(format!("{}/{path}/info.yml", dir::charts()?))?)?;
+                let mut info = ChartInfo::from_yaml(&std::fs::read_to_string(format!("{}/{path}/info.yml", dir::charts()?))?)?;
                 info.id = self.info.id;
                 UPLOAD_NOT_SAVED.store(false, Ordering::SeqCst);
                 self.info_edit = Some(ChartInfoEdit::new(info));
@@ -1573,7 +1589,7 @@ impl Scene for SongScene {
                 return Ok(true);
             }
         }
-        if self.info.id.is_some() && self.ldb_btn.touch(touch) {
+        if self.online_id().is_some() && self.ldb_btn.touch(touch) {
             button_hit();
             self.side_content = SideContent::Leaderboard;
             self.side_enter_time = tm.real_time() as _;
@@ -1607,32 +1623,23 @@ impl Scene for SongScene {
                 edit.info.tags = tags;
                 edit.updated = true;
             } else {
-                let id = self.info.id.unwrap();
+                let id = self.online_id().unwrap();
                 self.entity.as_mut().unwrap().tags = tags.clone();
                 self.edit_tags_task = Some(Task::new(async move {
-                    recv_raw(Client::post(
-                        format!("/chart/{id}/edit-tags"),
-                        &json!({
-                            "tags": tags,
-                        }),
-                    ))
-                    .await?;
-                    Ok(())
+                    let entry = OutboxEntry::new(Action::EditTags {
+                        chart: id,
+                        tags: tags.clone(),
+                    });
+                    Ok(outbox::or_queue(api().edit_tags(id, &tags, &entry.key).await, entry)?.is_some())
                 }));
             }
         }
         if self.rate_dialog.confirmed.take() == Some(true) {
-            if let Some(id) = self.info.id {
+            if let Some(id) = self.online_id() {
                 let score = self.rate_dialog.rate.score;
                 self.rate_task = Some(Task::new(async move {
-                    recv_raw(Client::post(
-                        format!("/chart/{id}/rate"),
-                        &json!({
-                            "score": score,
-                        }),
-                    ))
-                    .await?;
-                    Ok(())
+                    let entry = OutboxEntry::new(Action::Rate { chart: id, score });
+                    Ok(outbox::or_queue(api().rate(id, score, &entry.key).await, entry)?.is_some())
                 }));
             }
         }
@@ -1670,13 +1677,14 @@ impl Scene for SongScene {
                             }
                         } else if let Some(local) = &self.local_path {
                             let conf = format!("{}/{}/info.yml", dir::charts()?, local);
-                            let mut info: ChartInfo = serde_yaml::from_reader(File::open(&conf)?)?;
+                            let mut info = ChartInfo::from_yaml(&std::fs::read_to_string(&conf)?)?;
                             info.id = None;
                             info.uploader = None;
                             info.created = None;
                             info.updated = None;
                             info.chart_updated = None;
-                            serde_yaml::to_writer(File::create(conf)?, &info)?;
+                            serde_yaml::to_writer(store::create_file(conf)?, &info)?;
+                            store::index(local, info.clone().into());
                             self.info = info.into();
                             self.update_chart_info()?;
                         }
@@ -1722,6 +1730,7 @@ impl Scene for SongScene {
                             .listener(move |_dialog, pos| {
                                 if pos == 1 {
                                     get_data_mut().config.offline_mode = true;
+                                    sync_cache_state();
                                     let _ = save_data();
                                     show_message(tl!("switched-to-offline")).ok();
                                 }
@@ -1762,25 +1771,58 @@ impl Scene for SongScene {
                 "offset" => {
                     self.launch(GameMode::TweakOffset, false)?;
                 }
+                "chart-offset-apply" => {
+                    let data = get_data_mut();
+                    let current = data.config.offset;
+                    if let Some(chart) = data.charts.iter_mut().find(|it| Some(&it.local_path) == self.local_path.as_ref()) {
+                        if let Some(offset) = chart.offset_history.suggest(chart.offset.unwrap_or(current)) {
+                            chart.offset = Some(offset);
+                            save_data()?;
+                            show_message(tl!("chart-offset-applied", "offset" => format!("{:.0}", offset * 1000.))).ok();
+                        }
+                    }
+                    self.update_menu();
+                }
+                "chart-offset-reset" => {
+                    if let Some(chart) = get_data_mut()
+                        .charts
+                        .iter_mut()
+                        .find(|it| Some(&it.local_path) == self.local_path.as_ref())
+                    {
+                        chart.offset = None;
+                        chart.offset_history.clear();
+                        save_data()?;
+                        show_message(tl!("chart-offset-reset-done")).ok();
+                    }
+                    self.update_menu();
+                }
                 "unlock" => {
                     self.launch(GameMode::Normal, true)?;
                 }
+                #[cfg(feature = "video")]
+                "render-video" => {
+                    self.render_video(GameMode::Normal)?;
+                }
+                "replay" | "render-replay" => {
+                    self.replay_render = option == "render-replay";
+                    let mut fs = fs_from_path(self.local_path.as_ref().unwrap())?;
+                    self.replay_task = Some(Task::new(async move {
+                        let info = fs::load_info(fs.as_mut()).await?;
+                        let bytes = GameScene::load_chart_bytes(fs.as_mut(), &info).await?;
+                        Replay::latest(&chart_hash(&bytes))?.map(Replay::load).transpose()
+                    }));
+                }
                 "review-approve" => {
-                    let id = self.info.id.unwrap();
+                    let id = self.online_id().unwrap();
                     self.review_task = Some(Task::new(async move {
-                        #[derive(Deserialize)]
-                        struct Resp {
-                            passed: bool,
-                        }
-                        let resp: Resp = recv_raw(Client::post(
-                            format!("/chart/{id}/review"),
-                            &json!({
-                                "approve": true
-                            }),
-                        ))
-                        .await?
-                        .json()
-                        .await?;
+                        let review = Review { approve: true, reason: None };
+                        let entry = OutboxEntry::new(Action::Review {
+                            chart: id,
+                            review: review.clone(),
+                        });
+                        let Some(resp) = outbox::or_queue(api().review(id, &review, &entry.key).await, entry)? else {
+                            return Ok(tl!("action-queued").into_owned());
+                        };
                         Ok((if resp.passed { tl!("review-passed") } else { tl!("review-approved") }).into_owned())
                     }));
                 }
@@ -1803,17 +1845,9 @@ impl Scene for SongScene {
                 }
                 "stabilize-approve" | "stabilize-approve-ranked" => {
                     let kind = if option == "stabilize-approve-ranked" { 1 } else { 0 };
-                    let id = self.info.id.unwrap();
+                    let id = self.online_id().unwrap();
                     self.review_task = Some(Task::new(async move {
-                        let resp: StableR = recv_raw(Client::post(
-                            format!("/chart/{id}/stabilize"),
-                            &json!({
-                                "kind": kind,
-                            }),
-                        ))
-                        .await?
-                        .json()
-                        .await?;
+                        let resp = api().stabilize(id, &Stabilize { kind, reason: None }).await?;
                         Ok((if resp.status == 0 {
                             tl!("stabilize-approved")
                         } else {
@@ -1852,16 +1886,16 @@ impl Scene for SongScene {
             }
         }
         if self.chart_should_delete.fetch_and(false, Ordering::Relaxed) {
-            let id = self.info.id.unwrap();
+            let id = self.online_id().unwrap();
             self.review_task = Some(Task::new(async move {
-                recv_raw(Client::delete(format!("/chart/{id}"))).await?;
+                api().delete_chart(id).await?;
                 Ok(tl!("review-deleted").into_owned())
             }));
         }
         if self.should_stabilize.fetch_and(false, Ordering::Relaxed) {
-            let id = self.info.id.unwrap();
+            let id = self.online_id().unwrap();
             self.stabilize_task = Some(Task::new(async move {
-                recv_raw(Client::post(format!("/chart/{id}/req-stabilize"), &())).await?;
+                api().request_stabilize(id).await?;
                 Ok(())
             }));
         }
@@ -1889,6 +1923,7 @@ impl Scene for SongScene {
                     Ok(info) => {
                         show_message(tl!("upload-success")).ok();
                         self.info = info;
+                        get_data_mut().set_chart_server(self.local_path.as_ref().unwrap());
                         self.update_chart_info()?;
                         self.side_enter_time = -tm.real_time() as _;
                     }
@@ -1916,28 +1951,46 @@ impl Scene for SongScene {
         }
         if CONFIRM_UPLOAD.fetch_and(false, Ordering::Relaxed) {
             let local_path = self.local_path.clone().unwrap();
-            let id = self.info.id;
+            let id = self.online_id();
             self.update_cksum_task = Some(Task::new(async move {
                 if let Some(id) = id {
                     use hex::ToHex;
                     let mut fs = fs_from_path(&local_path)?;
                     let info = prpr::fs::load_info(fs.as_mut()).await?;
-                    let chart = fs.load_file(&info.chart).await?;
-                    let cksum: String = Sha256::digest(&chart).encode_hex();
-                    #[derive(Deserialize)]
-                    struct VerifyR {
-                        ok: bool,
-                    }
-                    let resp: VerifyR = recv_raw(Client::get(format!("/chart/{id}/verify-cksum?checksum={cksum}")))
-                        .await?
-                        .json()
-                        .await?;
-                    Ok(resp.ok)
+                    let cksum: String = match store::file_hash(&local_path, &info.chart) {
+                        Some(hash) => hash,
+                        None => Sha256::digest(&fs.load_file(&info.chart).await?).encode_hex(),
+                    };
+                    api().verify_checksum(id, &cksum).await
                 } else {
                     Ok(true)
                 }
             }));
         }
+        if let Some(task) = &mut self.replay_task {
+            if let Some(res) = task.take() {
+                match res {
+                    Err(err) => {
+                        show_error(err.context(tl!("replay-load-failed")));
+                    }
+                    Ok(None) => {
+                        show_message(tl!("replay-none")).warn();
+                    }
+                    Ok(Some(replay)) => {
+                        let mode = GameMode::Replay(Arc::new(replay));
+                        #[cfg(feature = "video")]
+                        if self.replay_render {
+                            self.render_video(mode)?;
+                        } else {
+                            self.launch(mode, false)?;
+                        }
+                        #[cfg(not(feature = "video"))]
+                        self.launch(mode, false)?;
+                    }
+                }
+                self.replay_task = None;
+            }
+        }
         if let Some(task) = &mut self.update_cksum_task {
             if let Some(res) = task.take() {
                 match res {
@@ -1995,48 +2048,25 @@ impl Scene for SongScene {
                     .await
                     .with_context(|| tl!("upload-chart-failed"))?;
                 if let Some(id) = info.id {
-                    #[derive(Deserialize)]
-                    #[serde(rename_all = "camelCase")]
-                    struct Resp {
-                        updated: DateTime<Utc>,
-                        chart_updated: DateTime<Utc>,
-                    }
-                    let resp: Resp = recv_raw(Client::request(Method::PATCH, format!("/chart/{id}")).json(&json!({
-                        "file": file,
-                        "created": info.created.unwrap(),
-                    })))
-                    .await?
-                    .json()
-                    .await?;
+                    let resp = api().update_chart(id, &file, info.created.unwrap()).await?;
                     let conf = root.join("info.yml");
-                    let mut info: ChartInfo = serde_yaml::from_reader(File::open(&conf)?)?;
+                    let mut info = ChartInfo::from_yaml(&std::fs::read_to_string(&conf)?)?;
                     info.updated = Some(resp.updated);
                     info.chart_updated = Some(resp.chart_updated);
-                    serde_yaml::to_writer(File::create(conf)?, &info)?;
+                    serde_yaml::to_writer(store::create_file(conf)?, &info)?;
+                    store::index(&path, info.clone().into());
                     Ok(info.into())
                 } else {
-                    #[derive(Deserialize)]
-                    struct Resp {
-                        id: i32,
-                        created: DateTime<Utc>,
-                    }
-                    let resp: Resp = recv_raw(Client::post(
-                        "/chart/upload",
-                        &json!({
-                            "file": file,
-                        }),
-                    ))
-                    .await?
-                    .json()
-                    .await?;
+                    let resp = api().upload_chart(&file).await?;
                     let conf = root.join("info.yml");
-                    let mut info: ChartInfo = serde_yaml::from_reader(File::open(&conf)?)?;
+                    let mut info = ChartInfo::from_yaml(&std::fs::read_to_string(&conf)?)?;
                     info.id = Some(resp.id);
                     info.created = Some(resp.created);
                     info.updated = Some(resp.created);
                     info.chart_updated = Some(resp.created);
                     info.uploader = Some(get_data().me.as_ref().unwrap().id);
-                    serde_yaml::to_writer(File::create(conf)?, &info)?;
+                    serde_yaml::to_writer(store::create_file(conf)?, &info)?;
+                    store::index(&path, info.clone().into());
                     Ok(info.into())
                 }
             }));
@@ -2065,45 +2095,37 @@ impl Scene for SongScene {
         if let Some((id, text)) = take_input() {
             match id.as_str() {
                 "deny-reason" => {
-                    let id = self.info.id.unwrap();
+                    let id = self.online_id().unwrap();
                     self.review_task = Some(Task::new(async move {
-                        recv_raw(Client::post(
-                            format!("/chart/{id}/review"),
-                            &json!({
-                                "approve": false,
-                                "reason": text,
-                            }),
-                        ))
-                        .await?;
+                        let review = Review {
+                            approve: false,
+                            reason: Some(text),
+                        };
+                        let entry = OutboxEntry::new(Action::Review {
+                            chart: id,
+                            review: review.clone(),
+                        });
+                        if outbox::or_queue(api().review(id, &review, &entry.key).await, entry)?.is_none() {
+                            return Ok(tl!("action-queued").into_owned());
+                        }
                         Ok(tl!("review-denied").into_owned())
                     }));
                 }
                 "stabilize-comment" => {
-                    let id = self.info.id.unwrap();
+                    let id = self.online_id().unwrap();
                     self.review_task = Some(Task::new(async move {
-                        recv_raw(Client::post(
-                            format!("/chart/{id}/stabilize-comment"),
-                            &json!({
-                                "comment": text,
-                            }),
-                        ))
-                        .await?;
+                        api().stabilize_comment(id, &text).await?;
                         Ok(tl!("stabilize-commented").into())
                     }));
                 }
                 "stabilize-deny-reason" => {
-                    let id = self.info.id.unwrap();
+                    let id = self.online_id().unwrap();
                     self.review_task = Some(Task::new(async move {
-                        let resp: StableR = recv_raw(Client::post(
-                            format!("/chart/{id}/stabilize"),
-                            &json!({
-                                "kind": -1,
-                                "reason": text,
-                            }),
-                        ))
-                        .await?
-                        .json()
-                        .await?;
+                        let stabilize = Stabilize {
+                            kind: -1,
+                            reason: Some(text),
+                        };
+                        let resp = api().stabilize(id, &stabilize).await?;
                         Ok((if resp.status == 0 {
                             tl!("stabilize-denied")
                         } else {
@@ -2136,7 +2158,7 @@ impl Scene for SongScene {
             let path = self.overwrite_from.take().unwrap();
             let local_path = self.local_path.clone().unwrap();
             let def_illu = self.illu.texture.1.clone();
-            let chart_id = self.info.id.unwrap();
+            let chart_id = self.online_id().unwrap();
             let owner = self.info.uploader.as_ref().unwrap().id;
             self.overwrite_task = Some(Task::new(async move {
                 let (dir, id) = gen_custom_dir()?;
@@ -2150,10 +2172,11 @@ impl Scene for SongScene {
                 drop(fs);
                 info.id = Some(chart_id);
                 info.uploader = Some(owner);
-                serde_yaml::to_writer(File::create(dir.join("info.yml"))?, &info)?;
+                serde_yaml::to_writer(store::create_file(dir.join("info.yml"))?, &info)?;
 
                 std::fs::remove_dir_all(&to_path)?;
                 std::fs::rename(&dir, &to_path)?;
+                store::index(&local_path, info.clone().into());
 
                 load_local_tuple(&local_path, def_illu, info).await
             }));
@@ -2204,9 +2227,12 @@ impl Scene for SongScene {
                     Err(err) => {
                         show_error(err.context(tl!("review-edit-tags-failed")));
                     }
-                    Ok(_) => {
+                    Ok(true) => {
                         show_message(tl!("review-edit-tags-done")).ok();
                     }
+                    Ok(false) => {
+                        show_message(tl!("action-queued")).warn();
+                    }
                 }
                 self.edit_tags_task = None;
             }
@@ -2217,9 +2243,12 @@ impl Scene for SongScene {
                     Err(err) => {
                         show_error(err.context(tl!("rate-failed")));
                     }
-                    Ok(_) => {
+                    Ok(true) => {
                         show_message(tl!("rate-done")).ok();
                     }
+                    Ok(false) => {
+                        show_message(tl!("action-queued")).warn();
+                    }
                 }
                 self.rate_dialog.dismiss(rt);
                 self.rate_task = None;
@@ -2298,7 +2327,7 @@ impl Scene for SongScene {
                 .color(semi_white(0.7))
                 .draw();
 
-            if self.info.id.is_some() {
+            if self.online_id().is_some() {
                 let h = 0.09;
                 let mut r = Rect::new(r.x, r.y - h, h, h);
                 ui.fill_rect(r, (*self.icons.ldb, r, ScaleType::Fit));
@@ -2445,7 +2474,12 @@ impl Scene for SongScene {
         if self.review_task.is_some() {
             ui.full_loading(tl!("review-doing"), t);
         }
-        if self.edit_tags_task.is_some() || self.rate_task.is_some() || self.overwrite_task.is_some() || self.update_cksum_task.is_some() {
+        if self.edit_tags_task.is_some()
+            || self.rate_task.is_some()
+            || self.overwrite_task.is_some()
+            || self.update_cksum_task.is_some()
+            || self.replay_task.is_some()
+        {
             ui.full_loading("", t);
         }
         let rt = tm.real_time() as f32;
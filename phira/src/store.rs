@@ -0,0 +1,437 @@
+//! Content-addressed store of local chart files
+//!
+//! Every file of a local chart is hashed with SHA-256 and hard linked to `store/objects/<hash>`, so identical files
+//! shared by several charts only take up space once. The index remembers the hashes and the metadata of every chart,
+//! which lets startup skip reading `info.yml` and lets [check_and_repair] find and restore damaged files.
+
+use crate::{data::BriefChartInfo, dir};
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use prpr::{
+    info::{ChartFormat, ChartInfo},
+    parse::detect_format,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::File,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tracing::warn;
+use walkdir::WalkDir;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct StoredChart {
+    info: BriefChartInfo,
+    /// Hashes of the files, by path relative to the chart. A chart stored as a single file has one file with an empty path.
+    files: BTreeMap<String, String>,
+    /// Format of the chart, the declared one or else the detected one, `None` if neither is known
+    format: Option<ChartFormat>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct StoreIndex {
+    charts: HashMap<String, StoredChart>,
+}
+
+static INDEX: Lazy<Mutex<StoreIndex>> = Lazy::new(|| {
+    let index = index_path().and_then(|path| Ok(serde_json::from_slice(&std::fs::read(path)?)?));
+    Mutex::new(index.unwrap_or_default())
+});
+
+fn index_path() -> Result<String> {
+    Ok(format!("{}/index.json", dir::store()?))
+}
+
+fn save_index(index: &StoreIndex) -> Result<()> {
+    std::fs::write(index_path()?, serde_json::to_vec(index)?)?;
+    Ok(())
+}
+
+fn objects_dir() -> Result<PathBuf> {
+    Ok(Path::new(&dir::store()?).join("objects"))
+}
+
+fn object_path(hash: &str) -> Result<PathBuf> {
+    Ok(objects_dir()?.join(&hash[..2]).join(hash))
+}
+
+fn file_path(root: &Path, name: &str) -> PathBuf {
+    if name.is_empty() {
+        root.to_owned()
+    } else {
+        root.join(name)
+    }
+}
+
+pub fn hash_file(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn chart_files(root: &Path) -> Result<Vec<String>> {
+    if root.is_file() {
+        return Ok(vec![String::new()]);
+    }
+    let mut res = Vec::new();
+    for entry in WalkDir::new(root) {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            res.push(entry.path().strip_prefix(root)?.to_string_lossy().replace('\\', "/"));
+        }
+    }
+    Ok(res)
+}
+
+/// Creates a file in a chart for writing, replacing the old one instead of writing into it, since it may be a hard link
+/// to an object shared with other charts. The chart has to be [indexed](index) again afterwards.
+pub fn create_file(path: impl AsRef<Path>) -> Result<File> {
+    let path = path.as_ref();
+    if path.is_file() {
+        std::fs::remove_file(path)?;
+    }
+    Ok(File::create(path)?)
+}
+
+/// Replaces `path` with a hard link to `object`
+fn link_from(object: &Path, path: &Path) -> Result<()> {
+    let tmp = PathBuf::from(format!("{}.store-tmp", path.display()));
+    std::fs::hard_link(object, &tmp)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Links `path` with the object of its content, adding the object if it's new
+fn link_object(path: &Path, hash: &str) -> Result<()> {
+    let object = object_path(hash)?;
+    if object.exists() {
+        link_from(&object, path)
+    } else {
+        std::fs::create_dir_all(object.parent().unwrap())?;
+        std::fs::hard_link(path, &object)?;
+        Ok(())
+    }
+}
+
+/// Format declared in `info.yml` of a chart directory, or else detected from its chart file
+fn chart_format(root: &Path) -> Option<ChartFormat> {
+    if !root.is_dir() {
+        return None;
+    }
+    let info = ChartInfo::from_yaml(&std::fs::read_to_string(root.join("info.yml")).ok()?).ok()?;
+    if info.format.is_some() {
+        return info.format;
+    }
+    detect_format(&std::fs::read(root.join(&info.chart)).ok()?).ok()
+}
+
+/// Hashes every file of a chart and records it in the index, sharing files identical to those of other charts
+pub fn add(local_path: &str, info: BriefChartInfo) -> Result<()> {
+    let root = Path::new(&dir::charts()?).join(local_path);
+    let mut files = BTreeMap::new();
+    for name in chart_files(&root)? {
+        let path = file_path(&root, &name);
+        let hash = hash_file(&path)?;
+        if let Err(err) = link_object(&path, &hash) {
+            // the chart still works, it just isn't deduplicated nor repairable
+            warn!(?err, path = %path.display(), "failed to link chart file into store");
+        }
+        files.insert(name, hash);
+    }
+    let format = chart_format(&root);
+    let mut index = INDEX.lock().unwrap();
+    let old = index.charts.insert(
+        local_path.to_owned(),
+        StoredChart {
+            info,
+            files: files.clone(),
+            format,
+        },
+    );
+    save_index(&index)?;
+    if old.is_some_and(|it| it.files != files) {
+        collect_garbage(&index)?;
+    }
+    Ok(())
+}
+
+/// Like [add], only logging failures since charts missing from the index still work
+pub fn index(local_path: &str, info: BriefChartInfo) {
+    if let Err(err) = add(local_path, info) {
+        warn!(?err, local_path, "failed to index chart");
+    }
+}
+
+/// Drops a chart from the index, deleting objects no other chart uses
+pub fn remove(local_path: &str) -> Result<()> {
+    let mut index = INDEX.lock().unwrap();
+    if index.charts.remove(local_path).is_none() {
+        return Ok(());
+    }
+    save_index(&index)?;
+    collect_garbage(&index)
+}
+
+/// Drops every chart whose files are gone from the index
+pub fn forget_missing() -> Result<()> {
+    let charts = dir::charts()?;
+    let mut index = INDEX.lock().unwrap();
+    let len = index.charts.len();
+    index.charts.retain(|path, _| Path::new(&charts).join(path).exists());
+    if index.charts.len() != len {
+        save_index(&index)?;
+        collect_garbage(&index)?;
+    }
+    Ok(())
+}
+
+fn collect_garbage(index: &StoreIndex) -> Result<()> {
+    let used: HashSet<_> = index.charts.values().flat_map(|it| it.files.values()).collect();
+    let dir = objects_dir()?;
+    if !dir.exists() {
+        return Ok(());
+    }
+    for entry in WalkDir::new(dir) {
+        let entry = entry?;
+        if entry.file_type().is_file() && !used.contains(&entry.file_name().to_string_lossy().into_owned()) {
+            std::fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+pub fn is_indexed(local_path: &str) -> bool {
+    INDEX.lock().unwrap().charts.contains_key(local_path)
+}
+
+/// Metadata of an indexed chart
+pub fn cached_info(local_path: &str) -> Option<BriefChartInfo> {
+    INDEX.lock().unwrap().charts.get(local_path).map(|it| it.info.clone())
+}
+
+/// Format of an indexed chart, detected when it was indexed if `info.yml` doesn't declare one
+pub fn format(local_path: &str) -> Option<ChartFormat> {
+    INDEX.lock().unwrap().charts.get(local_path)?.format.clone()
+}
+
+/// Hash of a file of an indexed chart, as of when it was indexed
+pub fn file_hash(local_path: &str, name: &str) -> Option<String> {
+    INDEX.lock().unwrap().charts.get(local_path)?.files.get(name).cloned()
+}
+
+#[derive(Default)]
+pub struct RepairReport {
+    pub checked: usize,
+    pub repaired: usize,
+    /// Files replaced since they were indexed, as local path of the chart and name of the file. They may be edits made
+    /// on purpose, so they are only restored by [restore] once the user confirms.
+    pub modified: Vec<(String, String)>,
+    /// Charts with files that could not be restored, they have to be downloaded or imported again
+    pub damaged: Vec<String>,
+}
+
+/// Replaces `path` with a link to the object of `hash`, if the object is intact
+fn restore_file(path: &Path, hash: &str) -> Result<bool> {
+    let object = object_path(hash)?;
+    if !object.exists() || hash_file(&object)? != hash {
+        return Ok(false);
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if link_from(&object, path).is_err() {
+        std::fs::copy(&object, path)?;
+    }
+    Ok(true)
+}
+
+/// Compares every file of indexed charts with its hash, restoring missing files from the store. Files that are there
+/// but differ are reported as [modified](RepairReport::modified) and left alone.
+pub fn check_and_repair() -> Result<RepairReport> {
+    let charts = dir::charts()?;
+    let index: Vec<_> = INDEX
+        .lock()
+        .unwrap()
+        .charts
+        .iter()
+        .map(|(path, chart)| (path.clone(), chart.files.clone()))
+        .collect();
+    let mut report = RepairReport::default();
+    for (local_path, files) in index {
+        let root = Path::new(&charts).join(&local_path);
+        let mut damaged = false;
+        for (name, hash) in files {
+            report.checked += 1;
+            let path = file_path(&root, &name);
+            if !path.exists() {
+                if restore_file(&path, &hash)? {
+                    report.repaired += 1;
+                } else {
+                    damaged = true;
+                }
+                continue;
+            }
+            if hash_file(&path)? == hash {
+                continue;
+            }
+            // written through the hard link, the object is damaged too
+            let object = object_path(&hash)?;
+            if object.exists() && hash_file(&object)? == hash {
+                report.modified.push((local_path.clone(), name));
+            } else {
+                damaged = true;
+            }
+        }
+        if damaged {
+            report.damaged.push(local_path);
+        }
+    }
+    Ok(report)
+}
+
+/// Restores files reported as [modified](RepairReport::modified) from the store, returning how many were restored
+pub fn restore(files: &[(String, String)]) -> Result<usize> {
+    let charts = dir::charts()?;
+    let mut restored = 0;
+    for (local_path, name) in files {
+        let Some(hash) = file_hash(local_path, name) else { continue };
+        if restore_file(&file_path(&Path::new(&charts).join(local_path), name), &hash)? {
+            restored += 1;
+        }
+    }
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The index is global, tests touching the same files must not interleave
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    fn setup() -> std::sync::MutexGuard<'static, ()> {
+        crate::setup_test();
+        LOCK.lock().unwrap_or_else(|it| it.into_inner())
+    }
+
+    fn chart(local_path: &str, files: &[(&str, &str)]) -> Result<PathBuf> {
+        let root = Path::new(&dir::charts()?).join(local_path);
+        if root.exists() {
+            std::fs::remove_dir_all(&root)?;
+        }
+        for (name, content) in files {
+            let path = root.join(name);
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(path, content)?;
+        }
+        add(local_path, ChartInfo::default().into())?;
+        Ok(root)
+    }
+
+    fn object(content: &str) -> Result<PathBuf> {
+        object_path(&hex::encode(Sha256::digest(content)))
+    }
+
+    #[test]
+    fn dedup() -> Result<()> {
+        let _guard = setup();
+        let a = chart("store-test/dedup-a", &[("music.ogg", "shared music"), ("chart.json", "a")])?;
+        let b = chart("store-test/dedup-b", &[("music.ogg", "shared music"), ("chart.json", "b")])?;
+        assert_eq!(file_hash("store-test/dedup-a", "music.ogg"), file_hash("store-test/dedup-b", "music.ogg"));
+        assert!(object("shared music")?.exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let ino = |path: &Path| std::fs::metadata(path).unwrap().ino();
+            assert_eq!(ino(&a.join("music.ogg")), ino(&b.join("music.ogg")));
+            assert_ne!(ino(&a.join("chart.json")), ino(&b.join("chart.json")));
+        }
+
+        // writing a chart file must not change the other chart through the shared object
+        std::io::Write::write_all(&mut create_file(a.join("music.ogg"))?, b"edited")?;
+        index("store-test/dedup-a", ChartInfo::default().into());
+        assert_eq!(std::fs::read_to_string(b.join("music.ogg"))?, "shared music");
+        assert_eq!(hash_file(&object("shared music")?)?, hex::encode(Sha256::digest("shared music")));
+        let report = check_and_repair()?;
+        assert!(!report.damaged.iter().any(|it| it.starts_with("store-test/dedup")));
+        assert_eq!(std::fs::read_to_string(a.join("music.ogg"))?, "edited");
+        Ok(())
+    }
+
+    #[test]
+    fn garbage_collection() -> Result<()> {
+        let _guard = setup();
+        chart("store-test/gc-a", &[("music.ogg", "gc shared"), ("chart.json", "gc only a")])?;
+        chart("store-test/gc-b", &[("music.ogg", "gc shared")])?;
+
+        remove("store-test/gc-a")?;
+        assert!(!is_indexed("store-test/gc-a"));
+        assert!(!object("gc only a")?.exists());
+        assert!(object("gc shared")?.exists());
+
+        // objects replaced by an update are dropped as well
+        chart("store-test/gc-b", &[("music.ogg", "gc updated")])?;
+        assert!(!object("gc shared")?.exists());
+        assert!(object("gc updated")?.exists());
+
+        std::fs::remove_dir_all(Path::new(&dir::charts()?).join("store-test/gc-b"))?;
+        forget_missing()?;
+        assert!(!is_indexed("store-test/gc-b"));
+        assert!(!object("gc updated")?.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn detected_format() -> Result<()> {
+        let _guard = setup();
+        chart("store-test/format-detected", &[("info.yml", "chart: chart.json"), ("chart.json", "{\"META\":{}}")])?;
+        assert_eq!(format("store-test/format-detected"), Some(ChartFormat::Rpe));
+        // the declared format wins, whatever the file looks like
+        chart("store-test/format-declared", &[("info.yml", "chart: chart.json\nformat: pec"), ("chart.json", "{\"META\":{}}")])?;
+        assert_eq!(format("store-test/format-declared"), Some(ChartFormat::Pec));
+        chart("store-test/format-unknown", &[("chart.json", "{}")])?;
+        assert_eq!(format("store-test/format-unknown"), None);
+        assert!(is_indexed("store-test/format-unknown"));
+        Ok(())
+    }
+
+    #[test]
+    fn repair_deleted() -> Result<()> {
+        let _guard = setup();
+        let root = chart("store-test/deleted", &[("chart.json", "deleted chart"), ("res/illu.png", "deleted illu")])?;
+        std::fs::remove_file(root.join("chart.json"))?;
+        std::fs::remove_dir_all(root.join("res"))?;
+
+        let report = check_and_repair()?;
+        assert!(report.repaired >= 2);
+        assert!(!report.damaged.contains(&"store-test/deleted".to_owned()));
+        assert_eq!(std::fs::read_to_string(root.join("chart.json"))?, "deleted chart");
+        assert_eq!(std::fs::read_to_string(root.join("res/illu.png"))?, "deleted illu");
+        Ok(())
+    }
+
+    #[test]
+    fn repair_modified() -> Result<()> {
+        let _guard = setup();
+        let root = chart("store-test/modified", &[("chart.json", "modified chart"), ("music.ogg", "modified music")])?;
+        // replaced by another program, the object is left alone
+        std::fs::remove_file(root.join("chart.json"))?;
+        std::fs::write(root.join("chart.json"), "garbage")?;
+        // written through the hard link, the object is damaged too
+        std::fs::write(root.join("music.ogg"), "garbage")?;
+
+        let report = check_and_repair()?;
+        // an edit by hand is not reverted without asking
+        assert_eq!(std::fs::read_to_string(root.join("chart.json"))?, "garbage");
+        let modified: Vec<_> = report.modified.into_iter().filter(|it| it.0 == "store-test/modified").collect();
+        assert_eq!(modified, [("store-test/modified".to_owned(), "chart.json".to_owned())]);
+        assert!(report.damaged.contains(&"store-test/modified".to_owned()));
+
+        assert_eq!(restore(&modified)?, 1);
+        assert_eq!(std::fs::read_to_string(root.join("chart.json"))?, "modified chart");
+        remove("store-test/modified")
+    }
+}
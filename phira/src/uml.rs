@@ -6,7 +6,7 @@ pub use parse::parse_uml;
 use self::parse::{constant, ButtonState, TopLevel};
 use crate::{
     charts_view::{ChartDisplayItem, ChartsView},
-    client::{recv_raw, Client, File},
+    client::{api, Chart, File},
     icons::Icons,
 };
 use anyhow::{anyhow, bail, Result};
@@ -89,6 +89,10 @@ pub trait Element {
     fn next_scene(&self) -> Option<NextScene> {
         None
     }
+    /// Charts shown by the element, for downloading them all
+    fn charts(&self) -> Vec<Chart> {
+        Vec::new()
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -258,6 +262,7 @@ pub struct CollectionConfig {
 
 struct CollectionState {
     task: Option<Task<Result<crate::client::Collection>>>,
+    charts: Vec<Chart>,
     charts_view: ChartsView,
 }
 
@@ -274,7 +279,8 @@ impl Collection {
         Self {
             config,
             state: RefCell::new(CollectionState {
-                task: Some(Task::new(async move { Ok(recv_raw(Client::get(format!("/collection/{}", cid.0))).await?.json().await?) })),
+                task: Some(Task::new(async move { api().collection(cid.0).await })),
+                charts: Vec::new(),
                 charts_view,
             }),
         }
@@ -306,6 +312,7 @@ impl Element for Collection {
                         state
                             .charts_view
                             .set(uml.t, col.charts.iter().map(ChartDisplayItem::from_remote).collect());
+                        state.charts = col.charts;
                     }
                 }
                 state.task = None;
@@ -331,6 +338,10 @@ impl Element for Collection {
     fn next_scene(&self) -> Option<NextScene> {
         self.state.borrow_mut().charts_view.next_scene()
     }
+
+    fn charts(&self) -> Vec<Chart> {
+        self.state.borrow().charts.clone()
+    }
 }
 
 fn default_radius() -> Expr {
@@ -745,6 +756,18 @@ impl Uml {
         Ok(false)
     }
 
+    /// Charts of every collection on the page
+    pub fn charts(&self) -> Vec<Chart> {
+        self.elements
+            .iter()
+            .filter_map(|it| match it {
+                TopLevel::Element(el) => Some(el.charts()),
+                _ => None,
+            })
+            .flatten()
+            .collect()
+    }
+
     pub fn render(&mut self, ui: &mut Ui, t: f32, rt: f32, vars: &[(&str, f32)]) -> Result<(f32, f32)> {
         self.var_map = std::mem::take(&mut self.var_map)
             .into_iter()
@@ -1,5 +1,8 @@
-use crate::{ffi, handle, AVPacket, AVStreamRef, Error, OwnedPtr, Result};
-use std::{ffi::CString, ptr::null_mut};
+use crate::{ffi, handle, AVCodecContext, AVPacket, AVStreamRef, Error, OwnedPtr, Result};
+use std::{
+    ffi::CString,
+    ptr::{null, null_mut},
+};
 
 #[repr(transparent)]
 pub struct AVFormatContext(OwnedPtr<ffi::AVFormatContext>);
@@ -15,6 +18,43 @@ impl AVFormatContext {
         }
     }
 
+    /// Creates a muxer for `url`, guessing the container from its extension
+    pub fn open_output(url: &str) -> Result<Self> {
+        unsafe {
+            let url = CString::new(url).unwrap();
+            let mut ptr = null_mut();
+            handle(ffi::avformat_alloc_output_context2(&mut ptr, null_mut(), null_mut(), url.as_ptr()).min(0))?;
+            let mut this = OwnedPtr::new(ptr).map(Self).ok_or(Error::AllocationFailed)?;
+            handle(ffi::avio_open(&mut this.0.as_mut().pb, url.as_ptr(), ffi::AVIO_FLAG_WRITE).min(0))?;
+            Ok(this)
+        }
+    }
+
+    /// Adds a stream carrying the output of `encoder`
+    pub fn new_stream(&mut self, encoder: &AVCodecContext) -> Result<AVStreamRef> {
+        unsafe {
+            let stream = ffi::avformat_new_stream(self.0 .0, null());
+            if stream.is_null() {
+                return Err(Error::AllocationFailed);
+            }
+            handle(ffi::avcodec_parameters_from_context((*stream).codecpar, encoder.as_ptr()))?;
+            (*stream).time_base = encoder.time_base();
+            Ok(AVStreamRef(stream))
+        }
+    }
+
+    pub fn write_header(&mut self) -> Result<()> {
+        unsafe { handle(ffi::avformat_write_header(self.0 .0, null_mut()).min(0)) }
+    }
+
+    pub fn write_packet(&mut self, packet: &mut AVPacket) -> Result<()> {
+        unsafe { handle(ffi::av_interleaved_write_frame(self.0 .0, packet.0 .0)) }
+    }
+
+    pub fn write_trailer(&mut self) -> Result<()> {
+        unsafe { handle(ffi::av_write_trailer(self.0 .0)) }
+    }
+
     pub fn find_stream_info(&mut self) -> Result<()> {
         unsafe { handle(ffi::avformat_find_stream_info(self.0 .0, null_mut())) }
     }
@@ -44,6 +84,10 @@ unsafe impl Send for AVFormatContext {}
 impl Drop for AVFormatContext {
     fn drop(&mut self) {
         unsafe {
+            let this = self.0.as_mut();
+            if !this.oformat.is_null() && !this.pb.is_null() {
+                ffi::avio_closep(&mut this.pb);
+            }
             ffi::avformat_free_context(self.0 .0);
         }
     }
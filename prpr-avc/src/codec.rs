@@ -1,6 +1,7 @@
 use crate::{ffi, handle, AVFrame, AVPacket, AVPixelFormat, Error, OwnedPtr, Result, VideoStreamFormat};
 use std::{
-    ptr::null_mut,
+    ffi::CString,
+    ptr::{null, null_mut},
     sync::{
         atomic::{AtomicI32, Ordering},
         Mutex,
@@ -46,6 +47,29 @@ impl AVCodecRef {
             }
         }
     }
+
+    pub fn find_encoder(id: ffi::AVCodecID) -> Result<Self> {
+        unsafe {
+            let ptr = ffi::avcodec_find_encoder(id);
+            if ptr.is_null() {
+                Err(Error::EncoderNotFound(format!("codec id {id}")))
+            } else {
+                Ok(Self(ptr))
+            }
+        }
+    }
+
+    pub fn find_encoder_by_name(name: &str) -> Result<Self> {
+        unsafe {
+            let c_name = CString::new(name).unwrap();
+            let ptr = ffi::avcodec_find_encoder_by_name(c_name.as_ptr());
+            if ptr.is_null() {
+                Err(Error::EncoderNotFound(name.to_owned()))
+            } else {
+                Ok(Self(ptr))
+            }
+        }
+    }
 }
 
 static EXPECTED_PIX_FMT_EDIT: Mutex<()> = Mutex::new(());
@@ -83,6 +107,25 @@ impl AVCodecContext {
         }
     }
 
+    /// Opens an encoder, `setup` fills in the parameters before it is opened
+    pub(crate) fn new_encoder(codec: AVCodecRef, setup: impl FnOnce(&mut ffi::AVCodecContext)) -> Result<Self> {
+        unsafe {
+            let mut ptr = OwnedPtr::new(ffi::avcodec_alloc_context3(codec.0)).ok_or(Error::AllocationFailed)?;
+            setup(ptr.as_mut());
+            let this = Self(ptr);
+            handle(ffi::avcodec_open2(this.0 .0, codec.0, null_mut()))?;
+            Ok(this)
+        }
+    }
+
+    pub(crate) fn as_ptr(&self) -> *const ffi::AVCodecContext {
+        self.0 .0
+    }
+
+    pub(crate) fn time_base(&self) -> ffi::AVRational {
+        unsafe { self.0.as_ref().time_base }
+    }
+
     pub fn frame_size(&self) -> i32 {
         unsafe { self.0.as_ref().frame_size }
     }
@@ -102,6 +145,23 @@ impl AVCodecContext {
         unsafe { handle(ffi::avcodec_send_packet(self.0 .0, packet.0 .0)) }
     }
 
+    /// Sends `None` to flush the encoder
+    pub fn send_frame(&mut self, frame: Option<&AVFrame>) -> Result<()> {
+        unsafe { handle(ffi::avcodec_send_frame(self.0 .0, frame.map_or(null(), |it| it.0 .0))) }
+    }
+
+    pub fn receive_packet(&mut self, packet: &mut AVPacket) -> Result<bool> {
+        unsafe {
+            match handle(ffi::avcodec_receive_packet(self.0 .0, packet.0 .0)) {
+                Err(Error::TryAgain | Error::EndOfFile) => Ok(false),
+                x => {
+                    x?;
+                    Ok(true)
+                }
+            }
+        }
+    }
+
     pub fn receive_frame(&mut self, frame: &mut AVFrame) -> Result<bool> {
         unsafe {
             match handle(ffi::avcodec_receive_frame(self.0 .0, frame.0 .0)) {
@@ -0,0 +1,234 @@
+use crate::{
+    ffi, AVCodecContext, AVCodecRef, AVFormatContext, AVFrame, AVPacket, AVPixelFormat, AVStreamRef, AudioStreamFormat, Error, Result, SwsContext,
+    VideoStreamFormat,
+};
+
+#[derive(Debug, Clone)]
+pub struct EncoderOptions {
+    pub width: i32,
+    pub height: i32,
+    pub fps: i32,
+    pub video_bit_rate: i64,
+    pub sample_rate: i32,
+    pub audio_bit_rate: i64,
+}
+
+impl Default for EncoderOptions {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+            fps: 60,
+            video_bit_rate: 8_000_000,
+            sample_rate: 44100,
+            audio_bit_rate: 192_000,
+        }
+    }
+}
+
+struct Output {
+    codec_ctx: AVCodecContext,
+    stream: AVStreamRef,
+}
+
+impl Output {
+    fn drain(&mut self, format_ctx: &mut AVFormatContext, packet: &mut AVPacket) -> Result<()> {
+        while self.codec_ctx.receive_packet(packet)? {
+            packet.prepare_write(self.stream.index(), self.codec_ctx.time_base(), self.stream.time_base());
+            format_ctx.write_packet(packet)?;
+        }
+        Ok(())
+    }
+}
+
+/// Encodes RGBA frames and stereo samples into a H.264 / AAC video file
+///
+/// The container is guessed from the extension of the output path. [Encoder::finish] must be called, or the file will be
+/// incomplete.
+pub struct Encoder {
+    format_ctx: AVFormatContext,
+    packet: AVPacket,
+
+    video: Output,
+    sws: SwsContext,
+    rgba_frame: AVFrame,
+    yuv_frame: AVFrame,
+    width: usize,
+    height: usize,
+    video_pts: i64,
+
+    audio: Output,
+    audio_frame: AVFrame,
+    audio_frame_size: usize,
+    audio_pts: i64,
+    // interleaved samples not yet making up a full frame
+    pending: Vec<f32>,
+}
+
+impl Encoder {
+    pub fn new(path: impl AsRef<str>, options: &EncoderOptions) -> Result<Self> {
+        let mut format_ctx = AVFormatContext::open_output(path.as_ref())?;
+
+        let codec = AVCodecRef::find_encoder_by_name("libx264").or_else(|_| AVCodecRef::find_encoder(ffi::AV_CODEC_ID_H264))?;
+        let codec_ctx = AVCodecContext::new_encoder(codec, |ctx| {
+            ctx.width = options.width;
+            ctx.height = options.height;
+            ctx.time_base = ffi::AVRational { num: 1, den: options.fps };
+            ctx.framerate = ffi::AVRational { num: options.fps, den: 1 };
+            ctx.gop_size = options.fps * 2;
+            ctx.pix_fmt = AVPixelFormat::YUV420P.0;
+            ctx.bit_rate = options.video_bit_rate;
+            ctx.flags |= ffi::AV_CODEC_FLAG_GLOBAL_HEADER;
+        })?;
+        let stream = format_ctx.new_stream(&codec_ctx)?;
+        let video = Output { codec_ctx, stream };
+
+        let rgba_format = VideoStreamFormat {
+            width: options.width,
+            height: options.height,
+            pix_fmt: AVPixelFormat::RGBA,
+        };
+        let yuv_format = VideoStreamFormat {
+            pix_fmt: AVPixelFormat::YUV420P,
+            ..rgba_format.clone()
+        };
+        let sws = SwsContext::new(rgba_format.clone(), yuv_format.clone())?;
+        let mut rgba_frame = AVFrame::new()?;
+        rgba_frame.set_video_format(&rgba_format);
+        rgba_frame.get_buffer()?;
+        let mut yuv_frame = AVFrame::new()?;
+        yuv_frame.set_video_format(&yuv_format);
+        yuv_frame.get_buffer()?;
+
+        let audio_format = AudioStreamFormat {
+            channel_layout: ffi::AV_CH_LAYOUT_STEREO,
+            channels: 2,
+            sample_fmt: ffi::AV_SAMPLE_FMT_FLTP,
+            sample_rate: options.sample_rate,
+        };
+        let codec = AVCodecRef::find_encoder(ffi::AV_CODEC_ID_AAC)?;
+        let codec_ctx = AVCodecContext::new_encoder(codec, |ctx| {
+            ctx.sample_fmt = audio_format.sample_fmt;
+            ctx.sample_rate = audio_format.sample_rate;
+            ctx.channels = audio_format.channels;
+            ctx.channel_layout = audio_format.channel_layout;
+            ctx.ch_layout = ffi::AVChannelLayout {
+                order: ffi::AV_CHANNEL_ORDER_NATIVE,
+                nb_channels: audio_format.channels,
+                u: ffi::AVChannelLayout__bindgen_ty_1 {
+                    mask: audio_format.channel_layout,
+                },
+                opaque: std::ptr::null_mut(),
+            };
+            ctx.time_base = ffi::AVRational {
+                num: 1,
+                den: options.sample_rate,
+            };
+            ctx.bit_rate = options.audio_bit_rate;
+            ctx.flags |= ffi::AV_CODEC_FLAG_GLOBAL_HEADER;
+        })?;
+        let audio_frame_size = codec_ctx.frame_size().max(1) as usize;
+        let stream = format_ctx.new_stream(&codec_ctx)?;
+        let audio = Output { codec_ctx, stream };
+
+        let mut audio_frame = AVFrame::new()?;
+        audio_frame.set_audio_format(&audio_format);
+        audio_frame.set_number_of_samples(audio_frame_size as _);
+        audio_frame.get_buffer()?;
+
+        format_ctx.write_header()?;
+
+        Ok(Self {
+            format_ctx,
+            packet: AVPacket::new()?,
+
+            video,
+            sws,
+            rgba_frame,
+            yuv_frame,
+            width: options.width as usize,
+            height: options.height as usize,
+            video_pts: 0,
+
+            audio,
+            audio_frame,
+            audio_frame_size,
+            audio_pts: 0,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Encodes the next frame, given as tightly packed RGBA rows from top to bottom
+    pub fn write_video(&mut self, rgba: &[u8]) -> Result<()> {
+        let row = self.width * 4;
+        let expected = row * self.height;
+        if expected == 0 || rgba.len() != expected {
+            return Err(Error::InvalidFrameSize {
+                expected,
+                actual: rgba.len(),
+            });
+        }
+        self.rgba_frame.make_writable()?;
+        let line_size = self.rgba_frame.line_size() as usize;
+        let data = self.rgba_frame.data_mut(0);
+        for (src, dst) in rgba.chunks_exact(row).zip(data.chunks_mut(line_size)) {
+            dst[..row].copy_from_slice(src);
+        }
+        self.yuv_frame.make_writable()?;
+        self.sws.scale(&self.rgba_frame, &mut self.yuv_frame);
+        self.yuv_frame.set_pts(self.video_pts);
+        self.video_pts += 1;
+        self.video.codec_ctx.send_frame(Some(&self.yuv_frame))?;
+        self.video.drain(&mut self.format_ctx, &mut self.packet)
+    }
+
+    /// Queues interleaved stereo samples, which are encoded once they fill a frame
+    pub fn write_audio(&mut self, samples: &[f32]) -> Result<()> {
+        self.pending.extend_from_slice(samples);
+        let chunk = self.audio_frame_size * 2;
+        let full = self.pending.len() / chunk * chunk;
+        if full == 0 {
+            return Ok(());
+        }
+        let pending = std::mem::take(&mut self.pending);
+        for samples in pending[..full].chunks_exact(chunk) {
+            self.encode_audio(samples)?;
+        }
+        self.pending = pending[full..].to_vec();
+        Ok(())
+    }
+
+    fn encode_audio(&mut self, samples: &[f32]) -> Result<()> {
+        let count = samples.len() / 2;
+        self.audio_frame.make_writable()?;
+        self.audio_frame.set_number_of_samples(count as _);
+        let planes = self.audio_frame.raw_data();
+        unsafe {
+            let left = std::slice::from_raw_parts_mut(planes[0] as *mut f32, count);
+            let right = std::slice::from_raw_parts_mut(planes[1] as *mut f32, count);
+            for (i, frame) in samples.chunks_exact(2).enumerate() {
+                left[i] = frame[0];
+                right[i] = frame[1];
+            }
+        }
+        self.audio_frame.set_pts(self.audio_pts);
+        self.audio_pts += count as i64;
+        self.audio.codec_ctx.send_frame(Some(&self.audio_frame))?;
+        self.audio.drain(&mut self.format_ctx, &mut self.packet)
+    }
+
+    /// Flushes both encoders and writes the trailer of the file
+    pub fn finish(mut self) -> Result<()> {
+        let pending = std::mem::take(&mut self.pending);
+        if !pending.is_empty() {
+            self.encode_audio(&pending)?;
+        }
+        for output in [&mut self.video, &mut self.audio] {
+            output.codec_ctx.send_frame(None)?;
+            output.drain(&mut self.format_ctx, &mut self.packet)?;
+        }
+        self.format_ctx.write_trailer()
+    }
+}
+
+unsafe impl Send for Encoder {}
@@ -22,9 +22,15 @@ pub enum Error {
     #[error("decoder not found for codec id {0}")]
     DecoderNotFound(ffi::AVCodecID),
 
+    #[error("encoder not found: {0}")]
+    EncoderNotFound(String),
+
     #[error("end of file")]
     EndOfFile,
 
+    #[error("invalid frame size: expected {expected} bytes, got {actual}")]
+    InvalidFrameSize { expected: usize, actual: usize },
+
     #[error("AVError #{0}: {1:?}")]
     Unhandled(i32, Option<String>),
 
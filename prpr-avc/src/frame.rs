@@ -40,6 +40,17 @@ impl AVFrame {
         unsafe { handle(ffi::av_frame_get_buffer(self.0 .0, 0)) }
     }
 
+    /// Makes sure the buffer is not shared with an encoder before writing to it
+    pub fn make_writable(&mut self) -> Result<()> {
+        unsafe { handle(ffi::av_frame_make_writable(self.0 .0)) }
+    }
+
+    pub fn set_pts(&mut self, pts: i64) {
+        unsafe {
+            self.0.as_mut().pts = pts;
+        }
+    }
+
     pub fn raw_data(&self) -> [*mut u8; 8] {
         unsafe { self.0.as_ref().data }
     }
@@ -52,6 +63,13 @@ impl AVFrame {
         }
     }
 
+    pub fn data_mut(&mut self, index: usize) -> &mut [u8] {
+        unsafe {
+            let this = self.0.as_mut();
+            std::slice::from_raw_parts_mut(this.data[index], this.linesize[index] as usize * this.height as usize)
+        }
+    }
+
     pub fn data_half(&self, index: usize) -> &[u8] {
         let data = self.data(index);
         &data[..data.len() / 2]
@@ -1,5 +1,6 @@
 mod avformat;
 mod codec;
+mod encoder;
 mod error;
 mod ffi;
 mod frame;
@@ -12,6 +13,7 @@ mod video;
 
 pub use avformat::*;
 pub use codec::*;
+pub use encoder::*;
 pub use error::*;
 pub use frame::*;
 pub use misc::*;
@@ -10,6 +10,13 @@ impl AVPacket {
     pub fn stream_index(&self) -> i32 {
         unsafe { self.0.as_ref().stream_index }
     }
+
+    pub(crate) fn prepare_write(&mut self, stream_index: i32, from: ffi::AVRational, to: ffi::AVRational) {
+        unsafe {
+            ffi::av_packet_rescale_ts(self.0 .0, from, to);
+            self.0.as_mut().stream_index = stream_index;
+        }
+    }
 }
 
 unsafe impl Send for AVPacket {}
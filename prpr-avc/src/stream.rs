@@ -2,7 +2,7 @@ use crate::{ffi, AVCodecParamsRef, AVCodecRef, AVRational, Result};
 
 #[repr(transparent)]
 #[derive(Clone, Copy)]
-pub struct AVStreamRef(*const ffi::AVStream);
+pub struct AVStreamRef(pub(crate) *const ffi::AVStream);
 impl AVStreamRef {
     pub fn index(&self) -> i32 {
         #[allow(clippy::unnecessary_cast)]
@@ -11,6 +11,10 @@ impl AVStreamRef {
         }
     }
 
+    pub(crate) fn time_base(&self) -> ffi::AVRational {
+        unsafe { (*self.0).time_base }
+    }
+
     pub fn frame_rate(&self) -> AVRational {
         unsafe { (*self.0).r_frame_rate.into() }
     }
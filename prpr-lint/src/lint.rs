@@ -0,0 +1,707 @@
+//! Chart checks
+//!
+//! Checks are split into two passes: source checks that work on the raw chart data (so that problems the parser silently
+//! fixes up or refuses to handle can still be reported), and chart checks that work on the parsed [Chart].
+
+use anyhow::Result;
+use prpr::{
+    core::{declared_uniforms, Anim, Chart, Color, Effect, JudgeLineKind, NoteKind, Object, Tweenable, NOTE_WIDTH_RATIO_BASE},
+    fs::{ExternalFileSystem, FileSystem, ZipFileSystem},
+    parse::{parent_cycles, RPE_TWEEN_MAP},
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::{collections::HashSet, fmt};
+
+const IMAGE_EXTENSIONS: [&str; 8] = ["png", "jpg", "jpeg", "bmp", "gif", "webp", "avif", "ppm"];
+
+const LAYER_EVENTS: [&str; 4] = ["alphaEvents", "moveXEvents", "moveYEvents", "rotateEvents"];
+const EXTENDED_EVENTS: [&str; 7] = [
+    "colorEvents",
+    "textEvents",
+    "scaleXEvents",
+    "scaleYEvents",
+    "inclineEvents",
+    "paintEvents",
+    "gifEvents",
+];
+const CTRL_EVENTS: [&str; 4] = ["posControl", "sizeControl", "alphaControl", "yControl"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<usize>,
+    /// Time in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<f32>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            code,
+            message: message.into(),
+            line: None,
+            note: None,
+            time: None,
+        }
+    }
+
+    pub fn error(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, code, message)
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, code, message)
+    }
+
+    pub fn line(mut self, line: usize) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    pub fn note(mut self, note: usize) -> Self {
+        self.note = Some(note);
+        self
+    }
+
+    pub fn time(mut self, time: f32) -> Self {
+        self.time = Some(time);
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]", self.severity, self.code)?;
+        let mut location = Vec::new();
+        if let Some(line) = self.line {
+            location.push(format!("line #{line}"));
+        }
+        if let Some(note) = self.note {
+            location.push(format!("note #{note}"));
+        }
+        if let Some(time) = self.time {
+            location.push(format!("{time:.3}s"));
+        }
+        if !location.is_empty() {
+            write!(f, " {}", location.join(", "))?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+fn valid_easing(easing: i64) -> bool {
+    // the parser clamps 0 to 1, so it plays as linear as well
+    (0..RPE_TWEEN_MAP.len() as i64).contains(&easing)
+}
+
+/// RPE times are `[i, n, d]` triples, a zero denominator turns into NaN (or infinity) once converted
+fn bad_triple(value: &Value) -> bool {
+    value.as_array().and_then(|it| it.get(2)).and_then(Value::as_f64) == Some(0.)
+}
+
+fn check_rpe_events(events: &Value, line: usize, desc: &str, easing: bool, diags: &mut Vec<Diagnostic>) {
+    let Some(events) = events.as_array() else {
+        return;
+    };
+    for (id, event) in events.iter().enumerate() {
+        if easing && event["bezier"].as_u64().unwrap_or(0) == 0 {
+            if let Some(easing) = event["easingType"].as_i64().filter(|it| !valid_easing(*it)) {
+                diags.push(
+                    Diagnostic::warning("unknown-easing", format!("{desc} event #{id} uses unknown easing type {easing}, falling back to linear"))
+                        .line(line),
+                );
+            }
+        }
+        if bad_triple(&event["startTime"]) || bad_triple(&event["endTime"]) {
+            diags.push(Diagnostic::error("nan-keyframe", format!("{desc} event #{id} has a zero denominator in its time")).line(line));
+        }
+    }
+}
+
+/// Checks the raw RPE chart, and prepares it for headless parsing
+///
+/// Textured lines are turned into plain lines (texture loading needs a GL context) and parent cycles are broken, so that the
+/// parser can still go through the rest of the chart. Returns the texture paths referenced by the chart.
+pub fn check_rpe_source(rpe: &mut Value, diags: &mut Vec<Diagnostic>) -> HashSet<String> {
+    let mut textures = HashSet::new();
+    let Some(lines) = rpe.get_mut("judgeLineList").and_then(Value::as_array_mut) else {
+        return textures;
+    };
+    let count = lines.len();
+    let mut parents = Vec::with_capacity(count);
+    for (id, line) in lines.iter_mut().enumerate() {
+        for layer in line["eventLayers"].as_array().into_iter().flatten() {
+            for key in LAYER_EVENTS {
+                check_rpe_events(&layer[key], id, key.trim_end_matches("Events"), true, diags);
+            }
+            check_rpe_events(&layer["speedEvents"], id, "speed", false, diags);
+        }
+        for key in EXTENDED_EVENTS {
+            check_rpe_events(&line["extended"][key], id, key.trim_end_matches("Events"), true, diags);
+        }
+        for key in CTRL_EVENTS {
+            for (ctrl_id, event) in line[key].as_array().into_iter().flatten().enumerate() {
+                if let Some(easing) = event["easing"].as_i64().filter(|it| !valid_easing(*it)) {
+                    diags.push(
+                        Diagnostic::warning(
+                            "unknown-easing",
+                            format!("{key} event #{ctrl_id} uses unknown easing type {easing}, falling back to linear"),
+                        )
+                        .line(id),
+                    );
+                }
+            }
+        }
+        for (note_id, note) in line["notes"].as_array().into_iter().flatten().enumerate() {
+            if bad_triple(&note["startTime"]) || bad_triple(&note["endTime"]) {
+                diags.push(
+                    Diagnostic::error("nan-keyframe", "note has a zero denominator in its time")
+                        .line(id)
+                        .note(note_id),
+                );
+            }
+        }
+
+        if let Some(texture) = line["Texture"].as_str().filter(|it| *it != "line.png") {
+            textures.insert(texture.to_owned());
+            line["Texture"] = Value::from("line.png");
+            if let Some(extended) = line.get_mut("extended").and_then(Value::as_object_mut) {
+                extended.remove("gifEvents");
+            }
+        }
+
+        parents.push(match line["father"].as_i64() {
+            None | Some(-1) => None,
+            Some(parent) if parent >= 0 && (parent as usize) < count => Some(parent as usize),
+            Some(parent) => {
+                diags.push(Diagnostic::error("invalid-parent", format!("parent line #{parent} does not exist")).line(id));
+                line["father"] = Value::from(-1);
+                None
+            }
+        });
+    }
+    for cycle in parent_cycles(&parents) {
+        let path = cycle
+            .iter()
+            .chain(cycle.first())
+            .map(|it| format!("#{it}"))
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        diags.push(Diagnostic::error("attach-cycle", format!("lines {path} form a parent cycle")).line(cycle[0]));
+        lines[cycle[0]]["father"] = Value::from(-1);
+    }
+    textures
+}
+
+fn check_ext_anim(anim: &Value, desc: &str, diags: &mut Vec<Diagnostic>) {
+    for (id, kf) in anim.as_array().into_iter().flatten().enumerate() {
+        if let Some(easing) = kf["easingType"].as_i64().filter(|it| !valid_easing(*it)) {
+            diags.push(Diagnostic::warning(
+                "unknown-easing",
+                format!("{desc} keyframe #{id} uses unknown easing type {easing}, falling back to linear"),
+            ));
+        }
+        if bad_triple(&kf["startTime"]) || bad_triple(&kf["endTime"]) {
+            diags.push(Diagnostic::error("nan-keyframe", format!("{desc} keyframe #{id} has a zero denominator in its time")));
+        }
+    }
+}
+
+/// Checks `extra.json` without compiling shaders or decoding videos
+///
+/// `exists` tells whether a file exists in the chart package, and `read` reads a shader from it. Returns the files
+/// referenced by `extra.json`.
+pub fn check_extra_source(
+    extra: &Value,
+    mut exists: impl FnMut(&str) -> bool,
+    mut read: impl FnMut(&str) -> Option<String>,
+    diags: &mut Vec<Diagnostic>,
+) -> HashSet<String> {
+    let mut files = HashSet::new();
+    for (id, effect) in extra["effects"].as_array().into_iter().flatten().enumerate() {
+        let desc = format!("effect #{id}");
+        let shaders: Vec<_> = match &effect["shader"] {
+            Value::Array(shaders) => shaders.iter().map(Value::as_str).collect(),
+            shader => vec![shader.as_str()],
+        };
+        if shaders.is_empty() {
+            diags.push(Diagnostic::error("unknown-shader", format!("{desc} has no shader")));
+        }
+        // uniforms declared by the shaders, unknown if any of them can't be read
+        let mut declared = Some(HashSet::new());
+        for shader in shaders {
+            let code = match shader {
+                Some(shader) => {
+                    if let Some(path) = shader.strip_prefix('/') {
+                        if !exists(path) {
+                            diags.push(Diagnostic::error("missing-file", format!("{desc} references missing shader file {path}")));
+                        }
+                        files.insert(path.to_owned());
+                        read(path)
+                    } else if let Some(code) = Effect::get_preset(shader) {
+                        Some(code.to_owned())
+                    } else {
+                        diags.push(Diagnostic::error("unknown-shader", format!("{desc} uses unknown preset shader {shader}")));
+                        None
+                    }
+                }
+                None => {
+                    diags.push(Diagnostic::error("unknown-shader", format!("{desc} has no shader")));
+                    None
+                }
+            };
+            match (code, &mut declared) {
+                (Some(code), Some(declared)) => declared.extend(declared_uniforms(&code).into_keys()),
+                _ => declared = None,
+            }
+        }
+        for (name, var) in effect["vars"].as_object().into_iter().flatten() {
+            if declared.as_ref().is_some_and(|it| !it.contains(name)) {
+                // skipped when playing
+                diags.push(Diagnostic::warning("undeclared-uniform", format!("{desc} variable {name} is not declared by the shader")));
+            }
+            if let Some(path) = var.as_str() {
+                if !exists(path) {
+                    diags.push(Diagnostic::error("missing-file", format!("{desc} variable {name} references missing texture {path}")));
+                }
+                files.insert(path.to_owned());
+                continue;
+            }
+            check_ext_anim(var, &format!("{desc} variable {name}"), diags);
+        }
+    }
+    for (id, video) in extra["videos"].as_array().into_iter().flatten().enumerate() {
+        let desc = format!("video #{id}");
+        if let Some(path) = video["path"].as_str() {
+            if !exists(path) {
+                diags.push(Diagnostic::error("missing-file", format!("{desc} references missing file {path}")));
+            }
+            files.insert(path.to_owned());
+        }
+        check_ext_anim(&video["alpha"], &format!("{desc} alpha"), diags);
+        check_ext_anim(&video["dim"], &format!("{desc} dim"), diags);
+    }
+    files
+}
+
+fn anim_has_nan<T: Tweenable>(anim: &Anim<T>, is_nan: impl Fn(&T) -> bool) -> bool {
+    let mut cur = Some(anim);
+    while let Some(anim) = cur {
+        if anim.keyframes.iter().any(|kf| kf.time.is_nan() || is_nan(&kf.value)) {
+            return true;
+        }
+        cur = anim.next.as_deref();
+    }
+    false
+}
+
+fn object_nan_field(obj: &Object) -> Option<&'static str> {
+    [
+        (&obj.alpha, "alpha"),
+        (&obj.scale.0, "scale X"),
+        (&obj.scale.1, "scale Y"),
+        (&obj.rotation, "rotation"),
+        (&obj.translation.0, "move X"),
+        (&obj.translation.1, "move Y"),
+    ]
+    .into_iter()
+    .find(|(anim, _)| anim_has_nan(*anim, |it: &f32| it.is_nan()))
+    .map(|(_, desc)| desc)
+}
+
+/// Checks the parsed chart
+///
+/// `music_length` is the length of the music in seconds, if known. `offset` is the offset between chart time and music time,
+/// i.e. a note at `t` is played at `t + offset` of the music.
+pub fn check_chart(chart: &mut Chart, music_length: Option<f32>, offset: f32, diags: &mut Vec<Diagnostic>) {
+    for (line_id, line) in chart.lines.iter_mut().enumerate() {
+        if let Some(desc) = object_nan_field(&line.object) {
+            diags.push(Diagnostic::error("nan-keyframe", format!("{desc} events contain NaN")).line(line_id));
+        }
+        let ctrl_obj = line.ctrl_obj.borrow();
+        let mut anims = vec![
+            (&line.height, "speed"),
+            (&line.incline, "incline"),
+            (&ctrl_obj.alpha, "alpha control"),
+            (&ctrl_obj.size, "size control"),
+            (&ctrl_obj.pos, "position control"),
+            (&ctrl_obj.y, "y control"),
+        ];
+        match &line.kind {
+            JudgeLineKind::Paint(anim, _) => anims.push((anim, "paint")),
+            JudgeLineKind::TextureGif(anim, ..) => anims.push((anim, "gif")),
+            _ => {}
+        }
+        for (anim, desc) in anims {
+            if anim_has_nan(anim, |it: &f32| it.is_nan()) {
+                diags.push(Diagnostic::error("nan-keyframe", format!("{desc} events contain NaN")).line(line_id));
+            }
+        }
+        drop(ctrl_obj);
+        if anim_has_nan(&line.color, |it: &Color| it.r.is_nan() || it.g.is_nan() || it.b.is_nan() || it.a.is_nan()) {
+            diags.push(Diagnostic::error("nan-keyframe", "color events contain NaN").line(line_id));
+        }
+
+        for (note_id, note) in line.notes.iter().enumerate() {
+            let end_time = match note.kind {
+                NoteKind::Hold { end_time, end_height } => {
+                    if end_time.is_nan() || end_height.is_nan() {
+                        diags.push(Diagnostic::error("nan-keyframe", "hold end is NaN").line(line_id).note(note_id));
+                    }
+                    end_time
+                }
+                _ => note.time,
+            };
+            if note.time.is_nan() || note.height.is_nan() || note.speed.is_nan() {
+                diags.push(
+                    Diagnostic::error("nan-keyframe", "note time, height or speed is NaN")
+                        .line(line_id)
+                        .note(note_id),
+                );
+            } else if let Some(desc) = object_nan_field(&note.object) {
+                diags.push(
+                    Diagnostic::error("nan-keyframe", format!("note {desc} is NaN"))
+                        .line(line_id)
+                        .note(note_id)
+                        .time(note.time),
+                );
+            }
+            if let Some(length) = music_length {
+                if !note.fake && end_time + offset > length {
+                    diags.push(
+                        Diagnostic::error(
+                            "note-after-music",
+                            format!("note ends at {:.3}s of the music, but the music is only {length:.3}s long", end_time + offset),
+                        )
+                        .line(line_id)
+                        .note(note_id)
+                        .time(note.time),
+                    );
+                }
+            }
+        }
+
+        // (start, end, x, above, note id)
+        let mut holds = Vec::new();
+        for (note_id, note) in line.notes.iter_mut().enumerate() {
+            if note.fake {
+                continue;
+            }
+            if let NoteKind::Hold { end_time, .. } = note.kind {
+                let x = &mut note.object.translation.0;
+                x.set_time(note.time);
+                holds.push((note.time, end_time, x.now(), note.above, note_id));
+            }
+        }
+        holds.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (i, a) in holds.iter().enumerate() {
+            for b in &holds[i + 1..] {
+                if b.0 >= a.1 {
+                    break;
+                }
+                if a.3 == b.3 && (a.2 - b.2).abs() < NOTE_WIDTH_RATIO_BASE {
+                    diags.push(
+                        Diagnostic::warning("overlapping-holds", format!("hold note overlaps with hold note #{} ({:.3}s - {:.3}s)", a.4, a.0, a.1))
+                            .line(line_id)
+                            .note(b.4)
+                            .time(b.0),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Collects textures referenced by the parsed chart
+pub fn chart_textures(chart: &Chart) -> impl Iterator<Item = &str> {
+    chart.lines.iter().filter_map(|line| match &line.kind {
+        JudgeLineKind::Texture(_, path) | JudgeLineKind::TextureGif(_, _, path) => Some(path.as_str()),
+        _ => None,
+    })
+}
+
+/// Paths of every file in the package relative to its root, including those in subdirectories
+pub fn package_files(fs: &mut dyn FileSystem) -> Result<Vec<String>> {
+    let fs = fs.as_any();
+    if let Some(zip) = fs.downcast_ref::<ZipFileSystem>() {
+        return Ok(zip.file_names());
+    }
+    let Some(ExternalFileSystem(dir)) = fs.downcast_ref::<ExternalFileSystem>() else {
+        return Ok(Vec::new());
+    };
+    let mut res = Vec::new();
+    let mut dirs = vec![String::new()];
+    while let Some(prefix) = dirs.pop() {
+        for entry in dir.read_dir(&prefix)? {
+            let entry = entry?;
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if entry.file_type()?.is_dir() {
+                dirs.push(format!("{prefix}{name}/"));
+            } else {
+                res.push(format!("{prefix}{name}"));
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// Reports image files in the package that nothing refers to
+pub fn check_unused_textures<'a>(files: impl Iterator<Item = &'a str>, used: &HashSet<String>, diags: &mut Vec<Diagnostic>) {
+    for file in files {
+        let is_image = file
+            .rsplit_once('.')
+            .is_some_and(|(_, ext)| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()));
+        if is_image && !used.contains(file) {
+            diags.push(Diagnostic::warning("unused-texture", format!("{file} is not used by the chart")));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prpr::{
+        core::{ChartExtra, Keyframe},
+        fs::fs_from_file,
+        parse::parse_rpe,
+    };
+    use serde_json::json;
+    use std::{
+        io::{Cursor, Write},
+        path::Path,
+    };
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    fn rpe_line(father: i64, notes: Value) -> Value {
+        json!({
+            "Name": "",
+            "Texture": "line.png",
+            "father": father,
+            "isCover": 1,
+            "eventLayers": [{
+                "alphaEvents": [{ "startTime": [0, 0, 1], "endTime": [1, 0, 1], "start": 255.0, "end": 255.0, "easingType": 1 }],
+                "speedEvents": [{ "startTime": [0, 0, 1], "endTime": [1, 0, 1], "start": 10.0, "end": 10.0 }]
+            }],
+            "notes": notes
+        })
+    }
+
+    fn rpe_note(kind: u8, start: i32, end: i32, x: f32, fake: bool) -> Value {
+        json!({
+            "type": kind,
+            "above": 1,
+            "startTime": [start, 0, 1],
+            "endTime": [end, 0, 1],
+            "positionX": x,
+            "yOffset": 0.0,
+            "alpha": 255,
+            "size": 1.0,
+            "speed": 1.0,
+            "isFake": fake as u8,
+            "visibleTime": 999999.0
+        })
+    }
+
+    /// 60 BPM, so that one beat is one second
+    fn rpe_chart(lines: Vec<Value>) -> Value {
+        json!({
+            "META": { "RPEVersion": 150, "offset": 0 },
+            "BPMList": [{ "bpm": 60.0, "startTime": [0, 0, 1] }],
+            "judgeLineList": lines
+        })
+    }
+
+    fn parse(rpe: &Value) -> Chart {
+        // the fixtures don't reference any file
+        let mut fs = fs_from_file(Path::new(env!("CARGO_MANIFEST_DIR"))).unwrap();
+        let source = serde_json::to_string(rpe).unwrap();
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(parse_rpe(&source, fs.as_mut(), ChartExtra::default()))
+            .unwrap()
+    }
+
+    fn codes(diags: &[Diagnostic]) -> Vec<&'static str> {
+        diags.iter().map(|it| it.code).collect()
+    }
+
+    #[test]
+    fn parent_cycle() {
+        let mut rpe = rpe_chart(vec![
+            rpe_line(1, json!([])),
+            rpe_line(0, json!([])),
+            rpe_line(0, json!([])),
+            rpe_line(5, json!([])),
+        ]);
+        let mut diags = Vec::new();
+        check_rpe_source(&mut rpe, &mut diags);
+        assert_eq!(codes(&diags), ["invalid-parent", "attach-cycle"]);
+        assert_eq!(diags[0].line, Some(3));
+        assert_eq!(diags[1].line, Some(0));
+
+        // the cycle is broken, so the parser can go on
+        assert_eq!(rpe["judgeLineList"][0]["father"], -1);
+        assert_eq!(rpe["judgeLineList"][3]["father"], -1);
+        let mut diags = Vec::new();
+        check_rpe_source(&mut rpe, &mut diags);
+        assert!(diags.is_empty());
+        parse(&rpe);
+    }
+
+    #[test]
+    fn overlapping_holds() {
+        let notes = json!([
+            rpe_note(2, 0, 2, 0.0, false),
+            rpe_note(2, 1, 3, 10.0, false),
+            // far away from the others
+            rpe_note(2, 1, 3, 600.0, false),
+            // fake notes can't be hit anyway
+            rpe_note(2, 1, 3, 0.0, true),
+            // starts right after the second one ends
+            rpe_note(2, 3, 4, 0.0, false),
+        ]);
+        let mut chart = parse(&rpe_chart(vec![rpe_line(-1, notes)]));
+        let mut diags = Vec::new();
+        check_chart(&mut chart, None, 0., &mut diags);
+        assert_eq!(codes(&diags), ["overlapping-holds"]);
+        assert_eq!(diags[0].line, Some(0));
+        assert_eq!(diags[0].time, Some(1.));
+    }
+
+    #[test]
+    fn notes_after_music() {
+        let notes = json!([rpe_note(1, 1, 1, 0.0, false), rpe_note(2, 2, 5, 0.0, false), rpe_note(1, 6, 6, 0.0, true)]);
+        let mut chart = parse(&rpe_chart(vec![rpe_line(-1, notes)]));
+        let mut diags = Vec::new();
+        check_chart(&mut chart, Some(4.), 0., &mut diags);
+        assert_eq!(codes(&diags), ["note-after-music"]);
+        assert_eq!(diags[0].time, Some(2.));
+
+        let mut diags = Vec::new();
+        check_chart(&mut chart, Some(4.), -1.5, &mut diags);
+        assert!(diags.is_empty());
+        check_chart(&mut chart, None, 0., &mut diags);
+        assert!(diags.is_empty());
+    }
+
+    #[test]
+    fn zero_denominator() {
+        let mut note = rpe_note(1, 1, 1, 0.0, false);
+        note["startTime"] = json!([1, 0, 0]);
+        let mut line = rpe_line(-1, json!([note]));
+        line["eventLayers"][0]["alphaEvents"][0]["endTime"] = json!([1, 1, 0]);
+        let mut rpe = rpe_chart(vec![line]);
+        let mut diags = Vec::new();
+        check_rpe_source(&mut rpe, &mut diags);
+        assert_eq!(codes(&diags), ["nan-keyframe", "nan-keyframe"]);
+        assert!(diags.iter().all(|it| it.severity == Severity::Error));
+        assert_eq!(diags[1].note, Some(0));
+    }
+
+    #[test]
+    fn nan_keyframe() {
+        let mut chart = parse(&rpe_chart(vec![rpe_line(-1, json!([rpe_note(1, 1, 1, 0.0, false)]))]));
+        let mut diags = Vec::new();
+        check_chart(&mut chart, None, 0., &mut diags);
+        assert!(diags.is_empty());
+
+        chart.lines[0].object.alpha = Anim::new(vec![Keyframe::new(0., 1., 2), Keyframe::new(f32::NAN, 0., 0)]);
+        chart.lines[0].notes[0].speed = f32::NAN;
+        check_chart(&mut chart, None, 0., &mut diags);
+        assert_eq!(codes(&diags), ["nan-keyframe", "nan-keyframe"]);
+        assert_eq!(diags[0].note, None);
+        assert_eq!(diags[1].note, Some(0));
+    }
+
+    #[test]
+    fn unknown_easing() {
+        let mut line = rpe_line(-1, json!([]));
+        let alpha = &mut line["eventLayers"][0]["alphaEvents"];
+        alpha[0]["easingType"] = json!(0);
+        alpha.as_array_mut().unwrap().push(json!({
+            "startTime": [1, 0, 1], "endTime": [2, 0, 1], "start": 255.0, "end": 0.0, "easingType": 99
+        }));
+        // unknown bezier easing types don't matter
+        alpha.as_array_mut().unwrap().push(json!({
+            "startTime": [2, 0, 1], "endTime": [3, 0, 1], "start": 0.0, "end": 255.0, "easingType": 99,
+            "bezier": 1, "bezierPoints": [0.2, 0.1, 0.7, 0.9]
+        }));
+        line["alphaControl"] = json!([{ "easing": 0, "x": 0.0, "alpha": 1.0 }, { "easing": 255, "x": 100.0, "alpha": 1.0 }]);
+        let mut rpe = rpe_chart(vec![line]);
+        let mut diags = Vec::new();
+        check_rpe_source(&mut rpe, &mut diags);
+        assert_eq!(codes(&diags), ["unknown-easing", "unknown-easing"]);
+        assert!(diags[0].message.starts_with("alpha event #1"));
+        assert!(diags[1].message.starts_with("alphaControl event #1"));
+        assert!(diags.iter().all(|it| it.severity == Severity::Warning));
+        parse(&rpe);
+
+        let mut diags = Vec::new();
+        check_ext_anim(&json!([{ "easingType": 0 }, { "easingType": -1 }]), "video alpha", &mut diags);
+        assert_eq!(codes(&diags), ["unknown-easing"]);
+    }
+
+    #[test]
+    fn unused_textures() {
+        let mut line = rpe_line(-1, json!([]));
+        line["Texture"] = json!("line.jpg");
+        let mut rpe = rpe_chart(vec![line, rpe_line(-1, json!([]))]);
+        let mut diags = Vec::new();
+        let used = check_rpe_source(&mut rpe, &mut diags);
+        assert!(diags.is_empty());
+        assert_eq!(rpe["judgeLineList"][0]["Texture"], "line.png");
+
+        let files = ["chart.json", "info.yml", "illustration.PNG", "line.jpg", "extra/unused.webp", "music.ogg"];
+        let mut used = used;
+        used.insert("illustration.PNG".to_owned());
+        check_unused_textures(files.into_iter(), &used, &mut diags);
+        assert_eq!(codes(&diags), ["unused-texture"]);
+        assert!(diags[0].message.starts_with("extra/unused.webp"));
+    }
+
+    #[test]
+    fn unused_textures_in_root_folder() {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.add_directory("pack/", SimpleFileOptions::default()).unwrap();
+        for name in ["pack/chart.json", "pack/line.png", "pack/textures/used.png", "pack/textures/unused.png"] {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(b"").unwrap();
+        }
+        let bytes = zip.finish().unwrap().into_inner();
+        let mut fs = ZipFileSystem::new(bytes).unwrap();
+        let files = package_files(&mut fs).unwrap();
+
+        let used = HashSet::from(["line.png".to_owned(), "textures/used.png".to_owned()]);
+        let mut diags = Vec::new();
+        check_unused_textures(files.iter().map(String::as_str), &used, &mut diags);
+        assert_eq!(codes(&diags), ["unused-texture"]);
+        assert!(diags[0].message.starts_with("textures/unused.png"));
+    }
+}
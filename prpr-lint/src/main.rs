@@ -0,0 +1,246 @@
+mod lint;
+
+use anyhow::{anyhow, bail, Context, Result};
+use lint::{chart_textures, check_chart, check_extra_source, check_rpe_source, check_unused_textures, package_files, Diagnostic, Severity};
+use prpr::{
+    core::{Chart, ChartExtra, ResourcePack},
+    fs::{fs_from_file, load_info_checked, FileSystem},
+    info::{ChartFormat, ChartInfo, InfoIssueKind},
+    parse::{detect_format, parse_chart},
+};
+use sasa::AudioClip;
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::HashSet,
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::Path,
+    process::ExitCode,
+};
+use tokio::runtime::Runtime;
+
+const HELP: &str = "
+Usage: prpr-lint [options] input...
+
+Inputs can be chart packages (directories or zip files) or single chart files.
+Music and texture checks are only available for chart packages.
+
+Options:
+    -h, --help     Display this message
+    -j, --json     Output diagnostics as JSON
+    -r, --respack  Inputs are resource packs instead of charts
+    -s, --strict   Treat warnings as errors
+";
+
+#[derive(Serialize)]
+struct Report {
+    path: String,
+    errors: usize,
+    warnings: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Report {
+    fn new(path: String, mut diagnostics: Vec<Diagnostic>) -> Self {
+        diagnostics.sort_by_key(|it| (std::cmp::Reverse(it.severity), it.line, it.note));
+        let errors = diagnostics.iter().filter(|it| it.severity == Severity::Error).count();
+        Self {
+            path,
+            errors,
+            warnings: diagnostics.len() - errors,
+            diagnostics,
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_owned()
+    }
+}
+
+fn lint(rt: &Runtime, path: &Path, diags: &mut Vec<Diagnostic>) -> Result<()> {
+    let package = path.is_dir() || path.extension().is_some_and(|it| it.eq_ignore_ascii_case("zip"));
+    let (mut fs, info) = if package {
+        let mut fs = fs_from_file(path).context("Failed to open chart package")?;
+        let (info, issues) = rt.block_on(load_info_checked(fs.as_mut())).context("Failed to load chart info")?;
+        for issue in issues {
+            let message = format!("chart info {issue}");
+            diags.push(match issue.kind {
+                InfoIssueKind::UnknownKey => Diagnostic::warning("unknown-info-key", message),
+                InfoIssueKind::NewerVersion => Diagnostic::warning("info-version", message),
+                InfoIssueKind::OutOfRange => Diagnostic::error("invalid-info", message),
+                // the chart and the music are loaded below, which reports them
+                InfoIssueKind::MissingFile if issue.key == "chart" || issue.key == "music" => continue,
+                InfoIssueKind::MissingFile => Diagnostic::error("missing-file", message),
+            });
+        }
+        (fs, info)
+    } else {
+        let parent = path.parent().filter(|it| !it.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let name = path
+            .file_name()
+            .ok_or_else(|| anyhow!("Invalid chart path"))?
+            .to_string_lossy()
+            .into_owned();
+        (
+            fs_from_file(parent)?,
+            ChartInfo {
+                chart: name,
+                ..Default::default()
+            },
+        )
+    };
+
+    let mut used = HashSet::from([info.illustration.clone()]);
+    if package && rt.block_on(fs.exists("extra.json"))? {
+        let extra = rt.block_on(fs.load_file("extra.json"))?;
+        match serde_json::from_slice::<Value>(&extra) {
+            Ok(extra) => {
+                let mut shader_fs = fs.clone_box();
+                let read = |path: &str| String::from_utf8(rt.block_on(shader_fs.load_file(path)).ok()?).ok();
+                used.extend(check_extra_source(&extra, |path| rt.block_on(fs.exists(path)).unwrap_or(false), read, diags));
+            }
+            Err(err) => diags.push(Diagnostic::error("parse-failed", format!("failed to parse extra.json: {err}"))),
+        }
+    }
+
+    let music_length = if package {
+        match rt.block_on(fs.load_file(&info.music)).and_then(|it| Ok(AudioClip::new(it)?)) {
+            Ok(clip) => Some(clip.length()),
+            Err(err) => {
+                diags.push(Diagnostic::error("missing-file", format!("failed to load music {}: {err}", info.music)));
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let bytes = match rt.block_on(fs.load_file(&info.chart)) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            diags.push(Diagnostic::error("missing-file", format!("failed to load chart {}: {err}", info.chart)));
+            return Ok(());
+        }
+    };
+    let format = match info.format.clone().map_or_else(|| detect_format(&bytes), Ok) {
+        Ok(format) => format,
+        Err(err) => {
+            diags.push(Diagnostic::error("parse-failed", err.to_string()));
+            return Ok(());
+        }
+    };
+    let source = if format == ChartFormat::Rpe {
+        let mut rpe: Value = match serde_json::from_str(&String::from_utf8_lossy(&bytes)) {
+            Ok(rpe) => rpe,
+            Err(err) => {
+                diags.push(Diagnostic::error("parse-failed", format!("failed to parse chart JSON: {err}")));
+                return Ok(());
+            }
+        };
+        used.extend(check_rpe_source(&mut rpe, diags));
+        serde_json::to_vec(&rpe)?
+    } else {
+        bytes
+    };
+
+    // effects and videos need a GL context, they are checked separately
+    let extra = ChartExtra::default();
+    let result = catch_unwind(AssertUnwindSafe(|| -> Result<Chart> { Ok(rt.block_on(parse_chart(&source, Some(&format), fs.as_mut(), extra))?.0) }));
+    let mut chart = match result {
+        Ok(Ok(chart)) => chart,
+        Ok(Err(err)) => {
+            diags.push(Diagnostic::error("parse-failed", format!("{err:?}")));
+            return Ok(());
+        }
+        Err(payload) => {
+            diags.push(Diagnostic::error("parse-failed", format!("parser panicked: {}", panic_message(payload))));
+            return Ok(());
+        }
+    };
+
+    let offset = chart.offset + info.offset;
+    check_chart(&mut chart, music_length, offset, diags);
+    if package {
+        used.extend(chart_textures(&chart).map(str::to_owned));
+        let files = package_files(fs.as_mut()).context("Failed to list files")?;
+        check_unused_textures(files.iter().map(String::as_str), &used, diags);
+    }
+    Ok(())
+}
+
+fn lint_respack(rt: &Runtime, path: &Path, diags: &mut Vec<Diagnostic>) -> Result<()> {
+    let mut fs = fs_from_file(path).context("Failed to open resource pack")?;
+    diags.extend(
+        rt.block_on(ResourcePack::validate(fs.as_mut()))
+            .into_iter()
+            .map(|issue| Diagnostic::error("respack", issue.to_string())),
+    );
+    Ok(())
+}
+
+fn main() -> Result<ExitCode> {
+    let mut inputs = Vec::new();
+    let mut json = false;
+    let mut respack = false;
+    let mut strict = false;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", HELP.trim());
+                return Ok(ExitCode::SUCCESS);
+            }
+            "-j" | "--json" => json = true,
+            "-r" | "--respack" => respack = true,
+            "-s" | "--strict" => strict = true,
+            _ if arg.starts_with('-') => bail!("Unknown option: {arg}"),
+            _ => inputs.push(arg),
+        }
+    }
+    if inputs.is_empty() {
+        bail!("Missing input");
+    }
+
+    // panics are reported as diagnostics
+    std::panic::set_hook(Box::new(|_| {}));
+    let rt = Runtime::new()?;
+    let reports = inputs
+        .into_iter()
+        .map(|input| {
+            let mut diags = Vec::new();
+            let result = if respack {
+                lint_respack(&rt, Path::new(&input), &mut diags)
+            } else {
+                lint(&rt, Path::new(&input), &mut diags)
+            };
+            if let Err(err) = result {
+                diags.push(Diagnostic::error("io", format!("{err:?}")));
+            }
+            Report::new(input, diags)
+        })
+        .collect::<Vec<_>>();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    } else {
+        for report in &reports {
+            if report.diagnostics.is_empty() {
+                println!("{}: ok", report.path);
+                continue;
+            }
+            println!("{}:", report.path);
+            for diag in &report.diagnostics {
+                println!("  {diag}");
+            }
+            println!("  {} error(s), {} warning(s)", report.errors, report.warnings);
+        }
+    }
+
+    let failed = reports.iter().any(|it| it.errors != 0 || (strict && it.warnings != 0));
+    Ok(if failed { ExitCode::FAILURE } else { ExitCode::SUCCESS })
+}
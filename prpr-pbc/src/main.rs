@@ -1,55 +1,381 @@
 use anyhow::{anyhow, bail, Context, Result};
 use async_trait::async_trait;
 use prpr::{
-    bin::{BinaryReader, BinaryWriter},
-    core::ChartExtra,
-    fs::FileSystem,
-    info::ChartFormat,
-    parse::{parse_pec, parse_phigros, parse_rpe},
+    bin::BinaryWriter,
+    core::{Chart, ChartExtra, NoteKind},
+    export::{export_pec, export_rpe},
+    fs::{fs_from_file, load_info, FileSystem, ZipFileSystem},
+    info::{ChartFormat, ChartInfo},
+    package::{PackageBuilder, PackageOptions},
+    parse::{find_parser, parse_chart, registered_formats},
 };
 use std::{
     any::Any,
     fs::File,
-    io::{BufWriter, Cursor},
+    io::BufWriter,
+    path::{Path, PathBuf},
 };
+use tokio::runtime::Runtime;
 
 const HELP: &str = "
-Usage: prpr-pbc [options] input output
+Usage: prpr-pbc [options] input [output]
+       prpr-pbc pack [pack options] chart output.zip
+
+Inputs can be chart files, chart packages (directories or zip files) or directories containing them. In the last
+case every chart in the directory is converted, and output should be a directory.
 
 Options:
-    -h, --help  Display this message
+    -h, --help           Display this message
+    -f, --from <format>  Input format (rpe, pec, pgr, pbc, malody, notes), detected from the content by default
+    -t, --to <format>    Output format (pbc, rpe, pec), pbc by default
+    -s, --stats          Print statistics of the charts instead of converting
+
+Packing builds a chart package from loose files. The chart can also be a chart package, in which case it is repacked
+with the chart, music and illustration moved to the root of the archive.
+
+Pack options:
+    -m, --music <file>         Music of the chart
+    -i, --illustration <file>  Illustration of the chart
+    -e, --extra <file>         extra.json of the chart
+    -v, --video <file>         Unlock video of the chart
+    -a, --add <file>           Any other file to include, can be repeated
+        --info <file>          info.yml to start from
+        --max-image-size <px>  Scale down larger images, 2048 by default
+        --wav                  Always transcode the music into WAV
 ";
 
-struct DummyFileSystem;
+const INFO_FILES: [&str; 3] = ["info.yml", "info.txt", "info.csv"];
+const CHART_EXTENSIONS: [&str; 4] = ["json", "pec", "pbc", "mc"];
+const TEXTURE_EXTENSIONS: [&str; 6] = ["png", "jpg", "jpeg", "bmp", "gif", "webp"];
+
+fn has_extension(path: &str, extensions: &[&str]) -> bool {
+    path.rsplit_once('.')
+        .map_or(false, |(_, ext)| extensions.iter().any(|it| ext.eq_ignore_ascii_case(it)))
+}
+
+/// Textures can't be loaded without a GL context, refuse them instead of crashing
+struct HeadlessFileSystem(Box<dyn FileSystem>);
 #[async_trait]
-impl FileSystem for DummyFileSystem {
-    async fn load_file(&mut self, _path: &str) -> Result<Vec<u8>> {
-        bail!("Not implemented");
+impl FileSystem for HeadlessFileSystem {
+    async fn load_file(&mut self, path: &str) -> Result<Vec<u8>> {
+        if has_extension(path, &TEXTURE_EXTENSIONS) {
+            bail!("Textures are not supported: {path}");
+        }
+        self.0.load_file(path).await
     }
-    async fn exists(&mut self, _path: &str) -> Result<bool> {
-        Ok(false)
+    async fn exists(&mut self, path: &str) -> Result<bool> {
+        self.0.exists(path).await
     }
     fn list_root(&self) -> Result<Vec<String>> {
-        Ok(vec![])
+        self.0.list_root()
     }
     fn clone_box(&self) -> Box<dyn FileSystem> {
-        Box::new(DummyFileSystem)
+        Box::new(HeadlessFileSystem(self.0.clone_box()))
     }
     fn as_any(&mut self) -> &mut dyn Any {
         self
     }
 }
 
+fn parse_format(s: &str) -> Result<ChartFormat> {
+    let format = ChartFormat::from_name(s);
+    if find_parser(&format).is_none() {
+        let known: Vec<_> = registered_formats().iter().map(|it| it.name().to_owned()).collect();
+        bail!("Unknown format: {s} (expected one of {})", known.join(", "));
+    }
+    Ok(format)
+}
+
+fn extension(format: &ChartFormat) -> String {
+    find_parser(format).map_or_else(|| format.name().to_owned(), |it| it.extension().to_owned())
+}
+
+fn is_package(path: &Path) -> bool {
+    if path.is_dir() {
+        INFO_FILES.iter().any(|it| path.join(it).exists())
+    } else {
+        path.extension().map_or(false, |it| it.eq_ignore_ascii_case("zip"))
+    }
+}
+
+/// Collect charts and chart packages in a directory, returns `(path, is_package)` pairs
+fn batch_inputs(dir: &Path) -> Result<Vec<(PathBuf, bool)>> {
+    let mut res = Vec::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("Failed to read directory {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() || is_package(&path) {
+            res.push((path, true));
+        } else if has_extension(&path.to_string_lossy(), &CHART_EXTENSIONS) {
+            res.push((path, false));
+        }
+    }
+    res.sort();
+    Ok(res)
+}
+
+fn load(rt: &Runtime, path: &Path, package: bool, from: Option<&ChartFormat>) -> Result<(Chart, ChartInfo, ChartFormat)> {
+    let (mut fs, info) = if package {
+        let mut fs = fs_from_file(path).context("Failed to open chart package")?;
+        let info = rt.block_on(load_info(fs.as_mut())).context("Failed to load chart info")?;
+        (fs, info)
+    } else {
+        let parent = path.parent().filter(|it| !it.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let info = ChartInfo {
+            name: path.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+            chart: path
+                .file_name()
+                .ok_or_else(|| anyhow!("Invalid chart path"))?
+                .to_string_lossy()
+                .into_owned(),
+            ..Default::default()
+        };
+        (fs_from_file(parent)?, info)
+    };
+    let bytes = rt.block_on(fs.load_file(&info.chart)).context("Failed to read chart")?;
+    let mut fs = HeadlessFileSystem(fs);
+    let (chart, format) = rt
+        .block_on(parse_chart(&bytes, from.or(info.format.as_ref()), &mut fs, ChartExtra::default()))
+        .context("Failed to parse chart")?;
+    Ok((chart, info, format))
+}
+
+fn save(chart: &Chart, info: &ChartInfo, to: &ChartFormat, output: &Path) -> Result<()> {
+    match to {
+        ChartFormat::Pbc => {
+            let output = BufWriter::new(File::create(output)?);
+            let mut w = BinaryWriter::new(output);
+            w.write(chart)?;
+        }
+        ChartFormat::Rpe => std::fs::write(output, export_rpe(chart, info)?)?,
+        ChartFormat::Pec => std::fs::write(output, export_pec(chart)?)?,
+        _ => bail!("Exporting to {to} is not supported"),
+    }
+    Ok(())
+}
+
+fn format_time(time: f32) -> String {
+    let time = time.max(0.);
+    format!("{}:{:06.3}", (time / 60.) as u32, time % 60.)
+}
+
+fn print_stats(path: &Path, chart: &Chart, format: &ChartFormat) {
+    // click, hold, flick, drag
+    let mut counts = [0; 4];
+    let mut fake = 0;
+    let mut duration = 0_f32;
+    for note in chart.lines.iter().flat_map(|it| &it.notes) {
+        if note.fake {
+            fake += 1;
+            continue;
+        }
+        let (index, end_time) = match note.kind {
+            NoteKind::Click => (0, note.time),
+            NoteKind::Hold { end_time, .. } => (1, end_time),
+            NoteKind::Flick => (2, note.time),
+            NoteKind::Drag => (3, note.time),
+        };
+        counts[index] += 1;
+        duration = duration.max(end_time);
+    }
+
+    println!("{}:", path.display());
+    println!("  lines: {}", chart.lines.len());
+    println!(
+        "  notes: {} (click {}, hold {}, flick {}, drag {}), fake: {fake}",
+        counts.iter().sum::<u32>(),
+        counts[0],
+        counts[1],
+        counts[2],
+        counts[3]
+    );
+    let bpm_list = chart.bpm_list.borrow();
+    if *format == ChartFormat::Pbc {
+        println!("  bpm changes: unknown (not stored in PBC)");
+    } else if bpm_list.is_empty() {
+        // PGR times are in seconds, exporters write them at 60 BPM
+        println!("  bpm changes: none (times are in seconds)");
+    } else {
+        let bpms: Vec<_> = bpm_list.ranges().collect();
+        println!("  bpm changes: {}", bpms.len().saturating_sub(1));
+        for (beats, bpm) in bpms {
+            println!("    beat {beats}: {bpm} bpm");
+        }
+    }
+    println!("  duration: {}", format_time(duration));
+}
+
+struct Options {
+    from: Option<ChartFormat>,
+    to: ChartFormat,
+    stats: bool,
+    output: Option<PathBuf>,
+    batch: bool,
+}
+
+fn process(rt: &Runtime, path: &Path, package: bool, options: &Options) -> Result<()> {
+    let (chart, info, format) = load(rt, path, package, options.from.as_ref())?;
+    if options.stats {
+        print_stats(path, &chart, &format);
+    }
+    let Some(output) = &options.output else {
+        return Ok(());
+    };
+    let output = if output.is_dir() {
+        let stem = path.file_stem().ok_or_else(|| anyhow!("Invalid input path"))?;
+        output.join(format!("{}.{}", stem.to_string_lossy(), extension(&options.to)))
+    } else {
+        output.clone()
+    };
+    if output == path {
+        bail!("Refusing to overwrite the input");
+    }
+    save(&chart, &info, &options.to, &output).with_context(|| format!("Failed to write {}", output.display()))?;
+    if options.batch {
+        println!("{} -> {}", path.display(), output.display());
+    }
+    Ok(())
+}
+
+fn read(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+/// Paths of every file in the directory and its subdirectories, relative to it
+fn dir_files(root: &Path) -> Result<Vec<String>> {
+    let mut res = Vec::new();
+    let mut dirs = vec![root.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else {
+                res.push(path.strip_prefix(root)?.to_string_lossy().replace('\\', "/"));
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// Builder holding every file of an existing chart package
+fn repack(rt: &Runtime, path: &Path) -> Result<PackageBuilder> {
+    let (mut fs, names): (Box<dyn FileSystem>, _) = if path.is_dir() {
+        (fs_from_file(path).context("Failed to open chart package")?, dir_files(path)?)
+    } else {
+        let zip = ZipFileSystem::new(read(path)?).context("Failed to open chart package")?;
+        let names = zip.file_names();
+        (Box::new(zip), names)
+    };
+    let info = rt.block_on(load_info(fs.as_mut())).context("Failed to load chart info")?;
+    let mut load = |name: &str| rt.block_on(fs.load_file(name)).with_context(|| format!("Failed to read {name}"));
+    let mut builder = PackageBuilder::new(info.clone())
+        .chart(&info.chart, load(&info.chart)?)
+        .music(&info.music, load(&info.music)?)
+        .illustration(&info.illustration, load(&info.illustration)?);
+    if let Some(video) = &info.unlock_video {
+        builder = builder.unlock_video(video, load(video)?);
+    }
+    let known = [Some(&info.chart), Some(&info.music), Some(&info.illustration), info.unlock_video.as_ref()];
+    for name in names {
+        if INFO_FILES.contains(&name.as_str()) || known.contains(&Some(&name)) {
+            continue;
+        }
+        let bytes = rt.block_on(fs.load_file(&name)).with_context(|| format!("Failed to read {name}"))?;
+        builder = if name == "extra.json" {
+            builder.extra(bytes)
+        } else {
+            builder.file(&name, bytes)
+        };
+    }
+    Ok(builder)
+}
+
+fn pack(mut iter: impl Iterator<Item = String>) -> Result<()> {
+    let mut paths = Vec::new();
+    let mut music = None;
+    let mut illustration = None;
+    let mut extra = None;
+    let mut video = None;
+    let mut files = Vec::new();
+    let mut info = None;
+    let mut options = PackageOptions::default();
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().map(PathBuf::from).ok_or_else(|| anyhow!("Missing value after {arg}"));
+        match arg.as_str() {
+            "-m" | "--music" => music = Some(value()?),
+            "-i" | "--illustration" => illustration = Some(value()?),
+            "-e" | "--extra" => extra = Some(value()?),
+            "-v" | "--video" => video = Some(value()?),
+            "-a" | "--add" => files.push(value()?),
+            "--info" => info = Some(value()?),
+            "--max-image-size" => {
+                options.max_image_size = value()?.to_string_lossy().parse().context("Invalid image size")?;
+            }
+            "--wav" => options.transcode_music = true,
+            _ if arg.starts_with('-') => bail!("Unknown option: {arg}"),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    let [input, output]: [PathBuf; 2] = paths.try_into().map_err(|_| anyhow!("Expected a chart and an output"))?;
+
+    let rt = Runtime::new()?;
+    let mut builder = if is_package(&input) || input.is_dir() {
+        repack(&rt, &input)?
+    } else {
+        let info = match &info {
+            Some(path) => ChartInfo::from_yaml(&String::from_utf8(read(path)?)?).context("Failed to load chart info")?,
+            None => ChartInfo {
+                name: input.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+                ..Default::default()
+            },
+        };
+        PackageBuilder::new(info).chart(&file_name(&input), read(&input)?)
+    };
+    if let Some(path) = music {
+        builder = builder.music(&file_name(&path), read(&path)?);
+    }
+    if let Some(path) = illustration {
+        builder = builder.illustration(&file_name(&path), read(&path)?);
+    }
+    if let Some(path) = extra {
+        builder = builder.extra(read(&path)?);
+    }
+    if let Some(path) = video {
+        builder = builder.unlock_video(&file_name(&path), read(&path)?);
+    }
+    for path in files {
+        builder = builder.file(&file_name(&path), read(&path)?);
+    }
+    let bytes = builder.options(options).build().context("Failed to build chart package")?;
+    std::fs::write(&output, bytes).with_context(|| format!("Failed to write {}", output.display()))?;
+    Ok(())
+}
+
 fn main() -> Result<()> {
-    let iter = std::env::args().skip(1);
+    let mut iter = std::env::args().skip(1).peekable();
+    if iter.peek().map(String::as_str) == Some("pack") {
+        iter.next();
+        return pack(iter);
+    }
     let mut input = None;
     let mut output = None;
-    for arg in iter {
+    let mut from = None;
+    let mut to = ChartFormat::Pbc;
+    let mut stats = false;
+    while let Some(arg) = iter.next() {
         match arg.as_str() {
             "-h" | "--help" => {
                 println!("{}", HELP.trim());
                 return Ok(());
             }
+            "-f" | "--from" => from = Some(parse_format(&iter.next().ok_or_else(|| anyhow!("Missing format after {arg}"))?)?),
+            "-t" | "--to" => to = parse_format(&iter.next().ok_or_else(|| anyhow!("Missing format after {arg}"))?)?,
+            "-s" | "--stats" => stats = true,
+            _ if arg.starts_with('-') => bail!("Unknown option: {arg}"),
             _ => {
                 if input.is_none() {
                     input = Some(arg);
@@ -62,39 +388,47 @@ fn main() -> Result<()> {
         }
     }
 
-    let input = input.ok_or_else(|| anyhow!("Missing input"))?;
-    let output = output.ok_or_else(|| anyhow!("Missing output"))?;
+    let input = PathBuf::from(input.ok_or_else(|| anyhow!("Missing input"))?);
+    let output = match output {
+        Some(output) => Some(PathBuf::from(output)),
+        None if stats => None,
+        None => bail!("Missing output"),
+    };
+    if !stats && to == ChartFormat::Pgr {
+        bail!("Exporting to PGR is not supported");
+    }
 
-    let bytes = std::fs::read(input).context("Failed to read chart")?;
-    let format = if let Ok(text) = String::from_utf8(bytes.clone()) {
-        if text.starts_with('{') {
-            if text.contains("\"META\"") {
-                ChartFormat::Rpe
-            } else {
-                ChartFormat::Pgr
-            }
-        } else {
-            ChartFormat::Pec
-        }
+    let batch = input.is_dir() && !is_package(&input);
+    let inputs = if batch {
+        batch_inputs(&input)?
     } else {
-        ChartFormat::Pbc
+        vec![(input.clone(), is_package(&input))]
     };
+    if let (true, false, Some(output)) = (batch, stats, &output) {
+        std::fs::create_dir_all(output).context("Failed to create output directory")?;
+    }
 
-    let mut fs = Box::new(DummyFileSystem);
-    let extra = ChartExtra::default();
-    let chart = match format {
-        ChartFormat::Rpe => pollster::block_on(parse_rpe(&String::from_utf8_lossy(&bytes), fs.as_mut(), extra)),
-        ChartFormat::Pgr => parse_phigros(&String::from_utf8_lossy(&bytes), extra),
-        ChartFormat::Pec => parse_pec(&String::from_utf8_lossy(&bytes), extra),
-        ChartFormat::Pbc => {
-            let mut r = BinaryReader::new(Cursor::new(&bytes));
-            r.read()
+    let options = Options {
+        from,
+        to,
+        stats,
+        output,
+        batch,
+    };
+    let rt = Runtime::new()?;
+    let mut failed = 0;
+    for (path, package) in &inputs {
+        if let Err(err) = process(&rt, path, *package, &options) {
+            if !batch {
+                return Err(err);
+            }
+            eprintln!("{}: {err:?}", path.display());
+            failed += 1;
         }
-    }?;
-
-    let output = BufWriter::new(File::create(output)?);
-    let mut w = BinaryWriter::new(output);
-    w.write(&chart)?;
+    }
+    if failed != 0 {
+        bail!("{failed} of {} charts failed", inputs.len());
+    }
 
     Ok(())
 }
@@ -7,11 +7,15 @@ max-combo = MAX COMBO
 rks-delta = RKS CHANGE
 accuracy = Accuracy
 error = Error
+offset = Offset
+early = Early
+late = Late
 
 uploading = Uploading record…
 uploaded = Score uploaded.
 upload-failed = Failed to upload.
 upload-cancel = Cancel Upload
 upload-retry = Retry Upload
+upload-queued = No connection, the record will be uploaded later.
 
 still-uploading = Uploading record to leaderboard…
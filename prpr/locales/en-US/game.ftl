@@ -9,3 +9,12 @@ speed = Speed Multiplier
 ex-time-out-of-range = Make sure time is within bounds.
 ex-invalid-format = Invalid format.
 ex-time-set = Time changed.
+ex-loop = Loop { $loops }: { $last }
+ex-loop-stats = { $loops } loops, last { $last }, best { $best }
+speed-ramp = Speed Ramp
+
+replay-chart-mismatch = Replay was recorded on a different version of this chart.
+replay-result-mismatch = Replay result differs from the recorded one.
+
+res-pack-reloaded = Resource pack reloaded
+res-pack-reload-failed = Failed to reload resource pack
@@ -3,6 +3,9 @@
 unknown-note-type = Unknown note type: { $type }.
 json-parse-failed = Failed to parse chart JSON.
 judge-line-location = In judge line #{ $jlid }.
+format-unknown = No parser is registered for chart format { $format }.
+format-undetected = Cannot detect the format of the chart.
+format-parse-failed = Failed to parse chart as { $format }.
 
 # rpe
 type-events-parse-failed = Failed to parse { $type } events.
@@ -41,5 +44,14 @@ line-location = On line #{ $lid }.
 # extra
 shader-load-failed = Cannot load shader from { $path }.
 shader-not-found = Cannot find preset shader { $shader }.
+texture-load-failed = Cannot load texture from { $path }.
 effect-location = In effect #{ $id }.
 video-load-failed = Failed to read video from { $path }.
+
+# import
+import-mapping-invalid = Invalid import.yml.
+import-line-out-of-range = Note on line #{ $line }, but only { $count } lines are mapped.
+malody-unsupported-mode = Unsupported Malody mode { $mode }, only key and slide charts can be imported.
+malody-no-bpm = The Malody chart has no BPM.
+note-list-no-time = The header of the note list has no time column.
+note-list-invalid-value = Invalid { $key }: { $value }.
@@ -5,4 +5,8 @@ input-hint = Input text.
 
 read-file-failed = Failed to read file.
 pasted = Pasted from clipboard.
-audio-backend-init-failed = Failed to initialize audio backend, running in silent mode.
\ No newline at end of file
+audio-backend-init-failed = Failed to initialize audio backend, running in silent mode.
+
+render-progress = Rendering video… { $progress }%
+render-done = Video saved to { $path }
+render-failed = Failed to render video
@@ -7,11 +7,15 @@ max-combo = 最高连击
 rks-delta = RKS变化
 accuracy = 准度
 error = 误差
+offset = 偏移
+early = 过早
+late = 过晚
 
 uploading = 成绩上传中
 uploaded = 成绩上传成功
 upload-failed = 成绩上传失败
 upload-cancel = 取消上传
 upload-retry = 重试
+upload-queued = 网络不可用，成绩将在稍后上传
 
 still-uploading = 尚在上传成绩
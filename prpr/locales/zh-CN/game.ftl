@@ -9,3 +9,12 @@ speed = 速度
 ex-time-out-of-range = 时间不在范围内
 ex-invalid-format = 格式有误
 ex-time-set = 设置成功
+ex-loop = 第 { $loops } 遍：{ $last }
+ex-loop-stats = 已练习 { $loops } 遍，上次 { $last }，最佳 { $best }
+speed-ramp = 每遍加速
+
+replay-chart-mismatch = 回放录制时的谱面与当前谱面不一致
+replay-result-mismatch = 回放结果与录制时不一致
+
+res-pack-reloaded = 资源包已重新加载
+res-pack-reload-failed = 重新加载资源包失败
@@ -3,6 +3,9 @@
 unknown-note-type = 未知音符类型: { $type }
 json-parse-failed = JSON 解析失败
 judge-line-location = #{ $jlid } 判定线中
+format-unknown = 没有支持 { $format } 格式谱面的解析器
+format-undetected = 无法识别谱面格式
+format-parse-failed = 以 { $format } 格式解析谱面失败
 
 # rpe
 type-events-parse-failed = { $type } 事件解析失败
@@ -41,5 +44,14 @@ line-location = #{ $lid } 行中
 # extra
 shader-load-failed = 无法从 { $path } 中加载 shader
 shader-not-found = 未找到预置 shader { $shader }
+texture-load-failed = 无法从 { $path } 中加载纹理
 effect-location = #{ $id } 号 effect 中
 video-load-failed = 从 { $path } 中加载视频失败
+
+# import
+import-mapping-invalid = import.yml 无效
+import-line-out-of-range = 音符位于 #{ $line } 号判定线，但只映射了 { $count } 条判定线
+malody-unsupported-mode = 不支持的 Malody 模式 { $mode }，只能导入 Key 和 Slide 谱面
+malody-no-bpm = Malody 谱面没有 BPM
+note-list-no-time = 音符列表的表头缺少 time 列
+note-list-invalid-value = 无效的 { $key }: { $value }
@@ -5,4 +5,8 @@ input-hint = 文字
 
 read-file-failed = 读取文件失败
 pasted = 从剪贴板加载成功
-audio-backend-init-failed = 无法初始化音频后端，将静音运行
\ No newline at end of file
+audio-backend-init-failed = 无法初始化音频后端，将静音运行
+
+render-progress = 正在渲染视频… { $progress }%
+render-done = 视频已保存至 { $path }
+render-failed = 渲染视频失败
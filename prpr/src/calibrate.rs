@@ -0,0 +1,85 @@
+//! Offset calibration from play history
+//!
+//! Every finished play reports the mean of its hit errors (see [PlayResult::mean]). A player who is consistently late
+//! by `x` seconds would be centered if the offset were `x` seconds larger, so each play suggests a target offset; the
+//! suggestion of a history is the average of those targets, weighted by the number of hits.
+
+use crate::{judge::PlayResult, report::Reports};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Plays with fewer hits than this are too noisy to be used
+pub const MIN_HITS: u32 = 20;
+/// Number of hits needed in total before anything is suggested
+pub const MIN_TOTAL_HITS: u32 = 100;
+/// Suggestions closer than this to the current offset are ignored
+pub const THRESHOLD: f32 = 0.005;
+const MAX_SAMPLES: usize = 10;
+
+pub static REPORTED: Reports<OffsetSample> = Reports::new();
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct OffsetSample {
+    /// Offset the play was played with, in seconds
+    pub offset: f32,
+    /// Mean hit error in chart time, in seconds
+    pub mean: f32,
+    pub hits: u32,
+}
+
+impl OffsetSample {
+    /// Returns `None` if the play does not have enough hits
+    pub fn from_result(result: &PlayResult, offset: f32, speed: f32) -> Option<Self> {
+        let hits = result.hit_errors.len() as u32;
+        if hits < MIN_HITS {
+            return None;
+        }
+        Some(Self {
+            offset,
+            mean: result.mean * speed,
+            hits,
+        })
+    }
+
+    /// The offset that would have centered the hits of this play
+    pub fn target(&self) -> f32 {
+        self.offset + self.mean
+    }
+}
+
+/// Samples of the most recent plays
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct OffsetHistory {
+    samples: VecDeque<OffsetSample>,
+}
+
+impl OffsetHistory {
+    pub fn push(&mut self, sample: OffsetSample) {
+        if self.samples.len() == MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Suggested offset rounded to milliseconds, or `None` if there is not enough data or `current` is already close enough
+    pub fn suggest(&self, current: f32) -> Option<f32> {
+        let hits = self.samples.iter().map(|it| it.hits).sum::<u32>();
+        if hits < MIN_TOTAL_HITS {
+            return None;
+        }
+        let target = self.samples.iter().map(|it| it.target() * it.hits as f32).sum::<f32>() / hits as f32;
+        let target = (target * 1000.).round() / 1000.;
+        if (target - current).abs() < THRESHOLD {
+            return None;
+        }
+        Some(target)
+    }
+}
@@ -14,10 +14,20 @@ bitflags! {
         const AUTOPLAY = 1;
         const FLIP_X = 2;
         const FADE_OUT = 4;
-        // const STRICT_JUDGE = 8;
+        const STRICT_JUDGE = 8;
+        const FADE_IN = 16;
+        const HIDE_HOLD_BODY = 32;
+        const FLIP_Y = 64;
+        const RANDOM_SPEED = 128;
+        const SUDDEN_DEATH = 256;
     }
 }
 
+impl Mods {
+    /// Mods that change the score a play can get, plays with any of them set no records and are not uploaded
+    pub const UNRANKED: Mods = Mods::FADE_IN.union(Mods::HIDE_HOLD_BODY).union(Mods::FLIP_Y).union(Mods::RANDOM_SPEED);
+}
+
 #[derive(Clone, Deserialize, Serialize)]
 #[serde(default)]
 #[serde(rename_all = "camelCase")]
@@ -27,6 +37,8 @@ pub struct Config {
     pub aggressive: bool,
     pub aspect_ratio: Option<f32>,
     pub audio_buffer_size: Option<u32>,
+    /// Apply offset suggestions from play history without asking
+    pub auto_offset: bool,
     pub chart_debug: bool,
     pub disable_effect: bool,
     pub double_click_to_pause: bool,
@@ -46,6 +58,8 @@ pub struct Config {
     pub player_rks: f32,
     pub preferred_sample_rate: u32,
     pub res_pack_path: Option<String>,
+    /// Reload the resource pack when its files change, for developing resource packs
+    pub res_pack_watch: bool,
     pub sample_count: u32,
     pub show_acc: bool,
     pub show_remaining_acc: bool,
@@ -66,6 +80,7 @@ impl Default for Config {
             aggressive: true,
             aspect_ratio: None,
             audio_buffer_size: None,
+            auto_offset: false,
             chart_debug: false,
             disable_effect: false,
             double_click_to_pause: true,
@@ -85,6 +100,7 @@ impl Default for Config {
             player_rks: 15.,
             preferred_sample_rate: 44100,
             res_pack_path: None,
+            res_pack_watch: false,
             sample_count: 1,
             show_acc: false,
             show_remaining_acc: false,
@@ -120,4 +136,9 @@ impl Config {
     pub fn flip_x(&self) -> bool {
         self.has_mod(Mods::FLIP_X)
     }
+
+    #[inline]
+    pub fn flip_y(&self) -> bool {
+        self.has_mod(Mods::FLIP_Y)
+    }
 }
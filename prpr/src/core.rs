@@ -28,7 +28,7 @@ mod chart;
 pub use chart::{Chart, ChartExtra, ChartSettings, HitSoundMap};
 
 mod effect;
-pub use effect::{Effect, Uniform};
+pub use effect::{declared_uniforms, Effect, ShaderSource, Uniform};
 
 mod line;
 pub use line::{GifFrames, JudgeLine, JudgeLineCache, JudgeLineKind, UIElement};
@@ -44,7 +44,7 @@ mod render;
 pub use render::{copy_fbo, internal_id, MSRenderTarget};
 
 mod resource;
-pub use resource::{NoteStyle, ParticleEmitter, ResPackInfo, Resource, ResourcePack, BUFFER_SIZE, DPI_VALUE};
+pub use resource::{NoteStyle, ParticleEmitter, ResPackInfo, ResPackIssue, ResPackWatcher, Resource, ResourcePack, BUFFER_SIZE, DPI_VALUE};
 
 mod smooth;
 pub use smooth::Smooth;
@@ -79,7 +79,7 @@ pub fn init_assets() {
     set_pc_assets_folder("assets");
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, serde::Serialize)]
 /// `(i, n, d)`: `i + n / d`
 pub struct Triple(i32, u32, u32);
 impl Default for Triple {
@@ -92,6 +92,23 @@ impl Triple {
     pub fn beats(&self) -> f32 {
         self.0 as f32 + self.1 as f32 / self.2 as f32
     }
+
+    /// Approximate the given beats, preferring small denominators
+    pub fn from_beats(beats: f32) -> Self {
+        const MAX_DENOMINATOR: u32 = 128;
+        const FALLBACK_DENOMINATOR: u32 = 100000;
+        let int = beats.floor();
+        let frac = beats - int;
+        let (n, d) = (1..=MAX_DENOMINATOR)
+            .map(|d| ((frac * d as f32).round() as u32, d))
+            .find(|(n, d)| (*n as f32 / *d as f32 - frac).abs() < 1e-4)
+            .unwrap_or_else(|| ((frac * FALLBACK_DENOMINATOR as f32).round() as u32, FALLBACK_DENOMINATOR));
+        if n == d {
+            Self(int as i32 + 1, 0, 1)
+        } else {
+            Self(int as i32, n, d)
+        }
+    }
 }
 
 #[derive(Default)] // the default is a dummy
@@ -123,6 +140,15 @@ impl BpmList {
         BpmList { elements, cursor: 0 }
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Get the (beats, bpm) pairs this list was created from
+    pub fn ranges(&self) -> impl Iterator<Item = (f32, f32)> + '_ {
+        self.elements.iter().map(|(beats, _, bpm)| (*beats, *bpm))
+    }
+
     /// Get the time in seconds for a given beats
     pub fn time_beats(&mut self, beats: f32) -> f32 {
         while let Some(kf) = self.elements.get(self.cursor + 1) {
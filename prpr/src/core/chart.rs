@@ -1,12 +1,13 @@
 prpr_l10n::tl_file!("parser");
 
-use super::{BpmList, Effect, JudgeLine, JudgeLineKind, Matrix, Resource, UIElement, Vector};
+use super::{BpmList, Effect, JudgeLine, JudgeLineCache, JudgeLineKind, Matrix, Resource, UIElement, Vector};
 use crate::{core::Object, fs::FileSystem, judge::JudgeStatus, scene::show_error, ui::Ui};
+use ::rand::{rngs::StdRng, Rng, SeedableRng};
 use anyhow::{Context, Result};
 use macroquad::prelude::*;
 use nalgebra::Rotation2;
 use sasa::AudioClip;
-use std::{cell::RefCell, collections::HashMap};
+use std::{cell::RefCell, collections::HashMap, ops::Range};
 
 #[derive(Default)]
 pub struct ChartExtra {
@@ -123,6 +124,33 @@ impl Chart {
         }
     }
 
+    /// Scale the speed of every note by a random factor, used by [Mods::RANDOM_SPEED](crate::config::Mods::RANDOM_SPEED)
+    ///
+    /// Must be called before creating the [Judge](crate::judge::Judge), since notes get reordered.
+    pub fn randomize_speed(&mut self, seed: u64) {
+        // a few distinct factors so that notes can still be grouped by speed while rendering
+        const FACTORS: [f32; 5] = [0.8, 0.9, 1., 1.1, 1.2];
+        let mut rng = StdRng::seed_from_u64(seed);
+        for line in &mut self.lines {
+            for note in &mut line.notes {
+                note.speed *= FACTORS[rng.gen_range(0..FACTORS.len())];
+            }
+            line.cache = JudgeLineCache::new(&mut line.notes);
+        }
+    }
+
+    /// Reset judge status of notes starting within `range` (in chart time), leaving other notes as they are
+    pub fn reset_range(&mut self, range: Range<f32>) {
+        self.lines
+            .iter_mut()
+            .flat_map(|it| it.notes.iter_mut())
+            .filter(|note| range.contains(&note.time))
+            .for_each(|note| note.judge = JudgeStatus::NotJudged);
+        for line in &mut self.lines {
+            line.cache.reset(&mut line.notes);
+        }
+    }
+
     pub fn update(&mut self, res: &mut Resource) {
         for line in &mut self.lines {
             line.object.set_time(res.time);
@@ -148,7 +176,8 @@ impl Chart {
         for video in &self.extra.videos {
             video.render(res.time, res.aspect_ratio);
         }
-        res.apply_model_of(&Matrix::identity().append_nonuniform_scaling(&Vector::new(if res.config.flip_x() { -1. } else { 1. }, -1.)), |res| {
+        let scale = Vector::new(if res.config.flip_x() { -1. } else { 1. }, if res.config.flip_y() { 1. } else { -1. });
+        res.apply_model_of(&Matrix::identity().append_nonuniform_scaling(&scale), |res| {
             let mut guard = self.bpm_list.borrow_mut();
             for id in &self.order {
                 self.lines[*id].render(ui, res, &self.lines, &mut guard, &self.settings, *id);
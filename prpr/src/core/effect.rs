@@ -1,12 +1,16 @@
 use super::{Anim, Resource, Tweenable};
-use crate::ext::{get_viewport, screen_aspect};
-use anyhow::{anyhow, bail, Result};
+use crate::ext::{get_viewport, screen_aspect, SafeTexture};
+use anyhow::{anyhow, bail, Context, Result};
 use macroquad::prelude::*;
 use miniquad::UniformType;
 use once_cell::sync::Lazy;
 use phf::phf_map;
-use regex::Regex;
-use std::{collections::HashSet, ops::Range};
+use regex::{Captures, Regex};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
+use tracing::warn;
 
 static SHADERS: phf::Map<&'static str, &'static str> = phf_map! {
     "chromatic" => include_str!("shaders/chromatic.glsl"),
@@ -69,22 +73,73 @@ impl<T: UniformValue + Tweenable> Uniform for (String, Anim<T>) {
     }
 }
 
-pub struct Effect {
-    time_range: Range<f32>,
-    t: f32,
-    material: Material,
-    defaults: Vec<Box<dyn Uniform>>,
-    uniforms: Vec<Box<dyn Uniform>>,
-    pub global: bool,
+/// GLSL type name of a uniform type
+fn type_name(ty: UniformType) -> &'static str {
+    match ty {
+        UniformType::Float1 => "float",
+        UniformType::Float2 => "vec2",
+        UniformType::Float3 => "vec3",
+        UniformType::Float4 => "vec4",
+        UniformType::Int1 => "int",
+        UniformType::Int2 => "ivec2",
+        UniformType::Int3 => "ivec3",
+        UniformType::Int4 => "ivec4",
+        UniformType::Mat4 => "mat4",
+    }
 }
 
-impl Effect {
-    pub fn get_preset(name: &str) -> Option<&'static str> {
-        SHADERS.get(name).copied()
+/// Uniforms declared by a fragment shader, mapped to their GLSL type names
+pub fn declared_uniforms(shader: &str) -> HashMap<String, String> {
+    static DECL_REGEX: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"\buniform\s+(?:(?:lowp|mediump|highp)\s+)?(\w+)\s+(\w+)\s*(?:\[[^\]]*\])?\s*;").unwrap());
+    DECL_REGEX
+        .captures_iter(shader)
+        .map(|caps| (caps[2].to_owned(), caps[1].to_owned()))
+        .collect()
+}
+
+/// Fragment shader of an effect pass
+pub struct ShaderSource<'a> {
+    /// File name or preset name, used in error messages
+    pub name: &'a str,
+    pub code: &'a str,
+}
+
+impl ShaderSource<'_> {
+    /// Rewrites the `0:line(column)` and `0:line:column` locations reported by the driver into `name:line:column`,
+    /// quoting the offending lines
+    pub fn map_compile_error(&self, message: &str) -> String {
+        static LOC_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b0:(\d+)(?:\((\d+)\)|:(\d+))?").unwrap());
+        let lines: Vec<&str> = self.code.lines().collect();
+        let mut quoted = Vec::new();
+        let message = LOC_REGEX.replace_all(message, |caps: &Captures| {
+            let line: usize = caps[1].parse().unwrap_or_default();
+            if (1..=lines.len()).contains(&line) && !quoted.contains(&line) {
+                quoted.push(line);
+            }
+            match caps.get(2).or_else(|| caps.get(3)) {
+                Some(column) => format!("{}:{line}:{}", self.name, column.as_str()),
+                None => format!("{}:{line}", self.name),
+            }
+        });
+        let mut res = format!("failed to compile shader {}\n{}", self.name, message.trim());
+        quoted.sort_unstable();
+        for line in quoted {
+            res += &format!("\n{line:>4} | {}", lines[line - 1]);
+        }
+        res
     }
+}
 
-    pub fn new(time_range: Range<f32>, shader: &str, uniforms: Vec<Box<dyn Uniform>>, global: bool) -> Result<Self> {
+struct Pass {
+    material: Material,
+    defaults: Vec<Box<dyn Uniform>>,
+}
+
+impl Pass {
+    fn new(source: &ShaderSource, uniforms: &[Box<dyn Uniform>], textures: &[(String, SafeTexture)]) -> Result<Self> {
         static DEF_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"uniform\s+(\w+)\s+(\w+);\s+//\s+%([^%]+)%").unwrap());
+        let shader = source.code;
         let defaults = DEF_REGEX
             .captures_iter(shader)
             .map(|caps| -> Result<Box<dyn Uniform>> {
@@ -107,7 +162,8 @@ impl Effect {
                     _ => bail!("Unknown type: {type_name}"),
                 })
             })
-            .collect::<Result<Vec<Box<dyn Uniform>>>>()?;
+            .collect::<Result<Vec<Box<dyn Uniform>>>>()
+            .with_context(|| format!("invalid default value in shader {}", source.name))?;
         let mut ocurred_uniforms = HashSet::new();
         let mut new_uniforms = Vec::new();
         let mut add_uniform = |(name, its_type): (String, UniformType)| {
@@ -121,23 +177,102 @@ impl Effect {
         add_uniform(("time".to_owned(), UniformType::Float1));
         add_uniform(("screenSize".to_owned(), UniformType::Float2));
         add_uniform(("UVScale".to_owned(), UniformType::Float2));
-        for u in &uniforms {
+        for u in uniforms {
             add_uniform(u.uniform_pair());
         }
+        let material = load_material(
+            VERTEX_SHADER,
+            shader,
+            MaterialParams {
+                uniforms: new_uniforms,
+                textures: std::iter::once("screenTexture".to_owned())
+                    .chain(textures.iter().map(|it| it.0.clone()))
+                    .collect(),
+                ..Default::default()
+            },
+        )
+        .map_err(|err| anyhow!(source.map_compile_error(&err.to_string())))?;
+        Ok(Self { material, defaults })
+    }
+}
+
+pub struct Effect {
+    time_range: Range<f32>,
+    t: f32,
+    passes: Vec<Pass>,
+    uniforms: Vec<Box<dyn Uniform>>,
+    /// Sampler uniforms, bound in every pass
+    textures: Vec<(String, SafeTexture)>,
+    pub global: bool,
+}
+
+impl Effect {
+    pub fn get_preset(name: &str) -> Option<&'static str> {
+        SHADERS.get(name).copied()
+    }
+
+    pub fn new(time_range: Range<f32>, shader: &str, uniforms: Vec<Box<dyn Uniform>>, global: bool) -> Result<Self> {
+        Self::with_passes(
+            time_range,
+            &[ShaderSource {
+                name: "<shader>",
+                code: shader,
+            }],
+            uniforms,
+            Vec::new(),
+            global,
+        )
+    }
+
+    /// Creates an effect rendering `sources` one after another, each pass reading the output of the previous one
+    ///
+    /// Uniforms and textures declared by one of the passes must have a matching type. Those declared by none of them are
+    /// skipped with a warning, since older charts often set uniforms their shaders don't use.
+    pub fn with_passes(
+        time_range: Range<f32>,
+        sources: &[ShaderSource],
+        uniforms: Vec<Box<dyn Uniform>>,
+        textures: Vec<(String, SafeTexture)>,
+        global: bool,
+    ) -> Result<Self> {
+        if sources.is_empty() {
+            bail!("effect has no shader");
+        }
+        let declared: Vec<_> = sources.iter().map(|it| declared_uniforms(it.code)).collect();
+        let find = |name: &str| declared.iter().find_map(|it| it.get(name));
+        let check = |name: &str, expected: &str| match find(name) {
+            None => {
+                warn!("uniform `{name}` is not declared by the shader, skipping");
+                Ok(false)
+            }
+            Some(ty) if ty != expected => bail!("uniform `{name}` is declared as {ty} but given a {expected}"),
+            _ => Ok(true),
+        };
+        let mut kept_uniforms = Vec::new();
+        for uniform in uniforms {
+            let (name, ty) = uniform.uniform_pair();
+            if check(&name, type_name(ty))? {
+                kept_uniforms.push(uniform);
+            }
+        }
+        let uniforms = kept_uniforms;
+        let mut kept_textures = Vec::new();
+        for texture in textures {
+            if check(&texture.0, "sampler2D")? {
+                kept_textures.push(texture);
+            }
+        }
+        let textures = kept_textures;
+        let passes = sources
+            .iter()
+            .map(|source| Pass::new(source, &uniforms, &textures))
+            .collect::<Result<_>>()?;
         Ok(Self {
             time_range,
             t: f32::NEG_INFINITY,
-            defaults,
-            material: load_material(
-                VERTEX_SHADER,
-                shader,
-                MaterialParams {
-                    uniforms: new_uniforms,
-                    textures: vec!["screenTexture".to_owned()],
-                    ..Default::default()
-                },
-            )?,
+            passes,
             uniforms,
+            textures,
             global,
         })
     }
@@ -156,37 +291,45 @@ impl Effect {
         if !self.time_range.contains(&self.t) {
             return;
         }
-        let mut gl = unsafe { get_internal_gl() };
-        gl.flush();
+        for pass in &self.passes {
+            let material = pass.material;
+            let mut gl = unsafe { get_internal_gl() };
+            gl.flush();
 
-        for def in &self.defaults {
-            def.apply(&self.material);
-        }
-        for uniform in &self.uniforms {
-            uniform.apply(&self.material);
+            for def in &pass.defaults {
+                def.apply(&material);
+            }
+            for uniform in &self.uniforms {
+                uniform.apply(&material);
+            }
+            for (name, texture) in &self.textures {
+                material.set_texture(name, **texture);
+            }
+            material.set_uniform("time", self.t);
+            let target = res.chart_target.as_mut().unwrap();
+            target.swap();
+            let tex = target.old().texture;
+            material.set_texture("screenTexture", tex);
+            let screen_dim = vec2(tex.width(), tex.height());
+            material.set_uniform("screenSize", screen_dim);
+            gl.quad_gl.render_pass(Some(target.output().render_pass));
+
+            let vp = get_viewport();
+            material.set_uniform("UVScale", vec2(vp.2 as _, vp.3 as _) / screen_dim);
+
+            gl_use_material(material);
+            let top = 1. / if self.global { screen_aspect() } else { res.aspect_ratio };
+            draw_rectangle(-1., -top, 2., top * 2., WHITE);
+            gl_use_default_material();
         }
-        self.material.set_uniform("time", self.t);
-        let target = res.chart_target.as_mut().unwrap();
-        target.swap();
-        let tex = target.old().texture;
-        self.material.set_texture("screenTexture", tex);
-        let screen_dim = vec2(tex.width(), tex.height());
-        self.material.set_uniform("screenSize", screen_dim);
-        gl.quad_gl.render_pass(Some(target.output().render_pass));
-
-        let vp = get_viewport();
-        self.material.set_uniform("UVScale", vec2(vp.2 as _, vp.3 as _) / screen_dim);
-
-        gl_use_material(self.material);
-        let top = 1. / if self.global { screen_aspect() } else { res.aspect_ratio };
-        draw_rectangle(-1., -top, 2., top * 2., WHITE);
-        gl_use_default_material();
     }
 }
 
 impl Drop for Effect {
     fn drop(&mut self) {
-        self.material.delete();
+        for pass in &self.passes {
+            pass.material.delete();
+        }
     }
 }
 
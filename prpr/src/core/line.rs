@@ -8,10 +8,13 @@ use crate::{
 use macroquad::prelude::*;
 use miniquad::{RenderPass, Texture, TextureParams, TextureWrap};
 use nalgebra::Rotation2;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 
-#[derive(Clone, Copy, Deserialize)]
+/// Notes are only visible within this time before being hit with [Mods::FADE_IN]
+const FADE_IN_TIME: f32 = 0.6;
+
+#[derive(Clone, Copy, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 #[repr(u8)]
 pub enum UIElement {
@@ -209,17 +212,28 @@ impl JudgeLine {
     }
 
     pub fn fetch_pos(&self, res: &Resource, lines: &[JudgeLine]) -> Vector {
+        self.fetch_pos_with(res.aspect_ratio, lines)
+    }
+
+    fn fetch_pos_with(&self, aspect_ratio: f32, lines: &[JudgeLine]) -> Vector {
+        let mut tr = self.object.translation.now();
+        tr.y /= aspect_ratio;
         if let Some(parent) = self.parent {
             let parent = &lines[parent];
-            let mut parent_translation = parent.fetch_pos(res, lines);
-            parent_translation += Rotation2::new(parent.object.rotation.now().to_radians()) * self.object.now_translation(res);
+            let mut parent_translation = parent.fetch_pos_with(aspect_ratio, lines);
+            parent_translation += Rotation2::new(parent.object.rotation.now().to_radians()) * tr;
             return parent_translation;
         }
-        self.object.now_translation(res)
+        tr
     }
 
     pub fn now_transform(&self, res: &Resource, lines: &[JudgeLine]) -> Matrix {
-        self.object.now_rotation().append_translation(&self.fetch_pos(res, lines))
+        self.now_transform_with(res.aspect_ratio, lines)
+    }
+
+    /// Same as [JudgeLine::now_transform], but without a [Resource]
+    pub fn now_transform_with(&self, aspect_ratio: f32, lines: &[JudgeLine]) -> Matrix {
+        self.object.now_rotation().append_translation(&self.fetch_pos_with(aspect_ratio, lines))
     }
 
     pub fn render(&self, ui: &mut Ui, res: &mut Resource, lines: &[JudgeLine], bpm_list: &mut BpmList, settings: &ChartSettings, id: usize) {
@@ -349,12 +363,16 @@ impl JudgeLine {
                 line_height: self.height.now(),
                 appear_before: f32::INFINITY,
                 invisible_time: f32::INFINITY,
+                visible_time: f32::INFINITY,
                 draw_below: self.show_below,
                 incline_sin: self.incline.now_opt().map(|it| it.to_radians().sin()).unwrap_or_default(),
             };
             if res.config.has_mod(Mods::FADE_OUT) {
                 config.invisible_time = LIMIT_BAD;
             }
+            if res.config.has_mod(Mods::FADE_IN) {
+                config.visible_time = FADE_IN_TIME;
+            }
             if alpha < 0.0 {
                 if !settings.pe_alpha_extension {
                     return;
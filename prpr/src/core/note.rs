@@ -1,4 +1,5 @@
 use super::{chart::ChartSettings, BpmList, CtrlObject, JudgeLine, Matrix, Object, Point, Resource};
+use crate::config::Mods;
 pub use crate::{
     judge::{HitSound, JudgeStatus},
     parse::RPE_HEIGHT,
@@ -49,6 +50,7 @@ pub struct RenderConfig<'a> {
     pub line_height: f32,
     pub appear_before: f32,
     pub invisible_time: f32,
+    pub visible_time: f32,
     pub draw_below: bool,
     pub incline_sin: f32,
 }
@@ -196,6 +198,9 @@ impl Note {
         if config.invisible_time.is_finite() && self.time - config.invisible_time < res.time {
             return;
         }
+        if config.visible_time.is_finite() && self.time - config.visible_time > res.time {
+            return;
+        }
         let scale = (if res.config.double_hint && self.multiple_hint {
             res.res_pack.note_style_mh.click.width() / res.res_pack.note_style.click.width()
         } else {
@@ -271,33 +276,35 @@ impl Note {
                     let ratio = style.hold_ratio();
                     // body
                     // TODO (end_height - height) is not always total height
-                    draw_tex(
-                        res,
-                        **(if res.res_pack.info.hold_repeat {
-                            style.hold_body.as_ref().unwrap()
-                        } else {
-                            tex
-                        }),
-                        order,
-                        -scale,
-                        bottom,
-                        color,
-                        DrawTextureParams {
-                            source: Some({
-                                if res.res_pack.info.hold_repeat {
-                                    let hold_body = style.hold_body.as_ref().unwrap();
-                                    let width = hold_body.width();
-                                    let height = hold_body.height();
-                                    Rect::new(0., 0., 1., (top - bottom) / scale / 2. * width / height)
-                                } else {
-                                    style.hold_body_rect()
-                                }
+                    if !res.config.has_mod(Mods::HIDE_HOLD_BODY) {
+                        draw_tex(
+                            res,
+                            **(if res.res_pack.info.hold_repeat {
+                                style.hold_body.as_ref().unwrap()
+                            } else {
+                                tex
                             }),
-                            dest_size: Some(vec2(scale * 2., top - bottom)),
-                            ..Default::default()
-                        },
-                        false,
-                    );
+                            order,
+                            -scale,
+                            bottom,
+                            color,
+                            DrawTextureParams {
+                                source: Some({
+                                    if res.res_pack.info.hold_repeat {
+                                        let hold_body = style.hold_body.as_ref().unwrap();
+                                        let width = hold_body.width();
+                                        let height = hold_body.height();
+                                        Rect::new(0., 0., 1., (top - bottom) / scale / 2. * width / height)
+                                    } else {
+                                        style.hold_body_rect()
+                                    }
+                                }),
+                                dest_size: Some(vec2(scale * 2., top - bottom)),
+                                ..Default::default()
+                            },
+                            false,
+                        );
+                    }
                     // head
                     if res.time < self.time || res.res_pack.info.hold_keep_head {
                         let r = style.hold_head_rect();
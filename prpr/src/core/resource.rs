@@ -4,6 +4,7 @@ use crate::{
     ext::{create_audio_manger, nalgebra_to_glm, SafeTexture},
     fs::FileSystem,
     info::ChartInfo,
+    judge::HitSound,
     particle::{AtlasConfig, ColorCurve, Emitter, EmitterConfig},
 };
 use anyhow::{bail, Context, Result};
@@ -14,12 +15,15 @@ use miniquad::{
 };
 use sasa::{AudioClip, AudioManager, Sfx};
 use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
 use std::{
     cell::RefCell,
     collections::{BTreeMap, HashMap},
+    fmt,
     ops::DerefMut,
-    path::Path,
+    path::{Path, PathBuf},
     sync::atomic::AtomicU32,
+    time::SystemTime,
 };
 
 pub const MAX_SIZE: usize = 64; // needs tweaking
@@ -151,6 +155,73 @@ impl NoteStyle {
     }
 }
 
+/// A problem found in a resource pack by [ResourcePack::validate]
+#[derive(Clone, Debug)]
+pub struct ResPackIssue {
+    pub file: String,
+    pub message: String,
+}
+
+impl ResPackIssue {
+    fn new(file: &str, message: impl Into<String>) -> Self {
+        Self {
+            file: file.to_owned(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ResPackIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.file, self.message)
+    }
+}
+
+const NOTE_TEXTURES: [&str; 8] = [
+    "click.png",
+    "hold.png",
+    "flick.png",
+    "drag.png",
+    "click_mh.png",
+    "hold_mh.png",
+    "flick_mh.png",
+    "drag_mh.png",
+];
+/// Sounds of a resource pack, the default ones are used for those missing
+const SOUNDS: [&str; 4] = ["click", "drag", "flick", "ending"];
+const SOUND_EXTENSIONS: [&str; 3] = ["ogg", "wav", "mp3"];
+
+/// Checks every key of `info.yml` on its own, so that all invalid values are reported instead of the first one
+///
+/// Invalid values are removed, so that the rest can still be checked with the defaults in their place.
+fn check_info_keys(info: &mut Mapping, issues: &mut Vec<ResPackIssue>) {
+    fn check<T: serde::de::DeserializeOwned>(info: &mut Mapping, key: &str, required: bool, issues: &mut Vec<ResPackIssue>) {
+        match info.get(key) {
+            None if required => issues.push(ResPackIssue::new("info.yml", format!("missing {key}"))),
+            None => {}
+            Some(value) => {
+                if let Err(err) = serde_yaml::from_value::<T>(value.clone()) {
+                    issues.push(ResPackIssue::new("info.yml", format!("invalid {key}: {err}")));
+                    info.remove(key);
+                }
+            }
+        }
+    }
+    check::<String>(info, "name", true, issues);
+    check::<String>(info, "author", true, issues);
+    check::<(u32, u32)>(info, "hitFx", true, issues);
+    check::<f32>(info, "hitFxDuration", false, issues);
+    check::<f32>(info, "hitFxScale", false, issues);
+    check::<(u32, u32)>(info, "holdAtlas", true, issues);
+    check::<(u32, u32)>(info, "holdAtlasMH", true, issues);
+    for key in ["hitFxRotate", "hideParticles", "hitFxTinted", "holdKeepHead", "holdRepeat", "holdCompact"] {
+        check::<bool>(info, key, false, issues);
+    }
+    check::<u32>(info, "colorPerfect", false, issues);
+    check::<u32>(info, "colorGood", false, issues);
+    check::<String>(info, "description", false, issues);
+}
+
 pub struct ResourcePack {
     pub info: ResPackInfo,
     pub note_style: NoteStyle,
@@ -264,6 +335,142 @@ impl ResourcePack {
             hit_fx,
         })
     }
+
+    /// Checks a resource pack without loading it, reporting every problem instead of failing on the first one
+    ///
+    /// Needs no graphics context, so it can be used by tools as well.
+    pub async fn validate(fs: &mut dyn FileSystem) -> Vec<ResPackIssue> {
+        let mut issues = Vec::new();
+        let info = match fs.load_file("info.yml").await {
+            Ok(bytes) => serde_yaml::from_slice::<Value>(&bytes).map_err(|err| err.to_string()),
+            Err(_) => Err("missing".to_owned()),
+        };
+        let info = match info {
+            Ok(Value::Mapping(mut info)) => {
+                check_info_keys(&mut info, &mut issues);
+                serde_yaml::from_value::<ResPackInfo>(Value::Mapping(info)).ok()
+            }
+            Ok(_) => {
+                issues.push(ResPackIssue::new("info.yml", "expected a mapping"));
+                None
+            }
+            Err(err) => {
+                issues.push(ResPackIssue::new("info.yml", err));
+                None
+            }
+        };
+        if let Some(info) = &info {
+            if info.hit_fx.0 == 0 || info.hit_fx.1 == 0 {
+                issues.push(ResPackIssue::new("info.yml", "hitFx must have at least one column and one row"));
+            }
+            if !(info.hit_fx_duration.is_finite() && info.hit_fx_duration > 0.) {
+                issues.push(ResPackIssue::new("info.yml", "hitFxDuration must be positive"));
+            }
+            if !(info.hit_fx_scale.is_finite() && info.hit_fx_scale > 0.) {
+                issues.push(ResPackIssue::new("info.yml", "hitFxScale must be positive"));
+            }
+        }
+
+        let mut sizes = HashMap::new();
+        for name in NOTE_TEXTURES.into_iter().chain(["hit_fx.png"]) {
+            let Ok(bytes) = fs.load_file(name).await else {
+                issues.push(ResPackIssue::new(name, "missing"));
+                continue;
+            };
+            match image::load_from_memory(&bytes) {
+                Ok(image) => {
+                    sizes.insert(name, (image.width(), image.height()));
+                }
+                Err(err) => issues.push(ResPackIssue::new(name, format!("invalid image: {err}"))),
+            }
+        }
+        if let Some(info) = &info {
+            for (name, atlas) in [("hold.png", info.hold_atlas), ("hold_mh.png", info.hold_atlas_mh)] {
+                if let Some((_, height)) = sizes.get(name) {
+                    if atlas.0 + atlas.1 >= *height {
+                        issues.push(ResPackIssue::new(
+                            name,
+                            format!("hold atlas ({}, {}) leaves no room for the body in a height of {height}", atlas.0, atlas.1),
+                        ));
+                    }
+                }
+            }
+            if let (Some((width, height)), (columns @ 1.., rows @ 1..)) = (sizes.get("hit_fx.png"), info.hit_fx) {
+                if width % columns != 0 || height % rows != 0 {
+                    issues.push(ResPackIssue::new("hit_fx.png", format!("size {width}x{height} can't be split into {columns}x{rows} frames")));
+                }
+            }
+        }
+
+        for sound in SOUNDS {
+            for ext in SOUND_EXTENSIONS {
+                let name = format!("{sound}.{ext}");
+                let Ok(bytes) = fs.load_file(&name).await else {
+                    continue;
+                };
+                if let Err(err) = AudioClip::new(bytes) {
+                    issues.push(ResPackIssue::new(&name, format!("unsupported audio: {err}")));
+                }
+                break;
+            }
+        }
+        issues
+    }
+}
+
+/// Watches the files of a resource pack directory, for reloading it while it's being made
+pub struct ResPackWatcher {
+    path: PathBuf,
+    stamps: HashMap<PathBuf, SystemTime>,
+    next_check: f64,
+}
+
+impl ResPackWatcher {
+    /// Seconds between two checks
+    const INTERVAL: f64 = 0.5;
+
+    /// Watches `path`, `None` if it is not a directory
+    pub fn new(path: impl Into<PathBuf>) -> Option<Self> {
+        let path = path.into();
+        if !path.is_dir() {
+            return None;
+        }
+        let stamps = Self::scan(&path);
+        Some(Self {
+            path,
+            stamps,
+            next_check: 0.,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn scan(path: &Path) -> HashMap<PathBuf, SystemTime> {
+        let Ok(dir) = std::fs::read_dir(path) else {
+            return HashMap::new();
+        };
+        dir.filter_map(|entry| {
+            let entry = entry.ok()?;
+            Some((entry.path(), entry.metadata().ok()?.modified().ok()?))
+        })
+        .collect()
+    }
+
+    /// Whether any file was added, removed or modified since the last time this returned `true`
+    pub fn changed(&mut self, now: f64) -> bool {
+        if now < self.next_check {
+            return false;
+        }
+        self.next_check = now + Self::INTERVAL;
+        let stamps = Self::scan(&self.path);
+        if stamps == self.stamps {
+            return false;
+        }
+        self.stamps = stamps;
+        true
+    }
 }
 
 pub struct ParticleEmitter {
@@ -404,6 +611,10 @@ pub struct Resource {
     pub sfx_flick: Sfx,
 
     pub extra_sfxs: SfxMap,
+    /// Clips of [Self::extra_sfxs], kept for mixing audio offline
+    pub extra_clips: HashMap<String, AudioClip>,
+    /// Hit sounds played since last taken together with the chart time they were played at, only recorded when set to `Some`
+    pub sfx_log: Option<Vec<(f32, HitSound)>>,
 
     pub chart_target: Option<MSRenderTarget>,
     pub no_effect: bool,
@@ -510,6 +721,8 @@ impl Resource {
             sfx_drag,
             sfx_flick,
             extra_sfxs: SfxMap::new(),
+            extra_clips: HashMap::new(),
+            sfx_log: None,
 
             chart_target: None,
             no_effect,
@@ -520,6 +733,21 @@ impl Resource {
         })
     }
 
+    /// Switches to another resource pack while playing. On failure the current pack is kept as a whole.
+    pub fn set_res_pack(&mut self, res_pack: ResourcePack) -> Result<()> {
+        let buffer_size = Some(BUFFER_SIZE);
+        let sfx_click = self.audio.create_sfx(res_pack.sfx_click.clone(), buffer_size)?;
+        let sfx_drag = self.audio.create_sfx(res_pack.sfx_drag.clone(), buffer_size)?;
+        let sfx_flick = self.audio.create_sfx(res_pack.sfx_flick.clone(), buffer_size)?;
+        self.emitter = ParticleEmitter::new(&res_pack, self.config.note_scale, res_pack.info.hide_particles)?;
+        self.sfx_click = sfx_click;
+        self.sfx_drag = sfx_drag;
+        self.sfx_flick = sfx_flick;
+        self.judge_line_color = res_pack.info.fx_perfect();
+        self.res_pack = res_pack;
+        Ok(())
+    }
+
     pub fn create_sfx(&mut self, clip: AudioClip) -> Result<Sfx> {
         self.audio.create_sfx(clip, Some(BUFFER_SIZE))
     }
@@ -530,7 +758,7 @@ impl Resource {
         }
         let pt = self.world_to_screen(Point::default());
         self.emitter.emit_at(
-            vec2(if self.config.flip_x() { -pt.x } else { pt.x }, -pt.y),
+            vec2(if self.config.flip_x() { -pt.x } else { pt.x }, if self.config.flip_y() { pt.y } else { -pt.y }),
             if self.res_pack.info.hit_fx_rotate { rotation.to_radians() } else { 0. },
             color,
         );
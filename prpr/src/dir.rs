@@ -60,9 +60,14 @@ impl Dir {
         Self::new(self.join(p)?)
     }
 
-    #[inline]
+    /// Creates a new file, replacing any existing one instead of writing into it, since it may be a hard link shared with
+    /// other files
     pub fn create(&self, p: impl AsRef<Path>) -> Result<File> {
-        Ok(File::create(self.join(p)?)?)
+        let path = self.join(p)?;
+        if path.is_file() {
+            std::fs::remove_file(&path)?;
+        }
+        Ok(File::create(path)?)
     }
 
     #[inline]
@@ -0,0 +1,145 @@
+//! Chart exporters
+//!
+//! Converts a parsed [Chart](crate::core::Chart) back into editor formats. Anything the target format can't express
+//! exactly (e.g. chained animations or unsupported easings in PEC) is sampled into linear pieces.
+
+mod pec;
+pub use pec::export_pec;
+
+mod rpe;
+pub use rpe::export_rpe;
+
+use crate::{
+    core::{AnimFloat, BpmList, Chart, ClampedTween, Keyframe, StaticTween, TweenFunction, TweenId, EPS},
+    ext::NotNanExt,
+    parse::RPE_TWEEN_MAP,
+};
+
+/// Number of linear pieces an interval is split into when it has to be sampled
+const BAKE_STEPS: usize = 16;
+
+/// Get the BPM list beats are exported with
+///
+/// PGR charts carry an empty list since their times are already in seconds, 60 BPM makes one beat one second for them.
+fn bpm_list(chart: &Chart) -> BpmList {
+    let r = chart.bpm_list.borrow();
+    if r.is_empty() {
+        BpmList::new(vec![(0., 60.)])
+    } else {
+        BpmList::new(r.ranges().collect())
+    }
+}
+
+/// Find the RPE easing type of a tween, `None` if there isn't one
+fn rpe_easing(tween: TweenId) -> Option<u8> {
+    RPE_TWEEN_MAP.iter().skip(1).position(|it| *it == tween).map(|it| it as u8 + 1)
+}
+
+fn static_tween(tween: &dyn TweenFunction) -> Option<TweenId> {
+    tween.as_any().downcast_ref::<StaticTween>().map(|it| it.0)
+}
+
+/// Get the value of an animation that never changes
+fn fixed_value(anim: &AnimFloat) -> Option<f32> {
+    if anim.next.is_none() && anim.keyframes.len() == 1 {
+        Some(anim.keyframes[0].value)
+    } else {
+        None
+    }
+}
+
+/// A piece of the speed curve of a line, in which the speed changes linearly
+struct SpeedSegment {
+    start_time: f32,
+    end_time: f32,
+    start: f32,
+    end: f32,
+}
+
+/// Recover the speed curve from the height animation of a line
+///
+/// Parsers integrate speed into height, using linear tween for constant speed and clamped quad tween for linearly
+/// changing speed, so this is exact for anything they produce.
+fn speed_segments(height: &AnimFloat) -> Vec<SpeedSegment> {
+    height
+        .keyframes
+        .windows(2)
+        .filter(|it| it[1].time - it[0].time >= EPS)
+        .map(|it| {
+            let (kf, next) = (&it[0], &it[1]);
+            let avg = (next.value - kf.value) / (next.time - kf.time);
+            let (start, end) = match kf.tween.as_any().downcast_ref::<ClampedTween>() {
+                Some(ClampedTween(7 /*quadOut*/, range, _)) => {
+                    let ratio = 1. - range.end;
+                    let start = avg * 2. / (1. + ratio);
+                    (start, start * ratio)
+                }
+                Some(ClampedTween(6 /*quadIn*/, range, _)) => {
+                    let ratio = range.start;
+                    let end = avg * 2. / (1. + ratio);
+                    (end * ratio, end)
+                }
+                _ => (avg, avg),
+            };
+            SpeedSegment {
+                start_time: kf.time,
+                end_time: next.time,
+                start,
+                end,
+            }
+        })
+        .collect()
+}
+
+/// Sample animations into linear pieces
+///
+/// Returns `(time, values)` points, with one value for each animation. Two consecutive points sharing the same time
+/// denote a jump. Points in the middle of constant pieces are omitted.
+fn bake(anims: &[&AnimFloat]) -> Vec<(f32, Vec<f32>)> {
+    fn keyframes(anim: &AnimFloat) -> impl Iterator<Item = &Keyframe<f32>> {
+        std::iter::successors(Some(anim), |it| it.next.as_deref()).flat_map(|it| it.keyframes.iter())
+    }
+    let mut times: Vec<_> = anims.iter().flat_map(|it| keyframes(it).map(|it| it.time.not_nan())).collect();
+    times.sort();
+    times.dedup();
+    let mut anims: Vec<_> = anims.iter().map(|it| (*it).clone()).collect();
+    let mut sample = |time: f32| {
+        anims
+            .iter_mut()
+            .map(|anim| {
+                anim.set_time(time);
+                anim.now()
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut points = Vec::new();
+    for it in times.windows(2) {
+        let (start, end) = (*it[0], *it[1]);
+        for i in 0..BAKE_STEPS {
+            let time = start + (end - start) * i as f32 / BAKE_STEPS as f32;
+            points.push((time, sample(time)));
+        }
+        // the value right before the next keyframe
+        points.push((end, sample(end - ((end - start) / 2.).min(1e-4))));
+    }
+    if let Some(last) = times.last() {
+        points.push((**last, sample(**last)));
+    }
+
+    let same = |a: &[f32], b: &[f32]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < EPS);
+    let mut res: Vec<(f32, Vec<f32>)> = Vec::with_capacity(points.len());
+    for (time, values) in points {
+        if let [.., prev, last] = res.as_slice() {
+            if last.0 == time && same(&last.1, &values) {
+                continue;
+            }
+            if prev.0 < last.0 && last.0 < time && same(&prev.1, &last.1) && same(&last.1, &values) {
+                res.pop();
+            }
+        } else if res.last().is_some_and(|last| last.0 == time && same(&last.1, &values)) {
+            continue;
+        }
+        res.push((time, values));
+    }
+    res
+}
@@ -0,0 +1,215 @@
+use super::{bake, bpm_list, fixed_value, rpe_easing, speed_segments, static_tween, BAKE_STEPS};
+use crate::core::{AnimFloat, BpmList, Chart, JudgeLine, NoteKind, EPS};
+use anyhow::Result;
+use std::fmt::Write;
+
+/// An event in PEC, `tween` is `None` for events setting the value directly
+struct PECEvent<V = f32> {
+    start_time: f32,
+    end_time: f32,
+    value: V,
+    tween: Option<u8>,
+}
+
+impl<V> PECEvent<V> {
+    fn single(time: f32, value: V) -> Self {
+        Self {
+            start_time: time,
+            end_time: time,
+            value,
+            tween: None,
+        }
+    }
+}
+
+/// Convert an animation to PEC events, `None` if it can't be expressed exactly
+fn exact_events(anim: &AnimFloat, linear_only: bool) -> Option<Vec<PECEvent>> {
+    if anim.next.is_some() {
+        return None;
+    }
+    let kfs = &anim.keyframes;
+    let mut res = Vec::new();
+    // whether the value of the current keyframe is reached by the last event
+    let mut covered = false;
+    for (i, kf) in kfs.iter().enumerate() {
+        if !covered {
+            res.push(PECEvent::single(kf.time, kf.value));
+        }
+        covered = false;
+        let Some(next) = kfs.get(i + 1) else {
+            break;
+        };
+        if next.time - kf.time < EPS {
+            continue;
+        }
+        match static_tween(kf.tween.as_ref())? {
+            0 => {}
+            id => {
+                let easing = rpe_easing(id).filter(|it| !linear_only || *it == 1)?;
+                res.push(PECEvent {
+                    start_time: kf.time,
+                    end_time: next.time,
+                    value: next.value,
+                    tween: Some(easing),
+                });
+                covered = true;
+            }
+        }
+    }
+    Some(res)
+}
+
+/// Convert animations to PEC events by sampling them into linear pieces
+fn baked_events(anims: &[&AnimFloat]) -> Vec<PECEvent<Vec<f32>>> {
+    let mut last_time = None;
+    bake(anims)
+        .into_iter()
+        .map(|(time, values)| {
+            let event = match last_time {
+                Some(last) if last < time => PECEvent {
+                    start_time: last,
+                    end_time: time,
+                    value: values,
+                    tween: Some(1),
+                },
+                _ => PECEvent::single(time, values),
+            };
+            last_time = Some(time);
+            event
+        })
+        .collect()
+}
+
+fn events(anim: &AnimFloat, linear_only: bool) -> Vec<PECEvent> {
+    exact_events(anim, linear_only).unwrap_or_else(|| {
+        baked_events(&[anim])
+            .into_iter()
+            .map(|it| PECEvent {
+                start_time: it.start_time,
+                end_time: it.end_time,
+                value: it.value[0],
+                tween: it.tween,
+            })
+            .collect()
+    })
+}
+
+fn move_events(x: &AnimFloat, y: &AnimFloat) -> Vec<PECEvent<(f32, f32)>> {
+    if let (Some(ex), Some(ey)) = (exact_events(x, false), exact_events(y, false)) {
+        if ex.len() == ey.len()
+            && ex
+                .iter()
+                .zip(&ey)
+                .all(|(a, b)| a.start_time == b.start_time && a.end_time == b.end_time && a.tween == b.tween)
+        {
+            return ex
+                .into_iter()
+                .zip(ey)
+                .map(|(a, b)| PECEvent {
+                    start_time: a.start_time,
+                    end_time: a.end_time,
+                    value: (a.value, b.value),
+                    tween: a.tween,
+                })
+                .collect();
+        }
+    }
+    baked_events(&[x, y])
+        .into_iter()
+        .map(|it| PECEvent {
+            start_time: it.start_time,
+            end_time: it.end_time,
+            value: (it.value[0], it.value[1]),
+            tween: it.tween,
+        })
+        .collect()
+}
+
+fn write_line(out: &mut String, r: &mut BpmList, id: usize, line: &JudgeLine) -> std::fmt::Result {
+    let mut beat = |time: f32| r.beat(time);
+
+    let segments = speed_segments(&line.height);
+    if segments.is_empty() {
+        writeln!(out, "cv {id} 0 0")?;
+    }
+    for it in segments {
+        if (it.start - it.end).abs() < EPS {
+            writeln!(out, "cv {id} {} {}", beat(it.start_time), it.start * 5.85)?;
+            continue;
+        }
+        // PEC speed is piecewise constant
+        for i in 0..BAKE_STEPS {
+            let time = it.start_time + (it.end_time - it.start_time) * i as f32 / BAKE_STEPS as f32;
+            let speed = it.start + (it.end - it.start) * (i as f32 + 0.5) / BAKE_STEPS as f32;
+            writeln!(out, "cv {id} {} {}", beat(time), speed * 5.85)?;
+        }
+    }
+
+    let moves = move_events(&line.object.translation.0, &line.object.translation.1);
+    if moves.is_empty() {
+        writeln!(out, "cp {id} 0 1024 700")?;
+    }
+    for it in moves {
+        let (x, y) = ((it.value.0 + 1.) / 2. * 2048., (it.value.1 + 1.) / 2. * 1400.);
+        match it.tween {
+            None => writeln!(out, "cp {id} {} {x} {y}", beat(it.start_time))?,
+            Some(tween) => writeln!(out, "cm {id} {} {} {x} {y} {tween}", beat(it.start_time), beat(it.end_time))?,
+        }
+    }
+
+    let rotates = events(&line.object.rotation, false);
+    if rotates.is_empty() {
+        writeln!(out, "cd {id} 0 0")?;
+    }
+    for it in rotates {
+        match it.tween {
+            None => writeln!(out, "cd {id} {} {}", beat(it.start_time), -it.value)?,
+            Some(tween) => writeln!(out, "cr {id} {} {} {} {tween}", beat(it.start_time), beat(it.end_time), -it.value)?,
+        }
+    }
+
+    let alphas = events(&line.object.alpha, true);
+    if alphas.is_empty() {
+        writeln!(out, "ca {id} 0 255")?;
+    }
+    for it in alphas {
+        // negative alpha values are kept as is, see `ChartSettings::pe_alpha_extension`
+        let alpha = if it.value >= 0. { it.value * 255. } else { it.value };
+        match it.tween {
+            None => writeln!(out, "ca {id} {} {alpha}", beat(it.start_time))?,
+            Some(_) => writeln!(out, "cf {id} {} {} {alpha}", beat(it.start_time), beat(it.end_time))?,
+        }
+    }
+
+    for note in &line.notes {
+        let above = if note.above { 1 } else { 2 };
+        let x = fixed_value(&note.object.translation.0).unwrap_or_default() * 1024.;
+        let time = beat(note.time);
+        match note.kind {
+            NoteKind::Click => writeln!(out, "n1 {id} {time} {x} {above} {}", note.fake as u8)?,
+            NoteKind::Hold { end_time, .. } => writeln!(out, "n2 {id} {time} {} {x} {above} {}", beat(end_time), note.fake as u8)?,
+            NoteKind::Flick => writeln!(out, "n3 {id} {time} {x} {above} {}", note.fake as u8)?,
+            NoteKind::Drag => writeln!(out, "n4 {id} {time} {x} {above} {}", note.fake as u8)?,
+        }
+        writeln!(out, "# {}", note.speed)?;
+        writeln!(out, "& {}", fixed_value(&note.object.scale.0).unwrap_or(1.))?;
+    }
+    Ok(())
+}
+
+/// Export a chart to PEC
+///
+/// PEC only supports plain lines with notes and basic events. Textures, colors, note offsets and alpha, attachments,
+/// parents and control events are dropped, and animations PEC can't express are sampled.
+pub fn export_pec(chart: &Chart) -> Result<String> {
+    let mut r = bpm_list(chart);
+    let mut out = String::new();
+    writeln!(out, "{}", (chart.offset + 0.15) * 1000.)?;
+    for (beats, bpm) in r.ranges() {
+        writeln!(out, "bp {beats} {bpm}")?;
+    }
+    for (id, line) in chart.lines.iter().enumerate() {
+        write_line(&mut out, &mut r, id, line)?;
+    }
+    Ok(out)
+}
@@ -0,0 +1,421 @@
+use super::{bpm_list, fixed_value, rpe_easing, speed_segments, static_tween, BAKE_STEPS};
+use crate::{
+    core::{
+        Anim, AnimFloat, BezierTween, BpmList, Chart, ClampedTween, Color, JudgeLine, JudgeLineKind, Note, NoteKind, Triple, Tweenable, UIElement,
+        EPS,
+    },
+    info::ChartInfo,
+    judge::HitSound,
+    parse::{RPE_HEIGHT, RPE_WIDTH, SPEED_RATIO},
+};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+
+const RPE_VERSION: u32 = 150;
+const ALWAYS_VISIBLE: f32 = 999999.;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RPEBpmItem {
+    bpm: f32,
+    start_time: Triple,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RPEEvent<T = f32> {
+    easing_left: f32,
+    easing_right: f32,
+    bezier: u8,
+    bezier_points: [f32; 4],
+    easing_type: u8,
+    start: T,
+    end: T,
+    start_time: Triple,
+    end_time: Triple,
+    linkgroup: i32,
+}
+
+impl<T> RPEEvent<T> {
+    fn new(r: &mut BpmList, start_time: f32, end_time: f32, start: T, end: T, easing_type: u8) -> Self {
+        Self {
+            easing_left: 0.,
+            easing_right: 1.,
+            bezier: 0,
+            bezier_points: [0.; 4],
+            easing_type,
+            start,
+            end,
+            start_time: Triple::from_beats(r.beat(start_time)),
+            end_time: Triple::from_beats(r.beat(end_time)),
+            linkgroup: 0,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct RPECtrlEvent {
+    easing: u8,
+    x: f32,
+    #[serde(flatten)]
+    value: HashMap<&'static str, f32>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RPESpeedEvent {
+    start_time: Triple,
+    end_time: Triple,
+    start: f32,
+    end: f32,
+    linkgroup: i32,
+}
+
+#[derive(Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RPEEventLayer {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alpha_events: Option<Vec<RPEEvent>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    move_x_events: Option<Vec<RPEEvent>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    move_y_events: Option<Vec<RPEEvent>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rotate_events: Option<Vec<RPEEvent>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    speed_events: Option<Vec<RPESpeedEvent>>,
+}
+
+#[derive(Serialize)]
+struct RGBColor(u8, u8, u8);
+impl RGBColor {
+    fn new(color: &Color) -> Self {
+        let int = |it: f32| (it * 255.).round() as u8;
+        Self(int(color.r), int(color.g), int(color.b))
+    }
+}
+
+#[derive(Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RPEExtendedEvents {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color_events: Option<Vec<RPEEvent<RGBColor>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text_events: Option<Vec<RPEEvent<String>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scale_x_events: Option<Vec<RPEEvent>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scale_y_events: Option<Vec<RPEEvent>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    incline_events: Option<Vec<RPEEvent>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    paint_events: Option<Vec<RPEEvent>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RPENote {
+    #[serde(rename = "type")]
+    kind: u8,
+    above: u8,
+    start_time: Triple,
+    end_time: Triple,
+    position_x: f32,
+    y_offset: f32,
+    alpha: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hitsound: Option<String>,
+    size: f32,
+    speed: f32,
+    is_fake: u8,
+    visible_time: f32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RPEJudgeLine {
+    #[serde(rename = "Group")]
+    group: i32,
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "Texture")]
+    texture: String,
+    #[serde(rename = "father")]
+    parent: isize,
+    event_layers: Vec<RPEEventLayer>,
+    extended: RPEExtendedEvents,
+    notes: Vec<RPENote>,
+    num_of_notes: usize,
+    is_cover: u8,
+    z_order: i32,
+    #[serde(rename = "attachUI", skip_serializing_if = "Option::is_none")]
+    attach_ui: Option<UIElement>,
+    bpmfactor: f32,
+
+    pos_control: Vec<RPECtrlEvent>,
+    size_control: Vec<RPECtrlEvent>,
+    alpha_control: Vec<RPECtrlEvent>,
+    y_control: Vec<RPECtrlEvent>,
+}
+
+#[derive(Serialize)]
+struct RPEMetadata {
+    #[serde(rename = "RPEVersion")]
+    rpe_version: u32,
+    offset: i32,
+    name: String,
+    id: String,
+    song: String,
+    background: String,
+    charter: String,
+    composer: String,
+    illustration: String,
+    level: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RPEChart {
+    #[serde(rename = "META")]
+    meta: RPEMetadata,
+    #[serde(rename = "BPMList")]
+    bpm_list: Vec<RPEBpmItem>,
+    judge_line_group: Vec<&'static str>,
+    judge_line_list: Vec<RPEJudgeLine>,
+}
+
+fn non_empty<T>(v: Vec<T>) -> Option<Vec<T>> {
+    if v.is_empty() {
+        None
+    } else {
+        Some(v)
+    }
+}
+
+fn events<T: Tweenable, V>(r: &mut BpmList, anim: &Anim<T>, f: impl Fn(&T) -> V) -> Vec<RPEEvent<V>> {
+    let kfs = &anim.keyframes;
+    if let [kf] = &kfs[..] {
+        return vec![RPEEvent::new(r, kf.time, kf.time, f(&kf.value), f(&kf.value), 1)];
+    }
+    let mut res = Vec::new();
+    // whether the value of the last keyframe is not reached by any event
+    let mut pending = false;
+    for it in kfs.windows(2) {
+        let (kf, next) = (&it[0], &it[1]);
+        if next.time - kf.time < EPS {
+            continue;
+        }
+        pending = false;
+        let tween = kf.tween.as_any();
+        let easing = if let Some(id) = static_tween(kf.tween.as_ref()) {
+            match id {
+                0 => {
+                    res.push(RPEEvent::new(r, kf.time, next.time, f(&kf.value), f(&kf.value), 1));
+                    pending = true;
+                    continue;
+                }
+                1 => {
+                    res.push(RPEEvent::new(r, kf.time, next.time, f(&next.value), f(&next.value), 1));
+                    continue;
+                }
+                _ => rpe_easing(id).map(|easing| (easing, 0.0..1.0)),
+            }
+        } else if let Some(ClampedTween(id, range, _)) = tween.downcast_ref::<ClampedTween>() {
+            rpe_easing(*id).map(|easing| (easing, range.clone()))
+        } else if let Some(bezier) = tween.downcast_ref::<BezierTween>() {
+            let mut event = RPEEvent::new(r, kf.time, next.time, f(&kf.value), f(&next.value), 1);
+            event.bezier = 1;
+            event.bezier_points = [bezier.p1.0, bezier.p1.1, bezier.p2.0, bezier.p2.1];
+            res.push(event);
+            continue;
+        } else {
+            None
+        };
+        if let Some((easing, range)) = easing {
+            let mut event = RPEEvent::new(r, kf.time, next.time, f(&kf.value), f(&next.value), easing);
+            event.easing_left = range.start;
+            event.easing_right = range.end;
+            res.push(event);
+        } else {
+            // not expressible in RPE, sample it
+            let value = |i: usize| f(&T::tween(&kf.value, &next.value, kf.tween.y(i as f32 / BAKE_STEPS as f32)));
+            let time = |i: usize| kf.time + (next.time - kf.time) * i as f32 / BAKE_STEPS as f32;
+            for i in 0..BAKE_STEPS {
+                res.push(RPEEvent::new(r, time(i), time(i + 1), value(i), value(i + 1), 1));
+            }
+        }
+    }
+    if pending {
+        let last = kfs.last().unwrap();
+        res.push(RPEEvent::new(r, last.time, last.time, f(&last.value), f(&last.value), 1));
+    }
+    res
+}
+
+fn layers(anim: &AnimFloat) -> impl Iterator<Item = &AnimFloat> {
+    std::iter::successors(Some(anim), |it| it.next.as_deref()).filter(|it| !it.keyframes.is_empty())
+}
+
+fn ctrl_events(anim: &AnimFloat, key: &'static str) -> Vec<RPECtrlEvent> {
+    anim.keyframes
+        .iter()
+        .map(|kf| RPECtrlEvent {
+            easing: static_tween(kf.tween.as_ref()).and_then(rpe_easing).unwrap_or(1),
+            x: kf.time,
+            value: HashMap::from([(key, kf.value)]),
+        })
+        .collect()
+}
+
+fn export_note(r: &mut BpmList, note: &Note) -> RPENote {
+    let (kind, end_time) = match note.kind {
+        NoteKind::Click => (1, note.time),
+        NoteKind::Hold { end_time, .. } => (2, end_time),
+        NoteKind::Flick => (3, note.time),
+        NoteKind::Drag => (4, note.time),
+    };
+    let (alpha, visible_time) = match &note.object.alpha.keyframes[..] {
+        [] => (1., ALWAYS_VISIBLE),
+        [kf] => (kf.value, ALWAYS_VISIBLE),
+        [_, kf] => (kf.value, note.time - kf.time),
+        [.., kf] => (kf.value, ALWAYS_VISIBLE),
+    };
+    let y_offset = fixed_value(&note.object.translation.1).unwrap_or_default();
+    RPENote {
+        kind,
+        above: if note.above { 1 } else { 2 },
+        start_time: Triple::from_beats(r.beat(note.time)),
+        end_time: Triple::from_beats(r.beat(end_time)),
+        position_x: fixed_value(&note.object.translation.0).unwrap_or_default() * (RPE_WIDTH / 2.),
+        y_offset: if note.speed.abs() < EPS {
+            0.
+        } else {
+            y_offset / note.speed * (RPE_HEIGHT / 2.)
+        },
+        alpha: (alpha * 255.).round().clamp(0., 255.) as u16,
+        hitsound: match (&note.hitsound, &note.kind) {
+            (HitSound::Click, NoteKind::Click | NoteKind::Hold { .. }) | (HitSound::Flick, NoteKind::Flick) | (HitSound::Drag, NoteKind::Drag) => {
+                None
+            }
+            (HitSound::Click, _) => Some("tap.mp3".to_owned()),
+            (HitSound::Flick, _) => Some("flick.mp3".to_owned()),
+            (HitSound::Drag, _) => Some("drag.mp3".to_owned()),
+            (HitSound::Custom(s), _) => Some(s.clone()),
+            (HitSound::None, _) => None,
+        },
+        size: fixed_value(&note.object.scale.0).unwrap_or(1.),
+        speed: note.speed,
+        is_fake: note.fake as u8,
+        visible_time,
+    }
+}
+
+fn export_line(r: &mut BpmList, line: &JudgeLine) -> RPEJudgeLine {
+    let mut event_layers: Vec<RPEEventLayer> = Vec::new();
+    let mut put = |r: &mut BpmList, anim: &AnimFloat, factor: f32, get: fn(&mut RPEEventLayer) -> &mut Option<Vec<RPEEvent>>| {
+        for (i, anim) in layers(anim).enumerate() {
+            if event_layers.len() <= i {
+                event_layers.push(RPEEventLayer::default());
+            }
+            *get(&mut event_layers[i]) = Some(events(r, anim, |it| it * factor));
+        }
+    };
+    put(r, &line.object.alpha, 255., |it| &mut it.alpha_events);
+    put(r, &line.object.rotation, -1., |it| &mut it.rotate_events);
+    put(r, &line.object.translation.0, RPE_WIDTH / 2., |it| &mut it.move_x_events);
+    put(r, &line.object.translation.1, RPE_HEIGHT / 2., |it| &mut it.move_y_events);
+    let speed_events: Vec<_> = speed_segments(&line.height)
+        .into_iter()
+        .map(|it| RPESpeedEvent {
+            start_time: Triple::from_beats(r.beat(it.start_time)),
+            end_time: Triple::from_beats(r.beat(it.end_time)),
+            start: it.start / SPEED_RATIO,
+            end: it.end / SPEED_RATIO,
+            linkgroup: 0,
+        })
+        .collect();
+    if !speed_events.is_empty() {
+        if event_layers.is_empty() {
+            event_layers.push(RPEEventLayer::default());
+        }
+        event_layers[0].speed_events = Some(speed_events);
+    }
+
+    let texture = match &line.kind {
+        JudgeLineKind::Texture(_, name) | JudgeLineKind::TextureGif(_, _, name) => Some(name.clone()),
+        _ => None,
+    };
+    let scale_factor = if texture.is_some() { RPE_WIDTH / 2. } else { 1. };
+    let scale_x_factor = if texture.is_none() && !matches!(line.kind, JudgeLineKind::Text(_)) && line.attach_ui.is_none() {
+        scale_factor * 2.
+    } else {
+        scale_factor
+    };
+    let extended = RPEExtendedEvents {
+        color_events: non_empty(events(r, &line.color, RGBColor::new)),
+        text_events: match &line.kind {
+            JudgeLineKind::Text(anim) => non_empty(events(r, anim, String::clone)),
+            _ => None,
+        },
+        scale_x_events: non_empty(events(r, &line.object.scale.0, |it| it * scale_x_factor)),
+        scale_y_events: non_empty(events(r, &line.object.scale.1, |it| it * scale_factor)),
+        incline_events: non_empty(events(r, &line.incline, |it| *it)),
+        paint_events: match &line.kind {
+            JudgeLineKind::Paint(anim, _) => non_empty(events(r, anim, |it| *it)),
+            _ => None,
+        },
+    };
+
+    let ctrl_obj = line.ctrl_obj.borrow();
+    RPEJudgeLine {
+        group: 0,
+        name: "Untitled".to_owned(),
+        texture: texture.unwrap_or_else(|| "line.png".to_owned()),
+        parent: line.parent.map_or(-1, |it| it as isize),
+        event_layers,
+        extended,
+        notes: line.notes.iter().map(|it| export_note(r, it)).collect(),
+        num_of_notes: line.notes.iter().filter(|it| !it.fake).count(),
+        is_cover: if line.show_below { 0 } else { 1 },
+        z_order: line.z_index,
+        attach_ui: line.attach_ui,
+        bpmfactor: 1.,
+
+        pos_control: ctrl_events(&ctrl_obj.pos, "pos"),
+        size_control: ctrl_events(&ctrl_obj.size, "size"),
+        alpha_control: ctrl_events(&ctrl_obj.alpha, "alpha"),
+        y_control: ctrl_events(&ctrl_obj.y, "y"),
+    }
+}
+
+/// Export a chart to RPE JSON
+///
+/// GIF progress events are not exported, GIF lines are exported as static texture lines.
+pub fn export_rpe(chart: &Chart, info: &ChartInfo) -> Result<String> {
+    let mut r = bpm_list(chart);
+    let rpe = RPEChart {
+        meta: RPEMetadata {
+            rpe_version: RPE_VERSION,
+            offset: (chart.offset * 1000.).round() as i32,
+            name: info.name.clone(),
+            id: info.id.map(|it| it.to_string()).unwrap_or_default(),
+            song: info.music.clone(),
+            background: info.illustration.clone(),
+            charter: info.charter.clone(),
+            composer: info.composer.clone(),
+            illustration: info.illustrator.clone(),
+            level: info.level.clone(),
+        },
+        bpm_list: r
+            .ranges()
+            .map(|(beats, bpm)| RPEBpmItem {
+                bpm,
+                start_time: Triple::from_beats(beats),
+            })
+            .collect(),
+        judge_line_group: vec!["Default"],
+        judge_line_list: chart.lines.iter().map(|it| export_line(&mut r, it)).collect(),
+    };
+    Ok(serde_json::to_string(&rpe)?)
+}
@@ -1,6 +1,10 @@
 //! File system abstraction
 
-use crate::{ext::spawn_task, info::ChartInfo};
+use crate::{
+    ext::spawn_task,
+    info::{ChartInfo, InfoIssue, InfoIssueKind},
+    parse::infer_malody_info,
+};
 use anyhow::{anyhow, bail, Context, Result};
 use async_trait::async_trait;
 use chardetng::EncodingDetector;
@@ -135,6 +139,18 @@ impl ZipFileSystem {
         let root = if root_dirs.len() == 1 { root_dirs[0].to_owned() } else { String::new() };
         Ok(Self(Arc::new(Mutex::new(zip)), root))
     }
+
+    /// Paths of every file in the archive relative to the root, including those in subdirectories
+    pub fn file_names(&self) -> Vec<String> {
+        self.0
+            .lock()
+            .unwrap()
+            .file_names()
+            .filter(|it| !it.ends_with('/'))
+            .filter_map(|it| it.strip_prefix(&self.1))
+            .map(str::to_owned)
+            .collect()
+    }
 }
 
 #[async_trait]
@@ -316,7 +332,10 @@ pub async fn fix_info(fs: &mut dyn FileSystem, info: &mut ChartInfo) -> Result<(
     for file in fs.list_root().context("cannot list files")? {
         if let Some((_, ext)) = file.rsplit_once('.') {
             match ext.to_ascii_lowercase().as_str() {
-                "json" | "pec" => {
+                "json" | "pec" | "mc" => {
+                    put("charts", &mut chart, file);
+                }
+                "csv" if !file.eq_ignore_ascii_case("info.csv") => {
                     put("charts", &mut chart, file);
                 }
                 _ => {}
@@ -354,6 +373,15 @@ pub async fn fix_info(fs: &mut dyn FileSystem, info: &mut ChartInfo) -> Result<(
                     if music.is_none() {
                         music = get(fs, &mut meta.song).await?;
                     }
+                } else if let Some((mut background, song)) = infer_malody_info(&s, info) {
+                    let level = info.level.clone();
+                    infer_diff(info, &level);
+                    if illustration.is_none() {
+                        illustration = get(fs, &mut background).await?;
+                    }
+                    if let (None, Some(mut song)) = (&music, song) {
+                        music = get(fs, &mut song).await?;
+                    }
                 }
             }
         }
@@ -390,21 +418,71 @@ fn bytes_to_text_auto(data: &[u8]) -> String {
     s.into_owned()
 }
 
-pub async fn load_info(fs: &mut dyn FileSystem) -> Result<ChartInfo> {
-    let info = if let Ok(bytes) = fs.load_file(":info").await {
-        serde_yaml::from_str(&bytes_to_text_auto(&bytes))?
+async fn read_info(fs: &mut dyn FileSystem) -> Result<(ChartInfo, Vec<InfoIssue>)> {
+    Ok(if let Ok(bytes) = fs.load_file(":info").await {
+        ChartInfo::from_yaml_checked(&bytes_to_text_auto(&bytes))?
     } else if let Ok(bytes) = fs.load_file("info.yml").await {
-        serde_yaml::from_str(&bytes_to_text_auto(&bytes))?
+        ChartInfo::from_yaml_checked(&bytes_to_text_auto(&bytes))?
     } else if let Ok(bytes) = fs.load_file("info.txt").await {
-        info_from_txt(&bytes_to_text_auto(&bytes))?
+        let info = info_from_txt(&bytes_to_text_auto(&bytes))?;
+        let issues = info.check();
+        (info, issues)
     } else if let Ok(bytes) = fs.load_file("info.csv").await {
-        info_from_csv(&bytes_to_text_auto(&bytes))?
+        let info = info_from_csv(&bytes_to_text_auto(&bytes))?;
+        let issues = info.check();
+        (info, issues)
     } else {
         warn!("none of info.yml, info.txt and info.csv is found, inferring");
         let mut info = ChartInfo::default();
         fix_info(fs, &mut info).await?;
-        info
-    };
+        (info, Vec::new())
+    })
+}
+
+/// Loads the metadata of a chart package, falling back to defaults for anything missing or invalid
+///
+/// Problems found are only logged, see [load_info_checked] for a strict version.
+pub async fn load_info(fs: &mut dyn FileSystem) -> Result<ChartInfo> {
+    let (info, issues) = read_info(fs).await?;
+    for issue in issues {
+        warn!(%issue, "problem in chart info");
+    }
+    Ok(info)
+}
+
+/// Files referenced by `info` that are not in the package
+pub async fn missing_files(fs: &mut dyn FileSystem, info: &ChartInfo) -> Result<Vec<InfoIssue>> {
+    let mut res = Vec::new();
+    let files = [
+        ("chart", Some(&info.chart)),
+        ("music", Some(&info.music)),
+        ("illustration", Some(&info.illustration)),
+        ("unlockVideo", info.unlock_video.as_ref()),
+    ];
+    for (key, path) in files {
+        let Some(path) = path.filter(|it| !it.is_empty()) else {
+            continue;
+        };
+        if !fs.exists(path).await? {
+            res.push(InfoIssue::new(InfoIssueKind::MissingFile, key, format!("file {path} does not exist")));
+        }
+    }
+    Ok(res)
+}
+
+/// Loads the metadata like [load_info], returning every unknown key, value out of range and missing file along with it
+pub async fn load_info_checked(fs: &mut dyn FileSystem) -> Result<(ChartInfo, Vec<InfoIssue>)> {
+    let (info, mut issues) = read_info(fs).await?;
+    issues.extend(missing_files(fs, &info).await?);
+    Ok((info, issues))
+}
+
+/// Loads the metadata, failing if [load_info_checked] finds any problem
+pub async fn load_info_strict(fs: &mut dyn FileSystem) -> Result<ChartInfo> {
+    let (info, issues) = load_info_checked(fs).await?;
+    if !issues.is_empty() {
+        bail!("invalid chart info:\n{}", issues.iter().map(|it| format!("  {it}")).collect::<Vec<_>>().join("\n"));
+    }
     Ok(info)
 }
 
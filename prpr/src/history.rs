@@ -0,0 +1,23 @@
+//! Attempts of finished plays
+//!
+//! Each attempt holds everything needed to compare it with other attempts later, see [crate::report] for how they
+//! reach the embedder.
+
+use crate::{config::Mods, judge::PlayResult, report::Reports};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+pub static REPORTED: Reports<PlayAttempt> = Reports::new();
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlayAttempt {
+    /// When the play ended
+    pub time: DateTime<Utc>,
+    /// Checksum of the chart file, see [chart_hash](crate::replay::chart_hash)
+    pub chart_hash: String,
+    pub mods: Mods,
+    pub speed: f32,
+    /// Offset the play was played with, in seconds
+    pub offset: f32,
+    pub result: PlayResult,
+}
@@ -1,22 +1,85 @@
 //! Chart metadata
 
+use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_yaml::{Mapping, Value};
+use std::fmt;
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
-#[repr(u8)]
-#[serde(rename_all = "lowercase")]
+/// Version of the `info.yml` schema, written with every [ChartInfo]
+///
+/// Files written with an older version are upgraded by [migrate_info] when they are loaded.
+pub const INFO_VERSION: u32 = 1;
+
+/// Format of a chart file, written by its name in `info.yml`
+///
+/// Formats other than the built-in ones are handled by parsers registered with
+/// [register_parser](crate::parse::register_parser).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum ChartFormat {
-    Rpe = 0,
+    Rpe,
     Pec,
     Pgr,
     Pbc,
+    /// Malody key and slide charts, see [parse_malody](crate::parse::parse_malody)
+    Malody,
+    /// Plain note lists, see [parse_note_list](crate::parse::parse_note_list)
+    NoteList,
+    Custom(String),
+}
+
+impl ChartFormat {
+    /// Parses a format name, ignoring case. Names other than the built-in ones become [ChartFormat::Custom].
+    pub fn from_name(name: &str) -> Self {
+        match name.to_ascii_lowercase().as_str() {
+            "rpe" => Self::Rpe,
+            "pec" => Self::Pec,
+            "pgr" => Self::Pgr,
+            "pbc" => Self::Pbc,
+            "malody" => Self::Malody,
+            "notes" => Self::NoteList,
+            _ => Self::Custom(name.to_ascii_lowercase()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Rpe => "rpe",
+            Self::Pec => "pec",
+            Self::Pgr => "pgr",
+            Self::Pbc => "pbc",
+            Self::Malody => "malody",
+            Self::NoteList => "notes",
+            Self::Custom(name) => name,
+        }
+    }
+}
+
+impl fmt::Display for ChartFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Serialize for ChartFormat {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for ChartFormat {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        Ok(Self::from_name(&String::deserialize(deserializer)?))
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, PartialEq)]
 #[serde(default)]
 #[serde(rename_all = "camelCase")]
 pub struct ChartInfo {
+    /// See [INFO_VERSION]
+    pub version: u32,
+
     pub id: Option<i32>,
     pub uploader: Option<i32>,
 
@@ -50,11 +113,17 @@ pub struct ChartInfo {
     pub created: Option<DateTime<Utc>>,
     pub updated: Option<DateTime<Utc>>,
     pub chart_updated: Option<DateTime<Utc>>,
+
+    /// Local path of the chart, set by the embedder when launching it. Not part of `info.yml`.
+    #[serde(skip)]
+    pub local_path: Option<String>,
 }
 
 impl Default for ChartInfo {
     fn default() -> Self {
         Self {
+            version: INFO_VERSION,
+
             id: None,
             uploader: None,
 
@@ -88,6 +157,158 @@ impl Default for ChartInfo {
             created: None,
             updated: None,
             chart_updated: None,
+
+            local_path: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InfoIssueKind {
+    UnknownKey,
+    /// Written by a newer version of the schema
+    NewerVersion,
+    OutOfRange,
+    MissingFile,
+}
+
+/// A problem found in chart metadata
+#[derive(Clone, Debug, PartialEq)]
+pub struct InfoIssue {
+    pub kind: InfoIssueKind,
+    /// The offending key, as written in `info.yml`
+    pub key: String,
+    pub message: String,
+}
+
+impl InfoIssue {
+    pub fn new(kind: InfoIssueKind, key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for InfoIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+fn camel_case(key: &str) -> String {
+    let mut res = String::with_capacity(key.len());
+    let mut upper = false;
+    for c in key.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            res.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            res.push(c);
+        }
+    }
+    res
+}
+
+/// Version 0 is anything written before the schema was versioned. Some tools wrote keys in snake case, which were
+/// silently ignored.
+fn migrate_v0(info: &mut Mapping) {
+    let known = known_keys();
+    let renames: Vec<_> = info
+        .keys()
+        .filter_map(Value::as_str)
+        .filter(|key| key.contains('_'))
+        .map(|key| (key.to_owned(), camel_case(key)))
+        .filter(|(_, camel)| known.contains(camel) && !info.contains_key(camel.as_str()))
+        .collect();
+    for (key, camel) in renames {
+        let value = info.remove(key.as_str()).unwrap();
+        info.insert(camel.into(), value);
+    }
+}
+
+/// `MIGRATIONS[i]` upgrades version `i` to version `i + 1`
+const MIGRATIONS: [fn(&mut Mapping); INFO_VERSION as usize] = [migrate_v0];
+
+/// Upgrades the content of an `info.yml` to [INFO_VERSION], returning the version it was written with
+///
+/// Content written by a newer version is left untouched.
+pub fn migrate_info(info: &mut Mapping) -> Result<u32> {
+    let version = match info.get("version") {
+        Some(value) => value.as_u64().context("version must be a non-negative integer")? as u32,
+        None => 0,
+    };
+    for migrate in MIGRATIONS.iter().skip(version as usize) {
+        migrate(info);
+    }
+    if version < INFO_VERSION {
+        info.insert("version".into(), Value::Number(INFO_VERSION.into()));
+    }
+    Ok(version)
+}
+
+/// Keys of every field of [ChartInfo]
+fn known_keys() -> Vec<String> {
+    match serde_yaml::to_value(ChartInfo::default()) {
+        Ok(Value::Mapping(map)) => map.keys().filter_map(Value::as_str).map(str::to_owned).collect(),
+        _ => unreachable!(),
+    }
+}
+
+impl ChartInfo {
+    /// Parses the content of an `info.yml`, migrating it from older versions
+    pub fn from_yaml(text: &str) -> Result<Self> {
+        Ok(Self::from_yaml_checked(text)?.0)
+    }
+
+    /// Like [ChartInfo::from_yaml], also returning unknown keys and values out of range
+    pub fn from_yaml_checked(text: &str) -> Result<(Self, Vec<InfoIssue>)> {
+        let mut issues = Vec::new();
+        let mut value: Value = serde_yaml::from_str(text)?;
+        if value.is_null() {
+            value = Value::Mapping(Mapping::new());
+        }
+        let Value::Mapping(map) = &mut value else {
+            anyhow::bail!("expected a mapping");
+        };
+        let version = migrate_info(map)?;
+        if version > INFO_VERSION {
+            issues.push(InfoIssue::new(InfoIssueKind::NewerVersion, "version", format!("written by a newer version ({version} > {INFO_VERSION})")));
+        }
+        let known = known_keys();
+        for key in map.keys() {
+            match key.as_str() {
+                Some(key) if known.iter().any(|it| it == key) => {}
+                _ => issues.push(InfoIssue::new(InfoIssueKind::UnknownKey, serde_yaml::to_string(key)?.trim(), "unknown key")),
+            }
+        }
+        let info: Self = serde_yaml::from_value(value)?;
+        issues.extend(info.check());
+        Ok((info, issues))
+    }
+
+    /// Values out of their valid range
+    pub fn check(&self) -> Vec<InfoIssue> {
+        let mut issues = Vec::new();
+        let mut expect = |ok: bool, key: &str, message: &str| {
+            if !ok {
+                issues.push(InfoIssue::new(InfoIssueKind::OutOfRange, key, message));
+            }
+        };
+        expect(self.difficulty.is_finite() && self.difficulty >= 0., "difficulty", "must be a non-negative number");
+        expect(self.aspect_ratio.is_finite() && self.aspect_ratio > 0., "aspectRatio", "must be positive");
+        expect((0. ..=1.).contains(&self.background_dim), "backgroundDim", "must be between 0 and 1");
+        expect(self.line_length.is_finite() && self.line_length > 0., "lineLength", "must be positive");
+        expect(self.offset.is_finite(), "offset", "must be a number");
+        expect(self.preview_start.is_finite() && self.preview_start >= 0., "previewStart", "must be a non-negative number");
+        if let Some(end) = self.preview_end {
+            expect(end > self.preview_start, "previewEnd", "must be after previewStart");
         }
+        expect(!self.chart.is_empty(), "chart", "must not be empty");
+        expect(!self.music.is_empty(), "music", "must not be empty");
+        issues
     }
 }
@@ -12,8 +12,8 @@ use macroquad::prelude::{
 use miniquad::{EventHandler, MouseButton};
 use once_cell::sync::Lazy;
 use sasa::{PlaySfxParams, Sfx};
-use serde::Serialize;
-use std::{cell::RefCell, collections::HashMap, num::FpCategory};
+use serde::{Deserialize, Serialize};
+use std::{cell::RefCell, collections::HashMap, num::FpCategory, ops::Range};
 use tracing::debug;
 
 pub const FLICK_SPEED_THRESHOLD: f32 = 0.8;
@@ -22,9 +22,9 @@ pub const LIMIT_GOOD: f32 = 0.16;
 pub const LIMIT_BAD: f32 = 0.22;
 
 // 严判模式判定窗口
-// pub const STRICT_LIMIT_PERFECT: f32 = 0.04;
-// pub const STRICT_LIMIT_GOOD: f32 = 0.08;
-// pub const STRICT_LIMIT_BAD: f32 = 0.11;
+pub const STRICT_LIMIT_PERFECT: f32 = 0.04;
+pub const STRICT_LIMIT_GOOD: f32 = 0.08;
+pub const STRICT_LIMIT_BAD: f32 = 0.11;
 pub const UP_TOLERANCE: f32 = 0.05;
 pub const DIST_FACTOR: f32 = 0.2;
 
@@ -41,6 +41,14 @@ pub enum HitSound {
 
 impl HitSound {
     pub fn play(&self, res: &mut Resource) {
+        self.play_at(res, res.time);
+    }
+
+    /// Plays the sound, logging it at chart time `time` instead of `res.time`
+    pub fn play_at(&self, res: &mut Resource, time: f32) {
+        if let Some(log) = &mut res.sfx_log {
+            log.push((time, self.clone()));
+        }
         match self {
             HitSound::None => {}
             HitSound::Click => play_sfx(&mut res.sfx_click, &res.config),
@@ -152,7 +160,7 @@ pub enum JudgeStatus {
     NotJudged,
     PreJudge,
     Judged,
-    Hold(bool, f32, f32, bool, f32), // perfect, at, diff, pre-judge, up-time
+    Hold(bool, f32, f32, bool, f32), // perfect, at, hit time, pre-judge, up-time
 }
 
 #[repr(u8)]
@@ -257,7 +265,7 @@ impl JudgeInner {
             counts: self.counts,
             early,
             late: self.diffs.len() as u32 - early,
-            std: 0.,
+            ..Default::default()
         }
     }
 
@@ -270,6 +278,23 @@ impl JudgeInner {
     }
 }
 
+/// Input of a single judged frame, see [Judge::collect_input]
+#[derive(Clone, Default)]
+pub struct FrameInput {
+    /// Chart time of the frame
+    pub time: f32,
+    /// Active touches in chart coordinates, `time` being how long ago the touch happened
+    pub touches: Vec<Touch>,
+    /// Touch events since last frame in screen-local coordinates
+    pub events: Vec<Touch>,
+    /// Change in the number of keys held down
+    pub key_delta: i32,
+    /// Number of keys pressed in this frame
+    pub keys_down: u32,
+}
+
+pub mod sim;
+
 #[rustfmt::skip]
 #[cfg(feature = "closed")]
 pub mod inner;
@@ -288,6 +313,12 @@ pub struct Judge {
 
     pub(crate) inner: JudgeInner,
     pub judgements: RefCell<Vec<(f32, u32, u32, Result<Judgement, bool>)>>,
+    /// Inputs of every judged frame, only recorded when set to `Some`
+    pub record: Option<Vec<FrameInput>>,
+    /// Timing errors of every Perfect / Good click and hold, in commit order
+    pub hit_errors: Vec<HitError>,
+    /// Use the stricter judgement windows of [Mods::STRICT_JUDGE](crate::config::Mods::STRICT_JUDGE)
+    pub strict: bool,
 }
 
 static SUBSCRIBER_ID: Lazy<usize> = Lazy::new(register_input_subscriber);
@@ -315,6 +346,9 @@ impl Judge {
 
             inner: JudgeInner::new(chart.lines.iter().map(|it| it.notes.iter().filter(|it| !it.fake).count() as u32).sum()),
             judgements: RefCell::new(Vec::new()),
+            record: None,
+            hit_errors: Vec::new(),
+            strict: false,
         }
     }
 
@@ -323,6 +357,26 @@ impl Judge {
         self.trackers.clear();
         self.inner.reset();
         self.judgements.borrow_mut().clear();
+        self.hit_errors.clear();
+        if let Some(frames) = &mut self.record {
+            frames.clear();
+        }
+    }
+
+    /// Forget judgements of notes starting within `range` after [Chart::reset_range]. Statistics start over.
+    pub fn reset_range(&mut self, chart: &Chart, range: Range<f32>) {
+        for (line, (idx, st)) in chart.lines.iter().zip(self.notes.iter_mut()) {
+            *st = idx
+                .iter()
+                .position(|id| !matches!(line.notes[*id as usize].judge, JudgeStatus::Judged))
+                .unwrap_or(idx.len());
+        }
+        self.trackers.clear();
+        self.inner.reset();
+        self.judgements
+            .borrow_mut()
+            .retain(|(_, line_id, note_id, _)| !range.contains(&chart.lines[*line_id as usize].notes[*note_id as usize].time));
+        self.hit_errors.retain(|it| !range.contains(&it.time));
     }
 
     pub fn commit(&mut self, t: f32, what: Judgement, line_id: u32, note_id: u32, diff: f32) {
@@ -359,7 +413,7 @@ impl Judge {
         });
     }
 
-    fn touch_transform(flip_x: bool) -> impl Fn(&mut Touch) {
+    fn touch_transform(flip_x: bool, flip_y: bool) -> impl Fn(&mut Touch) {
         let vp = get_viewport();
         move |touch| {
             let p = touch.position;
@@ -370,13 +424,16 @@ impl Judge {
             if flip_x {
                 touch.position.x *= -1.;
             }
+            if flip_y {
+                touch.position.y *= -1.;
+            }
         }
     }
 
     pub fn get_touches() -> Vec<Touch> {
         TOUCHES.with(|it| {
             let guard = it.borrow();
-            let tr = Self::touch_transform(false);
+            let tr = Self::touch_transform(false, false);
             guard
                 .0
                 .iter()
@@ -389,88 +446,170 @@ impl Judge {
         })
     }
 
+    /// Collect the input of the current frame
+    ///
+    /// Touches are transformed into chart coordinates and their time is turned into the age of the touch, so that the
+    /// result is independent from the screen and the clock and can be fed back through [Judge::update_with].
+    pub fn collect_input(res: &Resource) -> FrameInput {
+        fn to_local(Vec2 { x, y }: Vec2) -> Vec2 {
+            vec2(x / screen_width() * 2. - 1., y / screen_height() * 2. - 1.)
+        }
+        let uptime = get_uptime();
+        let age = |time: f64| if time.is_infinite() { f64::NEG_INFINITY } else { uptime - time };
+        let mut touches = touches();
+        let btn = MouseButton::Left;
+        let phase = if is_mouse_button_pressed(btn) {
+            Some(TouchPhase::Started)
+        } else if is_mouse_button_down(btn) {
+            Some(TouchPhase::Moved)
+        } else if is_mouse_button_released(btn) {
+            Some(TouchPhase::Ended)
+        } else {
+            None
+        };
+        if let Some(phase) = phase {
+            let p = mouse_position();
+            touches.push(Touch {
+                id: button_to_id(btn),
+                phase,
+                position: vec2(p.0, p.1),
+                time: f64::NEG_INFINITY,
+            });
+        }
+        let tr = Self::touch_transform(res.config.flip_x(), res.config.flip_y());
+        let touches = touches
+            .into_iter()
+            .map(|mut it| {
+                tr(&mut it);
+                it.time = age(it.time);
+                it
+            })
+            .collect();
+        TOUCHES.with(|it| {
+            let guard = it.borrow();
+            FrameInput {
+                time: res.time,
+                touches,
+                events: guard
+                    .0
+                    .iter()
+                    .map(|it| Touch {
+                        position: to_local(it.position),
+                        time: age(it.time),
+                        ..it.clone()
+                    })
+                    .collect(),
+                key_delta: guard.1,
+                keys_down: guard.2,
+            }
+        })
+    }
+
     pub fn update(&mut self, res: &mut Resource, chart: &mut Chart, bad_notes: &mut Vec<BadNote>) {
         if res.config.autoplay() {
             self.auto_play_update(res, chart);
             return;
         }
-        const X_DIFF_MAX: f32 = 0.21 / (16. / 9.) * 2.;
-        let spd = res.config.speed;
+        let input = Self::collect_input(res);
+        if let Some(frames) = &mut self.record {
+            frames.push(input.clone());
+        }
+        self.update_with(res, chart, bad_notes, &input);
+    }
+
+    /// Judge a frame with the given input, at the time of the input instead of `res.time`
+    pub fn update_with(&mut self, res: &mut Resource, chart: &mut Chart, bad_notes: &mut Vec<BadNote>, input: &FrameInput) {
+        let t = input.time;
+        let (holds, judgements) = self.judge_frame(chart, input, res.config.speed, res.aspect_ratio);
+        for (line_id, id) in holds {
+            chart.lines[line_id].notes[id as usize].hitsound.play(res);
+        }
+        for (judgement, line_id, id) in judgements {
+            let line = &mut chart.lines[line_id];
+            let note = &mut line.notes[id as usize];
+            line.object.set_time(t);
+            note.object.set_time(t);
+            let line = &chart.lines[line_id];
+            let note = &line.notes[id as usize];
+            let line_tr = line.now_transform(res, &chart.lines);
+            if matches!(note.kind, NoteKind::Hold { .. }) {
+                continue;
+            }
+            if match judgement {
+                Judgement::Perfect => {
+                    res.with_model(line_tr * note.object.now(res), |res| res.emit_at_origin(note.rotation(line), res.res_pack.info.fx_perfect()));
+                    true
+                }
+                Judgement::Good => {
+                    res.with_model(line_tr * note.object.now(res), |res| res.emit_at_origin(note.rotation(line), res.res_pack.info.fx_good()));
+                    true
+                }
+                Judgement::Bad => {
+                    bad_notes.push(BadNote {
+                        time: t,
+                        kind: note.kind.clone(),
+                        matrix: {
+                            let mut mat = line_tr;
+                            if !note.above {
+                                mat.append_nonuniform_scaling_mut(&Vector::new(1., -1.));
+                            }
+                            let incline_sin = line.incline.now_opt().map(|it| it.to_radians().sin()).unwrap_or_default();
+                            mat *= note.now_transform(
+                                res,
+                                &line.ctrl_obj.borrow_mut(),
+                                (note.height - line.height.now()) / res.aspect_ratio * note.speed,
+                                incline_sin,
+                            );
+                            mat
+                        },
+                    });
+                    false
+                }
+                _ => false,
+            } {
+                note.hitsound.play(res);
+            }
+        }
+    }
 
-        // let strict = res.config.has_mod(crate::config::Mods::STRICT_JUDGE);
-        // let limit_perfect = if strict { STRICT_LIMIT_PERFECT } else { LIMIT_PERFECT };
-        // let limit_good = if strict { STRICT_LIMIT_GOOD } else { LIMIT_GOOD };
-        // let limit_bad = if strict { STRICT_LIMIT_BAD } else { LIMIT_BAD };
-        let limit_perfect = LIMIT_PERFECT;
-        let limit_good = LIMIT_GOOD;
-        let limit_bad = LIMIT_BAD;
+    /// Judge a frame without any side effects other than updating the judge and the notes
+    ///
+    /// Returns the hold notes that just started being held and the committed judgements, as `(line_id, note_id)`
+    /// pairs, so that the caller can play hitsounds and particles. Doesn't need a [Resource] and thus works headless.
+    pub fn judge_frame(
+        &mut self,
+        chart: &mut Chart,
+        input: &FrameInput,
+        spd: f32,
+        aspect_ratio: f32,
+    ) -> (Vec<(usize, u32)>, Vec<(Judgement, usize, u32)>) {
+        const X_DIFF_MAX: f32 = 0.21 / (16. / 9.) * 2.;
 
-        let uptime = get_uptime();
+        let strict = self.strict;
+        let limit_perfect = if strict { STRICT_LIMIT_PERFECT } else { LIMIT_PERFECT };
+        let limit_good = if strict { STRICT_LIMIT_GOOD } else { LIMIT_GOOD };
+        let limit_bad = if strict { STRICT_LIMIT_BAD } else { LIMIT_BAD };
 
-        let t = res.time;
+        let t = input.time;
         // TODO optimize
-        let mut touches: HashMap<u64, Touch> = {
-            let mut touches = touches();
-            let btn = MouseButton::Left;
-            let id = button_to_id(btn);
-            if is_mouse_button_pressed(btn) {
-                let p = mouse_position();
-                touches.push(Touch {
-                    id,
-                    phase: TouchPhase::Started,
-                    position: vec2(p.0, p.1),
-                    time: f64::NEG_INFINITY,
-                });
-            } else if is_mouse_button_down(btn) {
-                let p = mouse_position();
-                touches.push(Touch {
-                    id,
-                    phase: TouchPhase::Moved,
-                    position: vec2(p.0, p.1),
-                    time: f64::NEG_INFINITY,
-                });
-            } else if is_mouse_button_released(btn) {
-                let p = mouse_position();
-                touches.push(Touch {
-                    id,
-                    phase: TouchPhase::Ended,
-                    position: vec2(p.0, p.1),
-                    time: f64::NEG_INFINITY,
-                });
-            }
-            let tr = Self::touch_transform(res.config.flip_x());
-            touches
-                .into_iter()
-                .map(|mut it| {
-                    tr(&mut it);
-                    (it.id, it)
-                })
-                .collect()
-        };
-        let (events, keys_down) = TOUCHES.with(|it| {
-            let guard = it.borrow();
-            (guard.0.clone(), guard.2)
-        });
-        self.key_down_count = self.key_down_count.saturating_add_signed(TOUCHES.with(|it| it.borrow().1));
+        let mut touches: HashMap<u64, Touch> = input.touches.iter().map(|it| (it.id, it.clone())).collect();
+        self.key_down_count = self.key_down_count.saturating_add_signed(input.key_delta);
         {
-            fn to_local(Vec2 { x, y }: Vec2) -> Point {
-                Point::new(x / screen_width() * 2. - 1., y / screen_height() * 2. - 1.)
-            }
-            let delta = (t / spd - self.last_time) as f64 / (events.len() + 1) as f64;
+            let delta = (t / spd - self.last_time) as f64 / (input.events.len() + 1) as f64;
             let mut t = self.last_time as f64;
             for Touch {
                 id,
                 phase,
                 position: p,
                 time,
-            } in events.into_iter()
+            } in input.events.iter().cloned()
             {
                 t += delta;
                 let t = t as f32;
-                let p = to_local(p);
+                let p = Point::new(p.x, p.y);
                 match phase {
                     TouchPhase::Started => {
-                        self.trackers.insert(id, FlickTracker::new(res.dpi, t, p));
+                        self.trackers.insert(id, FlickTracker::new(0, t, p));
                         touches
                             .entry(id)
                             .or_insert_with(|| Touch {
@@ -492,22 +631,25 @@ impl Judge {
                 }
             }
         }
-        let touches: Vec<Touch> = touches
+        let mut touches: Vec<Touch> = touches
             .into_values()
             .map(|mut it| {
                 it.time = if it.time.is_infinite() {
                     f64::NEG_INFINITY
                 } else {
-                    t as f64 - (uptime - it.time) * spd as f64
+                    t as f64 - it.time * spd as f64
                 };
                 it
             })
             .collect();
+        // keep the order stable so that replays are judged the same way
+        touches.sort_by_key(|it| it.id);
+        let keys_down = input.keys_down;
         // pos[line][touch]
         let mut pos = Vec::<Vec<Option<Point>>>::with_capacity(chart.lines.len());
         for id in 0..pos.capacity() {
             chart.lines[id].object.set_time(t);
-            let inv = chart.lines[id].now_transform(res, &chart.lines).try_inverse().unwrap();
+            let inv = chart.lines[id].now_transform_with(aspect_ratio, &chart.lines).try_inverse().unwrap();
             pos.push(
                 touches
                     .iter()
@@ -533,6 +675,7 @@ impl Judge {
                 touch.time as f32
             }
         };
+        let mut holds = Vec::new();
         let mut judgements = Vec::new();
         // clicks & flicks
         for (id, touch) in touches.iter().enumerate() {
@@ -606,7 +749,7 @@ impl Judge {
                                 judgements.push((if dt <= limit_perfect { Judgement::Perfect } else { Judgement::Good }, line_id, id, Some(t)));
                             }
                             NoteKind::Hold { .. } => {
-                                note.hitsound.play(res);
+                                holds.push((line_id, id));
                                 self.judgements.borrow_mut().push((t, line_id as _, id, Err(dt <= limit_perfect)));
                                 note.judge = JudgeStatus::Hold(dt <= limit_perfect, t, t, false, f32::INFINITY);
                             }
@@ -668,9 +811,9 @@ impl Judge {
                             ));
                         }
                         NoteKind::Hold { .. } => {
-                            note.hitsound.play(res);
+                            holds.push((line_id, id));
                             self.judgements.borrow_mut().push((t, line_id as _, id, Err(dt <= limit_perfect)));
-                            note.judge = JudgeStatus::Hold(dt <= limit_perfect, t, (t - note.time) / spd, false, f32::INFINITY);
+                            note.judge = JudgeStatus::Hold(dt <= limit_perfect, t, t, false, f32::INFINITY);
                         }
                         _ => unreachable!(),
                     };
@@ -773,66 +916,22 @@ impl Judge {
                 }
             }
         }
+        let mut committed = Vec::with_capacity(judgements.len());
         for (judgement, line_id, id, diff) in judgements {
-            let line = &mut chart.lines[line_id];
-            let note = &mut line.notes[id as usize];
-            line.object.set_time(t);
-            note.object.set_time(t);
-            let line = &chart.lines[line_id];
-            let note = &line.notes[id as usize];
-            let line_tr = line.now_transform(res, &chart.lines);
-            self.commit(
-                t,
-                judgement,
-                line_id as _,
-                id,
-                if matches!(judgement, Judgement::Miss) {
-                    0.25
-                } else if matches!(note.kind, NoteKind::Drag | NoteKind::Flick) {
-                    0.
-                } else {
-                    (diff.unwrap_or(t) - note.time) / spd
-                },
-            );
-            if matches!(note.kind, NoteKind::Hold { .. }) {
-                continue;
-            }
-            if match judgement {
-                Judgement::Perfect => {
-                    res.with_model(line_tr * note.object.now(res), |res| res.emit_at_origin(note.rotation(line), res.res_pack.info.fx_perfect()));
-                    true
-                }
-                Judgement::Good => {
-                    res.with_model(line_tr * note.object.now(res), |res| res.emit_at_origin(note.rotation(line), res.res_pack.info.fx_good()));
-                    true
-                }
-                Judgement::Bad => {
-                    if !matches!(note.kind, NoteKind::Hold { .. }) {
-                        bad_notes.push(BadNote {
-                            time: t,
-                            kind: note.kind.clone(),
-                            matrix: {
-                                let mut mat = line_tr;
-                                if !note.above {
-                                    mat.append_nonuniform_scaling_mut(&Vector::new(1., -1.));
-                                }
-                                let incline_sin = line.incline.now_opt().map(|it| it.to_radians().sin()).unwrap_or_default();
-                                mat *= note.now_transform(
-                                    res,
-                                    &line.ctrl_obj.borrow_mut(),
-                                    (note.height - line.height.now()) / res.aspect_ratio * note.speed,
-                                    incline_sin,
-                                );
-                                mat
-                            },
-                        });
-                    }
-                    false
-                }
-                _ => false,
-            } {
-                note.hitsound.play(res);
+            let note = &chart.lines[line_id].notes[id as usize];
+            let diff = if matches!(judgement, Judgement::Miss) {
+                0.25
+            } else if matches!(note.kind, NoteKind::Drag | NoteKind::Flick) {
+                0.
+            } else {
+                (diff.unwrap_or(t) - note.time) / spd
+            };
+            // drags and flicks are judged regardless of timing
+            if matches!(judgement, Judgement::Perfect | Judgement::Good) && matches!(note.kind, NoteKind::Click | NoteKind::Hold { .. }) {
+                self.hit_errors.push(HitError { time: note.time, diff });
             }
+            self.commit(t, judgement, line_id as _, id, diff);
+            committed.push((judgement, line_id, id));
         }
         for (line, (idx, st)) in chart.lines.iter().zip(self.notes.iter_mut()) {
             while idx
@@ -843,6 +942,7 @@ impl Judge {
             }
         }
         self.last_time = t / spd;
+        (holds, committed)
     }
 
     fn auto_play_update(&mut self, res: &mut Resource, chart: &mut Chart) {
@@ -870,7 +970,7 @@ impl Judge {
                 note.judge = if matches!(note.kind, NoteKind::Hold { .. }) {
                     note.hitsound.play(res);
                     self.judgements.borrow_mut().push((t, line_id as _, *id, Err(true)));
-                    JudgeStatus::Hold(true, t, (t - note.time) / spd, false, f32::INFINITY)
+                    JudgeStatus::Hold(true, t, t, false, f32::INFINITY)
                 } else {
                     judgements.push((line_id, *id));
                     JudgeStatus::Judged
@@ -885,27 +985,35 @@ impl Judge {
         }
         for (line_id, id) in judgements.into_iter() {
             self.commit(t, Judgement::Perfect, line_id as _, id, 0.);
-            let (note_transform, note_hitsound) = {
+            let (note_transform, note_hitsound, nt) = {
                 let line = &mut chart.lines[line_id];
                 let note = &mut line.notes[id as usize];
                 let nt = if matches!(note.kind, NoteKind::Hold { .. }) { t } else { note.time };
                 line.object.set_time(nt);
                 note.object.set_time(nt);
-                (note.object.now(res), note.hitsound.clone())
+                (note.object.now(res), note.hitsound.clone(), nt)
             };
             let line = &chart.lines[line_id];
             res.with_model(line.now_transform(res, &chart.lines) * note_transform, |res| {
                 res.emit_at_origin(line.notes[id as usize].rotation(line), res.res_pack.info.fx_perfect())
             });
             if !matches!(chart.lines[line_id].notes[id as usize].kind, NoteKind::Hold { .. }) {
-                note_hitsound.play(res);
+                note_hitsound.play_at(res, nt);
             }
         }
     }
 
-    #[inline]
     pub fn result(&self) -> PlayResult {
-        self.inner.result()
+        let mut result = self.inner.result();
+        if !self.hit_errors.is_empty() {
+            let n = self.hit_errors.len() as f32;
+            let mean = self.hit_errors.iter().map(|it| it.diff).sum::<f32>() / n;
+            let var = self.hit_errors.iter().map(|it| (it.diff - mean).powi(2)).sum::<f32>() / n;
+            result.mean = mean;
+            result.std = var.sqrt();
+        }
+        result.hit_errors = self.hit_errors.clone();
+        result
     }
 
     #[inline]
@@ -985,7 +1093,16 @@ impl EventHandler for Handler {
     }
 }
 
-#[derive(Default)]
+/// Signed timing error of a hit note
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct HitError {
+    /// Time of the note in the chart
+    pub time: f32,
+    /// Hit time minus note time in real seconds, negative when early
+    pub diff: f32,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct PlayResult {
     pub score: u32,
     pub accuracy: f64,
@@ -994,7 +1111,25 @@ pub struct PlayResult {
     pub counts: [u32; 4],
     pub early: u32,
     pub late: u32,
+    /// Mean and standard deviation of hit errors, in seconds
+    pub mean: f32,
     pub std: f32,
+    pub hit_errors: Vec<HitError>,
+}
+
+impl PlayResult {
+    /// Count hit errors into `bins` equal buckets over `[-LIMIT_GOOD, LIMIT_GOOD]`, errors out of range fall into the outermost ones
+    pub fn histogram(&self, bins: usize) -> Vec<u32> {
+        let mut res = vec![0; bins];
+        if bins == 0 {
+            return res;
+        }
+        for it in &self.hit_errors {
+            let p = (it.diff + LIMIT_GOOD) / (LIMIT_GOOD * 2.);
+            res[((p * bins as f32).max(0.) as usize).min(bins - 1)] += 1;
+        }
+        res
+    }
 }
 
 pub fn icon_index(score: u32, full_combo: bool) -> usize {
@@ -0,0 +1,179 @@
+//! Headless judge simulation
+//!
+//! Runs the [Judge] over a chart with a scripted sequence of touches and key presses, stepping frames at a fixed rate.
+//! No window, GL context or audio is needed, which makes judgement logic testable in CI.
+
+use super::{FrameInput, Judge, Judgement, PlayResult, LIMIT_BAD};
+use crate::core::{Chart, NoteKind};
+use macroquad::prelude::{vec2, Touch, TouchPhase, Vec2};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug)]
+pub enum SimInput {
+    /// A touch event, position is in chart coordinates (x in `[-1, 1]`, y in `[-1 / aspect_ratio, 1 / aspect_ratio]`)
+    Touch {
+        id: u64,
+        phase: TouchPhase,
+        position: Vec2,
+    },
+    KeyDown,
+    KeyUp,
+}
+
+/// A scripted input at the given chart time
+#[derive(Clone, Debug)]
+pub struct SimEvent {
+    pub time: f32,
+    pub input: SimInput,
+}
+
+impl SimEvent {
+    pub fn touch(time: f32, id: u64, phase: TouchPhase, x: f32, y: f32) -> Self {
+        Self {
+            time,
+            input: SimInput::Touch {
+                id,
+                phase,
+                position: vec2(x, y),
+            },
+        }
+    }
+
+    #[inline]
+    pub fn touch_start(time: f32, id: u64, x: f32, y: f32) -> Self {
+        Self::touch(time, id, TouchPhase::Started, x, y)
+    }
+
+    #[inline]
+    pub fn touch_move(time: f32, id: u64, x: f32, y: f32) -> Self {
+        Self::touch(time, id, TouchPhase::Moved, x, y)
+    }
+
+    #[inline]
+    pub fn touch_end(time: f32, id: u64, x: f32, y: f32) -> Self {
+        Self::touch(time, id, TouchPhase::Ended, x, y)
+    }
+
+    pub fn key_down(time: f32) -> Self {
+        Self {
+            time,
+            input: SimInput::KeyDown,
+        }
+    }
+
+    pub fn key_up(time: f32) -> Self {
+        Self {
+            time,
+            input: SimInput::KeyUp,
+        }
+    }
+}
+
+pub struct SimResult {
+    /// Judgement log in the same form as [Judge::judgements]
+    pub judgements: Vec<(f32, u32, u32, Result<Judgement, bool>)>,
+    pub result: PlayResult,
+}
+
+pub struct Simulator {
+    pub speed: f32,
+    pub aspect_ratio: f32,
+    pub fps: f32,
+    pub strict: bool,
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Self {
+            speed: 1.,
+            aspect_ratio: 16. / 9.,
+            fps: 120.,
+            strict: false,
+        }
+    }
+}
+
+impl Simulator {
+    /// Play the chart with the given inputs, until every note is judged
+    pub fn run(&self, chart: &mut Chart, events: &[SimEvent]) -> SimResult {
+        chart.reset();
+        let mut judge = Judge::new(chart);
+        judge.strict = self.strict;
+        let mut events = events.to_vec();
+        events.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        let last_note = chart
+            .lines
+            .iter()
+            .flat_map(|it| &it.notes)
+            .map(|it| match it.kind {
+                NoteKind::Hold { end_time, .. } => end_time,
+                _ => it.time,
+            })
+            .fold(0_f32, f32::max);
+        let end = last_note.max(events.last().map_or(0., |it| it.time)) + LIMIT_BAD * self.speed + 1.;
+        let dt = self.speed / self.fps;
+
+        // id -> (position, phase, time of the last event)
+        let mut active: BTreeMap<u64, (Vec2, TouchPhase, f32)> = BTreeMap::new();
+        let mut pending = events.iter().peekable();
+        let mut frame = 0;
+        loop {
+            let t = frame as f32 * dt;
+            frame += 1;
+            let age = |time: f32| ((t - time) / self.speed) as f64;
+            let mut input = FrameInput {
+                time: t,
+                ..Default::default()
+            };
+            while let Some(event) = pending.next_if(|it| it.time <= t) {
+                match event.input {
+                    SimInput::Touch { id, phase, position } => {
+                        active.insert(id, (position, phase, event.time));
+                        input.events.push(Touch {
+                            id,
+                            phase,
+                            // screen-local coordinates, assuming the chart fills the screen
+                            position: vec2(position.x, position.y * self.aspect_ratio),
+                            time: age(event.time),
+                        });
+                    }
+                    SimInput::KeyDown => {
+                        input.key_delta += 1;
+                        input.keys_down += 1;
+                    }
+                    SimInput::KeyUp => {
+                        input.key_delta -= 1;
+                    }
+                }
+            }
+            input.touches = active
+                .iter()
+                .map(|(id, (position, phase, time))| Touch {
+                    id: *id,
+                    phase: *phase,
+                    position: *position,
+                    time: age(*time),
+                })
+                .collect();
+            // nothing else updates the chart here, and lines may be transformed relative to their parents
+            for line in &mut chart.lines {
+                line.object.set_time(t);
+            }
+            judge.judge_frame(chart, &input, self.speed, self.aspect_ratio);
+
+            active.retain(|_, it| !matches!(it.1, TouchPhase::Ended | TouchPhase::Cancelled));
+            for it in active.values_mut() {
+                it.1 = TouchPhase::Stationary;
+            }
+            if t > end {
+                break;
+            }
+        }
+
+        SimResult {
+            judgements: judge.judgements.take(),
+            result: judge.result(),
+        }
+    }
+}
@@ -1,13 +1,20 @@
 pub mod bin;
+pub mod calibrate;
 pub mod config;
 pub mod core;
 pub mod dir;
+pub mod export;
 pub mod ext;
 pub mod fs;
+pub mod history;
 pub mod info;
 pub mod judge;
+pub mod package;
 pub mod parse;
 pub mod particle;
+pub mod rating;
+pub mod replay;
+pub mod report;
 pub mod scene;
 pub mod task;
 pub mod time;
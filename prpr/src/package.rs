@@ -0,0 +1,302 @@
+//! Chart package builder
+//!
+//! Assembles a chart package from loose files. The chart, music and illustration are put at the root of the archive while
+//! other files keep their relative paths, and `info.yml` is generated from the [ChartInfo] so that it always points at the
+//! files actually in the package.
+
+use crate::{
+    info::{ChartInfo, INFO_VERSION},
+    parse::{detect_format, find_parser},
+};
+use anyhow::{anyhow, bail, Context, Result};
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage, ImageFormat};
+use std::{
+    collections::HashSet,
+    io::{Cursor, Write},
+    path::{Component, Path},
+};
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::{DecoderOptions, CODEC_TYPE_FLAC, CODEC_TYPE_MP3, CODEC_TYPE_NULL, CODEC_TYPE_PCM_S16LE, CODEC_TYPE_VORBIS},
+    errors::Error as AudioError,
+    formats::FormatOptions,
+    io::MediaSourceStream,
+    meta::MetadataOptions,
+    probe::Hint,
+};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+#[derive(Clone, Debug)]
+pub struct PackageOptions {
+    /// Images larger than this on either side are scaled down
+    pub max_image_size: u32,
+    /// Quality of recompressed images, from 1 to 100
+    pub image_quality: u8,
+    /// Always decode the music into WAV, even if it can be played as is
+    pub transcode_music: bool,
+}
+
+impl Default for PackageOptions {
+    fn default() -> Self {
+        Self {
+            max_image_size: 2048,
+            image_quality: 90,
+            transcode_music: false,
+        }
+    }
+}
+
+/// Builds a chart package from a chart, its music and its illustration
+///
+/// ```ignore
+/// let zip = PackageBuilder::new(info)
+///     .chart("chart.json", chart)
+///     .music("song.ogg", music)
+///     .illustration("bg.png", illustration)
+///     .build()?;
+/// ```
+pub struct PackageBuilder {
+    info: ChartInfo,
+    options: PackageOptions,
+    chart: Option<(String, Vec<u8>)>,
+    music: Option<(String, Vec<u8>)>,
+    illustration: Option<(String, Vec<u8>)>,
+    unlock_video: Option<(String, Vec<u8>)>,
+    extra: Option<Vec<u8>>,
+    files: Vec<(String, Vec<u8>)>,
+}
+
+/// Name of the file in the package, with any directory removed
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map_or_else(|| path.to_owned(), |it| it.to_string_lossy().into_owned())
+}
+
+/// Path of the file in the package, which must stay inside it
+fn package_path(path: &str) -> Result<String> {
+    let mut res = Vec::new();
+    for component in Path::new(&path.replace('\\', "/")).components() {
+        match component {
+            Component::Normal(it) => res.push(it.to_string_lossy().into_owned()),
+            Component::CurDir => {}
+            _ => bail!("invalid path in package: {path}"),
+        }
+    }
+    if res.is_empty() {
+        bail!("invalid path in package: {path}");
+    }
+    Ok(res.join("/"))
+}
+
+fn with_extension(name: &str, ext: &str) -> String {
+    format!("{}.{ext}", Path::new(name).file_stem().unwrap_or_default().to_string_lossy())
+}
+
+impl PackageBuilder {
+    pub fn new(info: ChartInfo) -> Self {
+        Self {
+            info,
+            options: PackageOptions::default(),
+            chart: None,
+            music: None,
+            illustration: None,
+            unlock_video: None,
+            extra: None,
+            files: Vec::new(),
+        }
+    }
+
+    pub fn options(mut self, options: PackageOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn chart(mut self, name: &str, bytes: Vec<u8>) -> Self {
+        self.chart = Some((file_name(name), bytes));
+        self
+    }
+
+    pub fn music(mut self, name: &str, bytes: Vec<u8>) -> Self {
+        self.music = Some((file_name(name), bytes));
+        self
+    }
+
+    pub fn illustration(mut self, name: &str, bytes: Vec<u8>) -> Self {
+        self.illustration = Some((file_name(name), bytes));
+        self
+    }
+
+    pub fn unlock_video(mut self, name: &str, bytes: Vec<u8>) -> Self {
+        self.unlock_video = Some((name.to_owned(), bytes));
+        self
+    }
+
+    /// Content of `extra.json`
+    pub fn extra(mut self, bytes: Vec<u8>) -> Self {
+        self.extra = Some(bytes);
+        self
+    }
+
+    /// Any other file, e.g. textures and shaders referenced by `extra.json`, kept at its path relative to the chart
+    pub fn file(mut self, name: &str, bytes: Vec<u8>) -> Self {
+        self.files.push((name.to_owned(), bytes));
+        self
+    }
+
+    /// Checks and converts every file, returning the package as a zip archive
+    pub fn build(self) -> Result<Vec<u8>> {
+        let mut info = self.info;
+        info.version = INFO_VERSION;
+        let mut entries = Vec::new();
+
+        let (name, chart) = self.chart.ok_or_else(|| anyhow!("missing chart"))?;
+        match &info.format {
+            Some(format) => {
+                if find_parser(format).is_none() {
+                    bail!("unknown chart format: {format}");
+                }
+            }
+            None => {
+                detect_format(&chart).with_context(|| format!("cannot recognize chart {name}"))?;
+            }
+        }
+        info.chart = name.clone();
+        entries.push((name, chart));
+
+        let (name, music) = self.music.ok_or_else(|| anyhow!("missing music"))?;
+        let (name, music) = convert_music(&name, music, &self.options).with_context(|| format!("invalid music {name}"))?;
+        info.music = name.clone();
+        entries.push((name, music));
+
+        let (name, illustration) = self.illustration.ok_or_else(|| anyhow!("missing illustration"))?;
+        let (name, illustration) = convert_image(&name, illustration, &self.options).with_context(|| format!("invalid illustration {name}"))?;
+        info.illustration = name.clone();
+        entries.push((name, illustration));
+
+        if let Some((name, video)) = self.unlock_video {
+            let name = package_path(&name)?;
+            info.unlock_video = Some(name.clone());
+            entries.push((name, video));
+        } else {
+            info.unlock_video = None;
+        }
+
+        if let Some(extra) = self.extra {
+            serde_json::from_slice::<serde_json::Value>(&extra).context("invalid extra.json")?;
+            entries.push(("extra.json".to_owned(), extra));
+        }
+        for (name, bytes) in self.files {
+            entries.push((package_path(&name)?, bytes));
+        }
+
+        let issues = info.check();
+        if !issues.is_empty() {
+            bail!("invalid chart info:\n{}", issues.iter().map(|it| format!("  {it}")).collect::<Vec<_>>().join("\n"));
+        }
+        let mut names = HashSet::new();
+        for (name, _) in &entries {
+            if !names.insert(name.as_str()) || name == "info.yml" {
+                bail!("duplicate file in package: {name}");
+            }
+        }
+
+        let mut buffer = Vec::new();
+        let mut w = ZipWriter::new(Cursor::new(&mut buffer));
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .unix_permissions(0o755);
+        w.start_file("info.yml", options)?;
+        w.write_all(serde_yaml::to_string(&info)?.as_bytes())?;
+        for (name, data) in entries {
+            w.start_file(name, options)?;
+            w.write_all(&data)?;
+        }
+        w.finish()?;
+        Ok(buffer)
+    }
+}
+
+/// Keeps PNG and JPEG images that are small enough, everything else is scaled down and recompressed into JPEG
+fn convert_image(name: &str, bytes: Vec<u8>, options: &PackageOptions) -> Result<(String, Vec<u8>)> {
+    let format = image::guess_format(&bytes)?;
+    let image = image::load_from_memory_with_format(&bytes, format)?;
+    let max = options.max_image_size;
+    if matches!(format, ImageFormat::Png | ImageFormat::Jpeg) && image.width() <= max && image.height() <= max {
+        return Ok((name.to_owned(), bytes));
+    }
+    let image = if image.width() > max || image.height() > max {
+        image.resize(max, max, FilterType::Lanczos3)
+    } else {
+        image
+    };
+    let mut res = Vec::new();
+    DynamicImage::ImageRgb8(image.to_rgb8()).write_with_encoder(JpegEncoder::new_with_quality(&mut res, options.image_quality.clamp(1, 100)))?;
+    Ok((with_extension(name, "jpg"), res))
+}
+
+/// Keeps music in a format that can be streamed, anything else is decoded into WAV
+fn convert_music(name: &str, bytes: Vec<u8>, options: &PackageOptions) -> Result<(String, Vec<u8>)> {
+    let mut hint = Hint::new();
+    if let Some(ext) = Path::new(name).extension() {
+        hint.with_extension(&ext.to_string_lossy());
+    }
+    let stream = MediaSourceStream::new(Box::new(Cursor::new(bytes.clone())), Default::default());
+    let probed = symphonia::default::get_probe()
+        .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+        .context("unsupported audio format")?;
+    let mut reader = probed.format;
+    let track = reader
+        .tracks()
+        .iter()
+        .find(|it| it.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| anyhow!("no audio track"))?;
+    let codec = track.codec_params.codec;
+    if !options.transcode_music && [CODEC_TYPE_VORBIS, CODEC_TYPE_MP3, CODEC_TYPE_FLAC, CODEC_TYPE_PCM_S16LE].contains(&codec) {
+        return Ok((name.to_owned(), bytes));
+    }
+
+    let track_id = track.id;
+    let mut decoder = symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+    let mut samples = Vec::new();
+    let mut spec = None;
+    loop {
+        let packet = match reader.next_packet() {
+            Ok(packet) => packet,
+            Err(AudioError::IoError(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = decoder.decode(&packet)?;
+        spec = Some(*decoded.spec());
+        let mut buffer = SampleBuffer::<i16>::new(decoded.capacity() as u64, *decoded.spec());
+        buffer.copy_interleaved_ref(decoded);
+        samples.extend_from_slice(buffer.samples());
+    }
+    let spec = spec.ok_or_else(|| anyhow!("empty audio"))?;
+    Ok((with_extension(name, "wav"), encode_wav(&samples, spec.channels.count() as u16, spec.rate)))
+}
+
+/// 16-bit PCM WAV from interleaved samples
+fn encode_wav(samples: &[i16], channels: u16, sample_rate: u32) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+    let mut res = Vec::with_capacity(44 + data_len as usize);
+    res.extend_from_slice(b"RIFF");
+    res.extend_from_slice(&(36 + data_len).to_le_bytes());
+    res.extend_from_slice(b"WAVEfmt ");
+    res.extend_from_slice(&16_u32.to_le_bytes());
+    res.extend_from_slice(&1_u16.to_le_bytes());
+    res.extend_from_slice(&channels.to_le_bytes());
+    res.extend_from_slice(&sample_rate.to_le_bytes());
+    res.extend_from_slice(&(sample_rate * channels as u32 * 2).to_le_bytes());
+    res.extend_from_slice(&(channels * 2).to_le_bytes());
+    res.extend_from_slice(&16_u16.to_le_bytes());
+    res.extend_from_slice(b"data");
+    res.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        res.extend_from_slice(&sample.to_le_bytes());
+    }
+    res
+}
@@ -3,14 +3,63 @@
 mod extra;
 pub use extra::parse_extra;
 
+mod import;
+pub use import::{ImportColumn, ImportKind, ImportLine, ImportMapping};
+
+mod malody;
+pub(crate) use malody::infer_info as infer_malody_info;
+pub use malody::parse_malody;
+
+mod note_list;
+pub use note_list::parse_note_list;
+
 mod pec;
 pub use pec::parse_pec;
 
 mod pgr;
 pub use pgr::parse_phigros;
 
+mod registry;
+pub use registry::{detect_format, find_parser, parse_chart, rank_formats, register_parser, registered_formats, ChartParseError, ChartParser};
+
 mod rpe;
 pub use rpe::{parse_rpe, RPE_HEIGHT, RPE_WIDTH};
+pub(crate) use rpe::SPEED_RATIO;
+
+/// Find all cycles in the parent relations of judge lines
+///
+/// `parents[i]` is the parent of line `i`. Parents out of range are ignored. Each cycle is returned once, as the list of
+/// line indices in it starting from the smallest one.
+pub fn parent_cycles(parents: &[Option<usize>]) -> Vec<Vec<usize>> {
+    // 0: not visited, 1: on the current path, 2: done
+    let mut state = vec![0_u8; parents.len()];
+    let mut cycles = Vec::new();
+    for start in 0..parents.len() {
+        let mut path = Vec::new();
+        let mut cur = Some(start);
+        while let Some(id) = cur.filter(|it| *it < parents.len()) {
+            match state[id] {
+                0 => {
+                    state[id] = 1;
+                    path.push(id);
+                    cur = parents[id];
+                }
+                1 => {
+                    let mut cycle = path[path.iter().position(|it| *it == id).unwrap()..].to_vec();
+                    let min = cycle.iter().enumerate().min_by_key(|(_, it)| **it).unwrap().0;
+                    cycle.rotate_left(min);
+                    cycles.push(cycle);
+                    break;
+                }
+                _ => break,
+            }
+        }
+        for id in path {
+            state[id] = 2;
+        }
+    }
+    cycles
+}
 
 pub(crate) fn process_lines(v: &mut [crate::core::JudgeLine]) {
     use crate::ext::NotNanExt;
@@ -4,8 +4,8 @@ use super::RPE_TWEEN_MAP;
 #[cfg(feature = "video")]
 use crate::core::Video;
 use crate::{
-    core::{Anim, BpmList, ChartExtra, ClampedTween, Effect, Keyframe, StaticTween, Triple, Tweenable, Uniform, EPS},
-    ext::ScaleType,
+    core::{Anim, BpmList, ChartExtra, ClampedTween, Effect, Keyframe, ShaderSource, StaticTween, Triple, Tweenable, Uniform, EPS},
+    ext::{SafeTexture, ScaleType},
     fs::FileSystem,
 };
 use anyhow::{Context, Result};
@@ -110,13 +110,23 @@ enum Variable {
     Float(ExtAnim<f32>),
     Vec2(ExtAnim<(f32, f32)>),
     Color(ExtAnim<[u8; 4]>),
+    /// Path of an image in the chart package, bound to a `sampler2D` uniform
+    Texture(String),
+}
+
+/// A single shader, or a chain of passes each reading the output of the previous one
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ExtShader {
+    Single(String),
+    Chain(Vec<String>),
 }
 
 #[derive(Deserialize)]
 struct ExtEffect {
     start: Triple,
     end: Triple,
-    shader: String,
+    shader: ExtShader,
     #[serde(default)]
     vars: HashMap<String, Variable>,
     #[serde(default)]
@@ -149,29 +159,46 @@ struct Extra {
 
 async fn parse_effect(r: &mut BpmList, rpe: ExtEffect, fs: &mut dyn FileSystem) -> Result<Effect> {
     let range = r.time(&rpe.start)..r.time(&rpe.end);
-    let vars = rpe
-        .vars
-        .into_iter()
-        .map(|(name, var)| -> Result<Box<dyn Uniform>> {
-            Ok(match var {
-                Variable::Float(events) => Box::new((name, events.into::<f32>(r, None))),
-                Variable::Vec2(events) => Box::new((name, events.into::<Vec2>(r, None))),
-                Variable::Color(events) => Box::new((name, events.into::<Color>(r, None))),
-            })
-        })
-        .collect::<Result<_>>()?;
-    let string;
-    Effect::new(
-        range,
-        if let Some(path) = rpe.shader.strip_prefix('/') {
-            string = String::from_utf8(fs.load_file(path).await?).with_context(|| ptl!("shader-load-failed", "path" => path))?;
-            &string
+    let mut vars: Vec<Box<dyn Uniform>> = Vec::new();
+    let mut textures = Vec::new();
+    for (name, var) in rpe.vars {
+        match var {
+            Variable::Float(events) => vars.push(Box::new((name, events.into::<f32>(r, None)))),
+            Variable::Vec2(events) => vars.push(Box::new((name, events.into::<Vec2>(r, None)))),
+            Variable::Color(events) => vars.push(Box::new((name, events.into::<Color>(r, None)))),
+            Variable::Texture(path) => {
+                let bytes = fs
+                    .load_file(&path)
+                    .await
+                    .with_context(|| ptl!("texture-load-failed", "path" => path.as_str()))?;
+                let image = image::load_from_memory(&bytes).with_context(|| ptl!("texture-load-failed", "path" => path.as_str()))?;
+                textures.push((name, SafeTexture::from(image)));
+            }
+        }
+    }
+    let shaders = match rpe.shader {
+        ExtShader::Single(shader) => vec![shader],
+        ExtShader::Chain(shaders) => shaders,
+    };
+    let mut codes = Vec::with_capacity(shaders.len());
+    for shader in &shaders {
+        codes.push(if let Some(path) = shader.strip_prefix('/') {
+            String::from_utf8(fs.load_file(path).await?).with_context(|| ptl!("shader-load-failed", "path" => path))?
         } else {
-            Effect::get_preset(&rpe.shader).ok_or_else(|| ptl!(err "shader-not-found", "shader" => rpe.shader))?
-        },
-        vars,
-        rpe.global,
-    )
+            Effect::get_preset(shader)
+                .ok_or_else(|| ptl!(err "shader-not-found", "shader" => shader.as_str()))?
+                .to_owned()
+        });
+    }
+    let sources: Vec<_> = shaders
+        .iter()
+        .zip(&codes)
+        .map(|(name, code)| ShaderSource {
+            name: name.strip_prefix('/').unwrap_or(name),
+            code,
+        })
+        .collect();
+    Effect::with_passes(range, &sources, vars, textures, rpe.global)
 }
 
 pub async fn parse_extra(source: &str, fs: &mut dyn FileSystem) -> Result<ChartExtra> {
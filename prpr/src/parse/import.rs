@@ -0,0 +1,179 @@
+prpr_l10n::tl_file!("parser" ptl);
+
+use super::{process_lines, SPEED_RATIO};
+use crate::{
+    core::{
+        Anim, AnimFloat, AnimVector, BpmList, Chart, ChartExtra, ChartSettings, JudgeLine, JudgeLineCache, JudgeLineKind, Keyframe, Note, NoteKind,
+        Object,
+    },
+    judge::{HitSound, JudgeStatus},
+};
+use anyhow::Result;
+use serde::Deserialize;
+use std::{cell::RefCell, collections::HashMap};
+
+/// Static judge line notes of imported charts are put on
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ImportLine {
+    pub x: f32,
+    pub y: f32,
+    /// Rotation in degrees
+    pub rotation: f32,
+}
+
+impl Default for ImportLine {
+    fn default() -> Self {
+        Self {
+            x: 0.,
+            y: -0.6,
+            rotation: 0.,
+        }
+    }
+}
+
+/// Placement of a column of a column based chart
+#[derive(Clone, Debug, Deserialize)]
+pub struct ImportColumn {
+    pub line: usize,
+    pub x: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportKind {
+    Click,
+    Drag,
+    Flick,
+    Hold,
+}
+
+/// How notes of charts from other games are mapped onto judge lines and note kinds
+///
+/// Chart packages can override the default mapping with an `import.yml` file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ImportMapping {
+    pub lines: Vec<ImportLine>,
+    /// Placement of each column, columns without one are spread evenly over the first line
+    pub columns: Vec<ImportColumn>,
+    /// Width the columns are spread over, 2 being the whole screen
+    pub width: f32,
+    /// Note speed, in the unit used by RPE
+    pub speed: f32,
+    /// Note kinds by the name used in the source, taking precedence over the default names
+    pub kinds: HashMap<String, ImportKind>,
+}
+
+impl Default for ImportMapping {
+    fn default() -> Self {
+        Self {
+            lines: vec![ImportLine::default()],
+            columns: Vec::new(),
+            width: 1.5,
+            speed: 10.,
+            kinds: HashMap::new(),
+        }
+    }
+}
+
+impl ImportMapping {
+    pub fn kind(&self, name: &str) -> Option<ImportKind> {
+        if let Some(kind) = self.kinds.get(name) {
+            return Some(*kind);
+        }
+        let name = name.to_ascii_lowercase();
+        self.kinds.get(&name).copied().or(match name.as_str() {
+            "click" | "tap" | "note" => Some(ImportKind::Click),
+            "drag" => Some(ImportKind::Drag),
+            "flick" => Some(ImportKind::Flick),
+            "hold" => Some(ImportKind::Hold),
+            _ => None,
+        })
+    }
+
+    /// Line and X position of `column` in a chart with `count` columns
+    pub fn column(&self, column: usize, count: usize) -> (usize, f32) {
+        if let Some(it) = self.columns.get(column) {
+            return (it.line, it.x);
+        }
+        let count = count.max(column + 1);
+        (0, self.width * ((column as f32 + 0.5) / count as f32 - 0.5))
+    }
+}
+
+/// Note read by an importer, with times in seconds
+pub(crate) struct ImportedNote {
+    pub time: f32,
+    pub end_time: Option<f32>,
+    pub kind: ImportKind,
+    pub line: usize,
+    pub x: f32,
+    pub above: bool,
+}
+
+/// Builds a chart of static lines, see [ImportMapping]
+pub(crate) fn build_chart(offset: f32, notes: Vec<ImportedNote>, bpm_list: BpmList, mapping: &ImportMapping, extra: ChartExtra) -> Result<Chart> {
+    let speed = mapping.speed * SPEED_RATIO;
+    let max_time = notes.iter().map(|it| it.end_time.unwrap_or(it.time)).fold(0., f32::max) + 1.;
+    let mut line_notes: Vec<Vec<Note>> = mapping.lines.iter().map(|_| Vec::new()).collect();
+    for note in notes {
+        let Some(target) = line_notes.get_mut(note.line) else {
+            ptl!(bail "import-line-out-of-range", "line" => note.line, "count" => mapping.lines.len());
+        };
+        let kind = match (note.kind, note.end_time) {
+            (ImportKind::Hold, Some(end_time)) if end_time > note.time => NoteKind::Hold {
+                end_time,
+                end_height: end_time * speed,
+            },
+            (ImportKind::Click | ImportKind::Hold, _) => NoteKind::Click,
+            (ImportKind::Drag, _) => NoteKind::Drag,
+            (ImportKind::Flick, _) => NoteKind::Flick,
+        };
+        target.push(Note {
+            object: Object {
+                translation: AnimVector(AnimFloat::fixed(note.x), AnimFloat::default()),
+                ..Default::default()
+            },
+            hitsound: HitSound::default_from_kind(&kind),
+            kind,
+            time: note.time,
+            speed: 1.,
+            height: note.time * speed,
+
+            above: note.above,
+            multiple_hint: false,
+            fake: false,
+            judge: JudgeStatus::NotJudged,
+        });
+    }
+    let mut lines: Vec<_> = mapping
+        .lines
+        .iter()
+        .zip(line_notes)
+        .map(|(line, mut notes)| {
+            let cache = JudgeLineCache::new(&mut notes);
+            JudgeLine {
+                object: Object {
+                    rotation: AnimFloat::fixed(line.rotation),
+                    translation: AnimVector(AnimFloat::fixed(line.x), AnimFloat::fixed(line.y)),
+                    ..Default::default()
+                },
+                ctrl_obj: RefCell::default(),
+                kind: JudgeLineKind::Normal,
+                height: AnimFloat::new(vec![Keyframe::new(0., 0., 2), Keyframe::new(max_time, max_time * speed, 0)]),
+                incline: AnimFloat::default(),
+                notes,
+                color: Anim::default(),
+                parent: None,
+                z_index: 0,
+                show_below: false,
+                attach_ui: None,
+
+                cache,
+            }
+        })
+        .collect();
+    process_lines(&mut lines);
+    Ok(Chart::new(offset, lines, bpm_list, ChartSettings::default(), extra, HashMap::new()))
+}
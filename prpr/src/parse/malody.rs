@@ -0,0 +1,120 @@
+prpr_l10n::tl_file!("parser" ptl);
+
+use super::import::{build_chart, ImportKind, ImportMapping, ImportedNote};
+use crate::{
+    core::{BpmList, Chart, ChartExtra, Triple},
+    info::ChartInfo,
+};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+const MODE_KEY: u32 = 0;
+const MODE_SLIDE: u32 = 7;
+
+/// Width of the playfield of slide charts
+const SLIDE_WIDTH: f32 = 256.;
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct McSong {
+    title: String,
+    artist: String,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct McModeExt {
+    column: Option<usize>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct McMeta {
+    creator: String,
+    version: String,
+    background: String,
+    mode: u32,
+    mode_ext: McModeExt,
+    song: McSong,
+}
+
+#[derive(Deserialize)]
+struct McTime {
+    beat: Triple,
+    bpm: f32,
+}
+
+#[derive(Deserialize)]
+struct McNote {
+    beat: Triple,
+    endbeat: Option<Triple>,
+    column: Option<usize>,
+    x: Option<f32>,
+    #[serde(default)]
+    w: f32,
+    /// Set on the note holding the music instead of a playable note
+    sound: Option<String>,
+    /// Offset of the music in milliseconds, on the sound note
+    #[serde(default)]
+    offset: f32,
+}
+
+#[derive(Deserialize)]
+struct McChart {
+    meta: McMeta,
+    time: Vec<McTime>,
+    note: Vec<McNote>,
+}
+
+/// Imports a Malody key or slide chart
+pub fn parse_malody(source: &str, mapping: &ImportMapping, extra: ChartExtra) -> Result<Chart> {
+    let mc: McChart = serde_json::from_str(source).with_context(|| ptl!("json-parse-failed"))?;
+    if mc.meta.mode != MODE_KEY && mc.meta.mode != MODE_SLIDE {
+        ptl!(bail "malody-unsupported-mode", "mode" => mc.meta.mode);
+    }
+    if mc.time.is_empty() {
+        ptl!(bail "malody-no-bpm");
+    }
+    let mut r = BpmList::new(mc.time.iter().map(|it| (it.beat.beats(), it.bpm)).collect());
+    let columns = mc
+        .meta
+        .mode_ext
+        .column
+        .unwrap_or_else(|| mc.note.iter().filter_map(|it| it.column).max().map_or(0, |it| it + 1));
+    let mut offset = 0.;
+    let mut notes = Vec::new();
+    for note in &mc.note {
+        if note.sound.is_some() {
+            offset = note.offset / 1000.;
+            continue;
+        }
+        let (line, x) = if mc.meta.mode == MODE_KEY {
+            mapping.column(note.column.unwrap_or_default(), columns)
+        } else {
+            let center = (note.x.unwrap_or(SLIDE_WIDTH / 2.) + note.w / 2.) / SLIDE_WIDTH;
+            (0, mapping.width * (center - 0.5))
+        };
+        let name = if note.endbeat.is_some() { "hold" } else { "tap" };
+        notes.push(ImportedNote {
+            time: r.time(&note.beat),
+            end_time: note.endbeat.as_ref().map(|it| r.time(it)),
+            kind: mapping.kind(name).unwrap_or(ImportKind::Click),
+            line,
+            x,
+            above: true,
+        });
+    }
+    build_chart(offset, notes, r, mapping, extra)
+}
+
+/// Fills metadata from the `meta` of a Malody chart, returning the paths of the background and the music if any
+pub(crate) fn infer_info(source: &str, info: &mut ChartInfo) -> Option<(String, Option<String>)> {
+    let mc: McChart = serde_json::from_str(source).ok()?;
+    let meta = mc.meta;
+    info.name = meta.song.title;
+    info.composer = meta.song.artist;
+    info.charter = meta.creator;
+    info.level = meta.version;
+    let music = mc.note.into_iter().find_map(|it| it.sound);
+    Some((meta.background, music))
+}
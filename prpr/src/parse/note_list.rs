@@ -0,0 +1,137 @@
+prpr_l10n::tl_file!("parser" ptl);
+
+use super::import::{build_chart, ImportMapping, ImportedNote};
+use crate::core::{BpmList, Chart, ChartExtra};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+fn default_kind() -> String {
+    "tap".to_owned()
+}
+
+fn default_above() -> bool {
+    true
+}
+
+/// A note of a note list, with times in seconds
+///
+/// The note is placed at `x` on `line` if given, otherwise at the place of `column`.
+#[derive(Deserialize)]
+struct ListNote {
+    time: f32,
+    end: Option<f32>,
+    #[serde(rename = "type", default = "default_kind")]
+    kind: String,
+    line: Option<usize>,
+    x: Option<f32>,
+    column: Option<usize>,
+    #[serde(default = "default_above")]
+    above: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ListChart {
+    Plain(Vec<ListNote>),
+    Full {
+        #[serde(default)]
+        offset: f32,
+        notes: Vec<ListNote>,
+    },
+}
+
+impl ListNote {
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "time" => self.time = value.parse()?,
+            "end" => self.end = Some(value.parse()?),
+            "type" => self.kind = value.to_owned(),
+            "line" => self.line = Some(value.parse()?),
+            "x" => self.x = Some(value.parse()?),
+            "column" => self.column = Some(value.parse()?),
+            "above" => self.above = !matches!(value, "0" | "false"),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Reads CSV with a header naming the columns, e.g. `time,type,x`. Empty lines and lines starting with `#` are skipped.
+fn parse_csv(source: &str) -> Result<Vec<ListNote>> {
+    let mut rows = source
+        .lines()
+        .enumerate()
+        .map(|(id, line)| (id + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+    let Some((_, header)) = rows.next() else {
+        return Ok(Vec::new());
+    };
+    let header: Vec<_> = header.split(',').map(|it| it.trim().to_ascii_lowercase()).collect();
+    if !header.iter().any(|it| it == "time") {
+        ptl!(bail "note-list-no-time");
+    }
+    let mut notes = Vec::new();
+    for (id, row) in rows {
+        let mut note = ListNote {
+            time: 0.,
+            end: None,
+            kind: default_kind(),
+            line: None,
+            x: None,
+            column: None,
+            above: true,
+        };
+        for (key, value) in header.iter().zip(row.split(',').map(str::trim)) {
+            if !value.is_empty() {
+                note.set(key, value)
+                    .with_context(|| ptl!("note-list-invalid-value", "key" => key.as_str(), "value" => value))
+                    .with_context(|| ptl!("line-location", "lid" => id))?;
+            }
+        }
+        notes.push(note);
+    }
+    Ok(notes)
+}
+
+/// Imports a list of notes, either as CSV or as JSON
+///
+/// JSON lists are either an array of notes or an object with `offset` and `notes`. Notes have the same fields as the
+/// columns of CSV lists: `time`, `end`, `type`, `line`, `x`, `column` and `above`.
+pub fn parse_note_list(source: &str, mapping: &ImportMapping, extra: ChartExtra) -> Result<Chart> {
+    let trimmed = source.trim_start();
+    let (offset, list) = if trimmed.starts_with('[') || trimmed.starts_with('{') {
+        match serde_json::from_str(trimmed).with_context(|| ptl!("json-parse-failed"))? {
+            ListChart::Plain(notes) => (0., notes),
+            ListChart::Full { offset, notes } => (offset, notes),
+        }
+    } else {
+        (0., parse_csv(source)?)
+    };
+    let columns = list.iter().filter_map(|it| it.column).max().map_or(0, |it| it + 1);
+    let notes = list
+        .into_iter()
+        .map(|note| -> Result<ImportedNote> {
+            let kind = mapping
+                .kind(&note.kind)
+                .ok_or_else(|| ptl!(err "unknown-note-type", "type" => note.kind.as_str()))?;
+            let (line, x) = match (note.line, note.x, note.column) {
+                (line, Some(x), _) => (line.unwrap_or_default(), x),
+                (line, None, Some(column)) => {
+                    let (column_line, x) = mapping.column(column, columns);
+                    (line.unwrap_or(column_line), x)
+                }
+                (line, None, None) => (line.unwrap_or_default(), 0.),
+            };
+            Ok(ImportedNote {
+                time: note.time,
+                end_time: note.end,
+                kind,
+                line,
+                x,
+                above: note.above,
+            })
+        })
+        .collect::<Result<_>>()?;
+    let bpm_list = BpmList::new(vec![(0., 60.)]);
+    build_chart(offset, notes, bpm_list, mapping, extra)
+}
@@ -0,0 +1,317 @@
+//! Registry of chart formats
+//!
+//! Every format is handled by a [ChartParser]. A chart declaring its format in `info.yml` goes to the parser with that
+//! name; otherwise every registered parser rates how confident it is that the file is in its format, and the most
+//! confident one parses it. Formats added with [register_parser] are used everywhere the built-in ones are.
+
+prpr_l10n::tl_file!("parser" ptl);
+
+use super::{parse_malody, parse_note_list, parse_pec, parse_phigros, parse_rpe, ImportMapping};
+use crate::{
+    bin::BinaryReader,
+    core::{Chart, ChartExtra},
+    fs::FileSystem,
+    info::ChartFormat,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use std::{
+    fmt,
+    io::Cursor,
+    sync::{Arc, RwLock},
+};
+
+#[async_trait(?Send)]
+pub trait ChartParser: Send + Sync {
+    /// The format handled by this parser
+    fn format(&self) -> ChartFormat;
+
+    /// Extension of files in this format, without the dot
+    fn extension(&self) -> &str;
+
+    /// How confident this parser is that `bytes` is in its format, from 0 (certainly not) to 1 (certainly)
+    fn detect(&self, bytes: &[u8]) -> f32;
+
+    async fn parse(&self, bytes: &[u8], fs: &mut dyn FileSystem, extra: ChartExtra) -> Result<Chart>;
+}
+
+#[derive(Debug)]
+pub enum ChartParseError {
+    /// No parser is registered for the format
+    UnknownFormat(ChartFormat),
+    /// No parser recognizes the file
+    Undetected,
+    /// The parser of the format rejected the file
+    Invalid { format: ChartFormat, source: anyhow::Error },
+}
+
+impl fmt::Display for ChartParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownFormat(format) => write!(f, "{}", ptl!("format-unknown", "format" => format.name())),
+            Self::Undetected => write!(f, "{}", ptl!("format-undetected")),
+            Self::Invalid { format, .. } => write!(f, "{}", ptl!("format-parse-failed", "format" => format.name())),
+        }
+    }
+}
+
+impl std::error::Error for ChartParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Invalid { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Text with a leading byte order mark and whitespace removed, `None` if `bytes` is not UTF-8
+fn text(bytes: &[u8]) -> Option<&str> {
+    std::str::from_utf8(bytes).ok().map(|it| it.trim_start_matches('\u{feff}').trim_start())
+}
+
+struct RpeParser;
+#[async_trait(?Send)]
+impl ChartParser for RpeParser {
+    fn format(&self) -> ChartFormat {
+        ChartFormat::Rpe
+    }
+
+    fn extension(&self) -> &str {
+        "json"
+    }
+
+    fn detect(&self, bytes: &[u8]) -> f32 {
+        match text(bytes) {
+            Some(text) if text.starts_with('{') && text.contains("\"META\"") => 1.,
+            _ => 0.,
+        }
+    }
+
+    async fn parse(&self, bytes: &[u8], fs: &mut dyn FileSystem, extra: ChartExtra) -> Result<Chart> {
+        parse_rpe(&String::from_utf8_lossy(bytes), fs, extra).await
+    }
+}
+
+struct PgrParser;
+#[async_trait(?Send)]
+impl ChartParser for PgrParser {
+    fn format(&self) -> ChartFormat {
+        ChartFormat::Pgr
+    }
+
+    fn extension(&self) -> &str {
+        "json"
+    }
+
+    fn detect(&self, bytes: &[u8]) -> f32 {
+        match text(bytes) {
+            Some(text) if text.starts_with('{') => {
+                if text.contains("\"formatVersion\"") {
+                    0.9
+                } else {
+                    0.5
+                }
+            }
+            _ => 0.,
+        }
+    }
+
+    async fn parse(&self, bytes: &[u8], _fs: &mut dyn FileSystem, extra: ChartExtra) -> Result<Chart> {
+        parse_phigros(&String::from_utf8_lossy(bytes), extra)
+    }
+}
+
+struct PecParser;
+#[async_trait(?Send)]
+impl ChartParser for PecParser {
+    fn format(&self) -> ChartFormat {
+        ChartFormat::Pec
+    }
+
+    fn extension(&self) -> &str {
+        "pec"
+    }
+
+    fn detect(&self, bytes: &[u8]) -> f32 {
+        match text(bytes) {
+            Some(text) if !text.starts_with('{') => {
+                // PEC files start with the offset
+                if text.lines().next().map_or(false, |it| it.trim().parse::<f32>().is_ok()) {
+                    0.8
+                } else {
+                    0.3
+                }
+            }
+            _ => 0.,
+        }
+    }
+
+    async fn parse(&self, bytes: &[u8], _fs: &mut dyn FileSystem, extra: ChartExtra) -> Result<Chart> {
+        parse_pec(&String::from_utf8_lossy(bytes), extra)
+    }
+}
+
+struct PbcParser;
+#[async_trait(?Send)]
+impl ChartParser for PbcParser {
+    fn format(&self) -> ChartFormat {
+        ChartFormat::Pbc
+    }
+
+    fn extension(&self) -> &str {
+        "pbc"
+    }
+
+    fn detect(&self, bytes: &[u8]) -> f32 {
+        // binary charts have no magic number, anything that is not text may be one
+        if std::str::from_utf8(bytes).is_err() {
+            0.5
+        } else {
+            0.
+        }
+    }
+
+    async fn parse(&self, bytes: &[u8], _fs: &mut dyn FileSystem, _extra: ChartExtra) -> Result<Chart> {
+        BinaryReader::new(Cursor::new(bytes)).read()
+    }
+}
+
+/// Mapping from the `import.yml` of the chart package, or the default one
+async fn import_mapping(fs: &mut dyn FileSystem) -> Result<ImportMapping> {
+    if !fs.exists("import.yml").await? {
+        return Ok(ImportMapping::default());
+    }
+    let bytes = fs.load_file("import.yml").await?;
+    serde_yaml::from_slice(&bytes).with_context(|| ptl!("import-mapping-invalid"))
+}
+
+struct MalodyParser;
+#[async_trait(?Send)]
+impl ChartParser for MalodyParser {
+    fn format(&self) -> ChartFormat {
+        ChartFormat::Malody
+    }
+
+    fn extension(&self) -> &str {
+        "mc"
+    }
+
+    fn detect(&self, bytes: &[u8]) -> f32 {
+        match text(bytes) {
+            Some(text) if text.starts_with('{') && text.contains("\"meta\"") && text.contains("\"note\"") => 0.95,
+            _ => 0.,
+        }
+    }
+
+    async fn parse(&self, bytes: &[u8], fs: &mut dyn FileSystem, extra: ChartExtra) -> Result<Chart> {
+        parse_malody(&String::from_utf8_lossy(bytes), &import_mapping(fs).await?, extra)
+    }
+}
+
+struct NoteListParser;
+#[async_trait(?Send)]
+impl ChartParser for NoteListParser {
+    fn format(&self) -> ChartFormat {
+        ChartFormat::NoteList
+    }
+
+    fn extension(&self) -> &str {
+        "csv"
+    }
+
+    fn detect(&self, bytes: &[u8]) -> f32 {
+        let Some(text) = text(bytes) else {
+            return 0.;
+        };
+        if text.starts_with('[') {
+            return if text.contains("\"time\"") { 0.8 } else { 0. };
+        }
+        if text.starts_with('{') {
+            return if text.contains("\"notes\"") && text.contains("\"time\"") {
+                0.6
+            } else {
+                0.
+            };
+        }
+        let header = text.lines().map(str::trim).find(|it| !it.starts_with('#'));
+        if header.map_or(false, |it| it.split(',').any(|it| it.trim().eq_ignore_ascii_case("time"))) {
+            0.9
+        } else {
+            0.
+        }
+    }
+
+    async fn parse(&self, bytes: &[u8], fs: &mut dyn FileSystem, extra: ChartExtra) -> Result<Chart> {
+        parse_note_list(&String::from_utf8_lossy(bytes), &import_mapping(fs).await?, extra)
+    }
+}
+
+static PARSERS: Lazy<RwLock<Vec<Arc<dyn ChartParser>>>> = Lazy::new(|| {
+    RwLock::new(vec![
+        Arc::new(RpeParser),
+        Arc::new(PgrParser),
+        Arc::new(PecParser),
+        Arc::new(PbcParser),
+        Arc::new(MalodyParser),
+        Arc::new(NoteListParser),
+    ])
+});
+
+/// Registers a parser, replacing the one previously registered for the same format
+///
+/// When detecting formats, ties are won by the parser registered first, so built-in formats win over new ones.
+pub fn register_parser(parser: impl ChartParser + 'static) {
+    let mut parsers = PARSERS.write().unwrap();
+    let format = parser.format();
+    match parsers.iter().position(|it| it.format() == format) {
+        Some(index) => parsers[index] = Arc::new(parser),
+        None => parsers.push(Arc::new(parser)),
+    }
+}
+
+/// Formats of every registered parser, in registration order
+pub fn registered_formats() -> Vec<ChartFormat> {
+    PARSERS.read().unwrap().iter().map(|it| it.format()).collect()
+}
+
+pub fn find_parser(format: &ChartFormat) -> Option<Arc<dyn ChartParser>> {
+    PARSERS.read().unwrap().iter().find(|it| it.format() == *format).cloned()
+}
+
+/// Every format that may be the one of `bytes`, paired with its confidence, the most confident first
+pub fn rank_formats(bytes: &[u8]) -> Vec<(ChartFormat, f32)> {
+    let mut res: Vec<_> = PARSERS
+        .read()
+        .unwrap()
+        .iter()
+        .map(|it| (it.format(), it.detect(bytes)))
+        .filter(|it| it.1 > 0.)
+        .collect();
+    // stable, so earlier parsers win ties
+    res.sort_by(|x, y| y.1.total_cmp(&x.1));
+    res
+}
+
+/// The most likely format of `bytes`
+pub fn detect_format(bytes: &[u8]) -> Result<ChartFormat, ChartParseError> {
+    rank_formats(bytes).into_iter().next().map(|it| it.0).ok_or(ChartParseError::Undetected)
+}
+
+/// Parses a chart in `format`, or in the detected format if it is `None`. Returns the chart with the format it was parsed as.
+pub async fn parse_chart(
+    bytes: &[u8],
+    format: Option<&ChartFormat>,
+    fs: &mut dyn FileSystem,
+    extra: ChartExtra,
+) -> Result<(Chart, ChartFormat), ChartParseError> {
+    let format = match format {
+        Some(format) => format.clone(),
+        None => detect_format(bytes)?,
+    };
+    let parser = find_parser(&format).ok_or_else(|| ChartParseError::UnknownFormat(format.clone()))?;
+    match parser.parse(bytes, fs, extra).await {
+        Ok(chart) => Ok((chart, format)),
+        Err(source) => Err(ChartParseError::Invalid { format, source }),
+    }
+}
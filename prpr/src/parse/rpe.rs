@@ -1,6 +1,6 @@
 prpr_l10n::tl_file!("parser" ptl);
 
-use super::{process_lines, RPE_TWEEN_MAP};
+use super::{parent_cycles, process_lines, RPE_TWEEN_MAP};
 use crate::{
     core::{
         Anim, AnimFloat, AnimVector, BezierTween, BpmList, Chart, ChartExtra, ChartSettings, ClampedTween, CtrlObject, GifFrames, HitSoundMap,
@@ -21,7 +21,7 @@ use tracing::debug;
 
 pub const RPE_WIDTH: f32 = 1350.;
 pub const RPE_HEIGHT: f32 = 900.;
-const SPEED_RATIO: f32 = 10. / 45. / HEIGHT_RATIO;
+pub(crate) const SPEED_RATIO: f32 = 10. / 45. / HEIGHT_RATIO;
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -672,22 +672,8 @@ pub async fn parse_rpe(source: &str, fs: &mut dyn FileSystem, extra: ChartExtra)
                 .with_context(move || ptl!("judge-line-location-name", "jlid" => id, "name" => name))?,
         );
     }
-    fn has_cycle(line: &JudgeLine, lines: &[JudgeLine], visited: &mut Vec<usize>) -> Option<usize> {
-        if let Some(parent_index) = line.parent {
-            if visited.contains(&parent_index) {
-                return Some(parent_index);
-            }
-            visited.push(parent_index);
-            return has_cycle(&lines[parent_index], lines, visited);
-        }
-        None
-    }
-    for (i, line) in lines.iter().enumerate() {
-        let mut vec = Vec::new();
-        vec.push(i);
-        if let Some(line) = has_cycle(line, &lines, &mut vec) {
-            ptl!(bail "found infinite recursive parent relations", "line" => line)
-        }
+    if let Some(cycle) = parent_cycles(&lines.iter().map(|it| it.parent).collect::<Vec<_>>()).first() {
+        ptl!(bail "found infinite recursive parent relations", "line" => cycle[0])
     }
     process_lines(&mut lines);
     Ok(Chart::new(rpe.meta.offset as f32 / 1000.0, lines, r, ChartSettings::default(), extra, hitsounds))
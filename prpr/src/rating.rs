@@ -0,0 +1,111 @@
+//! Player rating (RKS) computed from best records
+//!
+//! A chart played with at least [MIN_ACCURACY] is worth `difficulty * ((accuracy - 0.55) / 0.45)²`, so a full perfect
+//! play is worth exactly its difficulty. The rating is the average over `BEST_N + 1` slots: the [BEST_N] best charts,
+//! plus the hardest chart played with 100% accuracy.
+
+pub const BEST_N: usize = 19;
+pub const MIN_ACCURACY: f32 = 0.7;
+
+/// Rating of a single chart
+pub fn chart_rating(difficulty: f32, accuracy: f32) -> f32 {
+    if accuracy < MIN_ACCURACY {
+        return 0.;
+    }
+    let p = (accuracy - 0.55) / 0.45;
+    difficulty.max(0.) * p * p
+}
+
+/// Best record on a chart
+#[derive(Clone, Debug)]
+pub struct ChartRecord {
+    /// Anything identifying the chart, e.g. its path
+    pub key: String,
+    pub difficulty: f32,
+    pub accuracy: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct RatedChart {
+    pub record: ChartRecord,
+    pub rating: f32,
+}
+
+impl RatedChart {
+    fn new(record: ChartRecord) -> Self {
+        Self {
+            rating: chart_rating(record.difficulty, record.accuracy),
+            record,
+        }
+    }
+
+    fn is_phi(&self) -> bool {
+        self.record.accuracy >= 1.
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Rating {
+    pub rks: f32,
+    /// Every rated chart, best first
+    pub charts: Vec<RatedChart>,
+    /// Number of leading [Rating::charts] filling the best slots
+    pub best: usize,
+    /// Index in [Rating::charts] of the chart filling the 100% accuracy slot
+    pub phi: Option<usize>,
+}
+
+impl Rating {
+    pub fn compute(records: impl IntoIterator<Item = ChartRecord>) -> Self {
+        let mut charts: Vec<_> = records.into_iter().map(RatedChart::new).filter(|it| it.rating > 0.).collect();
+        charts.sort_by(|x, y| y.rating.total_cmp(&x.rating));
+        let best = charts.len().min(BEST_N);
+        let phi = charts.iter().position(RatedChart::is_phi);
+        let sum = charts[..best].iter().map(|it| it.rating).sum::<f32>() + phi.map_or(0., |it| charts[it].rating);
+        Self {
+            rks: sum / (BEST_N + 1) as f32,
+            charts,
+            best,
+            phi,
+        }
+    }
+
+    /// Whether the chart at `index` of [Rating::charts] counts toward the rating
+    pub fn counts(&self, index: usize) -> bool {
+        index < self.best || self.phi == Some(index)
+    }
+
+    /// The rating if the best record on `record.key` were `record`
+    pub fn with(&self, record: ChartRecord) -> Self {
+        let others = self.charts.iter().filter(|it| it.record.key != record.key).map(|it| it.record.clone());
+        Self::compute(others.collect::<Vec<_>>().into_iter().chain(std::iter::once(record)))
+    }
+
+    /// Lowest accuracy on the chart that would raise the displayed rating (two decimals) by one step, `None` if even
+    /// a full perfect play would not
+    pub fn target_accuracy(&self, key: &str, difficulty: f32) -> Option<f32> {
+        if difficulty <= 0. {
+            return None;
+        }
+        let goal = ((self.rks * 100.).round() + 0.5) / 100.;
+        let slots = (BEST_N + 1) as f32;
+        // below 100% accuracy only one of the best slots can change, either this chart's own or the lowest one
+        let sum = self.charts[..self.best].iter().map(|it| it.rating).sum::<f32>() + self.phi.map_or(0., |it| self.charts[it].rating);
+        let replaced = match self.charts[..self.best].iter().find(|it| it.record.key == key) {
+            Some(it) => it.rating,
+            None if self.best == BEST_N => self.charts[BEST_N - 1].rating,
+            None => 0.,
+        };
+        let needed = goal * slots - (sum - replaced);
+        if needed < difficulty {
+            let p = (needed.max(0.) / difficulty).sqrt();
+            return Some((0.55 + 0.45 * p).max(MIN_ACCURACY));
+        }
+        let full = self.with(ChartRecord {
+            key: key.to_owned(),
+            difficulty,
+            accuracy: 1.,
+        });
+        (full.rks >= goal).then_some(1.)
+    }
+}
@@ -0,0 +1,256 @@
+//! Replay recording and playback
+//!
+//! A replay stores the judge input of every frame (see [FrameInput]) together with the settings that affect judging.
+//! Feeding the frames back through [Judge::update_with] reproduces the original [PlayResult](crate::judge::PlayResult).
+
+use crate::{
+    bin::{BinaryData, BinaryReader, BinaryWriter},
+    config::Mods,
+    core::{BadNote, Chart, Resource},
+    judge::{FrameInput, Judge},
+};
+use anyhow::{bail, Context, Result};
+use macroquad::prelude::{vec2, Touch, TouchPhase};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+const MAGIC: &[u8; 4] = b"PRPL";
+const VERSION: u8 = 1;
+
+static REPLAY_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Set the directory finished plays are saved to, replays are not recorded if this is not set
+pub fn set_replay_dir(dir: impl Into<PathBuf>) {
+    *REPLAY_DIR.lock().unwrap() = Some(dir.into());
+}
+
+pub fn replay_dir() -> Option<PathBuf> {
+    REPLAY_DIR.lock().unwrap().clone()
+}
+
+/// Hash identifying the chart a replay was recorded on
+pub fn chart_hash(chart_bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(chart_bytes))
+}
+
+pub struct Replay {
+    pub chart_hash: String,
+    pub speed: f32,
+    pub offset: f32,
+    pub mods: Mods,
+    pub aspect_ratio: f32,
+    /// Seed of [Mods::RANDOM_SPEED]
+    pub seed: u64,
+
+    /// Score, max combo and judgement counts of the recorded play
+    pub score: u32,
+    pub max_combo: u32,
+    pub counts: [u32; 4],
+
+    pub frames: Vec<FrameInput>,
+}
+
+impl Replay {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let mut r = BinaryReader::new(BufReader::new(File::open(path)?));
+        r.read()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut w = BinaryWriter::new(BufWriter::new(File::create(path)?));
+        w.write(self)?;
+        w.0.flush()?;
+        Ok(())
+    }
+
+    /// Save the replay to the replay directory, returns the path if the directory is set
+    pub fn save_to_dir(&self) -> Result<Option<PathBuf>> {
+        let Some(dir) = replay_dir() else {
+            return Ok(None);
+        };
+        let dir = dir.join(&self.chart_hash);
+        std::fs::create_dir_all(&dir).context("Failed to create replay directory")?;
+        let path = dir.join(format!("{}.replay", chrono::Utc::now().timestamp_millis()));
+        self.save(&path)?;
+        Ok(Some(path))
+    }
+
+    /// Find the latest replay of a chart in the replay directory
+    pub fn latest(chart_hash: &str) -> Result<Option<PathBuf>> {
+        let Some(dir) = replay_dir() else {
+            return Ok(None);
+        };
+        let dir = dir.join(chart_hash);
+        if !dir.is_dir() {
+            return Ok(None);
+        }
+        let mut latest: Option<(u64, PathBuf)> = None;
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let Some(time) = path
+                .file_name()
+                .and_then(|it| it.to_str())
+                .and_then(|it| it.strip_suffix(".replay"))
+                .and_then(|it| it.parse().ok())
+            else {
+                continue;
+            };
+            if latest.as_ref().map_or(true, |it| it.0 < time) {
+                latest = Some((time, path));
+            }
+        }
+        Ok(latest.map(|it| it.1))
+    }
+
+    /// Whether the given result is the same as the recorded one
+    pub fn matches(&self, judge: &Judge) -> bool {
+        let result = judge.result();
+        result.score == self.score && result.max_combo == self.max_combo && result.counts == self.counts
+    }
+}
+
+/// Feeds the frames of a replay to the judge as the chart time goes on
+#[derive(Default)]
+pub struct ReplayPlayer {
+    cursor: usize,
+}
+
+impl ReplayPlayer {
+    pub fn reset(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn finished(&self, replay: &Replay) -> bool {
+        self.cursor >= replay.frames.len()
+    }
+
+    /// Judge all recorded frames up to `res.time`
+    pub fn update(&mut self, replay: &Replay, judge: &mut Judge, res: &mut Resource, chart: &mut Chart, bad_notes: &mut Vec<BadNote>) {
+        let time = res.time;
+        let aspect_ratio = std::mem::replace(&mut res.aspect_ratio, replay.aspect_ratio);
+        while let Some(frame) = replay.frames.get(self.cursor).filter(|it| it.time <= time) {
+            res.time = frame.time;
+            // frames lag behind the chart time, and lines may be transformed relative to their parents
+            for line in &mut chart.lines {
+                line.object.set_time(frame.time);
+            }
+            judge.update_with(res, chart, bad_notes, frame);
+            self.cursor += 1;
+        }
+        res.time = time;
+        res.aspect_ratio = aspect_ratio;
+    }
+}
+
+fn read_touch<R: Read>(r: &mut BinaryReader<R>) -> Result<Touch> {
+    let id = r.uleb()?;
+    let phase = match r.read::<u8>()? {
+        0 => TouchPhase::Started,
+        1 => TouchPhase::Stationary,
+        2 => TouchPhase::Moved,
+        3 => TouchPhase::Ended,
+        4 => TouchPhase::Cancelled,
+        x => bail!("Invalid touch phase: {x}"),
+    };
+    Ok(Touch {
+        id,
+        phase,
+        position: vec2(r.read()?, r.read()?),
+        time: r.read()?,
+    })
+}
+
+fn write_touch<W: Write>(w: &mut BinaryWriter<W>, touch: &Touch) -> Result<()> {
+    w.uleb(touch.id)?;
+    w.write_val(match touch.phase {
+        TouchPhase::Started => 0_u8,
+        TouchPhase::Stationary => 1,
+        TouchPhase::Moved => 2,
+        TouchPhase::Ended => 3,
+        TouchPhase::Cancelled => 4,
+    })?;
+    w.write_val(touch.position.x)?;
+    w.write_val(touch.position.y)?;
+    w.write_val(touch.time)?;
+    Ok(())
+}
+
+impl BinaryData for FrameInput {
+    fn read_binary<R: Read>(r: &mut BinaryReader<R>) -> Result<Self> {
+        Ok(Self {
+            time: r.read()?,
+            touches: (0..r.uleb()?).map(|_| read_touch(r)).collect::<Result<_>>()?,
+            events: (0..r.uleb()?).map(|_| read_touch(r)).collect::<Result<_>>()?,
+            key_delta: r.read()?,
+            keys_down: r.uleb()? as _,
+        })
+    }
+
+    fn write_binary<W: Write>(&self, w: &mut BinaryWriter<W>) -> Result<()> {
+        w.write_val(self.time)?;
+        w.uleb(self.touches.len() as _)?;
+        for touch in &self.touches {
+            write_touch(w, touch)?;
+        }
+        w.uleb(self.events.len() as _)?;
+        for touch in &self.events {
+            write_touch(w, touch)?;
+        }
+        w.write_val(self.key_delta)?;
+        w.uleb(self.keys_down as _)?;
+        Ok(())
+    }
+}
+
+impl BinaryData for Replay {
+    fn read_binary<R: Read>(r: &mut BinaryReader<R>) -> Result<Self> {
+        let mut magic = [0; 4];
+        r.0.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            bail!("Not a replay file");
+        }
+        let version: u8 = r.read()?;
+        if version != VERSION {
+            bail!("Unsupported replay version: {version}");
+        }
+        Ok(Self {
+            chart_hash: r.read()?,
+            speed: r.read()?,
+            offset: r.read()?,
+            mods: Mods::from_bits_truncate(r.read()?),
+            aspect_ratio: r.read()?,
+            seed: r.uleb()?,
+
+            score: r.uleb()? as _,
+            max_combo: r.uleb()? as _,
+            counts: [r.uleb()? as _, r.uleb()? as _, r.uleb()? as _, r.uleb()? as _],
+
+            frames: r.array()?,
+        })
+    }
+
+    fn write_binary<W: Write>(&self, w: &mut BinaryWriter<W>) -> Result<()> {
+        w.0.write_all(MAGIC)?;
+        w.write_val(VERSION)?;
+        w.write(&self.chart_hash)?;
+        w.write_val(self.speed)?;
+        w.write_val(self.offset)?;
+        w.write_val(self.mods.bits())?;
+        w.write_val(self.aspect_ratio)?;
+        w.uleb(self.seed)?;
+
+        w.uleb(self.score as _)?;
+        w.uleb(self.max_combo as _)?;
+        for count in self.counts {
+            w.uleb(count as _)?;
+        }
+
+        w.array(&self.frames)?;
+        Ok(())
+    }
+}
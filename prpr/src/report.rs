@@ -0,0 +1,30 @@
+//! Results reported by finished plays
+//!
+//! Every finished play that is not an autoplay or a replay reports what it measured, tagged with the
+//! [local path](crate::info::ChartInfo::local_path) of its chart. Keeping the reports is left to the embedder, which
+//! drains them wherever plays can be launched.
+
+use std::sync::Mutex;
+
+/// Queue of reports, as `(local_path, value)` pairs
+pub struct Reports<T>(Mutex<Vec<(String, T)>>);
+
+impl<T> Reports<T> {
+    pub const fn new() -> Self {
+        Self(Mutex::new(Vec::new()))
+    }
+
+    pub fn report(&self, local_path: String, value: T) {
+        self.0.lock().unwrap().push((local_path, value));
+    }
+
+    pub fn take(&self) -> Vec<(String, T)> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
+
+impl<T> Default for Reports<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -3,7 +3,7 @@
 prpr_l10n::tl_file!("scene" ttl);
 
 mod ending;
-pub use ending::{EndingScene, RecordUpdateState};
+pub use ending::{EndingScene, RecordUpdateState, UploadQueued};
 
 mod game;
 pub use game::{GameMode, GameScene, SimpleRecord};
@@ -11,6 +11,11 @@ pub use game::{GameMode, GameScene, SimpleRecord};
 mod loading;
 pub use loading::{BasicPlayer, LoadingScene, UpdateFn, UploadFn};
 
+#[cfg(feature = "video")]
+mod render;
+#[cfg(feature = "video")]
+pub use render::{EncoderOptions, RenderScene};
+
 use crate::{
     ext::{draw_image, screen_aspect, LocalTask, SafeTexture, ScaleType},
     judge::Judge,
@@ -2,12 +2,11 @@ prpr_l10n::tl_file!("ending");
 
 use super::{draw_background, game::SimpleRecord, loading::UploadFn, NextScene, Scene};
 use crate::{
-    // config::{Config,Mods},
-    config::Config,
+    config::{Config, Mods},
     core::{BOLD_FONT, PGR_FONT},
     ext::{create_audio_manger, rect_shadow, semi_black, semi_white, RectExt, SafeTexture, ScaleType},
     info::ChartInfo,
-    judge::{icon_index, PlayResult},
+    judge::{icon_index, PlayResult, LIMIT_GOOD, LIMIT_PERFECT},
     scene::show_message,
     task::Task,
     time::TimeManager,
@@ -17,7 +16,7 @@ use anyhow::Result;
 use macroquad::prelude::*;
 use sasa::{AudioClip, AudioManager, Music, MusicParams};
 use serde::Deserialize;
-use std::{cell::RefCell, ops::DerefMut};
+use std::{cell::RefCell, fmt, ops::DerefMut};
 
 #[derive(Deserialize)]
 pub struct RecordUpdateState {
@@ -27,6 +26,18 @@ pub struct RecordUpdateState {
     pub new_rks: Option<f32>,
 }
 
+/// Returned by an [UploadFn] when the record could not be sent right now but has been saved to be uploaded later
+#[derive(Debug)]
+pub struct UploadQueued;
+
+impl fmt::Display for UploadQueued {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("record queued for upload")
+    }
+}
+
+impl std::error::Error for UploadQueued {}
+
 pub struct EndingScene {
     background: SafeTexture,
     illustration: SafeTexture,
@@ -43,7 +54,7 @@ pub struct EndingScene {
     player_name: String,
     player_rks: Option<f32>,
     autoplay: bool,
-    // strict_judge: bool,
+    strict_judge: bool,
     speed: f32,
     next: u8, // 0 -> none, 1 -> pop, 2 -> exit
     update_state: Option<RecordUpdateState>,
@@ -122,7 +133,7 @@ impl EndingScene {
             player_name: config.player_name.clone(),
             player_rks,
             autoplay: config.autoplay(),
-            // strict_judge: config.has_mod(Mods::STRICT_JUDGE),  // 严判模式已注释
+            strict_judge: config.has_mod(Mods::STRICT_JUDGE),
             speed: config.speed,
             next: 0,
 
@@ -201,6 +212,9 @@ impl Scene for EndingScene {
             if let Some(result) = task.take() {
                 handle.cancel();
                 match result {
+                    Err(err) if err.is::<UploadQueued>() => {
+                        show_message(tl!("upload-queued")).warn();
+                    }
                     Err(err) => {
                         let error = format!("{:?}", err.context(tl!("upload-failed")));
                         Dialog::plain(tl!("upload-failed"), error)
@@ -352,7 +366,17 @@ impl Scene for EndingScene {
             let r = ui.text("|").pos(r.right() + 0.03, r.y).color(cs).size(s).draw();
 
             let r = ui.text(tl!("error")).pos(r.right() + 0.03, r.y).color(cl).size(s).draw_using(&BOLD_FONT);
-            ui.text(format!("±{}ms", (res.std * 1000.).round() as i32))
+            let r = ui
+                .text(format!("±{}ms", (res.std * 1000.).round() as i32))
+                .pos(r.right() + 0.02, r.y)
+                .size(s)
+                .color(ct)
+                .draw_using(&BOLD_FONT);
+
+            let r = ui.text("|").pos(r.right() + 0.03, r.y).color(cs).size(s).draw();
+
+            let r = ui.text(tl!("offset")).pos(r.right() + 0.03, r.y).color(cl).size(s).draw_using(&BOLD_FONT);
+            ui.text(format!("{:+}ms", (res.mean * 1000.).round() as i32))
                 .pos(r.right() + 0.02, r.y)
                 .size(s)
                 .color(ct)
@@ -457,6 +481,70 @@ impl Scene for EndingScene {
             };
             ui.text(text).pos(r.right() + 0.03, y).size(s).draw_using(&BOLD_FONT);
 
+            if !res.hit_errors.is_empty() {
+                let dy = r.h + 0.03;
+                y += dy;
+                x -= dy / 1.9 * 0.4;
+                let h = dy * 2. - 0.05;
+                let p = ran(t, 1.2, 2.4);
+                let p = 1. - (1. - p).powi(3);
+                let early_color = Color::from_hex(0x64b5f6);
+                let late_color = Color::from_hex(0xffb74d);
+                let color = |diff: f32| {
+                    if diff.abs() <= LIMIT_PERFECT {
+                        semi_white(0.9)
+                    } else if diff < 0. {
+                        early_color
+                    } else {
+                        late_color
+                    }
+                };
+
+                // histogram of hit errors, early on the left
+                let hr = Rect::new(x - 0.24, y, 0.3, h);
+                ui.fill_rect(hr, semi_black(0.4));
+                let bins = 15;
+                let hist = res.histogram(bins);
+                let max = hist.iter().copied().max().unwrap_or(0).max(1) as f32;
+                let bw = hr.w / bins as f32;
+                for (i, count) in hist.into_iter().enumerate() {
+                    let bh = (hr.h - 0.01) * count as f32 / max * p;
+                    let diff = ((i as f32 + 0.5) / bins as f32 * 2. - 1.) * LIMIT_GOOD;
+                    ui.fill_rect(Rect::new(hr.x + bw * i as f32 + 0.002, hr.bottom() - bh, bw - 0.004, bh), color(diff));
+                }
+                ui.fill_rect(Rect::new(hr.center().x - 0.001, hr.y, 0.002, hr.h), semi_white(0.4));
+                ui.text(format!("{} {}", tl!("early"), res.early))
+                    .pos(hr.x, hr.bottom() + 0.005)
+                    .color(early_color)
+                    .size(0.3)
+                    .draw();
+                ui.text(format!("{} {}", res.late, tl!("late")))
+                    .pos(hr.right(), hr.bottom() + 0.005)
+                    .anchor(1., 0.)
+                    .color(late_color)
+                    .size(0.3)
+                    .draw();
+
+                // early / late timeline over the chart, early above the center line
+                let tr = Rect::new(hr.right() + 0.03, y, 0.45, h);
+                ui.fill_rect(tr, semi_black(0.4));
+                ui.fill_rect(Rect::new(tr.x, tr.center().y - 0.001, tr.w, 0.002), semi_white(0.4));
+                let start = res.hit_errors.iter().map(|it| it.time).fold(f32::INFINITY, f32::min);
+                let end = res.hit_errors.iter().map(|it| it.time).fold(f32::NEG_INFINITY, f32::max);
+                let len = (end - start).max(1e-3);
+                let d = 0.004;
+                for it in &res.hit_errors {
+                    let px = (it.time - start) / len;
+                    if px > p {
+                        continue;
+                    }
+                    let py = (it.diff / LIMIT_GOOD).clamp(-1., 1.) * 0.5;
+                    let cx = tr.x + d + px * (tr.w - d * 2.);
+                    let cy = tr.center().y + py * (tr.h - d * 2.);
+                    ui.fill_rect(Rect::new(cx - d, cy - d, d * 2., d * 2.), color(it.diff));
+                }
+            }
+
             let mut r = Rect::new(0.96, ui.top - 0.04, 0.25, 0.1);
             r.x -= r.w;
             r.y -= r.h;
@@ -490,22 +578,21 @@ impl Scene for EndingScene {
             } else {
                 format!("{:.2}x", self.speed)
             };
-            // 严判模式已注释
-            // let strict = if self.strict_judge { "STRICT" } else { "" };
-            let text = if self.autoplay {
-                format!("AUTOPLAY {spd}")
+            let strict = if self.strict_judge { "STRICT" } else { "" };
+            let tag = if self.autoplay {
+                "AUTOPLAY"
             } else if !self.rated {
-                format!("UNRATED {spd}")
+                "UNRATED"
             } else {
-                spd
+                ""
             };
-            let text = text.trim();
+            let text = [tag, strict, &spd].into_iter().filter(|it| !it.is_empty()).collect::<Vec<_>>().join(" ");
             if !text.is_empty() {
                 let ty = br.bottom();
                 let x = -0.55 + (1.2 - ty) / 1.9 * 0.4;
                 let h = 0.04;
                 let mut text = ui
-                    .text(text)
+                    .text(&text)
                     .pos(x + 0.02, ty - h / 2.)
                     .anchor(0., 0.5)
                     .no_baseline()
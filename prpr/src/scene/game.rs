@@ -6,22 +6,25 @@ use super::{
     draw_background,
     ending::RecordUpdateState,
     loading::{BasicPlayer, UpdateFn, UploadFn},
-    request_input, return_input, show_message, take_input, EndingScene, NextScene, Scene,
+    request_input, return_input, show_error, show_message, take_input, EndingScene, NextScene, Scene,
 };
 use crate::{
-    bin::BinaryReader,
+    calibrate::{self, OffsetSample},
     config::{Config, Mods},
-    core::{copy_fbo, BadNote, Chart, ChartExtra, Effect, Point, Resource, UIElement, Vector, PGR_FONT},
-    ext::{parse_time, screen_aspect, semi_white, RectExt, SafeTexture, ScaleType},
-    fs::FileSystem,
+    core::{copy_fbo, BadNote, Chart, ChartExtra, Effect, Point, ResPackWatcher, Resource, ResourcePack, UIElement, Vector, PGR_FONT},
+    ext::{parse_time, poll_future, screen_aspect, semi_white, LocalTask, RectExt, SafeTexture, ScaleType},
+    fs::{fs_from_file, FileSystem},
+    history::{self, PlayAttempt},
     info::{ChartFormat, ChartInfo},
-    judge::Judge,
-    parse::{parse_extra, parse_pec, parse_phigros, parse_rpe},
+    judge::{FrameInput, Judge, Judgement, LIMIT_BAD},
+    parse::{parse_chart, parse_extra},
+    replay::{chart_hash, replay_dir, Replay, ReplayPlayer},
     task::Task,
     time::TimeManager,
     ui::{RectButton, TextPainter, Ui},
 };
 use anyhow::{bail, Context, Result};
+use chrono::Utc;
 use concat_string::concat_string;
 use lyon::path::Path;
 use macroquad::{prelude::*, window::InternalGlContext};
@@ -31,7 +34,7 @@ use std::{
     any::Any,
     cell::RefCell,
     fs::File,
-    io::{Cursor, ErrorKind},
+    io::ErrorKind,
     ops::{Deref, DerefMut, Range},
     path::PathBuf,
     process::{Command, Stdio},
@@ -96,15 +99,26 @@ extern "C" {
     fn on_game_start();
 }
 
-#[derive(PartialEq, Eq)]
 pub enum GameMode {
     Normal,
     TweakOffset,
     Exercise,
     NoRetry,
     View,
+    Replay(Arc<Replay>),
 }
 
+impl PartialEq for GameMode {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Replay(a), Self::Replay(b)) => Arc::ptr_eq(a, b),
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+impl Eq for GameMode {}
+
 #[derive(Clone)]
 enum State {
     Starting,
@@ -125,6 +139,7 @@ pub struct GameScene {
     player: Option<BasicPlayer>,
     chart_bytes: Vec<u8>,
     chart_format: ChartFormat,
+    seed: u64,
     info_offset: f32,
     effects: Vec<Effect>,
 
@@ -132,6 +147,12 @@ pub struct GameScene {
     exercise_range: Range<f32>,
     exercise_press: Option<(i8, u64)>,
     exercise_btns: (RectButton, RectButton),
+    /// Speed increase after each loop in exercise mode, until reaching 1x
+    exercise_ramp: f32,
+    /// Accuracy of the exercised section in each finished loop
+    exercise_loops: Vec<f32>,
+
+    replay_player: ReplayPlayer,
 
     pub music: Music,
 
@@ -146,12 +167,17 @@ pub struct GameScene {
     update_fn: Option<UpdateFn>,
 
     pub touch_points: Vec<(f32, f32)>,
+
+    res_pack_watcher: Option<ResPackWatcher>,
+    res_pack_task: LocalTask<Result<ResourcePack>>,
 }
 
 macro_rules! reset {
     ($self:ident, $res:expr, $tm:ident) => {{
         $self.bad_notes.clear();
         $self.judge.reset();
+        $self.replay_player.reset();
+        $self.exercise_loops.clear();
         $self.chart.reset();
         $res.judge_line_color = Color::from_hex($res.res_pack.info.color_perfect);
         $self.music.pause()?;
@@ -187,30 +213,7 @@ impl GameScene {
             ChartExtra::default()
         };
         let bytes = Self::load_chart_bytes(fs, info).await.context("Failed to load chart")?;
-        let format = info.format.clone().unwrap_or_else(|| {
-            if let Ok(text) = String::from_utf8(bytes.clone()) {
-                if text.starts_with('{') {
-                    if text.contains("\"META\"") {
-                        ChartFormat::Rpe
-                    } else {
-                        ChartFormat::Pgr
-                    }
-                } else {
-                    ChartFormat::Pec
-                }
-            } else {
-                ChartFormat::Pbc
-            }
-        });
-        let mut chart = match format {
-            ChartFormat::Rpe => parse_rpe(&String::from_utf8_lossy(&bytes), fs, extra).await,
-            ChartFormat::Pgr => parse_phigros(&String::from_utf8_lossy(&bytes), extra),
-            ChartFormat::Pec => parse_pec(&String::from_utf8_lossy(&bytes), extra),
-            ChartFormat::Pbc => {
-                let mut r = BinaryReader::new(Cursor::new(&bytes));
-                r.read()
-            }
-        }?;
+        let (mut chart, format) = parse_chart(&bytes, info.format.as_ref(), fs, extra).await?;
         chart.load_textures(fs).await?;
         chart.settings.hold_partial_cover = info.hold_partial_cover;
         Ok((chart, bytes, format))
@@ -234,10 +237,27 @@ impl GameScene {
             GameMode::Exercise => {
                 config.mods.remove(Mods::AUTOPLAY);
             }
+            GameMode::Replay(ref replay) => {
+                config.speed = replay.speed;
+                config.offset = replay.offset;
+                config.mods = replay.mods - Mods::AUTOPLAY;
+            }
             _ => {}
         }
 
         let (mut chart, chart_bytes, chart_format) = Self::load_chart(fs.deref_mut(), &info).await?;
+        if let GameMode::Replay(replay) = &mode {
+            if chart_hash(&chart_bytes) != replay.chart_hash {
+                bail!(tl!("replay-chart-mismatch"));
+            }
+        }
+        let seed = match &mode {
+            GameMode::Replay(replay) => replay.seed,
+            _ => ::rand::random(),
+        };
+        if config.has_mod(Mods::RANDOM_SPEED) {
+            chart.randomize_speed(seed);
+        }
         let effects = std::mem::take(&mut chart.extra.global_effects);
         if config.fxaa {
             chart
@@ -261,16 +281,25 @@ impl GameScene {
 
         // Prepare extra sfx from chart.hitsounds
         chart.hitsounds.drain().for_each(|(name, clip)| {
-            if let Ok(clip) = res.create_sfx(clip) {
-                res.extra_sfxs.insert(name, clip);
+            if let Ok(sfx) = res.create_sfx(clip.clone()) {
+                res.extra_sfxs.insert(name.clone(), sfx);
+                res.extra_clips.insert(name, clip);
             }
         });
 
         let exercise_range = (chart.offset + info_offset + res.config.offset)..res.track_length;
 
-        let judge = Judge::new(&chart);
+        let mut judge = Judge::new(&chart);
+        judge.strict = res.config.has_mod(Mods::STRICT_JUDGE);
+        if matches!(mode, GameMode::Normal | GameMode::NoRetry) && !res.config.autoplay() && replay_dir().is_some() {
+            judge.record = Some(Vec::new());
+        }
 
         let music = Self::new_music(&mut res)?;
+        let res_pack_watcher = match &res.config.res_pack_path {
+            Some(path) if res.config.res_pack_watch => ResPackWatcher::new(path),
+            _ => None,
+        };
         Ok(Self {
             should_exit: false,
             next_scene: None,
@@ -283,6 +312,7 @@ impl GameScene {
             player,
             chart_bytes,
             chart_format,
+            seed,
             effects,
             info_offset,
 
@@ -290,6 +320,10 @@ impl GameScene {
             exercise_range,
             exercise_press: None,
             exercise_btns: (RectButton::new(), RectButton::new()),
+            exercise_ramp: 0.,
+            exercise_loops: Vec::new(),
+
+            replay_player: ReplayPlayer::default(),
 
             music,
 
@@ -304,9 +338,47 @@ impl GameScene {
             update_fn,
 
             touch_points: Vec::new(),
+
+            res_pack_watcher,
+            res_pack_task: None,
         })
     }
 
+    /// Whether the music is playing, i.e. the play is neither starting nor ending
+    pub(crate) fn is_playing(&self) -> bool {
+        matches!(self.state, State::Playing)
+    }
+
+    /// Reloads the resource pack once its files change, see [Config::res_pack_watch]
+    ///
+    /// A pack that fails to load is reported and the previous one stays, the play goes on either way.
+    fn update_res_pack(&mut self, tm: &TimeManager) {
+        if let Some(watcher) = &mut self.res_pack_watcher {
+            if self.res_pack_task.is_none() && watcher.changed(tm.real_time()) {
+                let path = watcher.path().to_owned();
+                self.res_pack_task = Some(Box::pin(async move {
+                    let mut fs = fs_from_file(&path)?;
+                    let issues = ResourcePack::validate(fs.as_mut()).await;
+                    if !issues.is_empty() {
+                        bail!("{}", issues.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"));
+                    }
+                    ResourcePack::load(fs.as_mut()).await
+                }));
+            }
+        }
+        if let Some(task) = &mut self.res_pack_task {
+            if let Some(res) = poll_future(task.as_mut()) {
+                self.res_pack_task = None;
+                match res.and_then(|res_pack| self.res.set_res_pack(res_pack)) {
+                    Ok(()) => {
+                        show_message(tl!("res-pack-reloaded")).ok();
+                    }
+                    Err(err) => show_error(err.context(tl!("res-pack-reload-failed"))),
+                }
+            }
+        }
+    }
+
     fn new_music(res: &mut Resource) -> Result<Music> {
         res.audio.create_music(
             res.music.clone(),
@@ -318,6 +390,85 @@ impl GameScene {
         )
     }
 
+    /// The exercised section in chart time
+    fn exercise_chart_range(&self) -> Range<f32> {
+        let offset = self.offset();
+        (self.exercise_range.start - offset)..(self.exercise_range.end - offset)
+    }
+
+    /// Parse a boundary of the exercised section, `#n` stands for the start of the n-th measure (assuming 4 beats each)
+    fn parse_exercise_time(&self, text: &str) -> Option<f32> {
+        let Some(measure) = text.trim().strip_prefix('#') else {
+            return parse_time(text);
+        };
+        let measure: f32 = measure.parse().ok()?;
+        if measure < 1. || self.chart.bpm_list.borrow().is_empty() {
+            return None;
+        }
+        Some(self.chart.bpm_list.borrow_mut().time_beats((measure - 1.) * 4.) + self.offset())
+    }
+
+    fn fmt_exercise_time(&self, t: f32) -> String {
+        if self.chart.bpm_list.borrow().is_empty() {
+            return fmt_time(t);
+        }
+        let measure = self.chart.bpm_list.borrow_mut().beat(t - self.offset()) / 4. + 1.;
+        format!("{} #{measure:.1}", fmt_time(t))
+    }
+
+    /// Accuracy of the exercised section, counting notes not judged yet as missed
+    fn exercise_accuracy(&self) -> Option<f32> {
+        let range = self.exercise_chart_range();
+        let total = self
+            .chart
+            .lines
+            .iter()
+            .flat_map(|it| &it.notes)
+            .filter(|it| !it.fake && range.contains(&it.time))
+            .count();
+        if total == 0 {
+            return None;
+        }
+        let (mut perfect, mut good) = (0, 0);
+        for (_, line_id, note_id, judgement) in self.judge.judgements.borrow().iter() {
+            if !range.contains(&self.chart.lines[*line_id as usize].notes[*note_id as usize].time) {
+                continue;
+            }
+            match judgement {
+                Ok(Judgement::Perfect) => perfect += 1,
+                Ok(Judgement::Good) => good += 1,
+                _ => {}
+            }
+        }
+        Some((perfect as f32 + good as f32 * 0.65) / total as f32)
+    }
+
+    /// Jump back to the start of the exercised section, only resetting notes inside it
+    fn next_exercise_loop(&mut self, tm: &mut TimeManager) -> Result<()> {
+        if let Some(acc) = self.exercise_accuracy() {
+            self.exercise_loops.push(acc);
+        }
+        let range = self.exercise_chart_range();
+        self.bad_notes.clear();
+        self.chart.reset_range(range.clone());
+        self.judge.reset_range(&self.chart, range);
+        if self.exercise_ramp > 0. && self.res.config.speed < 1. {
+            self.res.config.speed = (self.res.config.speed + self.exercise_ramp).min(1.);
+            self.music = Self::new_music(&mut self.res)?;
+            tm.speed = self.res.config.speed as _;
+        }
+        let start = self.exercise_range.start;
+        tm.seek_to(start as f64);
+        if start < 0. {
+            self.music.pause()?;
+            self.state = State::BeforeMusic;
+        } else {
+            self.music.seek_to(start)?;
+            self.music.play()?;
+        }
+        Ok(())
+    }
+
     fn touch_scale(&self) -> f32 {
         (screen_width() / screen_height()) / self.res.aspect_ratio
     }
@@ -605,6 +756,11 @@ impl GameScene {
                     ui.dy(-0.3);
                     ui.slider(tl!("speed"), 0.5..2.0, 0.05, &mut self.res.config.speed, Some(0.5));
                 });
+                ui.scope(|ui| {
+                    ui.dx(-0.95);
+                    ui.dy(-0.3);
+                    ui.slider(tl!("speed-ramp"), 0.0..0.1, 0.01, &mut self.exercise_ramp, None);
+                });
                 ui.dy(0.06);
                 let hw = 0.7;
                 let h = 0.06;
@@ -679,7 +835,7 @@ impl GameScene {
                 ui.dy(0.2);
                 let r = ui.text(tl!("to")).size(0.8).anchor(0.5, 0.).draw();
                 let mut tx = ui
-                    .text(fmt_time(self.exercise_range.start))
+                    .text(self.fmt_exercise_time(self.exercise_range.start))
                     .pos(r.x - 0.02, 0.)
                     .anchor(1., 0.)
                     .size(0.8)
@@ -691,7 +847,7 @@ impl GameScene {
                 tx.draw();
 
                 let mut tx = ui
-                    .text(fmt_time(self.exercise_range.end))
+                    .text(self.fmt_exercise_time(self.exercise_range.end))
                     .pos(r.right() + 0.02, 0.)
                     .size(0.8)
                     .color(BLACK);
@@ -700,10 +856,33 @@ impl GameScene {
                 tx.ui
                     .fill_rect(re.feather(0.01), Color::new(1., 1., 1., if self.exercise_btns.1.touching() { 0.5 } else { 1. }));
                 tx.draw();
+                if let Some(last) = self.exercise_loops.last() {
+                    let best = self.exercise_loops.iter().copied().fold(0., f32::max);
+                    ui.dy(0.1);
+                    ui.text(tl!(
+                        "ex-loop-stats",
+                        "loops" => self.exercise_loops.len().to_string(),
+                        "last" => format!("{:.2}%", last * 100.),
+                        "best" => format!("{:.2}%", best * 100.)
+                    ))
+                    .anchor(0.5, 0.)
+                    .size(0.5)
+                    .draw();
+                }
                 for touch in ui.ensure_touches() {
                     touch.position /= asp;
                 }
             }
+        } else if self.mode == GameMode::Exercise {
+            if let Some(last) = self.exercise_loops.last() {
+                let h = 1. / self.res.aspect_ratio;
+                ui.text(tl!("ex-loop", "loops" => self.exercise_loops.len().to_string(), "last" => format!("{:.2}%", last * 100.)))
+                    .pos(-0.98, h - 0.02)
+                    .anchor(0., 1.)
+                    .size(0.4)
+                    .color(c)
+                    .draw();
+            }
         }
         if let Some(time) = self.pause_rewind {
             let dt = tm.now() - time;
@@ -728,6 +907,28 @@ impl GameScene {
         Ok(())
     }
 
+    fn save_replay(&self, frames: Vec<FrameInput>) -> Result<()> {
+        let result = self.judge.result();
+        let replay = Replay {
+            chart_hash: chart_hash(&self.chart_bytes),
+            speed: self.res.config.speed,
+            offset: self.res.config.offset,
+            mods: self.res.config.mods,
+            aspect_ratio: self.res.aspect_ratio,
+            seed: self.seed,
+
+            score: result.score,
+            max_combo: result.max_combo,
+            counts: result.counts,
+
+            frames,
+        };
+        if let Some(path) = replay.save_to_dir()? {
+            debug!(?path, "replay saved");
+        }
+        Ok(())
+    }
+
     fn interactive(res: &Resource, state: &State) -> bool {
         res.config.interactive && matches!(state, State::Playing)
     }
@@ -825,16 +1026,17 @@ impl Scene for GameScene {
 
     fn update(&mut self, tm: &mut TimeManager) -> Result<()> {
         self.res.audio.recover_if_needed()?;
+        self.update_res_pack(tm);
         if matches!(self.state, State::Playing) {
             tm.update(self.music.position() as f64);
         }
-        if self.mode == GameMode::Exercise && tm.now() > self.exercise_range.end as f64 && !tm.paused() {
-            let state = self.state.clone();
-            reset!(self, self.res, tm);
-            self.state = state;
-            tm.seek_to(self.exercise_range.start as f64);
-            tm.pause();
-            self.music.pause()?;
+        // wait for the last notes of the section to be judged
+        if self.mode == GameMode::Exercise
+            && tm.now() > (self.exercise_range.end + LIMIT_BAD * self.res.config.speed) as f64
+            && !tm.paused()
+            && matches!(self.state, State::Playing | State::BeforeMusic)
+        {
+            self.next_exercise_loop(tm)?;
         }
         let offset = self.offset();
         let time = tm.now() as f32;
@@ -895,11 +1097,17 @@ impl Scene for GameScene {
             State::Ending => {
                 let t = time - self.res.track_length - WAIT_TIME;
                 if t >= AFTER_TIME + 0.3 {
+                    let replaying = matches!(self.mode, GameMode::Replay(_));
                     let mut record_data = None;
                     // TODO strengthen the protection
                     #[cfg(feature = "closed")]
                     if let Some(upload_fn) = &self.upload_fn {
-                        if !self.res.config.offline_mode && !self.res.config.autoplay() && self.res.config.speed >= 1.0 - 1e-3 {
+                        if !replaying
+                            && !self.res.config.offline_mode
+                            && !self.res.config.autoplay()
+                            && !self.res.config.mods.intersects(Mods::UNRANKED)
+                            && self.res.config.speed >= 1.0 - 1e-3
+                        {
                             if let Some(player) = &self.player {
                                 if let Some(chart) = &self.res.info.id {
                                     record_data = Some(encode_record(self, player.id, *chart));
@@ -907,8 +1115,40 @@ impl Scene for GameScene {
                             }
                         }
                     }
+                    if let GameMode::Replay(replay) = &self.mode {
+                        if !replay.matches(&self.judge) {
+                            show_message(tl!("replay-result-mismatch")).warn();
+                        }
+                    }
+                    if let Some(frames) = self.judge.record.take() {
+                        if let Err(err) = self.save_replay(frames) {
+                            warn!(?err, "failed to save replay");
+                        }
+                    }
                     let result = self.judge.result();
-                    let record = if self.res.config.autoplay() || self.res.config.speed < 1.0 - 1e-3 {
+                    if matches!(self.mode, GameMode::Normal | GameMode::NoRetry) && !self.res.config.autoplay() {
+                        if let Some(local_path) = &self.res.info.local_path {
+                            if let Some(sample) = OffsetSample::from_result(&result, self.res.config.offset, self.res.config.speed) {
+                                calibrate::REPORTED.report(local_path.clone(), sample);
+                            }
+                            history::REPORTED.report(
+                                local_path.clone(),
+                                PlayAttempt {
+                                    time: Utc::now(),
+                                    chart_hash: chart_hash(&self.chart_bytes),
+                                    mods: self.res.config.mods,
+                                    speed: self.res.config.speed,
+                                    offset: self.res.config.offset,
+                                    result: result.clone(),
+                                },
+                            );
+                        }
+                    }
+                    let record = if replaying
+                        || self.res.config.autoplay()
+                        || self.res.config.mods.intersects(Mods::UNRANKED)
+                        || self.res.config.speed < 1.0 - 1e-3
+                    {
                         None
                     } else {
                         Some(SimpleRecord {
@@ -918,23 +1158,25 @@ impl Scene for GameScene {
                         })
                     };
                     self.next_scene = match self.mode {
-                        GameMode::Normal | GameMode::NoRetry | GameMode::View => Some(NextScene::Overlay(Box::new(EndingScene::new(
-                            self.res.background.clone(),
-                            self.res.illustration.clone(),
-                            self.res.player.clone(),
-                            self.res.icons.clone(),
-                            self.res.icon_retry.clone(),
-                            self.res.icon_proceed.clone(),
-                            self.res.info.clone(),
-                            self.judge.result(),
-                            &self.res.config,
-                            self.res.res_pack.ending.clone(),
-                            self.upload_fn.as_ref().map(Arc::clone),
-                            self.player.as_ref().map(|it| it.rks),
-                            self.player.as_ref().map_or(0, |it| it.historic_best),
-                            record_data,
-                            record,
-                        )?))),
+                        GameMode::Normal | GameMode::NoRetry | GameMode::View | GameMode::Replay(_) => {
+                            Some(NextScene::Overlay(Box::new(EndingScene::new(
+                                self.res.background.clone(),
+                                self.res.illustration.clone(),
+                                self.res.player.clone(),
+                                self.res.icons.clone(),
+                                self.res.icon_retry.clone(),
+                                self.res.icon_proceed.clone(),
+                                self.res.info.clone(),
+                                self.judge.result(),
+                                &self.res.config,
+                                self.res.res_pack.ending.clone(),
+                                self.upload_fn.as_ref().filter(|_| !replaying).map(Arc::clone),
+                                self.player.as_ref().map(|it| it.rks),
+                                self.player.as_ref().map_or(0, |it| it.historic_best),
+                                record_data,
+                                record,
+                            )?)))
+                        }
                         GameMode::TweakOffset => Some(NextScene::PopWithResult(Box::new(None::<f32>))),
                         GameMode::Exercise => None,
                     };
@@ -947,13 +1189,28 @@ impl Scene for GameScene {
         self.res.time = time;
         if !tm.paused() && self.pause_rewind.is_none() && self.mode != GameMode::View {
             self.gl.quad_gl.viewport(self.res.camera.viewport);
-            self.judge.update(&mut self.res, &mut self.chart, &mut self.bad_notes);
+            if let GameMode::Replay(replay) = &self.mode {
+                self.replay_player
+                    .update(replay, &mut self.judge, &mut self.res, &mut self.chart, &mut self.bad_notes);
+            } else {
+                self.judge.update(&mut self.res, &mut self.chart, &mut self.bad_notes);
+            }
             self.gl.quad_gl.viewport(None);
         }
         if let Some(update) = &mut self.update_fn {
             update(self.res.time, &mut self.res, &mut self.judge);
         }
         let counts = self.judge.counts();
+        if counts[2] + counts[3] != 0
+            && self.res.config.has_mod(Mods::SUDDEN_DEATH)
+            && matches!(self.mode, GameMode::Normal | GameMode::NoRetry | GameMode::Replay(_))
+            && matches!(self.state, State::Playing)
+        {
+            // end the play right away
+            self.music.pause()?;
+            tm.seek_to((self.res.track_length + WAIT_TIME) as f64);
+            self.state = State::Ending;
+        }
         self.res.judge_line_color = if counts[2] + counts[3] == 0 {
             Color::from_hex(if counts[1] == 0 {
                 self.res.res_pack.info.color_perfect
@@ -1003,7 +1260,7 @@ impl Scene for GameScene {
             let offset = self.offset().min(0.);
             match id.as_str() {
                 "exercise_start" => {
-                    if let Some(t) = parse_time(&text) {
+                    if let Some(t) = self.parse_exercise_time(&text) {
                         if !(offset..self.res.track_length.min(self.exercise_range.end - 3.).max(offset)).contains(&t) {
                             show_message(tl!("ex-time-out-of-range")).error();
                         } else {
@@ -1015,7 +1272,7 @@ impl Scene for GameScene {
                     }
                 }
                 "exercise_end" => {
-                    if let Some(t) = parse_time(&text) {
+                    if let Some(t) = self.parse_exercise_time(&text) {
                         if !((self.exercise_range.start + 3.).max(offset).min(self.res.track_length)..self.res.track_length).contains(&t) {
                             show_message(tl!("ex-time-out-of-range")).error();
                         } else {
@@ -1164,7 +1421,7 @@ impl Scene for GameScene {
             tm.speed = 1.0;
             tm.adjust_time = false;
             match self.mode {
-                GameMode::Normal | GameMode::Exercise | GameMode::NoRetry | GameMode::View => NextScene::Pop,
+                GameMode::Normal | GameMode::Exercise | GameMode::NoRetry | GameMode::View | GameMode::Replay(_) => NextScene::Pop,
                 GameMode::TweakOffset => NextScene::PopWithResult(Box::new(None::<f32>)),
             }
         } else if let Some(next_scene) = self.next_scene.take() {
@@ -0,0 +1,293 @@
+//! Offline rendering of a play into a video file
+//!
+//! The game is driven by a manual clock at a fixed frame rate and drawn onto an offscreen target, so no frame is dropped
+//! however long a frame takes to render. Audio is not played but mixed from the music and the hit sounds logged by
+//! [Resource::sfx_log](crate::core::Resource::sfx_log), keeping it in sync with the video. Hit sounds are only known
+//! after the frame they were played in, so audio is written one frame behind the video.
+
+use super::{draw_background, game::GameMode, ttl, GameScene, NextScene, Scene};
+use crate::{
+    config::{Config, Mods},
+    ext::{poll_future, LocalTask, SafeTexture},
+    fs::FileSystem,
+    info::ChartInfo,
+    judge::HitSound,
+    scene::{show_error, show_message},
+    time::TimeManager,
+    ui::{LoadingParams, Ui},
+};
+use anyhow::{bail, Context, Result};
+use macroquad::prelude::*;
+use prpr_avc::Encoder;
+pub use prpr_avc::EncoderOptions;
+use sasa::AudioClip;
+use std::{cell::Cell, ops::Range, rc::Rc, time::Instant};
+
+/// Real time spent rendering frames before giving control back to the main loop
+const FRAME_BUDGET: f64 = 1. / 30.;
+
+/// Samples waiting to be encoded, starting at output sample `offset`
+struct Mixer {
+    sample_rate: u32,
+    offset: usize,
+    buffer: Vec<f32>,
+}
+
+impl Mixer {
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            offset: 0,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Adds `clip` over the output samples in `range`, starting `pos` seconds into the clip and played at `rate`
+    fn add(&mut self, clip: &AudioClip, range: Range<usize>, pos: f64, rate: f64, volume: f32) {
+        let frames = clip.frames();
+        let step = rate * clip.sample_rate() as f64 / self.sample_rate as f64;
+        let mut src = pos * clip.sample_rate() as f64;
+        for index in range {
+            if src < 0. {
+                src += step;
+                continue;
+            }
+            let i = src as usize;
+            if i + 1 >= frames.len() {
+                break;
+            }
+            let p = (src - i as f64) as f32;
+            let (a, b) = (&frames[i], &frames[i + 1]);
+            let at = (index - self.offset) * 2;
+            if self.buffer.len() < at + 2 {
+                self.buffer.resize(at + 2, 0.);
+            }
+            self.buffer[at] += (a.0 + (b.0 - a.0) * p) * volume;
+            self.buffer[at + 1] += (a.1 + (b.1 - a.1) * p) * volume;
+            src += step;
+        }
+    }
+
+    /// Takes the mixed samples before output sample `end`
+    fn take(&mut self, end: usize) -> Vec<f32> {
+        let len = (end - self.offset) * 2;
+        if self.buffer.len() < len {
+            self.buffer.resize(len, 0.);
+        }
+        self.offset = end;
+        self.buffer.drain(..len).map(|it| it.clamp(-1., 1.)).collect()
+    }
+}
+
+pub struct RenderScene {
+    path: String,
+    options: EncoderOptions,
+    volume_music: f32,
+    volume_sfx: f32,
+
+    load_task: LocalTask<Result<GameScene>>,
+    game: Option<GameScene>,
+    target: Option<RenderTarget>,
+    encoder: Option<Encoder>,
+    mixer: Mixer,
+
+    clock: Rc<Cell<f64>>,
+    tm: TimeManager,
+    frame: u64,
+
+    next_scene: Option<NextScene>,
+}
+
+impl RenderScene {
+    /// Renders a play of `mode`, which is either [GameMode::Replay] or [GameMode::Normal] with autoplay forced on
+    pub fn new(
+        mode: GameMode,
+        info: ChartInfo,
+        mut config: Config,
+        fs: Box<dyn FileSystem>,
+        background: SafeTexture,
+        illustration: SafeTexture,
+        path: String,
+        options: EncoderOptions,
+    ) -> Self {
+        let volume_music = config.volume_music;
+        let volume_sfx = config.volume_sfx;
+        // audio is mixed offline, nothing should be heard or waited for while rendering
+        config.volume_music = 0.;
+        config.volume_sfx = 0.;
+        config.adjust_time = false;
+        config.interactive = false;
+        if !matches!(mode, GameMode::Replay(_)) {
+            config.mods.insert(Mods::AUTOPLAY);
+        }
+        let mode = if matches!(mode, GameMode::Replay(_)) { mode } else { GameMode::Normal };
+        let clock = Rc::new(Cell::new(0.));
+        let tm = TimeManager::manual(Box::new({
+            let clock = Rc::clone(&clock);
+            move || clock.get()
+        }));
+        Self {
+            mixer: Mixer::new(options.sample_rate as _),
+            path,
+            options,
+            volume_music,
+            volume_sfx,
+
+            load_task: Some(Box::pin(GameScene::new(mode, info, config, fs, None, background, illustration, None, None))),
+            game: None,
+            target: None,
+            encoder: None,
+            clock,
+            tm,
+            frame: 0,
+
+            next_scene: None,
+        }
+    }
+
+    fn start(&mut self, mut game: GameScene) -> Result<()> {
+        let target = render_target(self.options.width as _, self.options.height as _);
+        target.texture.set_filter(FilterMode::Linear);
+        game.res.sfx_log = Some(Vec::new());
+        game.enter(&mut self.tm, Some(target))?;
+        self.encoder = Some(Encoder::new(&self.path, &self.options).context("failed to create encoder")?);
+        self.target = Some(target);
+        self.game = Some(game);
+        Ok(())
+    }
+
+    /// Renders and encodes the next frame, returns `false` once the play is over
+    fn step(&mut self, ui: &mut Ui) -> Result<bool> {
+        let game = self.game.as_mut().unwrap();
+        let fps = self.options.fps as u64;
+        self.clock.set(self.frame as f64 / fps as f64);
+        game.update(&mut self.tm)?;
+        if !matches!(game.next_scene(&mut self.tm), NextScene::None) {
+            return Ok(false);
+        }
+
+        let (w, h) = (self.options.width, self.options.height);
+        let mut frame_ui = Ui::new(&mut *ui.text_painter, Some((0, 0, w, h)));
+        game.render(&mut self.tm, &mut frame_ui)?;
+        drop(frame_ui);
+        unsafe { get_internal_gl() }.flush();
+        let image = self.target.unwrap().texture.get_texture_data();
+        let row = w as usize * 4;
+        if row == 0 || image.bytes.len() != row * h as usize {
+            bail!("unexpected frame of {}x{} ({} bytes), expected {w}x{h}", image.width, image.height, image.bytes.len());
+        }
+        // OpenGL stores rows from bottom to top
+        let flipped: Vec<u8> = image.bytes.chunks_exact(row).rev().flatten().copied().collect();
+        let encoder = self.encoder.as_mut().unwrap();
+        encoder.write_video(&flipped)?;
+
+        let sample_rate = self.options.sample_rate as u64;
+        let start = (self.frame * sample_rate / fps) as usize;
+        let end = ((self.frame + 1) * sample_rate / fps) as usize;
+        let speed = game.res.config.speed as f64;
+        if game.is_playing() {
+            self.mixer.add(&game.res.music, start..end, self.tm.now(), speed, self.volume_music);
+        }
+        let now = self.clock.get();
+        for (time, sound) in game.res.sfx_log.as_mut().map(std::mem::take).unwrap_or_default() {
+            let res = &game.res;
+            let clip = match &sound {
+                HitSound::None => continue,
+                HitSound::Click => &res.res_pack.sfx_click,
+                HitSound::Flick => &res.res_pack.sfx_flick,
+                HitSound::Drag => &res.res_pack.sfx_drag,
+                HitSound::Custom(name) => match res.extra_clips.get(name) {
+                    Some(clip) => clip,
+                    None => continue,
+                },
+            };
+            // the sound was played at chart time `time`, which may lie between this frame and the last one
+            let at = ((now - (res.time - time) as f64 / speed) * sample_rate as f64).round().max(0.) as usize;
+            self.mixer.add(clip, at.max(self.mixer.offset)..usize::MAX, 0., 1., self.volume_sfx);
+        }
+        encoder.write_audio(&self.mixer.take(start))?;
+
+        self.frame += 1;
+        Ok(true)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if let Some(mut encoder) = self.encoder.take() {
+            let end = (self.frame * self.options.sample_rate as u64 / self.options.fps as u64) as usize;
+            encoder.write_audio(&self.mixer.take(end))?;
+            encoder.finish()?;
+        }
+        Ok(())
+    }
+
+    fn progress(&self) -> f32 {
+        self.game
+            .as_ref()
+            .map_or(0., |game| (game.res.time / game.res.track_length).clamp(0., 1.))
+    }
+}
+
+impl Scene for RenderScene {
+    fn update(&mut self, _tm: &mut TimeManager) -> Result<()> {
+        if let Some(future) = self.load_task.as_mut() {
+            if let Some(game) = poll_future(future.as_mut()) {
+                self.load_task = None;
+                if let Err(err) = game.and_then(|game| self.start(game)) {
+                    self.next_scene = Some(NextScene::PopWithResult(Box::new(err)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn render(&mut self, tm: &mut TimeManager, ui: &mut Ui) -> Result<()> {
+        if self.game.is_some() && self.next_scene.is_none() {
+            let start = Instant::now();
+            while start.elapsed().as_secs_f64() < FRAME_BUDGET {
+                match self.step(ui).and_then(|more| if more { Ok(true) } else { self.finish().map(|_| false) }) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        show_message(ttl!("render-done", "path" => self.path.clone())).ok();
+                        self.next_scene = Some(NextScene::Pop);
+                        break;
+                    }
+                    Err(err) => {
+                        self.encoder = None;
+                        show_error(err.context(ttl!("render-failed")));
+                        self.next_scene = Some(NextScene::Pop);
+                        break;
+                    }
+                }
+            }
+        }
+
+        set_camera(&ui.camera());
+        if let Some(game) = &self.game {
+            draw_background(*game.res.background);
+        } else {
+            clear_background(BLACK);
+        }
+        let t = tm.now() as f32;
+        let p = self.progress();
+        ui.text(ttl!("render-progress", "progress" => format!("{:.0}", p * 100.)))
+            .pos(0., -0.05)
+            .anchor(0.5, 1.)
+            .size(0.7)
+            .draw();
+        ui.loading(
+            0.,
+            0.1,
+            t,
+            WHITE,
+            LoadingParams {
+                progress: Some(p),
+                ..Default::default()
+            },
+        );
+        Ok(())
+    }
+
+    fn next_scene(&mut self, _tm: &mut TimeManager) -> NextScene {
+        self.next_scene.take().unwrap_or_default()
+    }
+}
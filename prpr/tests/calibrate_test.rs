@@ -0,0 +1,24 @@
+use prpr::calibrate::{OffsetHistory, OffsetSample};
+
+fn sample(offset: f32, mean: f32) -> OffsetSample {
+    OffsetSample { offset, mean, hits: 50 }
+}
+
+#[test]
+fn needs_enough_hits() {
+    let mut history = OffsetHistory::default();
+    history.push(sample(0., 0.03));
+    assert_eq!(history.suggest(0.), None);
+    history.push(sample(0., 0.03));
+    assert_eq!(history.suggest(0.), Some(0.03));
+}
+
+#[test]
+fn targets_survive_offset_changes() {
+    let mut history = OffsetHistory::default();
+    // late by 40ms at 0, then only late by 10ms after moving to 30ms
+    history.push(sample(0., 0.04));
+    history.push(sample(0.03, 0.01));
+    assert_eq!(history.suggest(0.03), Some(0.04));
+    assert_eq!(history.suggest(0.038), None);
+}
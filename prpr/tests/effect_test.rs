@@ -0,0 +1,43 @@
+use prpr::core::{declared_uniforms, ShaderSource};
+
+#[test]
+fn parse_declared_uniforms() {
+    let uniforms = declared_uniforms(
+        r#"#version 100
+precision mediump float;
+uniform sampler2D screenTexture;
+uniform highp vec2 center; // %0.5, 0.5%
+uniform float weights[4];
+varying lowp vec2 uv;
+"#,
+    );
+    assert_eq!(uniforms.get("screenTexture").map(String::as_str), Some("sampler2D"));
+    assert_eq!(uniforms.get("center").map(String::as_str), Some("vec2"));
+    assert_eq!(uniforms.get("weights").map(String::as_str), Some("float"));
+    assert!(!uniforms.contains_key("uv"));
+}
+
+#[test]
+fn map_compile_error_to_pass() {
+    let sources = [
+        ShaderSource {
+            name: "blur.glsl",
+            code: "#version 100\nprecision mediump float;\nvoid main() {\n    gl_FragColor = vec4(1.0);\n}\n",
+        },
+        ShaderSource {
+            name: "vignette.glsl",
+            code: "#version 100\nprecision mediump float;\nvoid main() {\n    gl_FragColor = vec4(strength);\n}\n",
+        },
+    ];
+    // the driver numbers lines of the failing pass only, in the formats of Mesa and of ANGLE
+    let mesa = sources[1].map_compile_error("0:4(25): error: `strength' undeclared\n0:4(25): error: no matching function");
+    assert!(mesa.starts_with("failed to compile shader vignette.glsl\n"));
+    assert!(mesa.contains("vignette.glsl:4:25: error: `strength' undeclared"));
+    assert!(!mesa.contains("blur.glsl"));
+    // each line is quoted once
+    assert_eq!(mesa.matches("   4 |     gl_FragColor = vec4(strength);").count(), 1);
+
+    let angle = sources[1].map_compile_error("ERROR: 0:4: 'strength' : undeclared identifier");
+    assert!(angle.contains("ERROR: vignette.glsl:4: 'strength' : undeclared identifier"));
+    assert!(angle.ends_with("\n   4 |     gl_FragColor = vec4(strength);"));
+}
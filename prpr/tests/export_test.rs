@@ -0,0 +1,379 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use prpr::{
+    core::{Anim, Chart, ChartExtra, JudgeLine, JudgeLineKind, Note, NoteKind, Tweenable},
+    export::{export_pec, export_rpe},
+    fs::FileSystem,
+    info::ChartInfo,
+    parse::{parse_pec, parse_phigros, parse_rpe},
+};
+use std::any::Any;
+
+const RPE_CHART: &str = r#"{
+    "META": { "RPEVersion": 150, "offset": 120 },
+    "BPMList": [
+        { "bpm": 120.0, "startTime": [0, 0, 1] },
+        { "bpm": 180.0, "startTime": [8, 0, 1] }
+    ],
+    "judgeLineList": [
+        {
+            "Name": "main",
+            "Texture": "line.png",
+            "father": -1,
+            "isCover": 1,
+            "zOrder": 0,
+            "eventLayers": [
+                {
+                    "alphaEvents": [
+                        { "startTime": [0, 0, 1], "endTime": [4, 0, 1], "start": 0.0, "end": 255.0, "easingType": 1 }
+                    ],
+                    "moveXEvents": [
+                        { "startTime": [0, 0, 1], "endTime": [4, 0, 1], "start": -300.0, "end": 300.0, "easingType": 3 },
+                        {
+                            "startTime": [4, 0, 1], "endTime": [8, 0, 1], "start": 300.0, "end": -200.0, "easingType": 1,
+                            "bezier": 1, "bezierPoints": [0.2, 0.1, 0.7, 0.9]
+                        }
+                    ],
+                    "moveYEvents": [
+                        {
+                            "startTime": [0, 0, 1], "endTime": [6, 0, 1], "start": -200.0, "end": 100.0, "easingType": 12,
+                            "easingLeft": 0.2, "easingRight": 0.8
+                        }
+                    ],
+                    "rotateEvents": [
+                        { "startTime": [0, 0, 1], "endTime": [8, 0, 1], "start": 0.0, "end": 90.0, "easingType": 2 }
+                    ],
+                    "speedEvents": [
+                        { "startTime": [0, 0, 1], "endTime": [4, 0, 1], "start": 10.0, "end": 10.0 },
+                        { "startTime": [4, 0, 1], "endTime": [8, 0, 1], "start": 10.0, "end": 20.0 },
+                        { "startTime": [8, 0, 1], "endTime": [12, 0, 1], "start": 15.0, "end": -5.0 }
+                    ]
+                },
+                {
+                    "moveXEvents": [
+                        { "startTime": [2, 0, 1], "endTime": [6, 0, 1], "start": 0.0, "end": 50.0, "easingType": 1 }
+                    ]
+                }
+            ],
+            "extended": {
+                "scaleXEvents": [
+                    { "startTime": [0, 0, 1], "endTime": [8, 0, 1], "start": 1.0, "end": 1.5, "easingType": 4 }
+                ],
+                "inclineEvents": [
+                    { "startTime": [2, 0, 1], "endTime": [6, 0, 1], "start": 0.0, "end": 30.0, "easingType": 1 }
+                ]
+            },
+            "notes": [
+                {
+                    "type": 1, "above": 1, "startTime": [1, 0, 1], "endTime": [1, 0, 1], "positionX": 100.0, "yOffset": 0.0,
+                    "alpha": 255, "size": 1.0, "speed": 1.0, "isFake": 0, "visibleTime": 999999.0
+                },
+                {
+                    "type": 2, "above": 1, "startTime": [2, 1, 2], "endTime": [3, 0, 1], "positionX": -200.0, "yOffset": 0.0,
+                    "alpha": 255, "size": 1.2, "speed": 1.5, "isFake": 0, "visibleTime": 999999.0
+                },
+                {
+                    "type": 3, "above": 2, "startTime": [5, 0, 1], "endTime": [5, 0, 1], "positionX": 300.0, "yOffset": 0.0,
+                    "alpha": 255, "size": 1.0, "speed": 1.0, "isFake": 0, "visibleTime": 999999.0
+                },
+                {
+                    "type": 4, "above": 1, "startTime": [6, 1, 4], "endTime": [6, 1, 4], "positionX": 0.0, "yOffset": 20.0,
+                    "alpha": 128, "size": 1.0, "speed": 1.0, "isFake": 1, "visibleTime": 1.0
+                },
+                {
+                    "type": 1, "above": 1, "startTime": [9, 1, 3], "endTime": [9, 1, 3], "positionX": -400.0, "yOffset": 0.0,
+                    "alpha": 255, "hitsound": "flick.mp3", "size": 1.0, "speed": 1.0, "isFake": 0, "visibleTime": 999999.0
+                }
+            ]
+        },
+        {
+            "Name": "child",
+            "Texture": "line.png",
+            "father": 0,
+            "isCover": 0,
+            "zOrder": 1,
+            "eventLayers": [
+                {
+                    "alphaEvents": [
+                        { "startTime": [0, 0, 1], "endTime": [6, 0, 1], "start": 255.0, "end": 100.0, "easingType": 5 }
+                    ],
+                    "moveYEvents": [
+                        { "startTime": [0, 0, 1], "endTime": [3, 0, 1], "start": 100.0, "end": 100.0, "easingType": 1 },
+                        { "startTime": [3, 0, 1], "endTime": [7, 0, 1], "start": -100.0, "end": 50.0, "easingType": 8 }
+                    ]
+                }
+            ],
+            "extended": {
+                "textEvents": [
+                    { "startTime": [0, 0, 1], "endTime": [4, 0, 1], "start": "", "end": "Hello", "easingType": 1 }
+                ],
+                "colorEvents": [
+                    { "startTime": [2, 0, 1], "endTime": [6, 0, 1], "start": [255, 0, 0], "end": [0, 0, 255], "easingType": 1 }
+                ]
+            },
+            "alphaControl": [
+                { "easing": 1, "x": 0.0, "alpha": 0.5 },
+                { "easing": 1, "x": 600.0, "alpha": 1.0 }
+            ],
+            "notes": []
+        }
+    ]
+}"#;
+
+const PEC_CHART: &str = "150
+bp 0 120
+bp 16 150
+cv 0 0 7
+cv 0 8 10
+cp 0 0 1024 200
+cm 0 4 8 512 700 4
+cd 0 0 0
+cr 0 8 12 90 2
+ca 0 0 255
+cf 0 12 16 0
+cv 1 0 5
+cp 1 0 1024 1200
+cd 1 0 30
+ca 1 0 128
+cm 1 2 6 1500 900 7
+n1 0 1 0 1 0
+# 1
+& 1
+n2 0 2 4 -512 1 0
+# 1.5
+& 1.2
+n3 0 5 256 2 0
+# 1
+& 1
+n4 0 6 -256 1 1
+# 1
+& 1
+n1 1 3 100 1 0
+# 2
+& 1
+";
+
+const PGR_CHART: &str = r#"{
+    "formatVersion": 3,
+    "offset": 0.1,
+    "judgeLineList": [
+        {
+            "bpm": 120.0,
+            "judgeLineDisappearEvents": [
+                { "startTime": -1.0, "endTime": 128.0, "start": 0.0, "end": 1.0 },
+                { "startTime": 128.0, "endTime": 1000.0, "start": 1.0, "end": 1.0 }
+            ],
+            "judgeLineRotateEvents": [
+                { "startTime": -1.0, "endTime": 256.0, "start": 0.0, "end": 45.0 },
+                { "startTime": 256.0, "endTime": 1000.0, "start": 45.0, "end": 45.0 }
+            ],
+            "judgeLineMoveEvents": [
+                { "startTime": -1.0, "endTime": 192.0, "start": 0.5, "end": 0.3, "start2": 0.5, "end2": 0.6 },
+                { "startTime": 192.0, "endTime": 1000.0, "start": 0.3, "end": 0.3, "start2": 0.6, "end2": 0.6 }
+            ],
+            "speedEvents": [
+                { "startTime": 0.0, "endTime": 320.0, "value": 1.0 },
+                { "startTime": 320.0, "endTime": 1000.0, "value": 2.0 }
+            ],
+            "notesAbove": [
+                { "type": 1, "time": 64.0, "positionX": 1.5, "holdTime": 0.0, "speed": 1.0, "floorPosition": 0.0 },
+                { "type": 3, "time": 160.0, "positionX": -2.0, "holdTime": 64.0, "speed": 1.0, "floorPosition": 0.0 },
+                { "type": 4, "time": 384.0, "positionX": 0.0, "holdTime": 0.0, "speed": 1.0, "floorPosition": 0.0 }
+            ],
+            "notesBelow": [
+                { "type": 2, "time": 288.0, "positionX": 3.0, "holdTime": 0.0, "speed": 1.5, "floorPosition": 0.0 }
+            ]
+        }
+    ]
+}"#;
+
+/// Charts in these tests don't reference any file
+struct EmptyFileSystem;
+
+#[async_trait]
+impl FileSystem for EmptyFileSystem {
+    async fn load_file(&mut self, path: &str) -> Result<Vec<u8>> {
+        anyhow::bail!("file not found: {path}")
+    }
+
+    async fn exists(&mut self, _path: &str) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn list_root(&self) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    fn clone_box(&self) -> Box<dyn FileSystem> {
+        Box::new(Self)
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+fn load_rpe(source: &str) -> Result<Chart> {
+    tokio::runtime::Runtime::new()?.block_on(parse_rpe(source, &mut EmptyFileSystem, ChartExtra::default()))
+}
+
+fn times() -> impl Iterator<Item = f32> {
+    // avoid hitting keyframes exactly
+    (0..80).map(|i| 0.0371 + i as f32 * 0.1713)
+}
+
+fn sample<T: Tweenable>(anim: &Anim<T>, time: f32) -> Option<T> {
+    let mut anim = anim.clone();
+    anim.set_time(time);
+    anim.now_opt()
+}
+
+fn assert_close(what: &str, a: f32, b: f32, tolerance: f32) {
+    assert!((a - b).abs() <= tolerance * a.abs().max(1.), "{what}: {a} != {b}");
+}
+
+fn assert_anim_close(what: &str, a: &Anim<f32>, b: &Anim<f32>, default: f32, tolerance: f32) {
+    for time in times() {
+        let (x, y) = (sample(a, time).unwrap_or(default), sample(b, time).unwrap_or(default));
+        assert_close(&format!("{what} at {time}"), x, y, tolerance);
+    }
+}
+
+fn sorted_notes(line: &JudgeLine) -> Vec<&Note> {
+    let mut notes: Vec<_> = line.notes.iter().collect();
+    notes.sort_by(|a, b| a.time.total_cmp(&b.time));
+    notes
+}
+
+fn assert_notes_eq(id: usize, a: &JudgeLine, b: &JudgeLine, full: bool) {
+    let (a, b) = (sorted_notes(a), sorted_notes(b));
+    assert_eq!(a.len(), b.len(), "number of notes on line #{id}");
+    for (x, y) in a.into_iter().zip(b) {
+        let what = |field: &str| format!("{field} of note at {} on line #{id}", x.time);
+        match (&x.kind, &y.kind) {
+            (NoteKind::Hold { end_time: p, .. }, NoteKind::Hold { end_time: q, .. }) => assert_close(&what("end time"), *p, *q, 1e-4),
+            (p, q) => assert_eq!(std::mem::discriminant(p), std::mem::discriminant(q), "{}", what("kind")),
+        }
+        assert_close(&what("time"), x.time, y.time, 1e-4);
+        assert_close(&what("height"), x.height, y.height, 1e-2);
+        assert_close(&what("speed"), x.speed, y.speed, 1e-4);
+        assert_eq!(x.above, y.above, "{}", what("side"));
+        assert_eq!(x.fake, y.fake, "{}", what("fake"));
+        assert_close(
+            &what("x"),
+            sample(&x.object.translation.0, 0.).unwrap_or_default(),
+            sample(&y.object.translation.0, 0.).unwrap_or_default(),
+            1e-4,
+        );
+        assert_close(&what("size"), sample(&x.object.scale.0, 0.).unwrap_or(1.), sample(&y.object.scale.0, 0.).unwrap_or(1.), 1e-4);
+        if full {
+            assert_anim_close(&what("alpha"), &x.object.alpha, &y.object.alpha, 1., 1e-2);
+            assert_anim_close(&what("y"), &x.object.translation.1, &y.object.translation.1, 0., 1e-4);
+        }
+    }
+}
+
+fn assert_charts_eq(a: &Chart, b: &Chart, full: bool) {
+    assert_close("offset", a.offset, b.offset, 1e-4);
+    assert_eq!(a.lines.len(), b.lines.len(), "number of lines");
+    for (id, (x, y)) in a.lines.iter().zip(&b.lines).enumerate() {
+        let what = |field: &str| format!("{field} of line #{id}");
+        assert_anim_close(&what("alpha"), &x.object.alpha, &y.object.alpha, 1., 1e-3);
+        assert_anim_close(&what("rotation"), &x.object.rotation, &y.object.rotation, 0., 1e-3);
+        assert_anim_close(&what("x"), &x.object.translation.0, &y.object.translation.0, 0., 1e-3);
+        assert_anim_close(&what("y"), &x.object.translation.1, &y.object.translation.1, 0., 1e-3);
+        assert_anim_close(&what("height"), &x.height, &y.height, 0., 1e-3);
+        assert_notes_eq(id, x, y, full);
+        if !full {
+            continue;
+        }
+        assert_anim_close(&what("scale x"), &x.object.scale.0, &y.object.scale.0, 1., 1e-3);
+        assert_anim_close(&what("scale y"), &x.object.scale.1, &y.object.scale.1, 1., 1e-3);
+        assert_anim_close(&what("incline"), &x.incline, &y.incline, 0., 1e-3);
+        let (p, q) = (x.ctrl_obj.borrow(), y.ctrl_obj.borrow());
+        assert_anim_close(&what("alpha control"), &p.alpha, &q.alpha, 1., 1e-3);
+        assert_anim_close(&what("size control"), &p.size, &q.size, 1., 1e-3);
+        for time in times() {
+            let (p, q) = (sample(&x.color, time), sample(&y.color, time));
+            assert_eq!(p.is_some(), q.is_some(), "{}", what("color"));
+            if let (Some(p), Some(q)) = (p, q) {
+                for (p, q) in [(p.r, q.r), (p.g, q.g), (p.b, q.b)] {
+                    assert_close(&what("color"), p, q, 1e-2);
+                }
+            }
+        }
+        match (&x.kind, &y.kind) {
+            (JudgeLineKind::Text(p), JudgeLineKind::Text(q)) => {
+                for time in times() {
+                    assert_eq!(sample(p, time), sample(q, time), "{} at {time}", what("text"));
+                }
+            }
+            (p, q) => assert_eq!(std::mem::discriminant(p), std::mem::discriminant(q), "{}", what("kind")),
+        }
+        assert_eq!(x.parent, y.parent, "{}", what("parent"));
+        assert_eq!(x.z_index, y.z_index, "{}", what("z index"));
+        assert_eq!(x.show_below, y.show_below, "{}", what("cover"));
+    }
+}
+
+#[test]
+fn rpe_round_trip() -> Result<()> {
+    let chart = load_rpe(RPE_CHART)?;
+    let exported = load_rpe(&export_rpe(&chart, &ChartInfo::default())?)?;
+    assert_charts_eq(&chart, &exported, true);
+    Ok(())
+}
+
+#[test]
+fn pec_round_trip() -> Result<()> {
+    let chart = parse_pec(PEC_CHART, ChartExtra::default())?;
+    let exported = parse_pec(&export_pec(&chart)?, ChartExtra::default())?;
+    assert_charts_eq(&chart, &exported, false);
+    Ok(())
+}
+
+#[test]
+fn pec_to_rpe() -> Result<()> {
+    let chart = parse_pec(PEC_CHART, ChartExtra::default())?;
+    let exported = load_rpe(&export_rpe(&chart, &ChartInfo::default())?)?;
+    assert_charts_eq(&chart, &exported, false);
+    Ok(())
+}
+
+#[test]
+fn rpe_to_pec() -> Result<()> {
+    let chart = load_rpe(RPE_CHART)?;
+    let exported = parse_pec(&export_pec(&chart)?, ChartExtra::default())?;
+    assert_eq!(chart.lines.len(), exported.lines.len());
+    for (id, (x, y)) in chart.lines.iter().zip(&exported.lines).enumerate() {
+        // sampled animations are only approximately equal
+        assert_anim_close(&format!("x of line #{id}"), &x.object.translation.0, &y.object.translation.0, 0., 2e-2);
+        assert_anim_close(&format!("y of line #{id}"), &x.object.translation.1, &y.object.translation.1, 0., 2e-2);
+        assert_anim_close(&format!("height of line #{id}"), &x.height, &y.height, 0., 1e-2);
+        assert_notes_eq(id, x, y, false);
+    }
+    Ok(())
+}
+
+#[test]
+fn pgr_to_rpe() -> Result<()> {
+    // PGR charts come without a BPM list
+    let chart = parse_phigros(PGR_CHART, ChartExtra::default())?;
+    let exported = load_rpe(&export_rpe(&chart, &ChartInfo::default())?)?;
+    assert_charts_eq(&chart, &exported, false);
+    Ok(())
+}
+
+#[test]
+fn pgr_to_pec() -> Result<()> {
+    let chart = parse_phigros(PGR_CHART, ChartExtra::default())?;
+    let exported = parse_pec(&export_pec(&chart)?, ChartExtra::default())?;
+    assert_eq!(chart.lines.len(), exported.lines.len());
+    for (id, (x, y)) in chart.lines.iter().zip(&exported.lines).enumerate() {
+        assert_anim_close(&format!("x of line #{id}"), &x.object.translation.0, &y.object.translation.0, 0., 2e-2);
+        assert_anim_close(&format!("y of line #{id}"), &x.object.translation.1, &y.object.translation.1, 0., 2e-2);
+        assert_anim_close(&format!("height of line #{id}"), &x.height, &y.height, 0., 1e-2);
+        assert_notes_eq(id, x, y, false);
+    }
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use prpr::{
+    core::{Chart, ChartExtra},
+    fs::FileSystem,
+    info::ChartFormat,
+    parse::{detect_format, parse_chart, register_parser, ChartParseError, ChartParser},
+};
+use std::any::Any;
+
+struct EmptyFileSystem;
+
+#[async_trait]
+impl FileSystem for EmptyFileSystem {
+    async fn load_file(&mut self, path: &str) -> Result<Vec<u8>> {
+        anyhow::bail!("file not found: {path}")
+    }
+
+    async fn exists(&mut self, _path: &str) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn list_root(&self) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    fn clone_box(&self) -> Box<dyn FileSystem> {
+        Box::new(Self)
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Recognizes files starting with `MYFMT`, but can't parse them
+struct MyParser;
+
+#[async_trait(?Send)]
+impl ChartParser for MyParser {
+    fn format(&self) -> ChartFormat {
+        ChartFormat::Custom("myfmt".to_owned())
+    }
+
+    fn extension(&self) -> &str {
+        "my"
+    }
+
+    fn detect(&self, bytes: &[u8]) -> f32 {
+        if bytes.starts_with(b"MYFMT") {
+            1.
+        } else {
+            0.
+        }
+    }
+
+    async fn parse(&self, _bytes: &[u8], _fs: &mut dyn FileSystem, _extra: ChartExtra) -> Result<Chart> {
+        anyhow::bail!("not implemented")
+    }
+}
+
+#[test]
+fn detect_builtin_formats() {
+    assert_eq!(detect_format(br#"{"META": {"RPEVersion": 150}}"#).unwrap(), ChartFormat::Rpe);
+    assert_eq!(detect_format(br#"{"formatVersion": 3, "judgeLineList": []}"#).unwrap(), ChartFormat::Pgr);
+    assert_eq!(detect_format(b"0\nbp 0.00 120.00\n").unwrap(), ChartFormat::Pec);
+    assert_eq!(detect_format(&[0, 0, 0xff, 0xfe, 0x80]).unwrap(), ChartFormat::Pbc);
+}
+
+#[test]
+fn custom_format() {
+    register_parser(MyParser);
+    let format = detect_format(b"MYFMT 1\n").unwrap();
+    assert_eq!(format, ChartFormat::from_name("MyFmt"));
+    assert_eq!(serde_yaml::to_string(&format).unwrap().trim(), "myfmt");
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let err = rt
+        .block_on(parse_chart(b"MYFMT 1\n", None, &mut EmptyFileSystem, ChartExtra::default()))
+        .unwrap_err();
+    assert!(matches!(err, ChartParseError::Invalid { format, .. } if format == ChartFormat::Custom("myfmt".to_owned())));
+    let err = rt
+        .block_on(parse_chart(b"", Some(&ChartFormat::from_name("other")), &mut EmptyFileSystem, ChartExtra::default()))
+        .unwrap_err();
+    assert!(matches!(err, ChartParseError::UnknownFormat(_)));
+}
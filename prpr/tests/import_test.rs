@@ -0,0 +1,66 @@
+use prpr::{
+    core::{ChartExtra, NoteKind},
+    parse::{parse_malody, parse_note_list, ImportKind, ImportMapping},
+};
+
+const MALODY_CHART: &str = r#"{
+    "meta": {
+        "creator": "someone",
+        "version": "4K Hard Lv.12",
+        "background": "bg.jpg",
+        "mode": 0,
+        "mode_ext": { "column": 4 },
+        "song": { "title": "Song", "artist": "Artist" }
+    },
+    "time": [{ "beat": [0, 0, 1], "bpm": 120 }, { "beat": [4, 0, 1], "bpm": 240 }],
+    "note": [
+        { "beat": [0, 0, 1], "column": 0 },
+        { "beat": [1, 0, 1], "column": 3 },
+        { "beat": [2, 0, 1], "endbeat": [6, 0, 1], "column": 1 },
+        { "beat": [0, 0, 1], "sound": "song.ogg", "vol": 100, "offset": 250, "type": 1 }
+    ]
+}"#;
+
+#[test]
+fn import_malody_key() {
+    let chart = parse_malody(MALODY_CHART, &ImportMapping::default(), ChartExtra::default()).unwrap();
+    assert!((chart.offset - 0.25).abs() < 1e-6);
+    assert_eq!(chart.lines.len(), 1);
+    let mut notes: Vec<_> = chart.lines[0].notes.iter().collect();
+    notes.sort_by(|x, y| x.time.total_cmp(&y.time));
+    assert_eq!(notes.len(), 3);
+    assert!((notes[1].time - 0.5).abs() < 1e-6);
+    // 4 beats at 120 bpm, then 2 beats at 240 bpm
+    match notes[2].kind {
+        NoteKind::Hold { end_time, .. } => assert!((end_time - 2.5).abs() < 1e-6),
+        _ => panic!("expected a hold"),
+    }
+    // columns are spread from left to right
+    assert!(notes[0].object.translation.0.now() < notes[2].object.translation.0.now());
+    assert!(notes[2].object.translation.0.now() < notes[1].object.translation.0.now());
+}
+
+#[test]
+fn import_note_list() {
+    let csv = "# exported from somewhere\ntime,type,x,end,line\n1.0,tap,0.5,,\n1.0,drag,-0.5,,1\n2.0,hold,0,3.0,\n2.5,slide,0,,";
+    let mut mapping = ImportMapping::default();
+    mapping.lines.push(Default::default());
+    assert!(parse_note_list(csv, &mapping, ChartExtra::default()).is_err());
+    mapping.kinds.insert("slide".to_owned(), ImportKind::Flick);
+    let chart = parse_note_list(csv, &mapping, ChartExtra::default()).unwrap();
+    assert_eq!(chart.lines.len(), 2);
+    assert_eq!(chart.lines[0].notes.len(), 3);
+    assert!(matches!(chart.lines[1].notes[0].kind, NoteKind::Drag));
+    // notes at the same time on different lines still get the multiple hint
+    assert!(chart.lines[0]
+        .notes
+        .iter()
+        .chain(&chart.lines[1].notes)
+        .filter(|it| it.time == 1.)
+        .all(|it| it.multiple_hint));
+
+    let json = r#"{ "offset": 0.1, "notes": [{ "time": 1, "type": "flick", "column": 2 }, { "time": 1.5, "column": 0 }] }"#;
+    let chart = parse_note_list(json, &ImportMapping::default(), ChartExtra::default()).unwrap();
+    assert_eq!(chart.lines[0].notes.len(), 2);
+    assert!(parse_note_list("time,line\n1,3", &ImportMapping::default(), ChartExtra::default()).is_err());
+}
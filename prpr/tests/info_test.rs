@@ -0,0 +1,37 @@
+use prpr::info::{ChartInfo, InfoIssueKind, INFO_VERSION};
+
+#[test]
+fn migrate_legacy_info() {
+    let (info, issues) = ChartInfo::from_yaml_checked("name: Song\naspect_ratio: 1.5\nbackgroundDim: 0.3\nbackground_dim: 0.9\n").unwrap();
+    assert_eq!(info.version, INFO_VERSION);
+    assert_eq!(info.aspect_ratio, 1.5);
+    // the camel case key wins, the other one is left as is
+    assert_eq!(info.background_dim, 0.3);
+    assert_eq!(issues.len(), 1);
+    assert_eq!((issues[0].kind, issues[0].key.as_str()), (InfoIssueKind::UnknownKey, "background_dim"));
+
+    // current files are not migrated
+    let (info, issues) = ChartInfo::from_yaml_checked(&format!("version: {INFO_VERSION}\naspect_ratio: 1.5\n")).unwrap();
+    assert_eq!(info.aspect_ratio, ChartInfo::default().aspect_ratio);
+    assert_eq!(issues[0].kind, InfoIssueKind::UnknownKey);
+}
+
+#[test]
+fn report_invalid_info() {
+    let (_, issues) = ChartInfo::from_yaml_checked("version: 100\nbackgroundDim: 2\npreviewStart: 10\npreviewEnd: 5\nsomething: 1\n").unwrap();
+    let keys: Vec<_> = issues.iter().map(|it| (it.kind, it.key.as_str())).collect();
+    assert_eq!(
+        keys,
+        [
+            (InfoIssueKind::NewerVersion, "version"),
+            (InfoIssueKind::UnknownKey, "something"),
+            (InfoIssueKind::OutOfRange, "backgroundDim"),
+            (InfoIssueKind::OutOfRange, "previewEnd"),
+        ]
+    );
+    assert!(ChartInfo::from_yaml_checked(&serde_yaml::to_string(&ChartInfo::default()).unwrap())
+        .unwrap()
+        .1
+        .is_empty());
+    assert!(ChartInfo::from_yaml("difficulty: hard").is_err());
+}
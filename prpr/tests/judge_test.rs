@@ -0,0 +1,200 @@
+use prpr::{
+    core::{Chart, ChartExtra},
+    judge::{
+        sim::{SimEvent, SimInput, Simulator},
+        Judge, JudgeStatus, Judgement,
+    },
+    parse::parse_pec,
+};
+
+// 60 BPM so that beats are seconds: a click at 1s, a hold from 2s to 3s, a flick at 4s and a drag at 5s, all at x = 0
+const CHART: &str = "150
+bp 0 60
+cv 0 0 1
+cp 0 0 1024 700
+cd 0 0 0
+ca 0 0 255
+n1 0 1 0 1 0
+# 1
+& 1
+n2 0 2 3 0 1 0
+# 1
+& 1
+n3 0 4 0 1 0
+# 1
+& 1
+n4 0 5 0 1 0
+# 1
+& 1
+";
+
+fn chart() -> Chart {
+    parse_pec(CHART, ChartExtra::default()).unwrap()
+}
+
+fn tap(time: f32, id: u64) -> [SimEvent; 2] {
+    [SimEvent::touch_start(time, id, 0., 0.), SimEvent::touch_end(time + 0.05, id, 0., 0.)]
+}
+
+fn flick(time: f32, id: u64) -> Vec<SimEvent> {
+    let mut events = vec![SimEvent::touch_start(time - 0.1, id, 0., 0.)];
+    events.extend((1..=10).map(|i| SimEvent::touch_move(time - 0.05 + i as f32 * 0.01, id, i as f32 * 0.02, 0.)));
+    events.push(SimEvent::touch_end(time + 0.1, id, 0.2, 0.));
+    events
+}
+
+fn all_clear() -> Vec<SimEvent> {
+    let mut events = tap(1., 0).to_vec();
+    events.push(SimEvent::touch_start(2., 1, 0., 0.));
+    events.push(SimEvent::touch_end(3.1, 1, 0., 0.));
+    events.extend(flick(4., 2));
+    events.push(SimEvent::touch_start(4.9, 3, 0., 0.));
+    events.push(SimEvent::touch_end(5.2, 3, 0., 0.));
+    events
+}
+
+#[test]
+fn all_perfect() {
+    let res = Simulator::default().run(&mut chart(), &all_clear());
+    assert_eq!(res.result.counts, [4, 0, 0, 0]);
+    assert_eq!(res.result.score, 1_000_000);
+    assert_eq!(res.result.max_combo, 4);
+    // the hold is logged when it starts being held
+    assert!(res.judgements.iter().any(|it| matches!(it.3, Err(true))));
+}
+
+#[test]
+fn no_input() {
+    let res = Simulator::default().run(&mut chart(), &[]);
+    assert_eq!(res.result.counts, [0, 0, 0, 4]);
+    assert_eq!(res.result.score, 0);
+    assert!(res.judgements.iter().all(|it| matches!(it.3, Ok(Judgement::Miss))));
+}
+
+#[test]
+fn early_tap_is_good() {
+    let res = Simulator::default().run(&mut chart(), &tap(0.88, 0));
+    assert_eq!(res.result.counts[1], 1);
+    assert_eq!(res.result.early, 1);
+}
+
+#[test]
+fn late_tap_within_early_offset_is_perfect() {
+    let res = Simulator::default().run(&mut chart(), &tap(1.12, 0));
+    assert_eq!(res.result.counts[0], 1);
+}
+
+#[test]
+fn strict_judge_narrows_windows() {
+    let res = Simulator::default().run(&mut chart(), &tap(0.94, 0));
+    assert_eq!(res.result.counts[0], 1);
+    let sim = Simulator {
+        strict: true,
+        ..Default::default()
+    };
+    let res = sim.run(&mut chart(), &tap(0.94, 0));
+    assert_eq!(res.result.counts[1], 1);
+}
+
+#[test]
+fn tap_out_of_range() {
+    let res = Simulator::default().run(
+        &mut chart(),
+        &tap(1., 0).map(|mut it| {
+            if let SimInput::Touch { position, .. } = &mut it.input {
+                position.x = 0.5;
+            }
+            it
+        }),
+    );
+    assert_eq!(res.result.counts, [0, 0, 0, 4]);
+}
+
+#[test]
+fn hold_released_early() {
+    let events = [SimEvent::touch_start(2., 0, 0., 0.), SimEvent::touch_end(2.3, 0, 0., 0.)];
+    let res = Simulator::default().run(&mut chart(), &events);
+    // the click, flick and drag are missed as well
+    assert_eq!(res.result.counts, [0, 0, 0, 4]);
+    assert!(res.judgements.iter().any(|it| it.2 == 1 && matches!(it.3, Err(true))));
+}
+
+#[test]
+fn hold_with_key() {
+    let events = [SimEvent::key_down(2.), SimEvent::key_up(3.1)];
+    let res = Simulator::default().run(&mut chart(), &events);
+    assert_eq!(res.result.counts, [1, 0, 0, 3]);
+}
+
+#[test]
+fn hold_with_key_hit_error() {
+    // keys and touches record the same timing error for holds
+    let events = [SimEvent::key_down(2.1), SimEvent::key_up(3.1)];
+    let by_key = Simulator::default().run(&mut chart(), &events).result;
+    let events = [SimEvent::touch_start(2.1, 0, 0., 0.), SimEvent::touch_end(3.1, 0, 0., 0.)];
+    let by_touch = Simulator::default().run(&mut chart(), &events).result;
+    assert_eq!(by_key.hit_errors.len(), 1);
+    assert!((by_key.hit_errors[0].diff - 0.1).abs() < 1e-2);
+    assert!((by_key.hit_errors[0].diff - by_touch.hit_errors[0].diff).abs() < 1e-3);
+}
+
+#[test]
+fn flick_needs_movement() {
+    let events = [SimEvent::touch_start(3.9, 0, 0., 0.), SimEvent::touch_end(4.1, 0, 0., 0.)];
+    let res = Simulator::default().run(&mut chart(), &events);
+    assert_eq!(res.result.counts[0], 0);
+
+    let res = Simulator::default().run(&mut chart(), &flick(4., 0));
+    assert_eq!(res.result.counts[0], 1);
+}
+
+#[test]
+fn drag_by_resting_finger() {
+    let events = [SimEvent::touch_start(3.9, 0, 0.1, 0.), SimEvent::touch_end(5.5, 0, 0.1, 0.)];
+    let res = Simulator::default().run(&mut chart(), &events);
+    // a resting finger catches the drag, but not the flick
+    assert_eq!(res.result.counts, [1, 0, 0, 3]);
+    assert!(res.judgements.iter().any(|it| it.2 == 3 && matches!(it.3, Ok(Judgement::Perfect))));
+}
+
+#[test]
+fn speed_scales_windows() {
+    // at half speed, 0.05s of chart time is 0.1s of real time, which is a good
+    let sim = Simulator {
+        speed: 0.5,
+        ..Default::default()
+    };
+    let res = sim.run(&mut chart(), &tap(0.95, 0));
+    assert_eq!(res.result.counts[1], 1);
+}
+
+#[test]
+fn hit_error_stats() {
+    let mut events = tap(0.9, 0).to_vec();
+    events.push(SimEvent::touch_start(2.1, 1, 0., 0.));
+    events.push(SimEvent::touch_end(3.1, 1, 0., 0.));
+    let res = Simulator::default().run(&mut chart(), &events).result;
+    // only the click and the hold count, the flick and drag are missed
+    assert_eq!(res.hit_errors.len(), 2);
+    assert!((res.hit_errors[0].diff + 0.1).abs() < 1e-3);
+    assert!(res.mean.abs() < 1e-3);
+    assert!((res.std - 0.1).abs() < 1e-3);
+    assert_eq!(res.histogram(4), vec![1, 0, 0, 1]);
+}
+
+#[test]
+fn reset_range_keeps_other_notes() {
+    let mut chart = chart();
+    let mut judge = Judge::new(&chart);
+    for note in chart.lines.iter_mut().flat_map(|it| &mut it.notes) {
+        note.judge = JudgeStatus::Judged;
+    }
+    // the hold and the flick
+    chart.reset_range(1.5..4.5);
+    judge.reset_range(&chart, 1.5..4.5);
+    for note in &chart.lines[0].notes {
+        assert_eq!(matches!(note.judge, JudgeStatus::Judged), !(1.5..4.5).contains(&note.time));
+    }
+    let (idx, st) = &judge.notes[0];
+    assert_eq!(chart.lines[0].notes[idx[*st] as usize].time, 2.);
+}
@@ -0,0 +1,99 @@
+use image::{DynamicImage, ImageFormat, RgbImage};
+use prpr::{
+    fs::{load_info_checked, FileSystem, ZipFileSystem},
+    info::{ChartInfo, INFO_VERSION},
+    package::PackageBuilder,
+};
+use std::io::Cursor;
+
+fn silent_wav(samples: u32) -> Vec<u8> {
+    let mut res = Vec::new();
+    res.extend_from_slice(b"RIFF");
+    res.extend_from_slice(&(36 + samples * 2).to_le_bytes());
+    res.extend_from_slice(b"WAVEfmt ");
+    for value in [16_u32, 1 | (1 << 16), 44100, 88200, 2 | (16 << 16)] {
+        res.extend_from_slice(&value.to_le_bytes());
+    }
+    res.extend_from_slice(b"data");
+    res.extend_from_slice(&(samples * 2).to_le_bytes());
+    res.resize(res.len() + samples as usize * 2, 0);
+    res
+}
+
+fn bmp() -> Vec<u8> {
+    let mut res = Vec::new();
+    DynamicImage::ImageRgb8(RgbImage::new(16, 8))
+        .write_to(&mut Cursor::new(&mut res), ImageFormat::Bmp)
+        .unwrap();
+    res
+}
+
+#[test]
+fn build_package() {
+    let info = ChartInfo {
+        name: "Song".to_owned(),
+        ..Default::default()
+    };
+    let bytes = PackageBuilder::new(info.clone())
+        .chart("charts/chart.pec", b"0\nbp 0.000 120.000\n".to_vec())
+        .music("song.wav", silent_wav(4410))
+        .illustration("bg.bmp", bmp())
+        .extra(b"{}".to_vec())
+        .file("textures/note.png", b"texture".to_vec())
+        .build()
+        .unwrap();
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let mut fs = ZipFileSystem::new(bytes).unwrap();
+    let (info, issues) = rt.block_on(load_info_checked(&mut fs)).unwrap();
+    assert!(issues.is_empty(), "{issues:?}");
+    assert_eq!(info.version, INFO_VERSION);
+    assert_eq!(info.name, "Song");
+    // the chart is moved to the root, and images other than PNG and JPEG are recompressed
+    assert_eq!(info.chart, "chart.pec");
+    assert_eq!(info.music, "song.wav");
+    assert_eq!(info.illustration, "bg.jpg");
+    let mut root = fs.list_root().unwrap();
+    root.sort();
+    assert_eq!(root, ["bg.jpg", "chart.pec", "extra.json", "info.yml", "song.wav"]);
+    // other files keep their directories
+    assert_eq!(rt.block_on(fs.load_file("textures/note.png")).unwrap(), b"texture");
+
+    assert!(PackageBuilder::new(ChartInfo::default())
+        .music("song.wav", silent_wav(10))
+        .build()
+        .is_err());
+    assert!(PackageBuilder::new(ChartInfo::default())
+        .chart("chart.pec", b"0\n".to_vec())
+        .music("song.ogg", b"not music".to_vec())
+        .build()
+        .is_err());
+
+    // the illustration is required as well
+    let err = PackageBuilder::new(ChartInfo::default())
+        .chart("chart.pec", b"0\nbp 0.000 120.000\n".to_vec())
+        .music("song.wav", silent_wav(10))
+        .build()
+        .unwrap_err();
+    assert!(err.to_string().contains("missing illustration"), "{err:?}");
+}
+
+#[test]
+fn reject_paths_outside_package() {
+    let build = |name: &str| {
+        let info = ChartInfo {
+            name: "Song".to_owned(),
+            ..Default::default()
+        };
+        PackageBuilder::new(info)
+            .chart("chart.pec", b"0\nbp 0.000 120.000\n".to_vec())
+            .music("song.wav", silent_wav(10))
+            .illustration("bg.bmp", bmp())
+            .file(name, b"file".to_vec())
+            .build()
+    };
+    assert!(build("./textures/note.png").is_ok());
+    for name in ["../evil.png", "/etc/passwd", "textures/../../evil.png"] {
+        assert!(build(name).is_err(), "{name}");
+    }
+}
@@ -0,0 +1,47 @@
+use prpr::rating::{chart_rating, ChartRecord, Rating, BEST_N};
+
+fn record(key: &str, difficulty: f32, accuracy: f32) -> ChartRecord {
+    ChartRecord {
+        key: key.to_owned(),
+        difficulty,
+        accuracy,
+    }
+}
+
+#[test]
+fn chart_rating_curve() {
+    assert_eq!(chart_rating(15., 1.), 15.);
+    assert_eq!(chart_rating(15., 0.69), 0.);
+    assert!((chart_rating(9., 0.7) - 1.).abs() < 1e-5);
+}
+
+#[test]
+fn best_slots_and_phi() {
+    let records = (0..25)
+        .map(|i| record(&i.to_string(), 10. + i as f32 * 0.1, 0.9))
+        .chain([record("ap", 5., 1.)]);
+    let rating = Rating::compute(records);
+    assert_eq!(rating.best, BEST_N);
+    let phi = rating.phi.unwrap();
+    assert_eq!(rating.charts[phi].record.key, "ap");
+    assert!(rating.counts(phi) && !rating.counts(BEST_N));
+    let expected = (rating.charts[..BEST_N].iter().map(|it| it.rating).sum::<f32>() + 5.) / (BEST_N + 1) as f32;
+    assert!((rating.rks - expected).abs() < 1e-5);
+}
+
+#[test]
+fn target_accuracy_raises_rating() {
+    let rating = Rating::compute((0..BEST_N).map(|i| record(&i.to_string(), 12., 0.95)));
+    let shown = (rating.rks * 100.).round();
+    for (key, difficulty) in [("0", 12.), ("new", 14.)] {
+        let acc = rating.target_accuracy(key, difficulty).unwrap();
+        let raised = rating.with(record(key, difficulty, acc + 1e-4));
+        assert!((raised.rks * 100.).round() > shown);
+        let short = rating.with(record(key, difficulty, acc - 1e-3));
+        assert_eq!((short.rks * 100.).round(), shown);
+    }
+    // a full perfect play on an easy chart still fills the empty 100% accuracy slot
+    assert_eq!(rating.target_accuracy("new", 1.), Some(1.));
+    let rating = rating.with(record("ap", 12., 1.));
+    assert_eq!(rating.target_accuracy("new", 1.), None);
+}
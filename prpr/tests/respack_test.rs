@@ -0,0 +1,39 @@
+use image::{ImageFormat, RgbaImage};
+use prpr::{core::ResourcePack, fs::fs_from_file};
+
+#[test]
+fn validate_respack() {
+    let dir = tempfile::tempdir().unwrap();
+    let save = |name: &str, width: u32, height: u32| {
+        RgbaImage::new(width, height)
+            .save_with_format(dir.path().join(name), ImageFormat::Png)
+            .unwrap();
+    };
+    for name in ["click", "flick", "drag", "click_mh", "flick_mh"] {
+        save(&format!("{name}.png"), 16, 16);
+    }
+    save("hold.png", 16, 64);
+    save("hold_mh.png", 16, 64);
+    save("hit_fx.png", 100, 100);
+    std::fs::write(
+        dir.path().join("info.yml"),
+        "name: Test\nauthor: Me\nhitFx: [3, 4]\nholdAtlas: [20, 20]\nholdAtlasMH: [40, 30]\ncolorPerfect: '#ffffff'\nhitFxScale: -1\n",
+    )
+    .unwrap();
+    std::fs::write(dir.path().join("click.ogg"), b"not audio").unwrap();
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let mut fs = fs_from_file(dir.path()).unwrap();
+    let issues = rt.block_on(ResourcePack::validate(fs.as_mut()));
+    let mut files: Vec<_> = issues.iter().map(|it| it.file.as_str()).collect();
+    files.sort();
+    files.dedup();
+    // every problem is reported at once
+    assert_eq!(files, ["click.ogg", "drag_mh.png", "hit_fx.png", "hold_mh.png", "info.yml"], "{issues:?}");
+    assert!(issues.iter().any(|it| it.message.contains("colorPerfect")));
+
+    std::fs::write(dir.path().join("info.yml"), "name: Test\nauthor: Me\nhitFx: [4, 4]\nholdAtlas: [20, 20]\nholdAtlasMH: [20, 20]\n").unwrap();
+    std::fs::remove_file(dir.path().join("click.ogg")).unwrap();
+    save("drag_mh.png", 16, 16);
+    assert!(rt.block_on(ResourcePack::validate(fs.as_mut())).is_empty());
+}